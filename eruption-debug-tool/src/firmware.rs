@@ -0,0 +1 @@
+../../eruption-hwutil/src/firmware.rs
\ No newline at end of file
@@ -39,6 +39,7 @@ use std::{path::PathBuf, time::Duration};
 use std::{sync::atomic::AtomicBool, sync::atomic::Ordering, time::Instant};
 
 mod constants;
+mod firmware;
 mod hwdevices;
 mod util;
 
@@ -19,5 +19,6 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+pub mod layers;
 pub mod manifest;
 pub mod parameters;
@@ -71,6 +71,14 @@ pub fn initialize_mouse_page(
     let debounce_switch: gtk::Switch = template.object("debounce_switch").unwrap();
     let angle_snapping_switch: gtk::Switch = template.object("angle_snapping_switch").unwrap();
 
+    let mouse_rate_caption_label: gtk::Label = template.object("mouse_rate_caption_label").unwrap();
+    let mouse_dpi_caption_label: gtk::Label = template.object("mouse_dpi_caption_label").unwrap();
+    let mouse_profile_caption_label: gtk::Label =
+        template.object("mouse_profile_caption_label").unwrap();
+    let debounce_caption_label: gtk::Label = template.object("debounce_caption_label").unwrap();
+    let angle_snapping_caption_label: gtk::Label =
+        template.object("angle_snapping_caption_label").unwrap();
+
     crate::dbus_client::ping().unwrap_or_else(|_e| {
         notification_box_global.show_now();
 
@@ -83,6 +91,46 @@ pub fn initialize_mouse_page(
 
     let mouse_device_handle = mouse_device.get_device();
 
+    // only show the widgets of the parameters that are supported by the device
+    let schema = util::get_device_schema(mouse_device_handle)?;
+
+    let widgets: [(&str, gtk::Widget, gtk::Widget); 5] = [
+        (
+            "rate",
+            mouse_rate_caption_label.upcast(),
+            mouse_rate_label.clone().upcast(),
+        ),
+        (
+            "dpi",
+            mouse_dpi_caption_label.upcast(),
+            mouse_dpi_label.clone().upcast(),
+        ),
+        (
+            "profile",
+            mouse_profile_caption_label.upcast(),
+            mouse_profile_label.clone().upcast(),
+        ),
+        (
+            "debounce",
+            debounce_caption_label.upcast(),
+            debounce_switch.clone().upcast(),
+        ),
+        (
+            "angle-snapping",
+            angle_snapping_caption_label.upcast(),
+            angle_snapping_switch.clone().upcast(),
+        ),
+    ];
+
+    for (name, caption, widget) in widgets.iter() {
+        let supported = schema.is_writable(name);
+
+        caption.set_visible(supported);
+        widget.set_visible(supported);
+        widget.set_no_show_all(!supported);
+        caption.set_no_show_all(!supported);
+    }
+
     let device_brightness = util::get_device_brightness(mouse_device_handle)?;
     device_brightness_scale.set_value(device_brightness as f64);
 
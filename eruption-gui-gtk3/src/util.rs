@@ -300,6 +300,45 @@ pub fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
     Ok(result)
 }
 
+/// A device specific configuration parameter, as published by the daemon
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct ConfigKey {
+    pub name: String,
+    pub writable: bool,
+}
+
+/// The configuration keys that a device supports
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct DeviceSchema {
+    pub config: Vec<ConfigKey>,
+}
+
+impl DeviceSchema {
+    /// Returns `true` if the device supports writing the configuration parameter `name`
+    pub fn is_writable(&self, name: &str) -> bool {
+        self.config
+            .iter()
+            .any(|key| key.name == name && key.writable)
+    }
+}
+
+/// Get the configuration schema of a device
+pub fn get_device_schema(device: u64) -> Result<DeviceSchema> {
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/devices",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let (schema,): (String,) =
+        proxy.method_call("org.eruption.Device", "GetDeviceSchema", (device,))?;
+
+    let result: DeviceSchema = serde_json::from_str(&schema)?;
+
+    Ok(result)
+}
+
 /// Get a device specific config param
 pub fn get_device_config(device: u64, param: &str) -> Result<String> {
     let conn = Connection::new_system()?;
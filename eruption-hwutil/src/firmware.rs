@@ -0,0 +1,620 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Firmware information and the firmware update pipeline
+//!
+//! Firmware images are expected in the following container format (all integers are little endian):
+//!
+//! | Field            | Type          | Description                                      |
+//! |------------------|---------------|--------------------------------------------------|
+//! | magic            | `[u8; 4]`     | `ERFW`                                           |
+//! | format version   | `u8`          | `1`                                              |
+//! | reserved         | `[u8; 3]`     |                                                  |
+//! | usb_vid          | `u16`         | USB vendor ID of the target device               |
+//! | usb_pid          | `u16`         | USB product ID of the target device              |
+//! | firmware version | `u16`         | e.g. `106` for version `1.06`                    |
+//! | reserved         | `u16`         |                                                  |
+//! | payload length   | `u32`         | Length of the payload, in bytes                  |
+//! | checksum         | `u32`         | CRC-32 (IEEE) of the payload                     |
+//! | payload          | `[u8]`        | The firmware, as it is written to the device     |
+//!
+//! An image is only staged if its size and checksum match, and if it has been built for the
+//! device. The payload is written block by block through the `FirmwareTrait` of the device,
+//! every block is read back and compared, and the update stops at the first mismatch.
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::hwdevices::DeviceTrait;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Magic bytes at the start of a firmware image
+pub const IMAGE_MAGIC: &[u8; 4] = b"ERFW";
+
+/// The supported version of the container format
+pub const IMAGE_FORMAT_VERSION: u8 = 1;
+
+/// Size of the header of a firmware image, in bytes
+pub const IMAGE_HEADER_SIZE: usize = 24;
+
+/// Size of the device info report of ROCCAT devices, in bytes
+const ROCCAT_DEVICE_INFO_SIZE: usize = 6;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FirmwareError {
+    #[error("Not a firmware image")]
+    InvalidMagic,
+
+    #[error("Unsupported format version of the firmware image: {version}")]
+    UnsupportedFormat { version: u8 },
+
+    #[error("Size mismatch, expected {expected} bytes of payload, found {found}")]
+    SizeMismatch { expected: usize, found: usize },
+
+    #[error("Checksum mismatch, expected 0x{expected:08x}, found 0x{found:08x}")]
+    ChecksumMismatch { expected: u32, found: u32 },
+
+    #[error("The image has been built for device {usb_vid:04x}:{usb_pid:04x}")]
+    DeviceMismatch { usb_vid: u16, usb_pid: u16 },
+
+    #[error("Invalid device info report")]
+    InvalidDeviceInfo,
+
+    #[error("The device did not enter its bootloader")]
+    BootloaderNotActive,
+
+    #[error("Verification failed, the data read back at offset 0x{offset:08x} differs")]
+    VerifyMismatch { offset: u32 },
+
+    #[error("The device reported an invalid block size")]
+    InvalidBlockSize,
+}
+
+/// Whether the device is running its bootloader or its application firmware
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BootloaderState {
+    /// The application firmware is running
+    Inactive,
+
+    /// The device is waiting for a firmware update
+    Active,
+}
+
+impl std::fmt::Display for BootloaderState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BootloaderState::Inactive => write!(f, "inactive"),
+            BootloaderState::Active => write!(f, "active"),
+        }
+    }
+}
+
+/// Information about the installed firmware
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirmwareInfo {
+    /// The raw version, as it is reported by the device, e.g. `106`
+    pub version: u16,
+    pub bootloader: BootloaderState,
+}
+
+impl FirmwareInfo {
+    /// The version in human readable form, e.g. `1.06`
+    pub fn version_string(&self) -> String {
+        format!("{}.{:02}", self.version / 100, self.version % 100)
+    }
+}
+
+/// Device specific staging of firmware updates
+pub trait FirmwareTrait {
+    /// Query whether the device is running its bootloader
+    fn bootloader_state(&self) -> Result<BootloaderState>;
+
+    /// The size of the blocks that are written to the device, in bytes
+    fn block_size(&self) -> usize;
+
+    /// Restart the device into its bootloader
+    fn enter_bootloader(&self) -> Result<()>;
+
+    /// Write a block of the payload at the specified offset
+    fn write_block(&self, offset: u32, data: &[u8]) -> Result<()>;
+
+    /// Read back a block that has been written previously
+    fn read_block(&self, offset: u32, len: usize) -> Result<Vec<u8>>;
+
+    /// Let the device verify the complete payload against the checksum and restart it into the
+    /// application firmware
+    fn leave_bootloader(&self, checksum: u32) -> Result<()>;
+}
+
+/// A validated firmware image
+#[derive(Debug, Clone)]
+pub struct FirmwareImage {
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+    pub version: u16,
+    pub checksum: u32,
+    pub payload: Vec<u8>,
+}
+
+impl FirmwareImage {
+    /// Load and validate a firmware image
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path)?;
+
+        Self::parse(&data)
+    }
+
+    /// Parse a firmware image, and verify the size and the checksum of its payload
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < IMAGE_HEADER_SIZE || &data[0..4] != IMAGE_MAGIC {
+            return Err(FirmwareError::InvalidMagic.into());
+        }
+
+        let mut cursor = Cursor::new(&data[4..IMAGE_HEADER_SIZE]);
+
+        let format_version = cursor.read_u8()?;
+        if format_version != IMAGE_FORMAT_VERSION {
+            return Err(FirmwareError::UnsupportedFormat {
+                version: format_version,
+            }
+            .into());
+        }
+
+        let mut reserved = [0; 3];
+        cursor.read_exact(&mut reserved)?;
+
+        let usb_vid = cursor.read_u16::<LittleEndian>()?;
+        let usb_pid = cursor.read_u16::<LittleEndian>()?;
+        let version = cursor.read_u16::<LittleEndian>()?;
+        let _reserved = cursor.read_u16::<LittleEndian>()?;
+        let payload_len = cursor.read_u32::<LittleEndian>()? as usize;
+        let checksum = cursor.read_u32::<LittleEndian>()?;
+
+        let payload = &data[IMAGE_HEADER_SIZE..];
+
+        if payload.len() != payload_len || payload_len == 0 {
+            return Err(FirmwareError::SizeMismatch {
+                expected: payload_len,
+                found: payload.len(),
+            }
+            .into());
+        }
+
+        let found = crc32(payload);
+        if found != checksum {
+            return Err(FirmwareError::ChecksumMismatch {
+                expected: checksum,
+                found,
+            }
+            .into());
+        }
+
+        Ok(Self {
+            usb_vid,
+            usb_pid,
+            version,
+            checksum,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Returns an error, if the image has not been built for the specified device
+    pub fn check_device(&self, usb_vid: u16, usb_pid: u16) -> Result<()> {
+        if self.usb_vid == usb_vid && self.usb_pid == usb_pid {
+            Ok(())
+        } else {
+            Err(FirmwareError::DeviceMismatch {
+                usb_vid: self.usb_vid,
+                usb_pid: self.usb_pid,
+            }
+            .into())
+        }
+    }
+
+    /// The version in human readable form, e.g. `1.06`
+    pub fn version_string(&self) -> String {
+        format!("{}.{:02}", self.version / 100, self.version % 100)
+    }
+}
+
+/// The result of a (dry) run of the update pipeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlashSummary {
+    pub blocks: usize,
+    pub bytes: usize,
+    pub dry_run: bool,
+}
+
+/// Stage a validated firmware image on a device
+///
+/// In dry-run mode, the device is only queried and the blocks that would be written are
+/// counted. Otherwise the device is restarted into its bootloader, each block is written and
+/// read back, and the update stops at the first mismatch. The device is left in its bootloader
+/// in that case, so that the update may be retried
+pub fn flash(
+    device: &dyn FirmwareTrait,
+    image: &FirmwareImage,
+    dry_run: bool,
+    progress: &mut dyn FnMut(usize, usize),
+) -> Result<FlashSummary> {
+    let block_size = device.block_size();
+    if block_size == 0 {
+        return Err(FirmwareError::InvalidBlockSize.into());
+    }
+
+    // make sure that the device responds, before anything is written
+    let _state = device.bootloader_state()?;
+
+    let blocks = image.payload.chunks(block_size).count();
+
+    let summary = FlashSummary {
+        blocks,
+        bytes: image.payload.len(),
+        dry_run,
+    };
+
+    if dry_run {
+        return Ok(summary);
+    }
+
+    device.enter_bootloader()?;
+
+    if device.bootloader_state()? != BootloaderState::Active {
+        return Err(FirmwareError::BootloaderNotActive.into());
+    }
+
+    for (index, block) in image.payload.chunks(block_size).enumerate() {
+        let offset = (index * block_size) as u32;
+
+        device.write_block(offset, block)?;
+
+        let data = device.read_block(offset, block.len())?;
+        if data != block {
+            return Err(FirmwareError::VerifyMismatch { offset }.into());
+        }
+
+        progress(index + 1, blocks);
+    }
+
+    device.leave_bootloader(image.checksum)?;
+
+    Ok(summary)
+}
+
+/// Query the firmware version of a ROCCAT device, via its device info feature report
+///
+/// Since the device info report is only answered by the application firmware, the bootloader
+/// is reported as inactive
+pub fn query_firmware_info(device: &dyn DeviceTrait, report_id: u8) -> Result<FirmwareInfo> {
+    let buf = device.read_feature_report(report_id, ROCCAT_DEVICE_INFO_SIZE)?;
+
+    if buf.len() < ROCCAT_DEVICE_INFO_SIZE
+        || buf[0] != report_id
+        || buf[1] as usize != ROCCAT_DEVICE_INFO_SIZE
+    {
+        return Err(FirmwareError::InvalidDeviceInfo.into());
+    }
+
+    Ok(FirmwareInfo {
+        version: buf[2] as u16,
+        bootloader: BootloaderState::Inactive,
+    })
+}
+
+/// CRC-32 (IEEE 802.3), as used by zlib
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xedb88320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::{DeviceStatus, RGBA};
+    use byteorder::WriteBytesExt;
+    use std::cell::{Cell, RefCell};
+
+    /// A mock HID transport, that emulates the device info report and the flash memory of a device
+    struct MockDevice {
+        info_report: Vec<u8>,
+        feature_reports: RefCell<Vec<Vec<u8>>>,
+
+        bootloader: Cell<bool>,
+        ignore_bootloader_request: bool,
+        flash: RefCell<Vec<u8>>,
+        corrupt_offset: Option<u32>,
+        written_blocks: Cell<usize>,
+        final_checksum: Cell<Option<u32>>,
+    }
+
+    impl MockDevice {
+        fn new() -> Self {
+            Self {
+                info_report: vec![0x09, 0x06, 106, 0x00, 0x00, 0x00],
+                feature_reports: RefCell::new(Vec::new()),
+                bootloader: Cell::new(false),
+                ignore_bootloader_request: false,
+                flash: RefCell::new(vec![0xff; 1024]),
+                corrupt_offset: None,
+                written_blocks: Cell::new(0),
+                final_checksum: Cell::new(None),
+            }
+        }
+    }
+
+    impl DeviceTrait for MockDevice {
+        fn send_init_sequence(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn write_data_raw(&self, _buf: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn read_data_raw(&self, size: usize) -> Result<Vec<u8>> {
+            Ok(vec![0; size])
+        }
+
+        fn write_feature_report(&self, buffer: &[u8]) -> Result<()> {
+            self.feature_reports.borrow_mut().push(buffer.to_vec());
+
+            Ok(())
+        }
+
+        fn read_feature_report(&self, id: u8, size: usize) -> Result<Vec<u8>> {
+            let mut buf = vec![0; size];
+
+            if id == self.info_report[0] {
+                let len = size.min(self.info_report.len());
+                buf[..len].copy_from_slice(&self.info_report[..len]);
+            } else {
+                buf[0] = id;
+            }
+
+            Ok(buf)
+        }
+
+        fn send_led_map(&self, _led_map: &[RGBA]) -> Result<()> {
+            Ok(())
+        }
+
+        fn send_test_pattern(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn device_status(&self) -> Result<DeviceStatus> {
+            Ok(DeviceStatus(Default::default()))
+        }
+
+        fn firmware_info(&self) -> Result<FirmwareInfo> {
+            query_firmware_info(self, 0x09)
+        }
+
+        fn firmware(&self) -> Option<&dyn FirmwareTrait> {
+            Some(self)
+        }
+    }
+
+    impl FirmwareTrait for MockDevice {
+        fn bootloader_state(&self) -> Result<BootloaderState> {
+            if self.bootloader.get() {
+                Ok(BootloaderState::Active)
+            } else {
+                Ok(BootloaderState::Inactive)
+            }
+        }
+
+        fn block_size(&self) -> usize {
+            64
+        }
+
+        fn enter_bootloader(&self) -> Result<()> {
+            self.write_feature_report(&[0x1f, 0x01])?;
+
+            if !self.ignore_bootloader_request {
+                self.bootloader.set(true);
+            }
+
+            Ok(())
+        }
+
+        fn write_block(&self, offset: u32, data: &[u8]) -> Result<()> {
+            let offset = offset as usize;
+
+            self.flash.borrow_mut()[offset..offset + data.len()].copy_from_slice(data);
+            self.written_blocks.set(self.written_blocks.get() + 1);
+
+            Ok(())
+        }
+
+        fn read_block(&self, offset: u32, len: usize) -> Result<Vec<u8>> {
+            let mut data = self.flash.borrow()[offset as usize..offset as usize + len].to_vec();
+
+            if self.corrupt_offset == Some(offset) {
+                data[0] ^= 0xff;
+            }
+
+            Ok(data)
+        }
+
+        fn leave_bootloader(&self, checksum: u32) -> Result<()> {
+            self.final_checksum.set(Some(checksum));
+            self.bootloader.set(false);
+
+            Ok(())
+        }
+    }
+
+    fn build_image(usb_vid: u16, usb_pid: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = IMAGE_MAGIC.to_vec();
+
+        data.write_u8(IMAGE_FORMAT_VERSION).unwrap();
+        data.extend_from_slice(&[0; 3]);
+        data.write_u16::<LittleEndian>(usb_vid).unwrap();
+        data.write_u16::<LittleEndian>(usb_pid).unwrap();
+        data.write_u16::<LittleEndian>(107).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data.write_u32::<LittleEndian>(payload.len() as u32)
+            .unwrap();
+        data.write_u32::<LittleEndian>(crc32(payload)).unwrap();
+        data.extend_from_slice(payload);
+
+        data
+    }
+
+    fn payload() -> Vec<u8> {
+        (0..200).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+    }
+
+    #[test]
+    fn parse_valid_image() {
+        let image = FirmwareImage::parse(&build_image(0x1e7d, 0x2dd2, &payload())).unwrap();
+
+        assert_eq!(image.version_string(), "1.07");
+        assert_eq!(image.payload, payload());
+        assert!(image.check_device(0x1e7d, 0x2dd2).is_ok());
+        assert!(image.check_device(0x1e7d, 0x2c88).is_err());
+    }
+
+    #[test]
+    fn reject_truncated_or_corrupted_images() {
+        let data = build_image(0x1e7d, 0x2dd2, &payload());
+
+        let err = FirmwareImage::parse(&data[..data.len() - 1]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FirmwareError>(),
+            Some(FirmwareError::SizeMismatch { .. })
+        ));
+
+        let mut corrupted = data.clone();
+        *corrupted.last_mut().unwrap() ^= 0x01;
+
+        let err = FirmwareImage::parse(&corrupted).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FirmwareError>(),
+            Some(FirmwareError::ChecksumMismatch { .. })
+        ));
+
+        let err = FirmwareImage::parse(b"not a firmware image").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FirmwareError>(),
+            Some(FirmwareError::InvalidMagic)
+        ));
+    }
+
+    #[test]
+    fn query_firmware_info_via_feature_report() {
+        let device = MockDevice::new();
+
+        let info = device.firmware_info().unwrap();
+        assert_eq!(info.version_string(), "1.06");
+        assert_eq!(info.bootloader, BootloaderState::Inactive);
+
+        let mut device = MockDevice::new();
+        device.info_report[1] = 0x08;
+
+        assert!(device.firmware_info().is_err());
+    }
+
+    #[test]
+    fn dry_run_does_not_touch_the_device() {
+        let device = MockDevice::new();
+        let image = FirmwareImage::parse(&build_image(0x1e7d, 0x2dd2, &payload())).unwrap();
+
+        let updater = device.firmware().unwrap();
+        let summary = flash(updater, &image, true, &mut |_, _| {}).unwrap();
+
+        assert_eq!(summary.blocks, 4);
+        assert_eq!(summary.bytes, 200);
+        assert!(device.feature_reports.borrow().is_empty());
+        assert_eq!(device.written_blocks.get(), 0);
+    }
+
+    #[test]
+    fn flash_writes_and_verifies_all_blocks() {
+        let device = MockDevice::new();
+        let image = FirmwareImage::parse(&build_image(0x1e7d, 0x2dd2, &payload())).unwrap();
+
+        let mut progress = Vec::new();
+        let summary = flash(&device, &image, false, &mut |done, total| {
+            progress.push((done, total))
+        })
+        .unwrap();
+
+        assert_eq!(summary.blocks, 4);
+        assert_eq!(progress.last(), Some(&(4, 4)));
+        assert_eq!(&device.flash.borrow()[..200], &payload()[..]);
+        assert_eq!(device.final_checksum.get(), Some(image.checksum));
+        assert!(!device.bootloader.get());
+    }
+
+    #[test]
+    fn flash_stops_on_verify_mismatch() {
+        let mut device = MockDevice::new();
+        device.corrupt_offset = Some(64);
+
+        let image = FirmwareImage::parse(&build_image(0x1e7d, 0x2dd2, &payload())).unwrap();
+
+        let err = flash(&device, &image, false, &mut |_, _| {}).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FirmwareError>(),
+            Some(FirmwareError::VerifyMismatch { offset: 64 })
+        ));
+
+        assert_eq!(device.written_blocks.get(), 2);
+        assert_eq!(device.final_checksum.get(), None);
+        assert!(device.bootloader.get());
+    }
+
+    #[test]
+    fn flash_requires_an_active_bootloader() {
+        let mut device = MockDevice::new();
+        device.ignore_bootloader_request = true;
+
+        let image = FirmwareImage::parse(&build_image(0x1e7d, 0x2dd2, &payload())).unwrap();
+
+        let err = flash(&device, &image, false, &mut |_, _| {}).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FirmwareError>(),
+            Some(FirmwareError::BootloaderNotActive)
+        ));
+
+        assert_eq!(device.written_blocks.get(), 0);
+    }
+}
@@ -50,6 +50,8 @@ use std::collections::HashMap;
 use hidapi::{HidApi, HidDevice};
 use thiserror::Error;
 
+use crate::firmware::{FirmwareInfo, FirmwareTrait};
+
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
 #[derive(Error, Debug)]
@@ -111,6 +113,16 @@ pub trait DeviceTrait {
     fn send_test_pattern(&self) -> Result<()>;
 
     fn device_status(&self) -> Result<DeviceStatus>;
+
+    /// Query the version of the installed firmware
+    fn firmware_info(&self) -> Result<FirmwareInfo> {
+        Err(HwDeviceError::OpNotSupported.into())
+    }
+
+    /// Returns the device specific firmware update support, if the driver implements it
+    fn firmware(&self) -> Option<&dyn FirmwareTrait> {
+        None
+    }
 }
 
 pub fn bind_device(
@@ -27,6 +27,7 @@ use std::{cell::RefCell, thread};
 use crate::{constants, eprintln_v, println_v};
 
 use super::{DeviceStatus, DeviceTrait, HwDeviceError, Result, RGBA};
+use crate::firmware::{self, FirmwareInfo};
 
 /// Device specific code for the ROCCAT Elo 7.1 Air
 pub struct RoccatElo71Air {
@@ -518,4 +519,8 @@ impl DeviceTrait for RoccatElo71Air {
             Ok(DeviceStatus(result.0))
         }
     }
+
+    fn firmware_info(&self) -> Result<FirmwareInfo> {
+        firmware::query_firmware_info(self, 0x09)
+    }
 }
@@ -26,6 +26,7 @@ use std::{cell::RefCell, thread};
 use crate::{constants, eprintln_v, println_v};
 
 use super::{DeviceTrait, HwDeviceError, Result, RGBA};
+use crate::firmware::{self, FirmwareInfo};
 
 /// Device specific code for the ROCCAT Kone Aimo mouse
 pub struct RoccatKoneAimo {
@@ -361,4 +362,8 @@ impl DeviceTrait for RoccatKoneAimo {
     fn device_status(&self) -> super::Result<super::DeviceStatus> {
         Err(HwDeviceError::OpNotSupported {}.into())
     }
+
+    fn firmware_info(&self) -> Result<FirmwareInfo> {
+        firmware::query_firmware_info(self, 0x09)
+    }
 }
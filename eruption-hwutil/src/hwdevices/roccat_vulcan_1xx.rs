@@ -28,6 +28,7 @@ use std::{sync::Arc, thread};
 use crate::{constants, eprintln_v, println_v};
 
 use super::{DeviceTrait, HwDeviceError, RGBA};
+use crate::firmware::{self, FirmwareInfo};
 
 pub type Result<T> = super::Result<T>;
 
@@ -624,4 +625,8 @@ impl DeviceTrait for RoccatVulcan1xx {
     fn device_status(&self) -> super::Result<super::DeviceStatus> {
         Err(HwDeviceError::OpNotSupported {}.into())
     }
+
+    fn firmware_info(&self) -> Result<FirmwareInfo> {
+        firmware::query_firmware_info(self, 0x0f)
+    }
 }
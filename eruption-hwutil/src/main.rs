@@ -30,11 +30,13 @@ use i18n_embed::{
     DesktopLanguageRequester,
 };
 use lazy_static::lazy_static;
-use log::error;
+use log::{error, warn};
 use parking_lot::Mutex;
 use rust_embed::RustEmbed;
 use std::{
     env,
+    io::Write,
+    path::PathBuf,
     process::{self},
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -46,6 +48,7 @@ use std::{
 
 mod constants;
 mod device;
+mod firmware;
 mod hwdevices;
 mod util;
 
@@ -130,6 +133,15 @@ lazy_static! {
 pub enum MainError {
     #[error("Unknown error: {description}")]
     UnknownError { description: String },
+
+    #[error("The Eruption daemon is running")]
+    DaemonRunning,
+
+    #[error("Invalid device index: {index}")]
+    InvalidDevice { index: u64 },
+
+    #[error("Firmware updates are not supported by the driver of this device")]
+    FirmwareUpdatesNotSupported,
 }
 
 /// Supported command line arguments
@@ -196,7 +208,16 @@ pub enum FirmwareSubcommands {
 
     /// Flash firmware to device (DANGEROUS, may brick the device)
     #[clap(display_order = 1)]
-    Flash { device: u64 },
+    Flash {
+        device: u64,
+
+        /// The firmware image
+        file: PathBuf,
+
+        /// Validate the image and query the device, but do not write anything to it
+        #[clap(long)]
+        dry_run: bool,
+    },
 }
 
 /// Print license information
@@ -523,7 +544,207 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             }
         }
 
-        Subcommands::Firmware { command: _ } => {}
+        Subcommands::Firmware { command } => match command {
+            FirmwareSubcommands::Info {
+                device: device_index,
+            } => {
+                // create the one and only hidapi instance
+                match hidapi::HidApi::new() {
+                    Ok(hidapi) => {
+                        if let Some((index, device)) =
+                            hidapi.device_list().enumerate().nth(device_index as usize)
+                        {
+                            println!(
+                                "{}: {}/{}",
+                                format!("{:02}", index).bold(),
+                                device.manufacturer_string().unwrap_or("<unknown>").bold(),
+                                device.product_string().unwrap_or("<unknown>").bold()
+                            );
+
+                            if let Ok(dev) = device.open_device(&hidapi) {
+                                let hwdev = hwdevices::bind_device(
+                                    dev,
+                                    &hidapi,
+                                    device.vendor_id(),
+                                    device.product_id(),
+                                )?;
+
+                                match hwdev.firmware_info() {
+                                    Ok(info) => {
+                                        let bootloader = match hwdev.firmware() {
+                                            Some(updater) => updater.bootloader_state()?,
+                                            None => info.bootloader,
+                                        };
+
+                                        println!(
+                                            "Firmware version:     {}",
+                                            info.version_string().bold()
+                                        );
+                                        println!(
+                                            "Bootloader:           {}",
+                                            format!("{}", bootloader).bold()
+                                        );
+                                        println!(
+                                            "Firmware updates:     {}",
+                                            if hwdev.firmware().is_some() {
+                                                "supported".bold()
+                                            } else {
+                                                "not supported by the driver".normal()
+                                            }
+                                        );
+                                    }
+
+                                    Err(e) => {
+                                        error!("Could not query the firmware of the device: {}", e)
+                                    }
+                                }
+                            } else {
+                                error!("Could not open the device, is the device in use?");
+                            }
+                        } else {
+                            return Err(MainError::InvalidDevice {
+                                index: device_index,
+                            }
+                            .into());
+                        }
+                    }
+
+                    Err(_) => {
+                        error!("Could not open HIDAPI");
+                    }
+                }
+            }
+
+            FirmwareSubcommands::Flash {
+                device: device_index,
+                file,
+                dry_run,
+            } => {
+                // the size and the checksum of the image are verified while it is loaded
+                let image = firmware::FirmwareImage::load(&file)?;
+
+                println!(
+                    "Firmware image: version {}, {} bytes, checksum 0x{:08x}",
+                    image.version_string().bold(),
+                    image.payload.len(),
+                    image.checksum
+                );
+
+                if !dry_run && util::is_eruption_daemon_running() {
+                    print_notice();
+
+                    return Err(MainError::DaemonRunning.into());
+                }
+
+                // create the one and only hidapi instance
+                match hidapi::HidApi::new() {
+                    Ok(hidapi) => {
+                        if let Some((index, device)) =
+                            hidapi.device_list().enumerate().nth(device_index as usize)
+                        {
+                            println!(
+                                "{}: {}/{}",
+                                format!("{:02}", index).bold(),
+                                device.manufacturer_string().unwrap_or("<unknown>").bold(),
+                                device.product_string().unwrap_or("<unknown>").bold()
+                            );
+
+                            image.check_device(device.vendor_id(), device.product_id())?;
+
+                            if let Ok(dev) = device.open_device(&hidapi) {
+                                let hwdev = hwdevices::bind_device(
+                                    dev,
+                                    &hidapi,
+                                    device.vendor_id(),
+                                    device.product_id(),
+                                )?;
+
+                                let installed = hwdev.firmware_info();
+
+                                match &installed {
+                                    Ok(info) => println!(
+                                        "Installed firmware: version {}",
+                                        info.version_string().bold()
+                                    ),
+
+                                    Err(e) => {
+                                        error!("Could not query the firmware of the device: {}", e)
+                                    }
+                                }
+
+                                match hwdev.firmware() {
+                                    Some(updater) => {
+                                        let result = firmware::flash(
+                                            updater,
+                                            &image,
+                                            dry_run,
+                                            &mut |done, total| {
+                                                print!("\rWriting block {}/{}", done, total);
+                                                let _ = std::io::stdout().flush();
+                                            },
+                                        );
+
+                                        match result {
+                                            Ok(summary) if summary.dry_run => {
+                                                println!(
+                                                    "Dry run: {} bytes in {} blocks would be written, the device has not been modified",
+                                                    summary.bytes, summary.blocks
+                                                );
+                                            }
+
+                                            Ok(summary) => {
+                                                println!(
+                                                    "\nSuccessfully flashed {} bytes in {} blocks",
+                                                    summary.bytes, summary.blocks
+                                                );
+                                            }
+
+                                            Err(e) => {
+                                                println!();
+                                                error!("Flashing has been aborted: {}", e);
+
+                                                return Err(e);
+                                            }
+                                        }
+                                    }
+
+                                    // without an updater, a dry run still validates the image
+                                    // against the device and its installed firmware
+                                    None if dry_run => {
+                                        let info = installed?;
+
+                                        println!(
+                                            "Dry run: the image has been built for this device, version {} would replace version {}, the device has not been modified",
+                                            image.version_string().bold(),
+                                            info.version_string().bold()
+                                        );
+
+                                        warn!(
+                                            "Firmware updates are not supported by the driver of this device"
+                                        );
+                                    }
+
+                                    None => {
+                                        return Err(MainError::FirmwareUpdatesNotSupported.into())
+                                    }
+                                }
+                            } else {
+                                error!("Could not open the device, is the device in use?");
+                            }
+                        } else {
+                            return Err(MainError::InvalidDevice {
+                                index: device_index,
+                            }
+                            .into());
+                        }
+                    }
+
+                    Err(_) => {
+                        error!("Could not open HIDAPI");
+                    }
+                }
+            }
+        },
 
         Subcommands::Completions { shell } => {
             const BIN_NAME: &str = env!("CARGO_PKG_NAME");
@@ -31,6 +31,7 @@ show-about = Zeigt verfügbare Informationen über eine Keymap
 macros-about = Zeigt eine Liste der verfügbaren Makros in einer Lua Datei
 events-about = Zeigt eine Liste der verfügbaren Linux EVDEV Ereignisse
 compile-about = Übersetzt eine Keymap zu Lua Code und macht sie für Eruption verfügbar
+compile-target-about = Ziel der Übersetzung: auto, lua oder firmware
 mapping-add-about = Ein Tasten Mapping von `source` nach `action` hinzufügen
 mapping-remove-about = Das Tasten Mapping von `source` entfernen
 mapping-enable-about = Aktiviert eine Mapping Regel
@@ -53,6 +54,9 @@ compiling = Übersetze Keymap:
 
 success = Erfolgreich
 
+firmware-remaps = In der Firmware umbelegte Tasten: 
+firmware-fallback = Tasten konnten nicht in der Firmware umbelegt werden, verwende Lua: 
+
 functions-in-file = Funktionen in Lua Datei: 
 
 action-not-implemented = Diese Aktion ist nicht implementiert
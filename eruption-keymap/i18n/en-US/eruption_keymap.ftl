@@ -31,6 +31,7 @@ show-about = Show some information about a keymap
 macros-about = Show a list of available macros in a Lua file
 events-about = Show a list of available Linux EVDEV events
 compile-about = Compile a keymap to Lua code and make it available to Eruption
+compile-target-about = Where to compile the keymap to: auto, lua or firmware
 mapping-add-about = Add a mapping rule for `source` that executes `action`
 mapping-remove-about = Remove the mapping rule for `source`
 mapping-enable-about = Enable a mapping rule
@@ -53,6 +54,9 @@ compiling = Compiling keymap:
 
 success = Success
 
+firmware-remaps = Keys remapped in firmware: 
+firmware-fallback = Could not remap keys in firmware, falling back to Lua: 
+
 functions-in-file = Functions in Lua file: 
 
 action-not-implemented = This action is not implemented
@@ -0,0 +1,436 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Compiles simple one-to-one key remappings to the firmware of keyboards that support it
+//!
+//! Rules that can not be expressed as a firmware remapping are left to the Lua backend.
+
+use std::collections::BTreeMap;
+
+use evdev_rs::enums::EV_KEY;
+use serde::Serialize;
+
+use crate::dbus_client;
+use crate::hwdevices;
+use crate::mapping::{Action, Event, Key, KeyMappingTable, Rule, TableMetadata};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// USB IDs of the keyboards that support key remapping in firmware
+pub const SUPPORTED_DEVICES: &[(u16, u16)] = &[
+    (0x1e7d, 0x3098), // ROCCAT Vulcan 100/12x
+    (0x1e7d, 0x307a), // ROCCAT Vulcan 100/12x
+    (0x1e7d, 0x2fee), // ROCCAT Vulcan TKL
+    (0x1e7d, 0x3124), // ROCCAT Magma
+];
+
+/// Keys that have a USB HID usage ID on the keyboard/keypad page, only those can be
+/// remapped in firmware
+#[rustfmt::skip]
+const REMAPPABLE_KEYS: &[EV_KEY] = &[
+    EV_KEY::KEY_A, EV_KEY::KEY_B, EV_KEY::KEY_C, EV_KEY::KEY_D, EV_KEY::KEY_E, EV_KEY::KEY_F,
+    EV_KEY::KEY_G, EV_KEY::KEY_H, EV_KEY::KEY_I, EV_KEY::KEY_J, EV_KEY::KEY_K, EV_KEY::KEY_L,
+    EV_KEY::KEY_M, EV_KEY::KEY_N, EV_KEY::KEY_O, EV_KEY::KEY_P, EV_KEY::KEY_Q, EV_KEY::KEY_R,
+    EV_KEY::KEY_S, EV_KEY::KEY_T, EV_KEY::KEY_U, EV_KEY::KEY_V, EV_KEY::KEY_W, EV_KEY::KEY_X,
+    EV_KEY::KEY_Y, EV_KEY::KEY_Z,
+
+    EV_KEY::KEY_1, EV_KEY::KEY_2, EV_KEY::KEY_3, EV_KEY::KEY_4, EV_KEY::KEY_5, EV_KEY::KEY_6,
+    EV_KEY::KEY_7, EV_KEY::KEY_8, EV_KEY::KEY_9, EV_KEY::KEY_0,
+
+    EV_KEY::KEY_ENTER, EV_KEY::KEY_ESC, EV_KEY::KEY_BACKSPACE, EV_KEY::KEY_TAB, EV_KEY::KEY_SPACE,
+    EV_KEY::KEY_MINUS, EV_KEY::KEY_EQUAL, EV_KEY::KEY_LEFTBRACE, EV_KEY::KEY_RIGHTBRACE,
+    EV_KEY::KEY_BACKSLASH, EV_KEY::KEY_SEMICOLON, EV_KEY::KEY_APOSTROPHE, EV_KEY::KEY_GRAVE,
+    EV_KEY::KEY_COMMA, EV_KEY::KEY_DOT, EV_KEY::KEY_SLASH, EV_KEY::KEY_CAPSLOCK,
+
+    EV_KEY::KEY_F1, EV_KEY::KEY_F2, EV_KEY::KEY_F3, EV_KEY::KEY_F4, EV_KEY::KEY_F5, EV_KEY::KEY_F6,
+    EV_KEY::KEY_F7, EV_KEY::KEY_F8, EV_KEY::KEY_F9, EV_KEY::KEY_F10, EV_KEY::KEY_F11,
+    EV_KEY::KEY_F12,
+
+    EV_KEY::KEY_SYSRQ, EV_KEY::KEY_SCROLLLOCK, EV_KEY::KEY_PAUSE, EV_KEY::KEY_INSERT,
+    EV_KEY::KEY_HOME, EV_KEY::KEY_PAGEUP, EV_KEY::KEY_DELETE, EV_KEY::KEY_END,
+    EV_KEY::KEY_PAGEDOWN, EV_KEY::KEY_RIGHT, EV_KEY::KEY_LEFT, EV_KEY::KEY_DOWN, EV_KEY::KEY_UP,
+
+    EV_KEY::KEY_NUMLOCK, EV_KEY::KEY_KPSLASH, EV_KEY::KEY_KPASTERISK, EV_KEY::KEY_KPMINUS,
+    EV_KEY::KEY_KPPLUS, EV_KEY::KEY_KPENTER, EV_KEY::KEY_KP1, EV_KEY::KEY_KP2, EV_KEY::KEY_KP3,
+    EV_KEY::KEY_KP4, EV_KEY::KEY_KP5, EV_KEY::KEY_KP6, EV_KEY::KEY_KP7, EV_KEY::KEY_KP8,
+    EV_KEY::KEY_KP9, EV_KEY::KEY_KP0, EV_KEY::KEY_KPDOT,
+
+    EV_KEY::KEY_102ND, EV_KEY::KEY_COMPOSE,
+
+    EV_KEY::KEY_LEFTCTRL, EV_KEY::KEY_LEFTSHIFT, EV_KEY::KEY_LEFTALT, EV_KEY::KEY_LEFTMETA,
+    EV_KEY::KEY_RIGHTCTRL, EV_KEY::KEY_RIGHTSHIFT, EV_KEY::KEY_RIGHTALT, EV_KEY::KEY_RIGHTMETA,
+];
+
+/// Number of layers of a keymap, a firmware remapping is in effect on all of them
+const NUM_LAYERS: usize = 6;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FirmwareError {
+    #[error("The keyboard is not connected: {usb_vid:04x}:{usb_pid:04x}")]
+    DeviceNotConnected { usb_vid: u16, usb_pid: u16 },
+}
+
+/// A one-to-one remapping of a key, as EVDEV key codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct KeyRemap {
+    pub from: u32,
+    pub to: u32,
+}
+
+/// Key remappings per device, identified by its USB vendor and product ID
+pub type FirmwareRemaps = BTreeMap<(u16, u16), Vec<KeyRemap>>;
+
+#[derive(Debug)]
+pub struct FirmwareBackend {}
+
+impl FirmwareBackend {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Split `table` into the key remappings that can be done in firmware, and a table
+    /// holding the remaining rules.
+    ///
+    /// Every supported device that the table refers to has an entry in the result, possibly
+    /// an empty one, so that stale remappings will be removed from the device
+    pub fn partition(&self, table: &KeyMappingTable) -> (FirmwareRemaps, KeyMappingTable) {
+        // a key is remapped in firmware only if all of its enabled rules agree on that, and
+        // if they cover all layers, since the remapping is in effect on every layer
+        let mut candidates: BTreeMap<(u16, u16, usize), Option<KeyRemap>> = BTreeMap::new();
+
+        for (source, rule) in table.mappings().iter().filter(|(_, rule)| rule.enabled) {
+            let (key, remap) = match &source.event {
+                Event::SimpleKeyDown(key) | Event::SimpleKeyUp(key) => {
+                    let all_layers = (1..=NUM_LAYERS).all(|l| source.layers.0.contains(&l));
+
+                    (key, firmware_remap(key, rule).filter(|_| all_layers))
+                }
+
+                // the key has a rule on the Easy Shift+ layers
+                Event::EasyShiftKeyDown(key) | Event::EasyShiftKeyUp(key) => (key, None),
+
+                _ => continue,
+            };
+
+            candidates
+                .entry((key.usb_vid, key.usb_pid, key.key_index))
+                .and_modify(|e| {
+                    if *e != remap {
+                        *e = None
+                    }
+                })
+                .or_insert(remap);
+        }
+
+        let mut remaps = FirmwareRemaps::new();
+
+        for ((usb_vid, usb_pid, _), remap) in candidates.iter() {
+            if SUPPORTED_DEVICES.contains(&(*usb_vid, *usb_pid)) {
+                let entry = remaps.entry((*usb_vid, *usb_pid)).or_default();
+
+                if let Some(remap) = remap {
+                    entry.push(*remap);
+                }
+            }
+        }
+
+        let metadata = table.metadata();
+        let mut remaining = KeyMappingTable::new();
+
+        *remaining.metadata_mut() = TableMetadata::new(
+            metadata.file_name.clone(),
+            metadata.description.clone(),
+            metadata.creation_date,
+        );
+
+        for (source, rule) in table.mappings().iter() {
+            let in_firmware = match &source.event {
+                Event::SimpleKeyDown(key) | Event::SimpleKeyUp(key) if rule.enabled => matches!(
+                    candidates.get(&(key.usb_vid, key.usb_pid, key.key_index)),
+                    Some(Some(_))
+                ),
+
+                _ => false,
+            };
+
+            if !in_firmware {
+                remaining.insert(source.clone(), rule.clone());
+            }
+        }
+
+        (remaps, remaining)
+    }
+
+    /// Send the key remappings to all connected keyboards of the respective make and model.
+    ///
+    /// Nothing is written unless all devices are connected, so that on error the rules may
+    /// be compiled to Lua code instead, without remapping any of the keys twice
+    pub fn apply(&self, remaps: &FirmwareRemaps) -> Result<()> {
+        if remaps.is_empty() {
+            return Ok(());
+        }
+
+        let (keyboards, _mice, _misc) = dbus_client::get_managed_devices()?;
+
+        let mut targets = Vec::new();
+
+        for ((usb_vid, usb_pid), remaps) in remaps.iter() {
+            let indices = keyboards
+                .iter()
+                .enumerate()
+                .filter(|(_, device)| **device == (*usb_vid, *usb_pid))
+                .map(|(index, _)| index as u64)
+                .collect::<Vec<_>>();
+
+            if indices.is_empty() {
+                return Err(FirmwareError::DeviceNotConnected {
+                    usb_vid: *usb_vid,
+                    usb_pid: *usb_pid,
+                }
+                .into());
+            }
+
+            let remaps = serde_json::to_string(remaps)?;
+
+            targets.extend(indices.into_iter().map(|index| (index, remaps.clone())));
+        }
+
+        for (index, remaps) in targets.iter() {
+            dbus_client::set_hardware_key_remap(*index, remaps)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the firmware remapping that is equivalent to `rule`, if there is one
+fn firmware_remap(key: &Key, rule: &Rule) -> Option<KeyRemap> {
+    let device = (key.usb_vid, key.usb_pid);
+
+    if !SUPPORTED_DEVICES.contains(&device) {
+        return None;
+    }
+
+    match &rule.action {
+        Action::InjectKey(dest) => {
+            let from = hwdevices::index_to_ev_key(key.key_index + 1, device)?;
+
+            if is_remappable(from as u32) && is_remappable(dest.event) {
+                Some(KeyRemap {
+                    from: from as u32,
+                    to: dest.event,
+                })
+            } else {
+                None
+            }
+        }
+
+        _ => None,
+    }
+}
+
+fn is_remappable(code: u32) -> bool {
+    REMAPPABLE_KEYS.iter().any(|key| *key as u32 == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapping::{EvdevEvent, Macro, Source};
+    use crate::util;
+
+    const VULCAN: (u16, u16) = (0x1e7d, 0x3098);
+    const VULCAN_PRO: (u16, u16) = (0x1e7d, 0x30f7);
+
+    fn key(symbol: &str, device: (u16, u16)) -> Key {
+        Key::new(util::symbol_to_key_index(symbol, device).unwrap(), device)
+    }
+
+    fn inject(table: &mut KeyMappingTable, event: Event, to: EV_KEY, enabled: bool) {
+        inject_on_layers(table, event, to, enabled, &[1, 2, 3, 4, 5, 6]);
+    }
+
+    fn inject_on_layers(
+        table: &mut KeyMappingTable,
+        event: Event,
+        to: EV_KEY,
+        enabled: bool,
+        layers: &[usize],
+    ) {
+        table.insert(
+            Source::new_with_layers(event, layers),
+            Rule::new(
+                Action::InjectKey(EvdevEvent { event: to as u32 }),
+                "",
+                enabled,
+            ),
+        );
+    }
+
+    #[test]
+    fn simple_remaps_are_done_in_firmware() {
+        let mut table = KeyMappingTable::new();
+
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_CAPSLOCK", VULCAN)),
+            EV_KEY::KEY_LEFTCTRL,
+            true,
+        );
+        inject(
+            &mut table,
+            Event::SimpleKeyUp(key("KEY_CAPSLOCK", VULCAN)),
+            EV_KEY::KEY_LEFTCTRL,
+            true,
+        );
+
+        let (remaps, remaining) = FirmwareBackend::new().partition(&table);
+
+        assert_eq!(
+            remaps.get(&VULCAN),
+            Some(&vec![KeyRemap {
+                from: EV_KEY::KEY_CAPSLOCK as u32,
+                to: EV_KEY::KEY_LEFTCTRL as u32,
+            }])
+        );
+        assert!(remaining.mappings().is_empty());
+    }
+
+    #[test]
+    fn other_rules_are_left_to_lua() {
+        let mut table = KeyMappingTable::new();
+
+        // not a key of the keyboard page
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_A", VULCAN)),
+            EV_KEY::KEY_VOLUMEUP,
+            true,
+        );
+
+        // key down and key up disagree
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_B", VULCAN)),
+            EV_KEY::KEY_C,
+            true,
+        );
+        inject(
+            &mut table,
+            Event::SimpleKeyUp(key("KEY_B", VULCAN)),
+            EV_KEY::KEY_D,
+            true,
+        );
+
+        // disabled rule
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_E", VULCAN)),
+            EV_KEY::KEY_F,
+            false,
+        );
+
+        // Easy Shift+ layer
+        inject(
+            &mut table,
+            Event::EasyShiftKeyDown(key("KEY_G", VULCAN)),
+            EV_KEY::KEY_H,
+            true,
+        );
+
+        // macro
+        table.insert(
+            Source::new(Event::SimpleKeyDown(key("KEY_I", VULCAN))),
+            Rule::new(
+                Action::Call(Macro {
+                    function_name: "easyshift_macro_1".to_string(),
+                }),
+                "",
+                true,
+            ),
+        );
+
+        // the device has no firmware remapping
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_J", VULCAN_PRO)),
+            EV_KEY::KEY_K,
+            true,
+        );
+
+        let (remaps, remaining) = FirmwareBackend::new().partition(&table);
+
+        // stale remappings of the supported device will be removed
+        assert_eq!(remaps.get(&VULCAN), Some(&vec![]));
+        assert_eq!(remaps.get(&VULCAN_PRO), None);
+
+        assert_eq!(remaining.mappings().len(), table.mappings().len());
+    }
+
+    #[test]
+    fn rules_for_some_layers_are_left_to_lua() {
+        let mut table = KeyMappingTable::new();
+
+        // the rule does not cover all layers
+        inject_on_layers(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_A", VULCAN)),
+            EV_KEY::KEY_B,
+            true,
+            &[1, 2],
+        );
+
+        // the rule covers all layers, but there is another rule for the key on layer 2
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_C", VULCAN)),
+            EV_KEY::KEY_D,
+            true,
+        );
+        inject_on_layers(
+            &mut table,
+            Event::SimpleKeyUp(key("KEY_C", VULCAN)),
+            EV_KEY::KEY_D,
+            true,
+            &[2],
+        );
+
+        // the rule covers all layers, but the key has a rule on an Easy Shift+ layer
+        inject(
+            &mut table,
+            Event::SimpleKeyDown(key("KEY_E", VULCAN)),
+            EV_KEY::KEY_F,
+            true,
+        );
+        inject_on_layers(
+            &mut table,
+            Event::EasyShiftKeyDown(key("KEY_E", VULCAN)),
+            EV_KEY::KEY_G,
+            true,
+            &[3],
+        );
+
+        let (remaps, remaining) = FirmwareBackend::new().partition(&table);
+
+        assert_eq!(remaps.get(&VULCAN), Some(&vec![]));
+        assert_eq!(remaining.mappings().len(), table.mappings().len());
+    }
+}
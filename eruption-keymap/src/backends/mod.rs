@@ -23,6 +23,7 @@ use std::path::Path;
 
 use crate::mapping::KeyMappingTable;
 
+pub mod firmware;
 pub mod lua;
 pub mod native;
 
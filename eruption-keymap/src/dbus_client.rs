@@ -80,6 +80,24 @@ pub fn get_managed_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u
     Ok(result)
 }
 
+/// Remap keys in the firmware of the keyboard `device`, `remaps` is a JSON encoded list
+pub fn set_hardware_key_remap(device: u64, remaps: &str) -> Result<()> {
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/devices",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS as u64),
+    );
+
+    let (_result,): (bool,) = proxy.method_call(
+        "org.eruption.Device",
+        "SetHardwareKeyRemap",
+        (device, remaps.to_owned()),
+    )?;
+
+    Ok(())
+}
+
 mod slot {
     // This code was autogenerated with `dbus-codegen-rust -s -d org.eruption -p /org/eruption/slot -m None`, see https://github.com/diwic/dbus-rs
     use dbus::arg;
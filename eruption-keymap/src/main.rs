@@ -50,7 +50,7 @@ use walkdir::WalkDir;
 
 use crate::{
     backends::Backend,
-    backends::{lua::LuaBackend, native::NativeBackend},
+    backends::{firmware::FirmwareBackend, lua::LuaBackend, native::NativeBackend},
     lua_introspection::LuaSyntaxIntrospection,
     mapping::{KeyMappingTable, Rule},
 };
@@ -120,6 +120,7 @@ lazy_static! {
     static ref MACROS_ABOUT: String = tr!("macros-about");
     static ref EVENTS_ABOUT: String = tr!("events-about");
     static ref COMPILE_ABOUT: String = tr!("compile-about");
+    static ref COMPILE_TARGET_ABOUT: String = tr!("compile-target-about");
     static ref MAPPING_ADD_ABOUT: String = tr!("mapping-add-about");
     static ref MAPPING_REMOVE_ABOUT: String = tr!("mapping-remove-about");
     static ref MAPPING_ENABLE_ABOUT: String = tr!("mapping-enable-about");
@@ -191,6 +192,16 @@ pub enum Subcommands {
     Compile {
         #[clap(required = false, short, long, default_value = "default.keymap")]
         keymap: PathBuf,
+
+        #[clap(
+            help(COMPILE_TARGET_ABOUT.as_str()),
+            required = false,
+            short,
+            long,
+            value_enum,
+            default_value = "auto"
+        )]
+        target: CompileTarget,
     },
 
     /// Generate shell completions
@@ -283,6 +294,19 @@ pub enum MappingSubcommands {
     },
 }
 
+/// Targets of the "compile" command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CompileTarget {
+    /// Remap keys in firmware where possible, and use Lua for all other rules
+    Auto,
+
+    /// Use Lua for all rules
+    Lua,
+
+    /// Remap keys in firmware where possible, fail if the keyboard is not available
+    Firmware,
+}
+
 /// Subcommands of the "completions" command
 #[derive(Debug, clap::Parser)]
 pub enum CompletionsSubcommands {
@@ -619,7 +643,7 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             println!("{}", tab);
         }
 
-        Subcommands::Compile { keymap } => {
+        Subcommands::Compile { keymap, target } => {
             let mut path = if keymap.components().count() > 1 {
                 keymap
             } else {
@@ -631,6 +655,31 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
             let table = NativeBackend::from_file(&path)?;
             path.set_extension("lua");
 
+            let table = if target == CompileTarget::Lua {
+                table
+            } else {
+                let backend = FirmwareBackend::new();
+                let (remaps, remaining) = backend.partition(&table);
+
+                match backend.apply(&remaps) {
+                    Ok(()) => {
+                        let count: usize = remaps.values().map(|r| r.len()).sum();
+
+                        println!("{} {}", tr!("firmware-remaps"), count);
+
+                        remaining
+                    }
+
+                    Err(e) if target == CompileTarget::Auto => {
+                        messages::warning!("{} {}", tr!("firmware-fallback"), e);
+
+                        table
+                    }
+
+                    Err(e) => return Err(e),
+                }
+            };
+
             LuaBackend::new().write_to_file(&path, &table)?;
 
             println!("{}", tr!("success"));
@@ -0,0 +1,429 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Actions of process monitor rules, and their textual representation
+//!
+//! The textual representation is used on the command line and on the D-Bus interface,
+//! please see [`USAGE`] for a description of the syntax.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Help text describing the textual representation of actions
+pub const USAGE: &str = r#"
+Actions:
+<name>.profile                              Switch to a profile
+<slot number>                               Switch to a slot
+brightness:<0-100>                          Set the global brightness
+sfx:<on|off>                                Enable or disable sound effects
+ambient:<on|off>                            Enable or disable the ambient effect
+param:[<profile>:]<script>:<param>=<value>  Set a parameter of a script
+<action>; <action>; ...                     Run a sequence of actions
+
+The effects of all actions are reverted when the process exits, or the window loses focus
+"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ActionError {
+    #[error("Syntax error in action: {description}")]
+    Parse { description: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    SwitchToProfile {
+        profile_name: String,
+    },
+    SwitchToSlot {
+        slot_index: u64,
+    },
+    SetParameter {
+        /// The profile to modify, or the active profile if not specified
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        profile_file: Option<String>,
+        script_file: String,
+        parameter_name: String,
+        value: String,
+    },
+    SetBrightness {
+        brightness: i64,
+    },
+    EnableSoundFx {
+        enabled: bool,
+    },
+    EnableAmbientEffect {
+        enabled: bool,
+    },
+    Sequence {
+        actions: Vec<Action>,
+    },
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn parse_on_off(s: &str) -> Result<bool, ActionError> {
+    match s.trim().to_lowercase().as_str() {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+
+        _ => Err(ActionError::Parse {
+            description: format!("Expected 'on' or 'off', got '{}'", s),
+        }),
+    }
+}
+
+impl Action {
+    /// Returns the textual representation of the action, that may be parsed by `from_str()`
+    pub fn to_rule_string(&self) -> String {
+        match self {
+            Action::SwitchToProfile { profile_name } => profile_name.to_string(),
+
+            Action::SwitchToSlot { slot_index } => format!("{}", slot_index),
+
+            Action::SetParameter {
+                profile_file,
+                script_file,
+                parameter_name,
+                value,
+            } => match profile_file {
+                Some(profile_file) => format!(
+                    "param:{}:{}:{}={}",
+                    profile_file, script_file, parameter_name, value
+                ),
+
+                None => format!("param:{}:{}={}", script_file, parameter_name, value),
+            },
+
+            Action::SetBrightness { brightness } => format!("brightness:{}", brightness),
+
+            Action::EnableSoundFx { enabled } => format!("sfx:{}", on_off(*enabled)),
+
+            Action::EnableAmbientEffect { enabled } => format!("ambient:{}", on_off(*enabled)),
+
+            Action::Sequence { actions } => actions
+                .iter()
+                .map(|a| a.to_rule_string())
+                .collect::<Vec<_>>()
+                .join("; "),
+        }
+    }
+
+    /// Slots are numbered starting from 1 on the command line, convert them to indices
+    pub fn slot_numbers_to_indices(self) -> Self {
+        match self {
+            Action::SwitchToSlot { slot_index } => Action::SwitchToSlot {
+                slot_index: slot_index.saturating_sub(1),
+            },
+
+            Action::Sequence { actions } => Action::Sequence {
+                actions: actions
+                    .into_iter()
+                    .map(|a| a.slot_numbers_to_indices())
+                    .collect(),
+            },
+
+            action => action,
+        }
+    }
+
+    fn parse_single(s: &str) -> Result<Self, ActionError> {
+        if let Some(value) = s.strip_prefix("brightness:") {
+            let brightness = value
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| ActionError::Parse {
+                    description: format!("Invalid brightness '{}'", value),
+                })?;
+
+            if !(0..=100).contains(&brightness) {
+                return Err(ActionError::Parse {
+                    description: format!("Brightness {} is out of range 0-100", brightness),
+                });
+            }
+
+            Ok(Action::SetBrightness { brightness })
+        } else if let Some(value) = s.strip_prefix("sfx:") {
+            Ok(Action::EnableSoundFx {
+                enabled: parse_on_off(value)?,
+            })
+        } else if let Some(value) = s.strip_prefix("ambient:") {
+            Ok(Action::EnableAmbientEffect {
+                enabled: parse_on_off(value)?,
+            })
+        } else if let Some(value) = s.strip_prefix("param:") {
+            let (target, value) = value.split_once('=').ok_or_else(|| ActionError::Parse {
+                description: "Expected <script>:<param>=<value>".to_string(),
+            })?;
+
+            let target = target.split(':').collect::<Vec<_>>();
+
+            let (profile_file, script_file, parameter_name) = match target.as_slice() {
+                [script_file, parameter_name] => (None, *script_file, *parameter_name),
+
+                [profile_file, script_file, parameter_name] => (
+                    Some(profile_file.to_string()),
+                    *script_file,
+                    *parameter_name,
+                ),
+
+                _ => {
+                    return Err(ActionError::Parse {
+                        description: "Expected [<profile>:]<script>:<param>=<value>".to_string(),
+                    })
+                }
+            };
+
+            Ok(Action::SetParameter {
+                profile_file,
+                script_file: script_file.to_string(),
+                parameter_name: parameter_name.to_string(),
+                value: value.to_string(),
+            })
+        } else if s.contains(".profile") {
+            Ok(Action::SwitchToProfile {
+                profile_name: s.to_string(),
+            })
+        } else {
+            let slot_index = s.parse::<u64>().map_err(|_| ActionError::Parse {
+                description: format!("Unknown action '{}'", s),
+            })?;
+
+            Ok(Action::SwitchToSlot { slot_index })
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = ActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut actions = s
+            .split(';')
+            .map(|a| a.trim())
+            .filter(|a| !a.is_empty())
+            .map(Action::parse_single)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match actions.len() {
+            0 => Err(ActionError::Parse {
+                description: "No action specified".to_string(),
+            }),
+
+            1 => Ok(actions.remove(0)),
+
+            _ => Ok(Action::Sequence { actions }),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::SwitchToProfile { profile_name } => {
+                write!(f, "Switch to profile: {}", profile_name)?;
+            }
+
+            Action::SwitchToSlot { slot_index } => {
+                write!(f, "Switch to slot: {}", slot_index + 1)?;
+            }
+
+            Action::SetParameter {
+                profile_file,
+                script_file,
+                parameter_name,
+                value,
+            } => {
+                write!(
+                    f,
+                    "Set parameter: {}: {} = {}",
+                    script_file, parameter_name, value
+                )?;
+
+                if let Some(profile_file) = profile_file {
+                    write!(f, " (in {})", profile_file)?;
+                }
+            }
+
+            Action::SetBrightness { brightness } => {
+                write!(f, "Set brightness: {}", brightness)?;
+            }
+
+            Action::EnableSoundFx { enabled } => {
+                write!(f, "Sound effects: {}", on_off(*enabled))?;
+            }
+
+            Action::EnableAmbientEffect { enabled } => {
+                write!(f, "Ambient effect: {}", on_off(*enabled))?;
+            }
+
+            Action::Sequence { actions } => {
+                let actions = actions
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", then ");
+
+                write!(f, "{}", actions)?;
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Action {
+        Action::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn parse_actions() {
+        assert_eq!(
+            parse("gaming.profile"),
+            Action::SwitchToProfile {
+                profile_name: "gaming.profile".to_string()
+            }
+        );
+
+        assert_eq!(parse(" 2 "), Action::SwitchToSlot { slot_index: 2 });
+
+        assert_eq!(
+            parse("param:solid.lua:color_background=0xff00ff00"),
+            Action::SetParameter {
+                profile_file: None,
+                script_file: "solid.lua".to_string(),
+                parameter_name: "color_background".to_string(),
+                value: "0xff00ff00".to_string(),
+            }
+        );
+
+        assert_eq!(
+            parse("param:gaming.profile:solid.lua:opacity=0.5"),
+            Action::SetParameter {
+                profile_file: Some("gaming.profile".to_string()),
+                script_file: "solid.lua".to_string(),
+                parameter_name: "opacity".to_string(),
+                value: "0.5".to_string(),
+            }
+        );
+
+        assert_eq!(
+            parse("brightness:85"),
+            Action::SetBrightness { brightness: 85 }
+        );
+
+        assert_eq!(parse("sfx:on"), Action::EnableSoundFx { enabled: true });
+        assert_eq!(
+            parse("ambient:false"),
+            Action::EnableAmbientEffect { enabled: false }
+        );
+    }
+
+    #[test]
+    fn parse_sequences() {
+        assert_eq!(
+            parse("brightness:20; sfx:off;"),
+            Action::Sequence {
+                actions: vec![
+                    Action::SetBrightness { brightness: 20 },
+                    Action::EnableSoundFx { enabled: false },
+                ]
+            }
+        );
+
+        // a sequence of a single action is just that action
+        assert_eq!(parse("3;"), Action::SwitchToSlot { slot_index: 3 });
+    }
+
+    #[test]
+    fn reject_invalid_actions() {
+        assert!(Action::from_str("").is_err());
+        assert!(Action::from_str(" ; ").is_err());
+        assert!(Action::from_str("brightness:101").is_err());
+        assert!(Action::from_str("brightness:bright").is_err());
+        assert!(Action::from_str("sfx:maybe").is_err());
+        assert!(Action::from_str("param:solid.lua:color_background").is_err());
+        assert!(Action::from_str("param:a:b:c:d=1").is_err());
+        assert!(Action::from_str("unknown").is_err());
+        assert!(Action::from_str("1; unknown").is_err());
+    }
+
+    #[test]
+    fn slot_numbers_are_converted_to_indices() {
+        assert_eq!(
+            parse("1; gaming.profile").slot_numbers_to_indices(),
+            Action::Sequence {
+                actions: vec![
+                    Action::SwitchToSlot { slot_index: 0 },
+                    Action::SwitchToProfile {
+                        profile_name: "gaming.profile".to_string()
+                    },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn rule_strings_round_trip() {
+        let actions = [
+            Action::SwitchToProfile {
+                profile_name: "gaming.profile".to_string(),
+            },
+            Action::SwitchToSlot { slot_index: 0 },
+            Action::SetParameter {
+                profile_file: None,
+                script_file: "solid.lua".to_string(),
+                parameter_name: "color_background".to_string(),
+                value: "0xff00ff00".to_string(),
+            },
+            Action::SetParameter {
+                profile_file: Some("gaming.profile".to_string()),
+                script_file: "solid.lua".to_string(),
+                parameter_name: "expression".to_string(),
+                value: "a=b".to_string(),
+            },
+            Action::SetBrightness { brightness: 0 },
+            Action::EnableSoundFx { enabled: true },
+            Action::EnableAmbientEffect { enabled: false },
+            Action::Sequence {
+                actions: vec![
+                    Action::SwitchToSlot { slot_index: 3 },
+                    Action::SetBrightness { brightness: 100 },
+                    Action::EnableAmbientEffect { enabled: true },
+                ],
+            },
+        ];
+
+        for action in actions {
+            assert_eq!(parse(&action.to_rule_string()), action);
+        }
+    }
+}
@@ -0,0 +1,514 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Evaluation of rule selectors against the current state of the system
+//!
+//! Rules are divided into three kinds, depending on the selectors they contain:
+//! * Process rules are evaluated each time a process is executed
+//! * Window rules are evaluated when the focus changes, the first matching rule wins
+//! * Condition rules are evaluated whenever the state of the system changes; each of
+//!   them takes effect while it matches, and is reverted as soon as it stops matching
+//!
+//! If multiple rules of the same kind match, the rule with the highest priority wins. Rules
+//! of equal priority are ranked by their order in the rules file.
+
+use indexmap::IndexMap;
+use regex::Regex;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::actions::Action;
+use crate::rule_metadata::RuleMetadata;
+use crate::selectors::{PowerSource, Selector, Weekday, WindowFocusedSelectorMode};
+use crate::sensors::WindowSensorData;
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Process,
+    Window,
+    Condition,
+}
+
+impl fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleKind::Process => write!(f, "process"),
+            RuleKind::Window => write!(f, "window"),
+            RuleKind::Condition => write!(f, "condition"),
+        }
+    }
+}
+
+/// The local time, with a resolution of minutes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalTime {
+    pub weekday: Weekday,
+    pub minute_of_day: u32,
+}
+
+impl LocalTime {
+    pub fn now() -> Self {
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+
+        unsafe {
+            let t = libc::time(std::ptr::null_mut());
+            libc::localtime_r(&t, &mut tm);
+        }
+
+        // tm_wday counts the days since Sunday
+        let weekday = Weekday::ALL[(tm.tm_wday as usize + 6) % 7];
+        let minute_of_day = (tm.tm_hour * 60 + tm.tm_min) as u32;
+
+        Self {
+            weekday,
+            minute_of_day,
+        }
+    }
+
+    fn previous_weekday(&self) -> Weekday {
+        let index = Weekday::ALL
+            .iter()
+            .position(|d| *d == self.weekday)
+            .unwrap_or_default();
+
+        Weekday::ALL[(index + 6) % 7]
+    }
+}
+
+/// The state of the system, as gathered by the system sensor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemState {
+    pub local_time: LocalTime,
+
+    /// The number of seconds the user has been idle, or 0 if the user is active
+    pub idle_seconds: u64,
+
+    pub screen_locked: bool,
+
+    pub on_battery: bool,
+
+    /// USB vendor and product ids of all connected devices
+    pub usb_devices: HashSet<(u16, u16)>,
+}
+
+impl Default for SystemState {
+    fn default() -> Self {
+        Self {
+            local_time: LocalTime::now(),
+            idle_seconds: 0,
+            screen_locked: false,
+            on_battery: false,
+            usb_devices: HashSet::new(),
+        }
+    }
+}
+
+/// The properties of the currently focused window
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowState {
+    pub window_name: String,
+    pub window_instance: String,
+    pub window_class: String,
+}
+
+impl WindowState {
+    pub fn from_sensor_data(data: &dyn WindowSensorData) -> Self {
+        Self {
+            window_name: data.window_name().unwrap_or_default().to_string(),
+            window_instance: data.window_instance().unwrap_or_default().to_string(),
+            window_class: data.window_class().unwrap_or_default().to_string(),
+        }
+    }
+}
+
+/// Everything a selector may be matched against
+pub struct MatchContext<'a> {
+    /// The comm of a process that has just been executed
+    pub comm: Option<&'a str>,
+
+    pub window: Option<&'a WindowState>,
+
+    pub system: &'a SystemState,
+}
+
+impl Selector {
+    pub fn kind(&self) -> RuleKind {
+        fn contains(selector: &Selector, kind: RuleKind) -> bool {
+            match selector {
+                Selector::ProcessExec { .. } => kind == RuleKind::Process,
+                Selector::WindowFocused { .. } => kind == RuleKind::Window,
+
+                Selector::All { selectors } | Selector::Any { selectors } => {
+                    selectors.iter().any(|s| contains(s, kind))
+                }
+
+                Selector::Not { selector } => contains(selector, kind),
+
+                _ => false,
+            }
+        }
+
+        if contains(self, RuleKind::Process) {
+            RuleKind::Process
+        } else if contains(self, RuleKind::Window) {
+            RuleKind::Window
+        } else {
+            RuleKind::Condition
+        }
+    }
+
+    pub fn matches(&self, ctx: &MatchContext) -> Result<bool> {
+        let result = match self {
+            Selector::ProcessExec { comm } => match ctx.comm {
+                Some(c) => Regex::new(comm)?.is_match(c),
+                None => false,
+            },
+
+            Selector::WindowFocused { mode, regex } => match ctx.window {
+                Some(window) => {
+                    let re = Regex::new(regex)?;
+
+                    match mode {
+                        WindowFocusedSelectorMode::WindowName => re.is_match(&window.window_name),
+
+                        WindowFocusedSelectorMode::WindowInstance => {
+                            re.is_match(&window.window_instance)
+                        }
+
+                        WindowFocusedSelectorMode::WindowClass => re.is_match(&window.window_class),
+                    }
+                }
+
+                None => false,
+            },
+
+            Selector::TimeOfDay { days, from, to } => {
+                let now = ctx.system.local_time;
+
+                let from = from.hour as u32 * 60 + from.minute as u32;
+                let to = to.hour as u32 * 60 + to.minute as u32;
+
+                let on_day = |day: Weekday| days.is_empty() || days.contains(&day);
+
+                if from < to {
+                    on_day(now.weekday) && (from..to).contains(&now.minute_of_day)
+                } else if from > to {
+                    // the time window spans midnight
+                    (on_day(now.weekday) && now.minute_of_day >= from)
+                        || (on_day(now.previous_weekday()) && now.minute_of_day < to)
+                } else {
+                    on_day(now.weekday)
+                }
+            }
+
+            Selector::UserIdle { seconds } => {
+                ctx.system.idle_seconds > 0 && ctx.system.idle_seconds >= *seconds
+            }
+
+            Selector::ScreenLocked => ctx.system.screen_locked,
+
+            Selector::PowerSource { source } => match source {
+                PowerSource::Ac => !ctx.system.on_battery,
+                PowerSource::Battery => ctx.system.on_battery,
+            },
+
+            Selector::DeviceConnected { usb_vid, usb_pid } => {
+                ctx.system.usb_devices.contains(&(*usb_vid, *usb_pid))
+            }
+
+            Selector::All { selectors } => {
+                for selector in selectors {
+                    if !selector.matches(ctx)? {
+                        return Ok(false);
+                    }
+                }
+
+                true
+            }
+
+            Selector::Any { selectors } => {
+                for selector in selectors {
+                    if selector.matches(ctx)? {
+                        return Ok(true);
+                    }
+                }
+
+                false
+            }
+
+            Selector::Not { selector } => !selector.matches(ctx)?,
+        };
+
+        Ok(result)
+    }
+}
+
+/// Find the enabled rule of kind `kind` that matches `ctx` and has the highest priority.
+/// Returns the index of the rule in `rules`
+pub fn find_winning_rule(
+    rules: &IndexMap<Selector, (RuleMetadata, Action)>,
+    kind: RuleKind,
+    ctx: &MatchContext,
+) -> Result<Option<usize>> {
+    let mut result: Option<(usize, i32)> = None;
+
+    for (index, (selector, (metadata, _action))) in rules.iter().enumerate() {
+        if metadata.enabled
+            && selector.kind() == kind
+            && result
+                .map(|(_, priority)| metadata.priority > priority)
+                .unwrap_or(true)
+            && selector.matches(ctx)?
+        {
+            result = Some((index, metadata.priority));
+        }
+    }
+
+    Ok(result.map(|(index, _)| index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selectors::ClockTime;
+
+    fn system_state(weekday: Weekday, hour: u32, minute: u32) -> SystemState {
+        SystemState {
+            local_time: LocalTime {
+                weekday,
+                minute_of_day: hour * 60 + minute,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn matches(selector: &Selector, system: &SystemState) -> bool {
+        let ctx = MatchContext {
+            comm: None,
+            window: None,
+            system,
+        };
+
+        selector.matches(&ctx).unwrap()
+    }
+
+    fn time_of_day(days: &[Weekday], from: (u8, u8), to: (u8, u8)) -> Selector {
+        Selector::TimeOfDay {
+            days: days.to_vec(),
+            from: ClockTime {
+                hour: from.0,
+                minute: from.1,
+            },
+            to: ClockTime {
+                hour: to.0,
+                minute: to.1,
+            },
+        }
+    }
+
+    #[test]
+    fn time_windows() {
+        let selector = time_of_day(&[], (9, 0), (17, 30));
+
+        assert!(!matches(&selector, &system_state(Weekday::Mon, 8, 59)));
+        assert!(matches(&selector, &system_state(Weekday::Mon, 9, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Sun, 17, 29)));
+        assert!(!matches(&selector, &system_state(Weekday::Sun, 17, 30)));
+
+        // a window that starts and ends at the same time spans the whole day
+        let selector = time_of_day(&[Weekday::Wed], (12, 0), (12, 0));
+
+        assert!(matches(&selector, &system_state(Weekday::Wed, 0, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Thu, 12, 0)));
+    }
+
+    #[test]
+    fn time_windows_spanning_midnight() {
+        let selector = time_of_day(&[], (22, 0), (6, 0));
+
+        assert!(!matches(&selector, &system_state(Weekday::Mon, 21, 59)));
+        assert!(matches(&selector, &system_state(Weekday::Mon, 22, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Mon, 23, 59)));
+        assert!(matches(&selector, &system_state(Weekday::Tue, 0, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Tue, 5, 59)));
+        assert!(!matches(&selector, &system_state(Weekday::Tue, 6, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Tue, 12, 0)));
+    }
+
+    #[test]
+    fn weekdays() {
+        let selector = time_of_day(&Weekday::WEEKDAYS, (8, 0), (18, 0));
+
+        assert!(matches(&selector, &system_state(Weekday::Mon, 12, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Fri, 12, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Sat, 12, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Sun, 12, 0)));
+    }
+
+    #[test]
+    fn weekdays_of_windows_spanning_midnight() {
+        // the days refer to the day on which the window starts
+        let selector = time_of_day(&[Weekday::Fri, Weekday::Sat], (23, 0), (2, 0));
+
+        assert!(!matches(&selector, &system_state(Weekday::Thu, 23, 30)));
+        assert!(!matches(&selector, &system_state(Weekday::Fri, 1, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Fri, 23, 30)));
+        assert!(matches(&selector, &system_state(Weekday::Sat, 1, 0)));
+        assert!(matches(&selector, &system_state(Weekday::Sun, 1, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Sun, 23, 30)));
+
+        // Sunday wraps around to Monday
+        let selector = time_of_day(&[Weekday::Sun], (22, 0), (4, 0));
+
+        assert!(matches(&selector, &system_state(Weekday::Mon, 3, 0)));
+        assert!(!matches(&selector, &system_state(Weekday::Sun, 3, 0)));
+    }
+
+    #[test]
+    fn composite_selectors() {
+        let on_battery = SystemState {
+            on_battery: true,
+            ..system_state(Weekday::Mon, 12, 0)
+        };
+
+        let locked = SystemState {
+            screen_locked: true,
+            ..system_state(Weekday::Mon, 12, 0)
+        };
+
+        let battery = Selector::PowerSource {
+            source: PowerSource::Battery,
+        };
+
+        let all = Selector::All {
+            selectors: vec![battery.clone(), Selector::ScreenLocked],
+        };
+
+        let any = Selector::Any {
+            selectors: vec![battery.clone(), Selector::ScreenLocked],
+        };
+
+        let not = Selector::Not {
+            selector: Box::new(all.clone()),
+        };
+
+        assert!(!matches(&all, &on_battery));
+        assert!(!matches(&all, &locked));
+        assert!(matches(
+            &all,
+            &SystemState {
+                screen_locked: true,
+                ..on_battery.clone()
+            }
+        ));
+
+        assert!(matches(&any, &on_battery));
+        assert!(matches(&any, &locked));
+        assert!(!matches(&any, &system_state(Weekday::Mon, 12, 0)));
+
+        assert!(matches(&not, &on_battery));
+    }
+
+    fn window_class(regex: &str) -> Selector {
+        Selector::WindowFocused {
+            mode: WindowFocusedSelectorMode::WindowClass,
+            regex: regex.to_string(),
+        }
+    }
+
+    fn rule(priority: i32, slot_index: u64) -> (RuleMetadata, Action) {
+        (
+            RuleMetadata {
+                priority,
+                ..Default::default()
+            },
+            Action::SwitchToSlot { slot_index },
+        )
+    }
+
+    fn winner(rules: &IndexMap<Selector, (RuleMetadata, Action)>, class: &str) -> Option<usize> {
+        let window = WindowState {
+            window_class: class.to_string(),
+            ..Default::default()
+        };
+
+        let system = system_state(Weekday::Mon, 12, 0);
+
+        let ctx = MatchContext {
+            comm: None,
+            window: Some(&window),
+            system: &system,
+        };
+
+        find_winning_rule(rules, RuleKind::Window, &ctx).unwrap()
+    }
+
+    #[test]
+    fn highest_priority_wins() {
+        let mut rules = IndexMap::new();
+
+        rules.insert(window_class(".*"), rule(i32::MIN, 0));
+        rules.insert(window_class("steam.*"), rule(0, 1));
+        rules.insert(window_class("steam_app_.*"), rule(10, 2));
+        rules.insert(window_class("firefox"), rule(20, 3));
+
+        assert_eq!(winner(&rules, "steam_app_42"), Some(2));
+        assert_eq!(winner(&rules, "steam"), Some(1));
+        assert_eq!(winner(&rules, "xterm"), Some(0));
+    }
+
+    #[test]
+    fn priority_ties_are_won_by_the_first_rule() {
+        let mut rules = IndexMap::new();
+
+        rules.insert(window_class("steam.*"), rule(5, 1));
+        rules.insert(window_class(".*app.*"), rule(5, 2));
+        rules.insert(window_class("steam_app_.*"), rule(5, 3));
+
+        assert_eq!(winner(&rules, "steam_app_42"), Some(0));
+        assert_eq!(winner(&rules, "my_app"), Some(1));
+    }
+
+    #[test]
+    fn disabled_rules_and_rules_of_other_kinds_never_win() {
+        let mut rules = IndexMap::new();
+
+        rules.insert(window_class("steam.*"), rule(0, 1));
+
+        let (mut metadata, action) = rule(10, 2);
+        metadata.enabled = false;
+        rules.insert(window_class("steam_app_.*"), (metadata, action));
+
+        rules.insert(
+            Selector::ProcessExec {
+                comm: ".*".to_string(),
+            },
+            rule(20, 3),
+        );
+
+        rules.insert(time_of_day(&[], (0, 0), (0, 0)), rule(30, 4));
+
+        assert_eq!(winner(&rules, "steam_app_42"), Some(0));
+        assert_eq!(winner(&rules, "xterm"), None);
+    }
+}
@@ -30,6 +30,9 @@ pub const STATE_DIR: &str = "~/.local/share/eruption-process-monitor/";
 /// Main loop delay
 pub const MAIN_LOOP_SLEEP_MILLIS: u64 = 250;
 
+/// Interval in which the system sensor gathers the state of the system
+pub const SYSTEM_SENSOR_POLL_MILLIS: u64 = 1000;
+
 /// Timeout of D-Bus operations
 pub const DBUS_TIMEOUT_MILLIS: u64 = 5000;
 
@@ -43,3 +46,15 @@ pub const DEFAULT_X11_DISPLAY: &str = ":0";
 
 /// The default profile to use
 pub const DEFAULT_PROFILE: &str = "default.profile";
+
+/// Directory that contains the profile files, used to resolve relative paths
+pub const DEFAULT_PROFILE_DIR: &str = "/var/lib/eruption/profiles/";
+
+/// Directory that contains the Lua scripts, used to resolve relative paths
+pub const DEFAULT_SCRIPT_DIR: &str = "/usr/share/eruption/scripts/";
+
+/// Directory that contains the power supplies, used by the system sensor plugin
+pub const POWER_SUPPLY_DIR: &str = "/sys/class/power_supply/";
+
+/// Directory that contains the connected USB devices, used by the system sensor plugin
+pub const USB_DEVICES_DIR: &str = "/sys/bus/usb/devices/";
@@ -110,6 +110,174 @@ pub fn get_active_slot() -> Result<u64> {
     Ok(result)
 }
 
+/// Get the current value of a parameter of a script in a profile
+pub fn get_parameter(profile_file: &str, script_file: &str, param_name: &str) -> Result<String> {
+    use profile::OrgEruptionProfile;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/profile",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let result = proxy.get_parameter(profile_file, script_file, param_name)?;
+
+    Ok(result)
+}
+
+/// Set a parameter of a script in a profile
+pub fn set_parameter(
+    profile_file: &str,
+    script_file: &str,
+    param_name: &str,
+    value: &str,
+) -> Result<()> {
+    use profile::OrgEruptionProfile;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/profile",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let _result = proxy.set_parameter(profile_file, script_file, param_name, value)?;
+
+    Ok(())
+}
+
+pub fn get_brightness() -> Result<i64> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let result = proxy.brightness()?;
+
+    Ok(result)
+}
+
+/// Set the global brightness
+pub fn set_brightness(brightness: i64) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    proxy.set_brightness(brightness)?;
+
+    Ok(())
+}
+
+pub fn is_sound_fx_enabled() -> Result<bool> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let result = proxy.enable_sfx()?;
+
+    Ok(result)
+}
+
+/// Enable or disable sound effects
+pub fn set_sound_fx(enabled: bool) -> Result<()> {
+    use config::OrgEruptionConfig;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.eruption",
+        "/org/eruption/config",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    proxy.set_enable_sfx(enabled)?;
+
+    Ok(())
+}
+
+pub fn is_ambient_effect_enabled() -> Result<bool> {
+    use fx_proxy::OrgEruptionFxProxyEffects;
+
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.eruption.fx_proxy",
+        "/org/eruption/fx_proxy/effects",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let result = proxy.ambient_effect()?;
+
+    Ok(result)
+}
+
+/// Enable or disable the ambient effect of eruption-fx-proxy
+pub fn set_ambient_effect(enabled: bool) -> Result<()> {
+    use fx_proxy::OrgEruptionFxProxyEffects;
+
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.eruption.fx_proxy",
+        "/org/eruption/fx_proxy/effects",
+        Duration::from_secs(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    if enabled {
+        proxy.enable_ambient_effect()?;
+    } else {
+        proxy.disable_ambient_effect()?;
+    }
+
+    Ok(())
+}
+
+/// Get the idle and lock state of the current login session from logind
+///
+/// Returns a tuple of: (idle hint, idle since (in microseconds since the epoch), locked hint)
+pub fn get_login_session_state() -> Result<(bool, u64, bool)> {
+    use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+
+    let conn = Connection::new_system()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1/session/auto",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let idle_hint: bool = proxy.get("org.freedesktop.login1.Session", "IdleHint")?;
+    let idle_since: u64 = proxy.get("org.freedesktop.login1.Session", "IdleSinceHint")?;
+    let locked_hint: bool = proxy.get("org.freedesktop.login1.Session", "LockedHint")?;
+
+    Ok((idle_hint, idle_since, locked_hint))
+}
+
+/// Query the screen saver of the desktop session, whether the screen is locked
+pub fn is_screensaver_active() -> Result<bool> {
+    let conn = Connection::new_session()?;
+    let proxy = conn.with_proxy(
+        "org.freedesktop.ScreenSaver",
+        "/org/freedesktop/ScreenSaver",
+        Duration::from_millis(constants::DBUS_TIMEOUT_MILLIS),
+    );
+
+    let (result,): (bool,) = proxy.method_call("org.freedesktop.ScreenSaver", "GetActive", ())?;
+
+    Ok(result)
+}
+
 pub mod slot {
     // This code was autogenerated with `dbus-codegen-rust -s -d org.eruption -p /org/eruption/slot -m None`, see https://github.com/diwic/dbus-rs
     use dbus::arg;
@@ -315,6 +483,19 @@ pub mod profile {
         fn enum_profiles(&self) -> Result<Vec<(String, String)>, dbus::Error>;
         fn switch_profile(&self, filename: &str) -> Result<bool, dbus::Error>;
         fn active_profile(&self) -> Result<String, dbus::Error>;
+        fn set_parameter(
+            &self,
+            profile_file: &str,
+            script_file: &str,
+            param_name: &str,
+            value: &str,
+        ) -> Result<bool, dbus::Error>;
+        fn get_parameter(
+            &self,
+            profile_file: &str,
+            script_file: &str,
+            param_name: &str,
+        ) -> Result<String, dbus::Error>;
     }
 
     impl<'a, C: ::std::ops::Deref<Target = blocking::Connection>> OrgEruptionProfile
@@ -337,6 +518,35 @@ pub mod profile {
                 "ActiveProfile",
             )
         }
+
+        fn set_parameter(
+            &self,
+            profile_file: &str,
+            script_file: &str,
+            param_name: &str,
+            value: &str,
+        ) -> Result<bool, dbus::Error> {
+            self.method_call(
+                "org.eruption.Profile",
+                "SetParameter",
+                (profile_file, script_file, param_name, value),
+            )
+            .map(|r: (bool,)| r.0)
+        }
+
+        fn get_parameter(
+            &self,
+            profile_file: &str,
+            script_file: &str,
+            param_name: &str,
+        ) -> Result<String, dbus::Error> {
+            self.method_call(
+                "org.eruption.Profile",
+                "GetParameter",
+                (profile_file, script_file, param_name),
+            )
+            .map(|r: (String,)| r.0)
+        }
     }
 
     #[derive(Debug)]
@@ -830,3 +1040,33 @@ pub mod status {
         const INTERFACE: &'static str = "org.freedesktop.DBus.Properties";
     }
 }
+
+pub mod fx_proxy {
+    use dbus::blocking;
+
+    pub trait OrgEruptionFxProxyEffects {
+        fn disable_ambient_effect(&self) -> Result<(), dbus::Error>;
+        fn enable_ambient_effect(&self) -> Result<(), dbus::Error>;
+        fn ambient_effect(&self) -> Result<bool, dbus::Error>;
+    }
+
+    impl<'a, T: blocking::BlockingSender, C: ::std::ops::Deref<Target = T>>
+        OrgEruptionFxProxyEffects for blocking::Proxy<'a, C>
+    {
+        fn disable_ambient_effect(&self) -> Result<(), dbus::Error> {
+            self.method_call("org.eruption.fx_proxy.Effects", "DisableAmbientEffect", ())
+        }
+
+        fn enable_ambient_effect(&self) -> Result<(), dbus::Error> {
+            self.method_call("org.eruption.fx_proxy.Effects", "EnableAmbientEffect", ())
+        }
+
+        fn ambient_effect(&self) -> Result<bool, dbus::Error> {
+            <Self as blocking::stdintf::org_freedesktop_dbus::Properties>::get(
+                self,
+                "org.eruption.fx_proxy.Effects",
+                "AmbientEffect",
+            )
+        }
+    }
+}
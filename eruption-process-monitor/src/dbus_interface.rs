@@ -26,7 +26,7 @@ use indexmap::IndexMap;
 use log::*;
 use std::sync::Arc;
 
-use crate::{Action, RuleMetadata, Selector, WindowFocusedSelectorMode};
+use crate::{Action, RuleMetadata, Selector};
 
 /// D-Bus messages and signals that are processed by the main thread
 #[derive(Debug, Clone)]
@@ -84,45 +84,11 @@ impl DbusApi {
                                 let s = rules_map
                                     .iter()
                                     .map(|(selector, (metadata, action))| {
-                                        let (sensor_val, selector_val) = match selector {
-                                            Selector::ProcessExec { comm } => {
-                                                ("exec".to_string(), comm)
-                                            }
-
-                                            Selector::WindowFocused { mode, regex } => match mode {
-                                                WindowFocusedSelectorMode::WindowName => {
-                                                    ("window-name".to_string(), regex)
-                                                }
-                                                WindowFocusedSelectorMode::WindowInstance => {
-                                                    ("window-instance".to_string(), regex)
-                                                }
-                                                WindowFocusedSelectorMode::WindowClass => {
-                                                    ("window-class".to_string(), regex)
-                                                }
-                                            },
-                                        };
-
-                                        let action_val = match action {
-                                            Action::SwitchToProfile { profile_name } => {
-                                                profile_name.to_string()
-                                            }
-                                            Action::SwitchToSlot { slot_index } => {
-                                                format!("{}", slot_index)
-                                            }
-                                        };
-
-                                        let mut metadata_val = String::new();
-                                        if metadata.enabled {
-                                            metadata_val.push_str("enabled");
-                                        } else {
-                                            metadata_val.push_str("disabled");
-                                        }
-
-                                        if metadata.internal {
-                                            metadata_val.push_str(",internal");
-                                        } else {
-                                            metadata_val.push_str(",user-defined");
-                                        }
+                                        let (sensor_val, selector_val) = selector.to_rule_strings();
+
+                                        let action_val = action.to_rule_string();
+
+                                        let metadata_val = metadata.to_rule_string();
 
                                         (sensor_val, selector_val, action_val, metadata_val)
                                     })
@@ -150,57 +116,13 @@ impl DbusApi {
                                         metadata_val: &str,
                                     ) -> Result<(Selector, (RuleMetadata, Action))>
                                     {
-                                        let sensor;
-
-                                        let action;
-
-                                        match sensor_val {
-                                            "exec" => {
-                                                sensor = Selector::ProcessExec {
-                                                    comm: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-name" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowName,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-instance" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowInstance,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            "window-class" => {
-                                                sensor = Selector::WindowFocused {
-                                                    mode: WindowFocusedSelectorMode::WindowClass,
-                                                    regex: selector_val.into(),
-                                                }
-                                            }
-
-                                            _ => {
-                                                return Err(DbusApiError::InvalidArgument {}.into())
-                                            }
-                                        }
-
-                                        let enabled = metadata_val.contains("enabled");
-                                        let internal = metadata_val.contains("internal");
-
-                                        let metadata = RuleMetadata { enabled, internal };
-
-                                        if action_val.contains(".profile") {
-                                            action = Action::SwitchToProfile {
-                                                profile_name: action_val.to_string(),
-                                            };
-                                        } else {
-                                            action = Action::SwitchToSlot {
-                                                slot_index: action_val.parse::<u64>()?,
-                                            };
-                                        }
+                                        let sensor =
+                                            Selector::from_rule_strings(sensor_val, selector_val)
+                                                .map_err(|_e| DbusApiError::InvalidArgument {})?;
+
+                                        let metadata = RuleMetadata::from_rule_string(metadata_val);
+
+                                        let action = action_val.parse::<Action>()?;
 
                                         Ok((sensor, (metadata, action)))
                                     }
@@ -244,39 +166,11 @@ impl DbusApi {
         let s = rules_map
             .iter()
             .map(|(selector, (metadata, action))| {
-                let (sensor_val, selector_val) = match selector {
-                    Selector::ProcessExec { comm } => ("exec".to_string(), comm),
-
-                    Selector::WindowFocused { mode, regex } => match mode {
-                        WindowFocusedSelectorMode::WindowName => ("window-name".to_string(), regex),
-                        WindowFocusedSelectorMode::WindowInstance => {
-                            ("window-instance".to_string(), regex)
-                        }
-                        WindowFocusedSelectorMode::WindowClass => {
-                            ("window-class".to_string(), regex)
-                        }
-                    },
-                };
-
-                let action_val = match action {
-                    Action::SwitchToProfile { profile_name } => profile_name.to_string(),
-                    Action::SwitchToSlot { slot_index } => {
-                        format!("{}", slot_index)
-                    }
-                };
-
-                let mut metadata_val = String::new();
-                if metadata.enabled {
-                    metadata_val.push_str("enabled");
-                } else {
-                    metadata_val.push_str("disabled");
-                }
+                let (sensor_val, selector_val) = selector.to_rule_strings();
 
-                if metadata.internal {
-                    metadata_val.push_str(",internal");
-                } else {
-                    metadata_val.push_str(",user-defined");
-                }
+                let action_val = action.to_rule_string();
+
+                let metadata_val = metadata.to_rule_string();
 
                 (sensor_val, selector_val, action_val, metadata_val)
             })
@@ -19,7 +19,11 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use crate::actions::Action;
+use crate::conditions::{MatchContext, RuleKind, SystemState, WindowState};
 use crate::dbus_client::Message;
+use crate::rule_metadata::RuleMetadata;
+use crate::selectors::{Selector, WindowFocusedSelectorMode};
 
 #[cfg(feature = "sensor-procmon")]
 use crate::sensors::PROCESS_SENSOR_FAILED;
@@ -40,6 +44,8 @@ use crate::sensors::SensorConfiguration;
 #[allow(unused)]
 use crate::sensors::SENSORS_CONFIGURATION;
 
+use crate::sensors::SystemSensorData;
+
 #[cfg(feature = "sensor-x11")]
 use crate::sensors::X11SensorData;
 
@@ -63,14 +69,15 @@ use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use log::*;
 use parking_lot::{Mutex, RwLock};
-use regex::Regex;
 use rust_embed::RustEmbed;
 use sensors::WindowSensorData;
-use serde::{Deserialize, Serialize};
-use std::{env, fmt, fs, path::PathBuf, process, sync::atomic::AtomicBool, sync::Arc};
+use std::io::Write;
+use std::{env, fs, path::PathBuf, process, sync::atomic::AtomicBool, sync::Arc};
 use std::{sync::atomic::Ordering, thread, time::Duration};
 use syslog::Facility;
 
+mod actions;
+mod conditions;
 mod constants;
 mod dbus_client;
 mod dbus_interface;
@@ -78,6 +85,8 @@ mod dbus_interface;
 mod logger;
 #[cfg(feature = "sensor-procmon")]
 mod procmon;
+mod rule_metadata;
+mod selectors;
 mod sensors;
 mod util;
 
@@ -114,8 +123,20 @@ lazy_static! {
     /// Mapping between event selector => action
     pub static ref RULES_MAP: Arc<RwLock<IndexMap<Selector, (RuleMetadata, Action)>>> = Arc::new(RwLock::new(IndexMap::new()));
 
-    /// Saved previous states
-    pub static ref PREVIOUS_STATES_MAP: Arc<RwLock<IndexMap<i32, Action>>> = Arc::new(RwLock::new(IndexMap::new()));
+    /// Saved previous states: The process rules that are in effect, and the actions that revert them
+    pub static ref PREVIOUS_STATES_MAP: Arc<RwLock<IndexMap<i32, (Selector, RuleMetadata, Action)>>> = Arc::new(RwLock::new(IndexMap::new()));
+
+    /// The window focus rule that is currently in effect, and the action that reverts it
+    pub static ref FOCUSED_RULE_STATE: Arc<RwLock<Option<(Selector, RuleMetadata, Option<Action>)>>> = Arc::new(RwLock::new(None));
+
+    /// The condition rules that are currently in effect, and the actions that revert them
+    pub static ref ACTIVE_CONDITIONS_MAP: Arc<RwLock<IndexMap<Selector, (RuleMetadata, Action)>>> = Arc::new(RwLock::new(IndexMap::new()));
+
+    /// The properties of the currently focused window
+    pub static ref FOCUSED_WINDOW: Arc<RwLock<Option<WindowState>>> = Arc::new(RwLock::new(None));
+
+    /// The state of the system, as reported by the system sensor
+    pub static ref SYSTEM_STATE: Arc<RwLock<SystemState>> = Arc::new(RwLock::new(SystemState::default()));
 
     /// Currently selected slot and profile
     pub static ref CURRENT_STATE: Arc<RwLock<(Option<u64>, Option<String>)>> = Arc::new(RwLock::new((None, None)));
@@ -155,109 +176,6 @@ pub enum MainError {
     SyslogLevelError {},
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum WindowFocusedSelectorMode {
-    WindowName,
-    WindowInstance,
-    WindowClass,
-}
-
-impl fmt::Display for WindowFocusedSelectorMode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            WindowFocusedSelectorMode::WindowName => {
-                write!(f, "Name")?;
-            }
-
-            WindowFocusedSelectorMode::WindowInstance => {
-                write!(f, "Instance")?;
-            }
-
-            WindowFocusedSelectorMode::WindowClass => {
-                write!(f, "Class")?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
-pub enum Selector {
-    ProcessExec {
-        comm: String,
-    },
-    WindowFocused {
-        mode: WindowFocusedSelectorMode,
-        regex: String,
-    },
-}
-
-impl fmt::Display for Selector {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Selector::ProcessExec { comm } => {
-                write!(f, "On process execution: comm: '{}'", comm)?;
-            }
-
-            Selector::WindowFocused { mode, regex } => {
-                write!(f, "On window focused: {}: '{}'", mode, regex)?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub enum Action {
-    SwitchToProfile { profile_name: String },
-    SwitchToSlot { slot_index: u64 },
-}
-
-impl fmt::Display for Action {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Action::SwitchToProfile { profile_name } => {
-                write!(f, "Switch to profile: {}", profile_name)?;
-            }
-
-            Action::SwitchToSlot { slot_index } => {
-                write!(f, "Switch to slot: {}", slot_index + 1)?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RuleMetadata {
-    /// Specifies whether the rule is enabled
-    pub enabled: bool,
-
-    /// Set to true if the rule is auto-generated
-    pub internal: bool,
-}
-
-impl std::default::Default for RuleMetadata {
-    fn default() -> Self {
-        RuleMetadata {
-            enabled: true,
-            internal: false,
-        }
-    }
-}
-
-impl fmt::Display for RuleMetadata {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "enabled: {}", self.enabled)?;
-        write!(f, ", internal: {}", self.internal)?;
-
-        Ok(())
-    }
-}
-
 #[cfg(feature = "sensor-procmon")]
 #[derive(Debug, Clone)]
 pub enum SystemEvent {
@@ -323,7 +241,21 @@ pub enum RulesSubcommands {
 
     /// Add a new rule
     #[clap(display_order = 1)]
-    Add { rule: Vec<String> },
+    Add {
+        /// If multiple rules match, the rule with the highest priority wins
+        #[clap(long, default_value_t = 0, allow_hyphen_values = true)]
+        priority: i32,
+
+        /// Do not revert the effects of the rule when it stops matching
+        #[clap(long)]
+        sticky: bool,
+
+        /// Do not apply rules with a lower priority while this rule is in effect
+        #[clap(long)]
+        exclusive: bool,
+
+        rule: Vec<String>,
+    },
 
     /// Remove a rule by its index
     #[clap(display_order = 2)]
@@ -336,6 +268,35 @@ pub enum RulesSubcommands {
     /// Mark a rule as disabled
     #[clap(display_order = 4)]
     Disable { rule_index: usize },
+
+    /// Replay an event against the rules, and show which rule wins and why
+    #[clap(display_order = 5)]
+    Explain {
+        #[clap(subcommand)]
+        event: ExplainSubcommands,
+    },
+}
+
+/// Sub-commands of the "rules explain" command
+#[derive(Debug, clap::Parser)]
+pub enum ExplainSubcommands {
+    /// A process has been executed
+    Exec { comm: String },
+
+    /// A window has been focused
+    Window {
+        /// The class of the window
+        #[clap(long, default_value = "")]
+        class: String,
+
+        /// The instance of the window
+        #[clap(long, default_value = "")]
+        instance: String,
+
+        /// The name (title) of the window
+        #[clap(long, default_value = "")]
+        name: String,
+    },
 }
 
 /// Subcommands of the "completions" command
@@ -379,10 +340,12 @@ Copyright (c) 2019-2022, The Eruption Development Team
     );
 }
 
-/// Execute an action
-fn process_action(action: &Action) -> Result<()> {
-    match action {
+/// Execute an action, and return an action that reverts its effects
+fn process_action(action: &Action) -> Result<Action> {
+    let revert_action = match action {
         Action::SwitchToProfile { profile_name } => {
+            let previous_profile = dbus_client::get_active_profile()?;
+
             if CURRENT_STATE.read().1.is_none()
                 || CURRENT_STATE.read().1.as_ref().unwrap() != profile_name
             {
@@ -394,9 +357,15 @@ fn process_action(action: &Action) -> Result<()> {
             }
 
             CURRENT_STATE.write().1 = Some(profile_name.clone());
+
+            Action::SwitchToProfile {
+                profile_name: previous_profile,
+            }
         }
 
         Action::SwitchToSlot { slot_index } => {
+            let previous_slot = dbus_client::get_active_slot()?;
+
             if CURRENT_STATE.read().0.is_none()
                 || CURRENT_STATE.read().0.as_ref().unwrap() != slot_index
             {
@@ -408,10 +377,106 @@ fn process_action(action: &Action) -> Result<()> {
             }
 
             CURRENT_STATE.write().0 = Some(*slot_index);
+
+            Action::SwitchToSlot {
+                slot_index: previous_slot,
+            }
         }
-    }
 
-    Ok(())
+        Action::SetParameter {
+            profile_file,
+            script_file,
+            parameter_name,
+            value,
+        } => {
+            info!("Triggered action: {}", action);
+
+            let profile_file = match profile_file {
+                Some(profile_file) => util::resolve_profile_file(profile_file),
+                None => dbus_client::get_active_profile()?,
+            };
+
+            let script_file = util::resolve_script_file(script_file);
+
+            let previous_value =
+                dbus_client::get_parameter(&profile_file, &script_file, parameter_name)?;
+
+            dbus_client::set_parameter(&profile_file, &script_file, parameter_name, value)?;
+
+            Action::SetParameter {
+                profile_file: Some(profile_file),
+                script_file,
+                parameter_name: parameter_name.clone(),
+                value: previous_value,
+            }
+        }
+
+        Action::SetBrightness { brightness } => {
+            info!("Triggered action: {}", action);
+
+            let previous_brightness = dbus_client::get_brightness()?;
+
+            dbus_client::set_brightness(*brightness)?;
+
+            Action::SetBrightness {
+                brightness: previous_brightness,
+            }
+        }
+
+        Action::EnableSoundFx { enabled } => {
+            info!("Triggered action: {}", action);
+
+            let previously_enabled = dbus_client::is_sound_fx_enabled()?;
+
+            dbus_client::set_sound_fx(*enabled)?;
+
+            Action::EnableSoundFx {
+                enabled: previously_enabled,
+            }
+        }
+
+        Action::EnableAmbientEffect { enabled } => {
+            info!("Triggered action: {}", action);
+
+            let previously_enabled = dbus_client::is_ambient_effect_enabled()?;
+
+            dbus_client::set_ambient_effect(*enabled)?;
+
+            Action::EnableAmbientEffect {
+                enabled: previously_enabled,
+            }
+        }
+
+        Action::Sequence { actions } => {
+            let mut revert_actions = Vec::with_capacity(actions.len());
+
+            for action in actions {
+                match process_action(action) {
+                    Ok(revert_action) => revert_actions.push(revert_action),
+
+                    Err(e) => {
+                        // undo the actions that have already been completed
+                        for revert_action in revert_actions.iter().rev() {
+                            if let Err(e) = process_action(revert_action) {
+                                error!("Could not revert action: {}", e);
+                            }
+                        }
+
+                        return Err(e);
+                    }
+                }
+            }
+
+            // undo the actions in the reverse order
+            revert_actions.reverse();
+
+            Action::Sequence {
+                actions: revert_actions,
+            }
+        }
+    };
+
+    Ok(revert_action)
 }
 
 /// Process system related events
@@ -424,41 +489,41 @@ fn process_system_event(event: &SystemEvent) -> Result<()> {
             comm,
         } => {
             if let Some(comm) = comm {
-                for (selector, (metadata, action)) in RULES_MAP.read().iter() {
-                    match selector {
-                        Selector::ProcessExec { comm: regex } => {
-                            if metadata.enabled {
-                                let re = Regex::new(regex)?;
-
-                                if re.is_match(comm) {
-                                    debug!("Matching rule for: {}", comm);
-
-                                    match action {
-                                        Action::SwitchToProfile { profile_name: _ } => {
-                                            let profile_name = dbus_client::get_active_profile()?;
-                                            let return_action =
-                                                Action::SwitchToProfile { profile_name };
-                                            PREVIOUS_STATES_MAP
-                                                .write()
-                                                .insert(event.pid, return_action);
-                                        }
-
-                                        Action::SwitchToSlot { slot_index: _ } => {
-                                            let slot_index = dbus_client::get_active_slot()?;
-                                            let return_action = Action::SwitchToSlot { slot_index };
-                                            PREVIOUS_STATES_MAP
-                                                .write()
-                                                .insert(event.pid, return_action);
-                                        }
-                                    }
-
-                                    process_action(action)?;
-                                    break;
-                                }
-                            }
-                        }
+                let matching_rule = {
+                    let focused_window = FOCUSED_WINDOW.read();
+                    let system_state = SYSTEM_STATE.read();
+
+                    let ctx = MatchContext {
+                        comm: Some(comm.as_str()),
+                        window: focused_window.as_ref(),
+                        system: &system_state,
+                    };
+
+                    let rules_map = RULES_MAP.read();
+
+                    conditions::find_winning_rule(&rules_map, RuleKind::Process, &ctx)?
+                        .and_then(|index| rules_map.get_index(index))
+                        .map(|(selector, (metadata, action))| {
+                            (selector.clone(), metadata.clone(), action.clone())
+                        })
+                };
+
+                if let Some((selector, metadata, action)) = matching_rule {
+                    debug!("Matching rule for: {}", comm);
 
-                        _ => { /* Ignore others */ }
+                    if let Some(blocking_rule) =
+                        find_blocking_rule(&selector, &metadata, RuleKind::Process)
+                    {
+                        debug!(
+                            "Not applied, blocked by the exclusive rule: {}",
+                            blocking_rule
+                        );
+                    } else {
+                        let revert_action = process_action(&action)?;
+
+                        PREVIOUS_STATES_MAP
+                            .write()
+                            .insert(event.pid, (selector, metadata, revert_action));
                     }
                 }
             } else {
@@ -467,24 +532,10 @@ fn process_system_event(event: &SystemEvent) -> Result<()> {
         }
 
         SystemEvent::ProcessExit { event } => {
-            match PREVIOUS_STATES_MAP.read().get(&event.pid) {
-                Some(action) => match action {
-                    Action::SwitchToProfile { profile_name } => {
-                        debug!("Returning to profile: {}", profile_name);
+            let previous_state = PREVIOUS_STATES_MAP.write().shift_remove(&event.pid);
 
-                        dbus_client::switch_profile(profile_name)?;
-                    }
-
-                    Action::SwitchToSlot { slot_index } => {
-                        debug!("Returning to slot: {}", slot_index + 1);
-
-                        dbus_client::switch_slot(*slot_index)?;
-                    }
-                },
-
-                None => {
-                    // no saved state available
-                }
+            if let Some((selector, metadata, revert_action)) = previous_state {
+                revert_rule(&selector, &metadata, &revert_action)?;
             }
         }
     }
@@ -507,6 +558,8 @@ fn process_fs_event(event: &FileSystemEvent, dbus_api_tx: &Sender<DbusApiEvent>)
             }
 
             dbus_api_tx.send(DbusApiEvent::RulesChanged {})?;
+
+            evaluate_condition_rules()?;
         }
     }
 
@@ -556,41 +609,246 @@ fn process_dbus_event(event: &dbus_client::Message) -> Result<()> {
 fn process_window_event(event: &dyn WindowSensorData) -> Result<()> {
     trace!("Sensor data: {:#?}", event);
 
-    for (selector, (metadata, action)) in RULES_MAP.read().iter() {
-        match selector {
-            Selector::WindowFocused { mode, regex } => {
-                if metadata.enabled {
-                    let re = Regex::new(regex)?;
-
-                    match mode {
-                        WindowFocusedSelectorMode::WindowName => {
-                            if re.is_match(event.window_name().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
+    *FOCUSED_WINDOW.write() = Some(WindowState::from_sensor_data(event));
 
-                        WindowFocusedSelectorMode::WindowInstance => {
-                            if re.is_match(event.window_instance().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
+    evaluate_window_rules()
+}
 
-                        WindowFocusedSelectorMode::WindowClass => {
-                            if re.is_match(event.window_class().unwrap_or_default()) {
-                                process_action(action)?;
-                                break;
-                            }
-                        }
-                    }
+/// Process changes of the state of the system, e.g. the time of day or the power source
+fn process_system_state(state: &SystemState) -> Result<()> {
+    if *SYSTEM_STATE.read() == *state {
+        return Ok(());
+    }
+
+    trace!("System state: {:#?}", state);
+
+    *SYSTEM_STATE.write() = state.clone();
+
+    evaluate_condition_rules()?;
+
+    if FOCUSED_WINDOW.read().is_some() {
+        evaluate_window_rules()?;
+    }
+
+    Ok(())
+}
+
+/// Returns the exclusive rule in effect that prevents the rule `selector` from being applied
+fn find_blocking_rule(
+    selector: &Selector,
+    metadata: &RuleMetadata,
+    kind: RuleKind,
+) -> Option<Selector> {
+    let is_blocking = |s: &Selector, m: &RuleMetadata| {
+        s != selector && m.exclusive && m.priority > metadata.priority
+    };
+
+    let blocking_process_rule = PREVIOUS_STATES_MAP
+        .read()
+        .values()
+        .find(|(s, m, _)| is_blocking(s, m))
+        .map(|(s, _, _)| s.clone());
+
+    // only one window rule is in effect at any time, so window rules do not block each other
+    let blocking_window_rule = if kind != RuleKind::Window {
+        FOCUSED_RULE_STATE
+            .read()
+            .as_ref()
+            .filter(|(s, m, _)| is_blocking(s, m))
+            .map(|(s, _, _)| s.clone())
+    } else {
+        None
+    };
+
+    let blocking_condition_rule = ACTIVE_CONDITIONS_MAP
+        .read()
+        .iter()
+        .find(|(s, (m, _))| is_blocking(s, m))
+        .map(|(s, _)| s.clone());
+
+    blocking_process_rule
+        .or(blocking_window_rule)
+        .or(blocking_condition_rule)
+}
+
+/// Undo the effects of a rule that stopped matching, unless it is sticky
+fn revert_rule(selector: &Selector, metadata: &RuleMetadata, revert_action: &Action) -> Result<()> {
+    if metadata.sticky {
+        debug!("Not reverting the sticky rule: {}", selector);
+    } else {
+        debug!("Reverting: {}", revert_action);
+
+        process_action(revert_action)?;
+    }
+
+    // rules that have been blocked by this rule may now be applied
+    if metadata.exclusive {
+        evaluate_condition_rules()?;
+
+        if FOCUSED_WINDOW.read().is_some() {
+            evaluate_window_rules()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the window rule `metadata` may take over from the window rule in effect,
+/// sticky rules are only superseded by rules of at least the same priority
+fn supersedes(metadata: &RuleMetadata, focused_metadata: &RuleMetadata) -> bool {
+    !focused_metadata.sticky || metadata.priority >= focused_metadata.priority
+}
+
+/// Apply the window rule with the highest priority that matches the focused window
+fn evaluate_window_rules() -> Result<()> {
+    let matching_rule = {
+        let focused_window = FOCUSED_WINDOW.read();
+        let system_state = SYSTEM_STATE.read();
+
+        let ctx = MatchContext {
+            comm: None,
+            window: focused_window.as_ref(),
+            system: &system_state,
+        };
+
+        let rules_map = RULES_MAP.read();
+
+        conditions::find_winning_rule(&rules_map, RuleKind::Window, &ctx)?
+            .and_then(|index| rules_map.get_index(index))
+            .map(|(selector, (metadata, action))| {
+                (selector.clone(), metadata.clone(), action.clone())
+            })
+    };
+
+    if let Some((selector, metadata, action)) = matching_rule {
+        // only act if the focus moved to a window that is matched by a different rule
+        if let Some((focused_selector, focused_metadata, _)) = &*FOCUSED_RULE_STATE.read() {
+            if *focused_selector == selector {
+                return Ok(());
+            }
+
+            if !supersedes(&metadata, focused_metadata) {
+                trace!("The sticky rule stays in effect: {}", focused_selector);
+
+                return Ok(());
+            }
+        }
+
+        if let Some(blocking_rule) = find_blocking_rule(&selector, &metadata, RuleKind::Window) {
+            trace!(
+                "Not applied, blocked by the exclusive rule: {}",
+                blocking_rule
+            );
+
+            return Ok(());
+        }
+
+        let mut focused_rule_state = FOCUSED_RULE_STATE.write();
+
+        // undo the effects of the rule that matched the previously focused window
+        if let Some((previous_selector, previous_metadata, Some(revert_action))) =
+            focused_rule_state.take()
+        {
+            if previous_metadata.sticky {
+                debug!("Not reverting the sticky rule: {}", previous_selector);
+            } else {
+                debug!("Reverting: {}", revert_action);
+
+                process_action(&revert_action)?;
+            }
+        }
+
+        let revert_action = process_action(&action)?;
+
+        // auto-generated rules establish the baseline state, so there is nothing to revert
+        let revert_action = if metadata.internal {
+            None
+        } else {
+            Some(revert_action)
+        };
+
+        *focused_rule_state = Some((selector, metadata, revert_action));
+    }
+
+    Ok(())
+}
+
+/// Apply all condition rules that started to match, and revert the ones that stopped matching
+fn evaluate_condition_rules() -> Result<()> {
+    let mut started_matching = Vec::new();
+    let mut stopped_matching = Vec::new();
+
+    {
+        let focused_window = FOCUSED_WINDOW.read();
+        let system_state = SYSTEM_STATE.read();
+
+        let ctx = MatchContext {
+            comm: None,
+            window: focused_window.as_ref(),
+            system: &system_state,
+        };
+
+        let rules_map = RULES_MAP.read();
+        let active_conditions = ACTIVE_CONDITIONS_MAP.read();
+
+        for (selector, (metadata, action)) in rules_map.iter() {
+            if selector.kind() == RuleKind::Condition {
+                let is_match = metadata.enabled && selector.matches(&ctx)?;
+                let is_active = active_conditions.contains_key(selector);
+
+                if is_match && !is_active {
+                    started_matching.push((selector.clone(), metadata.clone(), action.clone()));
+                } else if !is_match && is_active {
+                    stopped_matching.push(selector.clone());
                 }
             }
+        }
 
-            _ => { /* not a window related selector */ }
+        // rules that have been removed in the meantime
+        for selector in active_conditions.keys() {
+            if !rules_map.contains_key(selector) {
+                stopped_matching.push(selector.clone());
+            }
         }
     }
 
+    for selector in stopped_matching {
+        let active_condition = ACTIVE_CONDITIONS_MAP.write().shift_remove(&selector);
+
+        if let Some((metadata, revert_action)) = active_condition {
+            debug!("Condition no longer met: {}", selector);
+
+            revert_rule(&selector, &metadata, &revert_action)?;
+        }
+    }
+
+    // apply the rule with the highest priority last, so that its effects prevail
+    started_matching.sort_by_key(|(_, metadata, _)| metadata.priority);
+
+    for (selector, metadata, action) in started_matching {
+        // the rule may have been applied already, after an exclusive rule has been reverted
+        if ACTIVE_CONDITIONS_MAP.read().contains_key(&selector) {
+            continue;
+        }
+
+        debug!("Condition met: {}", selector);
+
+        if let Some(blocking_rule) = find_blocking_rule(&selector, &metadata, RuleKind::Condition) {
+            trace!(
+                "Not applied, blocked by the exclusive rule: {}",
+                blocking_rule
+            );
+
+            continue;
+        }
+
+        let revert_action = process_action(&action)?;
+
+        ACTIVE_CONDITIONS_MAP
+            .write()
+            .insert(selector, (metadata, revert_action));
+    }
+
     Ok(())
 }
 
@@ -925,6 +1183,16 @@ pub fn run_main_loop(
                         //     handled = true;
                         // }
 
+                        if let Some(data) = data.as_any().downcast_ref::<SystemSensorData>() {
+                            log::trace!("Processing system sensor data");
+
+                            process_system_state(&data.0).unwrap_or_else(|e| {
+                                error!("Could not process the state of the system: {}", e)
+                            });
+
+                            handled = true;
+                        }
+
                         #[cfg(feature = "sensor-x11")]
                         if let Some(data) = data.as_any().downcast_ref::<X11SensorData>() {
                             log::trace!("Processing X11 sensor data");
@@ -1036,8 +1304,10 @@ fn load_rules_map() -> Result<()> {
         regex: ".*".to_string(),
     };
 
+    // the default rule only applies if no other window rule matches
     let metadata = RuleMetadata {
         internal: true,
+        priority: i32::MIN,
         ..Default::default()
     };
 
@@ -1050,6 +1320,145 @@ fn load_rules_map() -> Result<()> {
     Ok(())
 }
 
+/// Replay an event against the rules, and print which rule wins and why
+fn explain_event(event: &ExplainSubcommands) -> Result<()> {
+    let system_state = sensors::get_system_state();
+
+    let (kind, comm, window) = match event {
+        ExplainSubcommands::Exec { comm } => (RuleKind::Process, Some(comm.as_str()), None),
+
+        ExplainSubcommands::Window {
+            class,
+            instance,
+            name,
+        } => (
+            RuleKind::Window,
+            None,
+            Some(WindowState {
+                window_name: name.clone(),
+                window_instance: instance.clone(),
+                window_class: class.clone(),
+            }),
+        ),
+    };
+
+    let ctx = MatchContext {
+        comm,
+        window: window.as_ref(),
+        system: &system_state,
+    };
+
+    explain_rules(&RULES_MAP.read(), kind, &ctx, &mut std::io::stdout())
+}
+
+/// Write which of the rules in `rules_map` wins for the event in `ctx`, and why
+fn explain_rules(
+    rules_map: &IndexMap<Selector, (RuleMetadata, Action)>,
+    kind: RuleKind,
+    ctx: &MatchContext,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let winner = conditions::find_winning_rule(rules_map, kind, ctx)?;
+
+    let mut matching_rules = Vec::new();
+
+    for (index, (selector, (metadata, action))) in rules_map.iter().enumerate() {
+        let is_match = metadata.enabled && selector.matches(ctx)?;
+
+        let verdict = if !metadata.enabled {
+            "disabled".to_string()
+        } else if selector.kind() == RuleKind::Condition {
+            if is_match {
+                "condition rule, currently in effect".to_string()
+            } else {
+                "condition rule, currently not in effect".to_string()
+            }
+        } else if selector.kind() != kind {
+            format!("skipped, this is a {} rule", selector.kind())
+        } else if !is_match {
+            "no match".to_string()
+        } else if Some(index) == winner {
+            matching_rules.push(index);
+
+            "match, wins".to_string()
+        } else {
+            matching_rules.push(index);
+
+            format!(
+                "match, but loses to rule {}",
+                winner.map(|w| w.to_string()).unwrap_or_default()
+            )
+        };
+
+        writeln!(
+            out,
+            "{:3}: {} => {} ({})",
+            index, selector, action, metadata
+        )?;
+        writeln!(out, "     {}", verdict)?;
+    }
+
+    writeln!(out)?;
+
+    match winner.and_then(|index| rules_map.get_index(index).map(|rule| (index, rule))) {
+        Some((index, (_selector, (metadata, action)))) => {
+            let equal_priority = matching_rules
+                .iter()
+                .filter(|i| rules_map[**i].0.priority == metadata.priority)
+                .count();
+
+            let reason = if matching_rules.len() == 1 {
+                "it is the only matching rule".to_string()
+            } else if equal_priority > 1 {
+                format!(
+                    "it has the highest priority ({}), and is listed first of {} rules with that priority",
+                    metadata.priority, equal_priority
+                )
+            } else {
+                format!("it has the highest priority ({})", metadata.priority)
+            };
+
+            writeln!(out, "Rule {} wins, since {}", index, reason)?;
+            writeln!(out, "Action: {}", action)?;
+
+            if metadata.sticky {
+                writeln!(
+                    out,
+                    "The rule is sticky, its effects will not be reverted when it stops matching"
+                )?;
+            }
+
+            if metadata.exclusive {
+                writeln!(
+                    out,
+                    "The rule is exclusive, rules with a priority lower than {} will not be applied while it is in effect",
+                    metadata.priority
+                )?;
+            }
+
+            // exclusive condition rules that are in effect block the winning rule
+            for (blocking_index, (selector, (m, _))) in rules_map.iter().enumerate() {
+                if selector.kind() == RuleKind::Condition
+                    && m.enabled
+                    && m.exclusive
+                    && m.priority > metadata.priority
+                    && selector.matches(ctx)?
+                {
+                    writeln!(
+                        out,
+                        "The rule will not be applied, since it is blocked by the exclusive rule {}",
+                        blocking_index
+                    )?;
+                }
+            }
+        }
+
+        None => writeln!(out, "No rule matches")?,
+    }
+
+    Ok(())
+}
+
 fn save_rules_map() -> Result<()> {
     let rules_dir = util::tilde_expand(constants::STATE_DIR)?;
     let rules_file = rules_dir.join("process-monitor.rules");
@@ -1268,76 +1677,58 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 }
             }
 
-            RulesSubcommands::Add { rule } => {
+            RulesSubcommands::Add {
+                priority,
+                sticky,
+                exclusive,
+                rule,
+            } => {
                 fn print_usage_examples() {
                     eprintln!("\nPlease see below for some examples:");
 
                     for s in sensors::SENSORS.read().iter() {
                         eprintln!("{}", s.get_usage_example());
                     }
-                }
 
-                if rule.len() != 3 {
-                    eprintln!("Malformed rule definition");
-                    print_usage_examples();
-                } else {
-                    let sensor = &rule[0];
-                    let selector = &rule[1];
-                    let action = &rule[2];
+                    eprintln!("{}", selectors::USAGE);
+                    eprintln!("{}", actions::USAGE);
+                }
 
-                    let mut parsed_selector = None;
-                    let parsed_action;
+                match rule.split_last() {
+                    Some((action, selector)) if !selector.is_empty() => {
+                        match Selector::from_args(selector) {
+                            Ok(parsed_selector) => {
+                                let parsed_action =
+                                    action.parse::<Action>()?.slot_numbers_to_indices();
+
+                                let metadata = RuleMetadata {
+                                    priority,
+                                    sticky,
+                                    exclusive,
+                                    ..Default::default()
+                                };
+
+                                RULES_MAP.write().insert(
+                                    parsed_selector.clone(),
+                                    (metadata, parsed_action.clone()),
+                                );
+
+                                // print resulting action to console
+                                println!("{} => {}", parsed_selector, parsed_action);
+
+                                save_rules_map()?;
+                            }
 
-                    // TODO: move this to the plugin code
-                    if sensor.contains("exec") {
-                        parsed_selector = Some(Selector::ProcessExec {
-                            comm: selector.clone(),
-                        });
-                    } else if sensor.contains("window-class") {
-                        parsed_selector = Some(Selector::WindowFocused {
-                            mode: WindowFocusedSelectorMode::WindowClass,
-                            regex: selector.clone(),
-                        });
-                    } else if sensor.contains("window-instance") {
-                        parsed_selector = Some(Selector::WindowFocused {
-                            mode: WindowFocusedSelectorMode::WindowInstance,
-                            regex: selector.clone(),
-                        });
-                    } else if sensor.contains("window-name") {
-                        parsed_selector = Some(Selector::WindowFocused {
-                            mode: WindowFocusedSelectorMode::WindowName,
-                            regex: selector.clone(),
-                        });
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                print_usage_examples();
+                            }
+                        }
                     }
 
-                    if parsed_selector.is_none() {
-                        eprintln!("Syntax error in selector");
+                    _ => {
+                        eprintln!("Malformed rule definition");
                         print_usage_examples();
-                    } else {
-                        if action.contains(".profile") {
-                            parsed_action = Action::SwitchToProfile {
-                                profile_name: action.clone(),
-                            };
-
-                            RULES_MAP.write().insert(
-                                parsed_selector.clone().unwrap(),
-                                (RuleMetadata::default(), parsed_action.clone()),
-                            );
-                        } else {
-                            parsed_action = Action::SwitchToSlot {
-                                slot_index: action.parse::<u64>()? - 1,
-                            };
-
-                            RULES_MAP.write().insert(
-                                parsed_selector.clone().unwrap(),
-                                (RuleMetadata::default(), parsed_action.clone()),
-                            );
-                        }
-
-                        // print resulting action to console
-                        println!("{} => {}", parsed_selector.unwrap(), parsed_action);
-
-                        save_rules_map()?;
                     }
                 }
             }
@@ -1384,6 +1775,10 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                 save_rules_map()?;
             }
 
+            RulesSubcommands::Explain { event } => {
+                explain_event(&event)?;
+            }
+
             RulesSubcommands::Remove { rule_index } => {
                 // print results to console
                 match RULES_MAP.write().shift_remove_index(rule_index) {
@@ -1438,3 +1833,221 @@ pub fn main() -> std::result::Result<(), eyre::Error> {
 
     runtime.block_on(async move { async_main().await })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    lazy_static! {
+        /// The states of the rules in effect are global, so tests that modify them are serialized
+        static ref STATE_LOCK: Mutex<()> = Mutex::new(());
+    }
+
+    fn window_class(regex: &str) -> Selector {
+        Selector::WindowFocused {
+            mode: WindowFocusedSelectorMode::WindowClass,
+            regex: regex.to_string(),
+        }
+    }
+
+    fn metadata(priority: i32, sticky: bool, exclusive: bool) -> RuleMetadata {
+        RuleMetadata {
+            priority,
+            sticky,
+            exclusive,
+            ..Default::default()
+        }
+    }
+
+    fn clear_rule_states() {
+        PREVIOUS_STATES_MAP.write().clear();
+        *FOCUSED_RULE_STATE.write() = None;
+        ACTIVE_CONDITIONS_MAP.write().clear();
+    }
+
+    #[test]
+    fn sticky_window_rules_are_only_superseded_by_higher_priorities() {
+        let sticky = metadata(5, true, false);
+
+        assert!(!supersedes(&metadata(4, false, false), &sticky));
+        assert!(supersedes(&metadata(5, false, false), &sticky));
+        assert!(supersedes(&metadata(6, false, false), &sticky));
+
+        // rules that are not sticky are superseded by any other rule
+        assert!(supersedes(
+            &metadata(i32::MIN, false, false),
+            &metadata(5, false, false)
+        ));
+    }
+
+    #[test]
+    fn sticky_rules_are_not_reverted() -> Result<()> {
+        let _guard = STATE_LOCK.lock();
+
+        // reverting would fail, since there is no Eruption daemon to talk to
+        revert_rule(
+            &Selector::ScreenLocked,
+            &metadata(0, true, false),
+            &Action::SetBrightness { brightness: 50 },
+        )
+    }
+
+    #[test]
+    fn exclusive_rules_block_rules_of_lower_priority() {
+        let _guard = STATE_LOCK.lock();
+
+        clear_rule_states();
+
+        ACTIVE_CONDITIONS_MAP.write().insert(
+            Selector::ScreenLocked,
+            (
+                metadata(10, false, true),
+                Action::SetBrightness { brightness: 0 },
+            ),
+        );
+
+        let selector = window_class("steam.*");
+
+        assert_eq!(
+            find_blocking_rule(&selector, &metadata(9, false, false), RuleKind::Window),
+            Some(Selector::ScreenLocked)
+        );
+
+        assert_eq!(
+            find_blocking_rule(&selector, &metadata(10, false, false), RuleKind::Window),
+            None
+        );
+
+        // an exclusive rule does not block itself
+        assert_eq!(
+            find_blocking_rule(
+                &Selector::ScreenLocked,
+                &metadata(0, false, false),
+                RuleKind::Condition
+            ),
+            None
+        );
+
+        clear_rule_states();
+    }
+
+    #[test]
+    fn exclusive_window_rules_do_not_block_window_rules() {
+        let _guard = STATE_LOCK.lock();
+
+        clear_rule_states();
+
+        *FOCUSED_RULE_STATE.write() = Some((
+            window_class("steam.*"),
+            metadata(10, false, true),
+            Some(Action::SwitchToSlot { slot_index: 0 }),
+        ));
+
+        let selector = Selector::PowerSource {
+            source: selectors::PowerSource::Battery,
+        };
+
+        assert_eq!(
+            find_blocking_rule(&selector, &metadata(0, false, false), RuleKind::Condition),
+            Some(window_class("steam.*"))
+        );
+
+        assert_eq!(
+            find_blocking_rule(
+                &window_class("firefox"),
+                &metadata(0, false, false),
+                RuleKind::Window
+            ),
+            None
+        );
+
+        clear_rule_states();
+    }
+
+    fn explain(rules_map: &IndexMap<Selector, (RuleMetadata, Action)>, class: &str) -> String {
+        let window = WindowState {
+            window_class: class.to_string(),
+            ..Default::default()
+        };
+
+        let system = SystemState {
+            screen_locked: true,
+            ..Default::default()
+        };
+
+        let ctx = MatchContext {
+            comm: None,
+            window: Some(&window),
+            system: &system,
+        };
+
+        let mut out = Vec::new();
+        explain_rules(rules_map, RuleKind::Window, &ctx, &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn explain_the_winning_rule() {
+        let slot = |slot_index| Action::SwitchToSlot { slot_index };
+
+        let mut rules_map = IndexMap::new();
+
+        rules_map.insert(window_class("steam.*"), (metadata(5, true, false), slot(1)));
+        rules_map.insert(
+            window_class("steam_app_.*"),
+            (metadata(5, false, false), slot(2)),
+        );
+        rules_map.insert(
+            Selector::ProcessExec {
+                comm: "steam".to_string(),
+            },
+            (metadata(20, false, false), slot(3)),
+        );
+        rules_map.insert(
+            window_class("steam_app_42"),
+            (
+                RuleMetadata {
+                    enabled: false,
+                    ..metadata(30, false, false)
+                },
+                slot(3),
+            ),
+        );
+
+        let output = explain(&rules_map, "steam_app_42");
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[0],
+            "  0: On window focused: Class: 'steam.*' => Switch to slot: 2 (enabled: true, internal: false, priority: 5, sticky)"
+        );
+        assert_eq!(lines[1], "     match, wins");
+        assert_eq!(lines[3], "     match, but loses to rule 0");
+        assert_eq!(lines[5], "     skipped, this is a process rule");
+        assert_eq!(lines[7], "     disabled");
+
+        assert_eq!(
+            lines[9],
+            "Rule 0 wins, since it has the highest priority (5), and is listed first of 2 rules with that priority"
+        );
+        assert_eq!(lines[10], "Action: Switch to slot: 2");
+        assert_eq!(
+            lines[11],
+            "The rule is sticky, its effects will not be reverted when it stops matching"
+        );
+
+        assert!(explain(&rules_map, "firefox").ends_with("\nNo rule matches\n"));
+
+        // an exclusive condition rule of a higher priority is in effect
+        rules_map.insert(Selector::ScreenLocked, (metadata(10, false, true), slot(0)));
+
+        let output = explain(&rules_map, "steam");
+
+        assert!(output.contains("     condition rule, currently in effect\n"));
+        assert!(output.contains("Rule 0 wins, since it is the only matching rule\n"));
+        assert!(output.ends_with(
+            "The rule will not be applied, since it is blocked by the exclusive rule 4\n"
+        ));
+    }
+}
@@ -0,0 +1,177 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Metadata of process monitor rules, and its textual representation
+//!
+//! On the D-Bus interface, the metadata is represented by a comma separated list of flags,
+//! e.g.: `enabled,user-defined,priority=10,sticky,exclusive`
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleMetadata {
+    /// Specifies whether the rule is enabled
+    pub enabled: bool,
+
+    /// Set to true if the rule is auto-generated
+    pub internal: bool,
+
+    /// If multiple rules match, the rule with the highest priority wins
+    #[serde(default)]
+    pub priority: i32,
+
+    /// The effects of a sticky rule are not reverted when it stops matching,
+    /// e.g. when the process exits or the window loses focus
+    #[serde(default)]
+    pub sticky: bool,
+
+    /// While an exclusive rule is in effect, rules with a lower priority are not applied
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+impl std::default::Default for RuleMetadata {
+    fn default() -> Self {
+        RuleMetadata {
+            enabled: true,
+            internal: false,
+            priority: 0,
+            sticky: false,
+            exclusive: false,
+        }
+    }
+}
+
+impl RuleMetadata {
+    /// Returns the textual representation of the metadata, that may be parsed by `from_rule_string()`
+    pub fn to_rule_string(&self) -> String {
+        let mut result = vec![
+            if self.enabled { "enabled" } else { "disabled" }.to_string(),
+            if self.internal {
+                "internal"
+            } else {
+                "user-defined"
+            }
+            .to_string(),
+            format!("priority={}", self.priority),
+        ];
+
+        if self.sticky {
+            result.push("sticky".to_string());
+        }
+
+        if self.exclusive {
+            result.push("exclusive".to_string());
+        }
+
+        result.join(",")
+    }
+
+    /// Parse the metadata from its textual representation, unknown flags are ignored
+    pub fn from_rule_string(s: &str) -> Self {
+        let mut result = RuleMetadata {
+            enabled: false,
+            ..Default::default()
+        };
+
+        for flag in s
+            .trim_matches(|c| c == '(' || c == ')')
+            .split(',')
+            .map(|f| f.trim())
+        {
+            match flag {
+                "enabled" => result.enabled = true,
+                "internal" => result.internal = true,
+                "sticky" => result.sticky = true,
+                "exclusive" => result.exclusive = true,
+
+                _ => {
+                    if let Some(priority) = flag.strip_prefix("priority=") {
+                        result.priority = priority.parse().unwrap_or_default();
+                    }
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl fmt::Display for RuleMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "enabled: {}", self.enabled)?;
+        write!(f, ", internal: {}", self.internal)?;
+        write!(f, ", priority: {}", self.priority)?;
+
+        if self.sticky {
+            write!(f, ", sticky")?;
+        }
+
+        if self.exclusive {
+            write!(f, ", exclusive")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rule_strings_round_trip() {
+        let metadata = RuleMetadata {
+            enabled: true,
+            internal: false,
+            priority: -5,
+            sticky: true,
+            exclusive: true,
+        };
+
+        assert_eq!(
+            metadata.to_rule_string(),
+            "enabled,user-defined,priority=-5,sticky,exclusive"
+        );
+        assert_eq!(
+            RuleMetadata::from_rule_string(&metadata.to_rule_string()),
+            metadata
+        );
+
+        assert_eq!(
+            metadata.to_string(),
+            "enabled: true, internal: false, priority: -5, sticky, exclusive"
+        );
+    }
+
+    #[test]
+    fn parse_flags() {
+        let metadata = RuleMetadata::from_rule_string("(internal, priority=10, unknown)");
+
+        assert!(!metadata.enabled);
+        assert!(metadata.internal);
+        assert_eq!(metadata.priority, 10);
+        assert!(!metadata.sticky && !metadata.exclusive);
+
+        assert_eq!(RuleMetadata::from_rule_string("priority=high").priority, 0);
+    }
+}
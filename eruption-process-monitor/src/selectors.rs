@@ -0,0 +1,734 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Selectors of process monitor rules, and their textual representation
+//!
+//! On the D-Bus interface, a selector is represented by a pair of strings: The name of
+//! the sensor, and its argument. Composite selectors (`all`, `any` and `not`) carry their
+//! child selectors as JSON, in the same format that is used by the rules file.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Help text describing the textual representation of selectors
+pub const USAGE: &str = r#"
+Selectors:
+exec <regex>                                Process executed
+window-[class|instance|name] <regex>        Window focused
+time '[<days>] <HH:MM>-<HH:MM>'             Time of day, days: weekdays, weekends or mon,tue,...
+idle <seconds>                              User idle for at least the given number of seconds
+locked                                      Screen locked
+power <ac|battery>                          Power source
+device <vid>:<pid>                          USB device connected (hexadecimal ids)
+<selector> and|or <selector> ...            Combine selectors
+not <selector>                              Negate a selector
+"#;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SelectorError {
+    #[error("Syntax error in selector: {description}")]
+    Parse { description: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum WindowFocusedSelectorMode {
+    WindowName,
+    WindowInstance,
+    WindowClass,
+}
+
+impl fmt::Display for WindowFocusedSelectorMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowFocusedSelectorMode::WindowName => {
+                write!(f, "Name")?;
+            }
+
+            WindowFocusedSelectorMode::WindowInstance => {
+                write!(f, "Instance")?;
+            }
+
+            WindowFocusedSelectorMode::WindowClass => {
+                write!(f, "Class")?;
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+
+    pub const WEEKDAYS: [Weekday; 5] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+    ];
+
+    pub const WEEKENDS: [Weekday; 2] = [Weekday::Sat, Weekday::Sun];
+
+    fn get_name(&self) -> &'static str {
+        match *self {
+            Weekday::Mon => "mon",
+            Weekday::Tue => "tue",
+            Weekday::Wed => "wed",
+            Weekday::Thu => "thu",
+            Weekday::Fri => "fri",
+            Weekday::Sat => "sat",
+            Weekday::Sun => "sun",
+        }
+    }
+}
+
+/// A time of day, with a resolution of minutes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct ClockTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl TryFrom<String> for ClockTime {
+    type Error = SelectorError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let error = || SelectorError::Parse {
+            description: format!("Invalid time of day '{}', expected HH:MM", s),
+        };
+
+        let (hour, minute) = s.trim().split_once(':').ok_or_else(error)?;
+
+        let hour = hour.parse::<u8>().map_err(|_| error())?;
+        let minute = minute.parse::<u8>().map_err(|_| error())?;
+
+        if hour > 23 || minute > 59 {
+            return Err(error());
+        }
+
+        Ok(ClockTime { hour, minute })
+    }
+}
+
+impl From<ClockTime> for String {
+    fn from(time: ClockTime) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+}
+
+impl fmt::Display for PowerSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PowerSource::Ac => write!(f, "ac"),
+            PowerSource::Battery => write!(f, "battery"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum Selector {
+    ProcessExec {
+        comm: String,
+    },
+    WindowFocused {
+        mode: WindowFocusedSelectorMode,
+        regex: String,
+    },
+    /// The local time is within `from` and `to`, on one of `days` (or on any day, if empty).
+    /// The time window may span midnight, in that case `days` refers to the day it starts on
+    TimeOfDay {
+        #[serde(default)]
+        days: Vec<Weekday>,
+        from: ClockTime,
+        to: ClockTime,
+    },
+    UserIdle {
+        seconds: u64,
+    },
+    ScreenLocked,
+    PowerSource {
+        source: PowerSource,
+    },
+    DeviceConnected {
+        usb_vid: u16,
+        usb_pid: u16,
+    },
+    All {
+        selectors: Vec<Selector>,
+    },
+    Any {
+        selectors: Vec<Selector>,
+    },
+    Not {
+        selector: Box<Selector>,
+    },
+}
+
+fn days_to_string(days: &[Weekday]) -> Option<String> {
+    if days.is_empty() || Weekday::ALL.iter().all(|d| days.contains(d)) {
+        None
+    } else if days.len() == Weekday::WEEKDAYS.len()
+        && Weekday::WEEKDAYS.iter().all(|d| days.contains(d))
+    {
+        Some("weekdays".to_string())
+    } else if days.len() == Weekday::WEEKENDS.len()
+        && Weekday::WEEKENDS.iter().all(|d| days.contains(d))
+    {
+        Some("weekends".to_string())
+    } else {
+        Some(
+            days.iter()
+                .map(|d| d.get_name())
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+}
+
+fn parse_days(s: &str) -> Result<Vec<Weekday>, SelectorError> {
+    match s.to_lowercase().as_str() {
+        "daily" => Ok(vec![]),
+        "weekdays" => Ok(Weekday::WEEKDAYS.to_vec()),
+        "weekends" => Ok(Weekday::WEEKENDS.to_vec()),
+
+        days => days
+            .split(',')
+            .map(|day| {
+                Weekday::ALL
+                    .iter()
+                    .find(|d| d.get_name() == day.trim())
+                    .copied()
+                    .ok_or_else(|| SelectorError::Parse {
+                        description: format!("Unknown day of the week '{}'", day),
+                    })
+            })
+            .collect(),
+    }
+}
+
+fn parse_time_window(s: &str) -> Result<Selector, SelectorError> {
+    let s = s.trim();
+
+    let (days, window) = match s.rsplit_once(char::is_whitespace) {
+        Some((days, window)) => (parse_days(days.trim())?, window),
+        None => (vec![], s),
+    };
+
+    let (from, to) = window.split_once('-').ok_or_else(|| SelectorError::Parse {
+        description: format!("Invalid time window '{}', expected HH:MM-HH:MM", window),
+    })?;
+
+    Ok(Selector::TimeOfDay {
+        days,
+        from: ClockTime::try_from(from.to_string())?,
+        to: ClockTime::try_from(to.to_string())?,
+    })
+}
+
+fn parse_usb_id(s: &str) -> Result<(u16, u16), SelectorError> {
+    let error = || SelectorError::Parse {
+        description: format!("Invalid USB id '{}', expected <vid>:<pid>", s),
+    };
+
+    let (vid, pid) = s.trim().split_once(':').ok_or_else(error)?;
+
+    let vid = u16::from_str_radix(vid.trim_start_matches("0x"), 16).map_err(|_| error())?;
+    let pid = u16::from_str_radix(pid.trim_start_matches("0x"), 16).map_err(|_| error())?;
+
+    Ok((vid, pid))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(s: &str) -> Result<T, SelectorError> {
+    serde_json::from_str(s).map_err(|e| SelectorError::Parse {
+        description: format!("Invalid composite selector: {}", e),
+    })
+}
+
+impl Selector {
+    /// Returns the name of the sensor and the argument of the selector, as used on the D-Bus interface
+    pub fn to_rule_strings(&self) -> (String, String) {
+        match self {
+            Selector::ProcessExec { comm } => ("exec".to_string(), comm.clone()),
+
+            Selector::WindowFocused { mode, regex } => match mode {
+                WindowFocusedSelectorMode::WindowName => ("window-name".to_string(), regex.clone()),
+
+                WindowFocusedSelectorMode::WindowInstance => {
+                    ("window-instance".to_string(), regex.clone())
+                }
+
+                WindowFocusedSelectorMode::WindowClass => {
+                    ("window-class".to_string(), regex.clone())
+                }
+            },
+
+            Selector::TimeOfDay { days, from, to } => {
+                let window = match days_to_string(days) {
+                    Some(days) => format!("{} {}-{}", days, from, to),
+                    None => format!("{}-{}", from, to),
+                };
+
+                ("time".to_string(), window)
+            }
+
+            Selector::UserIdle { seconds } => ("idle".to_string(), seconds.to_string()),
+
+            Selector::ScreenLocked => ("locked".to_string(), String::new()),
+
+            Selector::PowerSource { source } => ("power".to_string(), source.to_string()),
+
+            Selector::DeviceConnected { usb_vid, usb_pid } => (
+                "device".to_string(),
+                format!("{:04x}:{:04x}", usb_vid, usb_pid),
+            ),
+
+            Selector::All { selectors } => (
+                "all".to_string(),
+                serde_json::to_string(selectors).unwrap_or_default(),
+            ),
+
+            Selector::Any { selectors } => (
+                "any".to_string(),
+                serde_json::to_string(selectors).unwrap_or_default(),
+            ),
+
+            Selector::Not { selector } => (
+                "not".to_string(),
+                serde_json::to_string(selector).unwrap_or_default(),
+            ),
+        }
+    }
+
+    /// Parse a selector from the name of a sensor and its argument, see `to_rule_strings()`
+    pub fn from_rule_strings(sensor: &str, argument: &str) -> Result<Self, SelectorError> {
+        let selector = match sensor {
+            "exec" => Selector::ProcessExec {
+                comm: argument.to_string(),
+            },
+
+            "window-name" => Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowName,
+                regex: argument.to_string(),
+            },
+
+            "window-instance" => Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowInstance,
+                regex: argument.to_string(),
+            },
+
+            "window-class" => Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowClass,
+                regex: argument.to_string(),
+            },
+
+            "time" => parse_time_window(argument)?,
+
+            "idle" => Selector::UserIdle {
+                seconds: argument
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| SelectorError::Parse {
+                        description: format!("Invalid number of seconds '{}'", argument),
+                    })?,
+            },
+
+            "locked" => Selector::ScreenLocked,
+
+            "power" => match argument.trim().to_lowercase().as_str() {
+                "ac" => Selector::PowerSource {
+                    source: PowerSource::Ac,
+                },
+
+                "battery" => Selector::PowerSource {
+                    source: PowerSource::Battery,
+                },
+
+                _ => {
+                    return Err(SelectorError::Parse {
+                        description: format!("Expected 'ac' or 'battery', got '{}'", argument),
+                    })
+                }
+            },
+
+            "device" => {
+                let (usb_vid, usb_pid) = parse_usb_id(argument)?;
+
+                Selector::DeviceConnected { usb_vid, usb_pid }
+            }
+
+            "all" => Selector::All {
+                selectors: from_json(argument)?,
+            },
+
+            "any" => Selector::Any {
+                selectors: from_json(argument)?,
+            },
+
+            "not" => Selector::Not {
+                selector: Box::new(from_json(argument)?),
+            },
+
+            _ => {
+                return Err(SelectorError::Parse {
+                    description: format!("Unknown sensor '{}'", sensor),
+                })
+            }
+        };
+
+        Ok(selector)
+    }
+
+    /// Parse a selector from command line arguments, e.g.:
+    /// `window-class 'steam_app_.*' and not power ac`
+    pub fn from_args(args: &[String]) -> Result<Self, SelectorError> {
+        fn parse_term(args: &[String]) -> Result<(Selector, &[String]), SelectorError> {
+            match args {
+                [keyword, rest @ ..] if keyword == "not" => {
+                    let (selector, rest) = parse_term(rest)?;
+
+                    Ok((
+                        Selector::Not {
+                            selector: Box::new(selector),
+                        },
+                        rest,
+                    ))
+                }
+
+                [sensor, rest @ ..] if sensor == "locked" => {
+                    Ok((Selector::from_rule_strings(sensor, "")?, rest))
+                }
+
+                [sensor, argument, rest @ ..] => {
+                    Ok((Selector::from_rule_strings(sensor, argument)?, rest))
+                }
+
+                _ => Err(SelectorError::Parse {
+                    description: "Missing selector".to_string(),
+                }),
+            }
+        }
+
+        let (selector, mut rest) = parse_term(args)?;
+
+        let mut selectors = vec![selector];
+        let mut operator: Option<&str> = None;
+
+        while let [op, tail @ ..] = rest {
+            if op != "and" && op != "or" {
+                return Err(SelectorError::Parse {
+                    description: format!("Expected 'and' or 'or', got '{}'", op),
+                });
+            }
+
+            if operator.map(|o| o != op).unwrap_or(false) {
+                return Err(SelectorError::Parse {
+                    description: "Mixing 'and' and 'or' is only supported in the rules file"
+                        .to_string(),
+                });
+            }
+
+            operator = Some(op.as_str());
+
+            let (selector, tail) = parse_term(tail)?;
+
+            selectors.push(selector);
+            rest = tail;
+        }
+
+        let selector = match operator {
+            Some("and") => Selector::All { selectors },
+            Some(_) => Selector::Any { selectors },
+            None => selectors.remove(0),
+        };
+
+        Ok(selector)
+    }
+}
+
+fn join(selectors: &[Selector], operator: &str) -> String {
+    selectors
+        .iter()
+        .map(|s| format!("({})", s))
+        .collect::<Vec<_>>()
+        .join(operator)
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::ProcessExec { comm } => {
+                write!(f, "On process execution: comm: '{}'", comm)?;
+            }
+
+            Selector::WindowFocused { mode, regex } => {
+                write!(f, "On window focused: {}: '{}'", mode, regex)?;
+            }
+
+            Selector::TimeOfDay { .. } => {
+                write!(f, "During time of day: {}", self.to_rule_strings().1)?;
+            }
+
+            Selector::UserIdle { seconds } => {
+                write!(f, "While user is idle: {} seconds", seconds)?;
+            }
+
+            Selector::ScreenLocked => {
+                write!(f, "While screen is locked")?;
+            }
+
+            Selector::PowerSource { source } => {
+                write!(f, "While on power source: {}", source)?;
+            }
+
+            Selector::DeviceConnected { usb_vid, usb_pid } => {
+                write!(
+                    f,
+                    "While device is connected: {:04x}:{:04x}",
+                    usb_vid, usb_pid
+                )?;
+            }
+
+            Selector::All { selectors } => {
+                write!(f, "{}", join(selectors, " and "))?;
+            }
+
+            Selector::Any { selectors } => {
+                write!(f, "{}", join(selectors, " or "))?;
+            }
+
+            Selector::Not { selector } => {
+                write!(f, "not ({})", selector)?;
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    fn time(hour: u8, minute: u8) -> ClockTime {
+        ClockTime { hour, minute }
+    }
+
+    fn exec(comm: &str) -> Selector {
+        Selector::ProcessExec {
+            comm: comm.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_clock_times() {
+        assert_eq!(
+            ClockTime::try_from("07:05".to_string()).unwrap(),
+            time(7, 5)
+        );
+        assert_eq!(time(7, 5).to_string(), "07:05");
+
+        assert!(ClockTime::try_from("24:00".to_string()).is_err());
+        assert!(ClockTime::try_from("12:60".to_string()).is_err());
+        assert!(ClockTime::try_from("1200".to_string()).is_err());
+    }
+
+    #[test]
+    fn parse_time_windows() {
+        assert_eq!(
+            Selector::from_rule_strings("time", "22:00-06:30").unwrap(),
+            Selector::TimeOfDay {
+                days: vec![],
+                from: time(22, 0),
+                to: time(6, 30),
+            }
+        );
+
+        assert_eq!(
+            Selector::from_rule_strings("time", "weekends 10:00-12:00").unwrap(),
+            Selector::TimeOfDay {
+                days: Weekday::WEEKENDS.to_vec(),
+                from: time(10, 0),
+                to: time(12, 0),
+            }
+        );
+
+        assert_eq!(
+            Selector::from_rule_strings("time", "mon,wed 18:00-20:00").unwrap(),
+            Selector::TimeOfDay {
+                days: vec![Weekday::Mon, Weekday::Wed],
+                from: time(18, 0),
+                to: time(20, 0),
+            }
+        );
+
+        assert!(Selector::from_rule_strings("time", "someday 18:00-20:00").is_err());
+        assert!(Selector::from_rule_strings("time", "18:00").is_err());
+    }
+
+    #[test]
+    fn weekdays_are_named_in_rule_strings() {
+        let window = |days: Vec<Weekday>| {
+            Selector::TimeOfDay {
+                days,
+                from: time(9, 0),
+                to: time(17, 0),
+            }
+            .to_rule_strings()
+            .1
+        };
+
+        assert_eq!(window(vec![]), "09:00-17:00");
+        assert_eq!(window(Weekday::ALL.to_vec()), "09:00-17:00");
+        assert_eq!(window(Weekday::WEEKDAYS.to_vec()), "weekdays 09:00-17:00");
+        assert_eq!(window(Weekday::WEEKENDS.to_vec()), "weekends 09:00-17:00");
+        assert_eq!(
+            window(vec![Weekday::Fri, Weekday::Sat]),
+            "fri,sat 09:00-17:00"
+        );
+    }
+
+    #[test]
+    fn compose_selectors_from_args() {
+        assert_eq!(
+            Selector::from_args(&args("exec steam")).unwrap(),
+            exec("steam")
+        );
+
+        assert_eq!(
+            Selector::from_args(&args("exec steam and not power ac")).unwrap(),
+            Selector::All {
+                selectors: vec![
+                    exec("steam"),
+                    Selector::Not {
+                        selector: Box::new(Selector::PowerSource {
+                            source: PowerSource::Ac
+                        })
+                    },
+                ]
+            }
+        );
+
+        assert_eq!(
+            Selector::from_args(&args("locked or idle 300 or exec vlc")).unwrap(),
+            Selector::Any {
+                selectors: vec![
+                    Selector::ScreenLocked,
+                    Selector::UserIdle { seconds: 300 },
+                    exec("vlc"),
+                ]
+            }
+        );
+
+        assert_eq!(
+            Selector::from_args(&args("not not locked")).unwrap(),
+            Selector::Not {
+                selector: Box::new(Selector::Not {
+                    selector: Box::new(Selector::ScreenLocked)
+                })
+            }
+        );
+    }
+
+    #[test]
+    fn reject_invalid_compositions() {
+        assert!(Selector::from_args(&[]).is_err());
+        assert!(Selector::from_args(&args("not")).is_err());
+        assert!(Selector::from_args(&args("exec steam and")).is_err());
+        assert!(Selector::from_args(&args("exec steam xor locked")).is_err());
+        assert!(Selector::from_args(&args("exec steam and locked or idle 60")).is_err());
+    }
+
+    #[test]
+    fn rule_strings_round_trip() {
+        let selectors = [
+            exec("steam"),
+            Selector::WindowFocused {
+                mode: WindowFocusedSelectorMode::WindowClass,
+                regex: "steam_app_.*".to_string(),
+            },
+            Selector::TimeOfDay {
+                days: vec![Weekday::Tue],
+                from: time(23, 30),
+                to: time(1, 0),
+            },
+            Selector::DeviceConnected {
+                usb_vid: 0x1e7d,
+                usb_pid: 0x3098,
+            },
+            Selector::Any {
+                selectors: vec![
+                    Selector::ScreenLocked,
+                    Selector::Not {
+                        selector: Box::new(Selector::PowerSource {
+                            source: PowerSource::Battery,
+                        }),
+                    },
+                ],
+            },
+        ];
+
+        for selector in selectors {
+            let (sensor, argument) = selector.to_rule_strings();
+
+            assert_eq!(
+                Selector::from_rule_strings(&sensor, &argument).unwrap(),
+                selector
+            );
+        }
+    }
+}
@@ -176,10 +176,11 @@ impl Sensor for GnomeShellExtensionSensor {
     fn get_usage_example(&self) -> String {
         r#"
 gnome-shellext:
-rules add window-[class|instance|name] <regex> [<profile-name.profile>|<slot number>]
+rules add window-[class|instance|name] <regex> <action>
 
 rules add window-name '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/profile1.profile
 rules add window-instance gnome-calculator 2
+rules add window-class 'steam_app_.*' 'brightness:30; param:wave.lua:speed_divisor=5'
 "#
         .to_string()
     }
@@ -33,6 +33,7 @@ mod gnome_shellext;
 mod mutter;
 #[cfg(feature = "sensor-procmon")]
 mod process;
+mod system;
 #[cfg(feature = "sensor-wayland")]
 mod wayland;
 #[cfg(feature = "sensor-x11")]
@@ -44,6 +45,7 @@ pub use gnome_shellext::*;
 pub use mutter::*;
 #[cfg(feature = "sensor-procmon")]
 pub use process::*;
+pub use system::*;
 #[cfg(feature = "sensor-wayland")]
 pub use wayland::*;
 #[cfg(feature = "sensor-x11")]
@@ -237,6 +239,8 @@ pub fn register_sensors() -> Result<()> {
     #[cfg(feature = "sensor-x11")]
     register_sensor(X11Sensor::new());
 
+    register_sensor(SystemSensor::new());
+
     // initialize all registered sensors
     for s in SENSORS.write().iter_mut() {
         s.initialize()?;
@@ -143,10 +143,11 @@ impl Sensor for ProcessSensor {
     fn get_usage_example(&self) -> String {
         r#"
 Process:
-rules add exec <comm> [<profile-name.profile>|<slot number>]
+rules add exec <comm> <action>
 
 rules add exec gnome-calc.* /var/lib/eruption/profiles/profile1.profile
 rules add exec gnome-calc.* 2
+rules add exec 'sc2.*' 'brightness:30; sfx:off; ambient:on'
 "#
         .to_string()
     }
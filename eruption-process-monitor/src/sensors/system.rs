@@ -0,0 +1,236 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use log::*;
+
+use crate::conditions::{LocalTime, SystemState};
+use crate::{constants, dbus_client};
+
+use super::{Sensor, SensorConfiguration, SENSORS_CONFIGURATION};
+
+type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, Clone)]
+pub struct SystemSensorData(pub SystemState);
+
+impl super::SensorData for SystemSensorData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemSensor {
+    pub is_failed: bool,
+    pub last_poll: Option<Instant>,
+    pub state: SystemState,
+}
+
+impl SystemSensor {
+    pub fn new() -> Self {
+        SystemSensor {
+            is_failed: false,
+            last_poll: None,
+            state: SystemState::default(),
+        }
+    }
+}
+
+/// Query logind (or the screen saver as a fallback) for the idle and lock state of the session
+fn get_session_state() -> (u64, bool) {
+    match dbus_client::get_login_session_state() {
+        Ok((idle_hint, idle_since, locked_hint)) => {
+            let idle_seconds = if idle_hint {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as u64;
+
+                now.saturating_sub(idle_since) / 1_000_000
+            } else {
+                0
+            };
+
+            let locked = locked_hint || dbus_client::is_screensaver_active().unwrap_or(false);
+
+            (idle_seconds, locked)
+        }
+
+        Err(e) => {
+            debug!("Could not query logind: {}", e);
+
+            (0, dbus_client::is_screensaver_active().unwrap_or(false))
+        }
+    }
+}
+
+/// Returns true if no mains power supply is online, but a battery is present
+fn is_on_battery() -> bool {
+    let mut mains_online = false;
+    let mut battery_present = false;
+
+    if let Ok(entries) = fs::read_dir(constants::POWER_SUPPLY_DIR) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            match read_attribute(&path, "type").as_deref() {
+                Some("Mains") => {
+                    mains_online |= read_attribute(&path, "online").as_deref() == Some("1");
+                }
+
+                Some("Battery") => {
+                    battery_present |= read_attribute(&path, "present").as_deref() != Some("0");
+                }
+
+                _ => { /* ignore other types of power supplies */ }
+            }
+        }
+    }
+
+    battery_present && !mains_online
+}
+
+/// Returns the USB vendor and product ids of all connected devices
+fn get_usb_devices() -> HashSet<(u16, u16)> {
+    let mut result = HashSet::new();
+
+    if let Ok(entries) = fs::read_dir(constants::USB_DEVICES_DIR) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            let vid = read_attribute(&path, "idVendor")
+                .and_then(|vid| u16::from_str_radix(&vid, 16).ok());
+            let pid = read_attribute(&path, "idProduct")
+                .and_then(|pid| u16::from_str_radix(&pid, 16).ok());
+
+            if let (Some(vid), Some(pid)) = (vid, pid) {
+                result.insert((vid, pid));
+            }
+        }
+    }
+
+    result
+}
+
+/// Gather the current state of the system
+pub fn get_system_state() -> SystemState {
+    let (idle_seconds, screen_locked) = get_session_state();
+
+    SystemState {
+        local_time: LocalTime::now(),
+        idle_seconds,
+        screen_locked,
+        on_battery: is_on_battery(),
+        usb_devices: get_usb_devices(),
+    }
+}
+
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+#[async_trait]
+impl Sensor for SystemSensor {
+    fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn is_enabled(&self) -> bool {
+        !SENSORS_CONFIGURATION
+            .read()
+            .contains(&SensorConfiguration::AllDisabledHint)
+    }
+
+    fn get_id(&self) -> String {
+        "system".to_string()
+    }
+
+    fn get_name(&self) -> String {
+        "System".to_string()
+    }
+
+    fn get_description(&self) -> String {
+        "Watches the time of day, idle and lock state, power source and connected devices"
+            .to_string()
+    }
+
+    fn get_usage_example(&self) -> String {
+        r#"
+System:
+rules add time '[<days>] <HH:MM>-<HH:MM>' <action>
+rules add idle <seconds> <action>
+rules add locked <action>
+rules add power <ac|battery> <action>
+rules add device <vid>:<pid> <action>
+
+rules add time 'weekdays 22:00-07:00' night.profile
+rules add power battery 'brightness:30'
+rules add idle 300 'brightness:5'
+rules add device 1e7d:3098 2
+rules add window-class 'steam_app_.*' and power ac 'sfx:on'
+"#
+        .to_string()
+    }
+
+    fn is_failed(&self) -> bool {
+        self.is_failed
+    }
+
+    fn set_failed(&mut self, failed: bool) {
+        self.is_failed = failed;
+    }
+
+    fn is_pollable(&self) -> bool {
+        true
+    }
+
+    fn poll(&mut self) -> Result<Box<dyn super::SensorData>> {
+        let interval = Duration::from_millis(constants::SYSTEM_SENSOR_POLL_MILLIS);
+
+        if self
+            .last_poll
+            .map(|last_poll| last_poll.elapsed() >= interval)
+            .unwrap_or(true)
+        {
+            self.state = get_system_state();
+
+            self.last_poll = Some(Instant::now());
+        }
+
+        Ok(Box::new(SystemSensorData(self.state.clone())))
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
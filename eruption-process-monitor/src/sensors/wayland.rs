@@ -266,10 +266,11 @@ impl Sensor for WaylandSensor {
     fn get_usage_example(&self) -> String {
         r#"
 Wayland:
-rules add [window-class|window-class-instance] <regex> [<profile-name.profile>|<slot number>]
+rules add [window-class|window-class-instance] <regex> <action>
 
 rules add window-class '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/profile1.profile
 rules add window-instance gnome-calculator 2
+rules add window-class 'steam_app_.*' 'brightness:30; param:wave.lua:speed_divisor=5'
 "#
         .to_string()
     }
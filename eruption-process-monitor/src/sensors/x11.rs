@@ -135,10 +135,11 @@ impl Sensor for X11Sensor {
     fn get_usage_example(&self) -> String {
         r#"
 X11:
-rules add window-[class|instance|name] <regex> [<profile-name.profile>|<slot number>]
+rules add window-[class|instance|name] <regex> <action>
 
 rules add window-name '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/profile1.profile
 rules add window-instance gnome-calculator 2
+rules add window-class 'steam_app_.*' 'brightness:30; param:wave.lua:speed_divisor=5'
 
 You may want to use the command line tool `xprop` to find the relevant information
 "#
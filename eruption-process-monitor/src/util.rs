@@ -19,6 +19,7 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+use crate::constants;
 use std::env;
 use std::{fs, path::Path};
 use std::{io, path::PathBuf};
@@ -77,3 +78,23 @@ pub fn create_rules_file_if_not_exists<P: AsRef<Path>>(path: &P) -> io::Result<(
 
     Ok(())
 }
+
+/// Make a profile file name given in a rule absolute, the Eruption daemon requires full paths
+pub fn resolve_profile_file(profile_file: &str) -> String {
+    resolve_path(constants::DEFAULT_PROFILE_DIR, profile_file)
+}
+
+/// Make a script file name given in a rule absolute, the Eruption daemon requires full paths
+pub fn resolve_script_file(script_file: &str) -> String {
+    resolve_path(constants::DEFAULT_SCRIPT_DIR, script_file)
+}
+
+fn resolve_path(base_dir: &str, file_name: &str) -> String {
+    let path = Path::new(file_name);
+
+    if path.is_absolute() {
+        file_name.to_string()
+    } else {
+        Path::new(base_dir).join(path).to_string_lossy().to_string()
+    }
+}
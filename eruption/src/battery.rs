@@ -0,0 +1,547 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Monitoring of the battery level and the signal strength of wireless devices
+//!
+//! The status maps that are reported by the device drivers are parsed into typed samples on each
+//! poll of the device status. A bounded history of the samples is kept for each device. When the
+//! battery level of a device drops below one of the configured thresholds, a `BatteryLevelLow`
+//! signal is emitted on the D-Bus, and optionally the LEDs of the device are overlaid with a
+//! pulsing red warning.
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::constants;
+use crate::hwdevices::{DeviceStatus, RGBA};
+use crate::state::DeviceMakeModelSerial;
+use crate::threads::DbusApiEvent;
+
+/// A typed sample of the power and connection related status of a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PowerStatus {
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+
+    pub battery_level: Option<u8>,
+    pub signal_strength: Option<u8>,
+    pub transceiver_enabled: Option<bool>,
+}
+
+impl PowerStatus {
+    /// Parse the status map of a device driver. Returns `None` for devices that neither report
+    /// a battery level nor a signal strength
+    pub fn from_device_status(status: &DeviceStatus, timestamp: u64) -> Option<Self> {
+        let percent = |key: &str| {
+            status
+                .get(key)
+                .and_then(|v| v.parse::<u8>().ok())
+                .map(|v| v.min(100))
+        };
+
+        let battery_level = percent("battery-level-percent");
+        let signal_strength = percent("signal-strength-percent");
+
+        if battery_level.is_none() && signal_strength.is_none() {
+            None
+        } else {
+            Some(Self {
+                timestamp,
+                battery_level,
+                signal_strength,
+                transceiver_enabled: status
+                    .get("transceiver-enabled")
+                    .and_then(|v| v.parse::<bool>().ok()),
+            })
+        }
+    }
+
+    /// Returns true if both samples carry the same readings, regardless of their timestamps
+    fn same_readings(&self, other: &Self) -> bool {
+        self.battery_level == other.battery_level
+            && self.signal_strength == other.signal_strength
+            && self.transceiver_enabled == other.transceiver_enabled
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BatteryState {
+    Normal,
+    Low,
+    Critical,
+}
+
+/// The battery levels (in percent) at or below which a device is considered low on battery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Thresholds {
+    pub low: u8,
+    pub critical: u8,
+
+    /// The battery level has to rise by this amount above a threshold, before the state of the
+    /// device is reset. This avoids repeated notifications of a fluctuating battery level
+    pub hysteresis: u8,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self {
+            low: constants::DEFAULT_BATTERY_LOW_THRESHOLD,
+            critical: constants::DEFAULT_BATTERY_CRITICAL_THRESHOLD,
+            hysteresis: constants::BATTERY_THRESHOLD_HYSTERESIS,
+        }
+    }
+}
+
+impl Thresholds {
+    /// Returns the state of a device with battery level `level`, that has been in state `previous`
+    pub fn classify(&self, level: u8, previous: BatteryState) -> BatteryState {
+        let state = if level <= self.critical {
+            BatteryState::Critical
+        } else if level <= self.low {
+            BatteryState::Low
+        } else {
+            BatteryState::Normal
+        };
+
+        // only leave a state once the level rose sufficiently above its threshold
+        let recovered = |threshold: u8| level > threshold.saturating_add(self.hysteresis);
+
+        match previous {
+            BatteryState::Critical if state < previous && !recovered(self.critical) => previous,
+            BatteryState::Low if state < previous && !recovered(self.low) => previous,
+
+            _ => state,
+        }
+    }
+}
+
+/// A device that crossed one of the battery thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatteryEvent {
+    pub device: u64,
+    pub level: u8,
+    pub state: BatteryState,
+}
+
+#[derive(Debug, Default)]
+struct DeviceHistory {
+    samples: VecDeque<PowerStatus>,
+    state: Option<BatteryState>,
+}
+
+/// Keeps the history of the samples and the battery state of each device
+#[derive(Debug, Default)]
+pub struct Monitor {
+    thresholds: Thresholds,
+    devices: HashMap<u64, DeviceHistory>,
+}
+
+impl Monitor {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Record the status of the device with index `device`. Returns an event if the battery
+    /// level of the device dropped below a threshold
+    pub fn update(
+        &mut self,
+        device: u64,
+        status: &DeviceStatus,
+        timestamp: u64,
+    ) -> Option<BatteryEvent> {
+        let sample = PowerStatus::from_device_status(status, timestamp)?;
+        let history = self.devices.entry(device).or_default();
+
+        // only changes are recorded, the poll timer fires twice a second
+        if !matches!(history.samples.back(), Some(last) if last.same_readings(&sample)) {
+            if history.samples.len() >= constants::BATTERY_HISTORY_SIZE {
+                history.samples.pop_front();
+            }
+
+            history.samples.push_back(sample);
+        }
+
+        let level = sample.battery_level?;
+
+        let previous = history.state.unwrap_or(BatteryState::Normal);
+        let state = self.thresholds.classify(level, previous);
+
+        history.state = Some(state);
+
+        if state > previous {
+            Some(BatteryEvent {
+                device,
+                level,
+                state,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the last known battery level of the device with index `device`
+    pub fn battery_level(&self, device: u64) -> Option<u8> {
+        self.devices
+            .get(&device)
+            .and_then(|h| h.samples.back())
+            .and_then(|s| s.battery_level)
+    }
+
+    pub fn battery_state(&self, device: u64) -> Option<BatteryState> {
+        self.devices.get(&device).and_then(|h| h.state)
+    }
+
+    pub fn history(&self, device: u64) -> Vec<PowerStatus> {
+        self.devices
+            .get(&device)
+            .map(|h| h.samples.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Forget all devices, e.g. after a hotplug event changed the indices of the devices
+    pub fn clear(&mut self) {
+        self.devices.clear();
+    }
+}
+
+/// Set to true while at least one device is overlaid with the low battery warning, so that
+/// the render thread may skip the overlay without acquiring a lock
+static OVERLAY_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref MONITOR: Arc<Mutex<Monitor>> = {
+        let config = crate::CONFIG.lock();
+
+        let get = |key: &str, default: u8| {
+            config
+                .as_ref()
+                .and_then(|config| config.get_int(key).ok())
+                .map(|v| v.clamp(0, 100) as u8)
+                .unwrap_or(default)
+        };
+
+        let thresholds = Thresholds {
+            low: get(
+                "battery.low_threshold_percent",
+                constants::DEFAULT_BATTERY_LOW_THRESHOLD,
+            ),
+            critical: get(
+                "battery.critical_threshold_percent",
+                constants::DEFAULT_BATTERY_CRITICAL_THRESHOLD,
+            ),
+            hysteresis: constants::BATTERY_THRESHOLD_HYSTERESIS,
+        };
+
+        Arc::new(Mutex::new(Monitor::new(thresholds)))
+    };
+
+    static ref OVERLAY_ENABLED: bool = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<bool>("battery.enable_overlay").ok())
+        .unwrap_or(false);
+
+    /// The devices that are overlaid with the low battery warning, and their battery state
+    static ref OVERLAY_DEVICES: Arc<RwLock<HashMap<DeviceMakeModelSerial, BatteryState>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
+    /// Time base of the pulsing animation of the overlay
+    static ref OVERLAY_EPOCH: Instant = Instant::now();
+}
+
+/// Record the status of the device with index `device` and the identifier `make_model_serial`.
+/// Emits a D-Bus signal if the battery level of the device dropped below a threshold
+pub fn update_device_status(
+    device: u64,
+    make_model_serial: &DeviceMakeModelSerial,
+    status: &DeviceStatus,
+) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let mut monitor = MONITOR.lock();
+
+    if let Some(event) = monitor.update(device, status, timestamp) {
+        warn!(
+            "Battery level of device {} is {}: {}%",
+            make_model_serial,
+            if event.state == BatteryState::Critical {
+                "critical"
+            } else {
+                "low"
+            },
+            event.level
+        );
+
+        if let Some(dbus_api_tx) = crate::DBUS_API_TX.lock().as_ref() {
+            dbus_api_tx
+                .send(DbusApiEvent::BatteryLevelLow(
+                    event.device,
+                    event.level,
+                    event.state == BatteryState::Critical,
+                ))
+                .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
+        }
+    }
+
+    if *OVERLAY_ENABLED {
+        let mut overlay_devices = OVERLAY_DEVICES.write();
+
+        match monitor.battery_state(device) {
+            Some(state) if state != BatteryState::Normal => {
+                overlay_devices.insert(make_model_serial.clone(), state);
+            }
+
+            _ => {
+                overlay_devices.remove(make_model_serial);
+            }
+        }
+
+        OVERLAY_ACTIVE.store(!overlay_devices.is_empty(), Ordering::SeqCst);
+    }
+}
+
+/// Drop the state of all devices, their indices are invalidated by hotplug events
+pub fn reset() {
+    MONITOR.lock().clear();
+
+    OVERLAY_DEVICES.write().clear();
+    OVERLAY_ACTIVE.store(false, Ordering::SeqCst);
+}
+
+/// Returns true if at least one device is overlaid with the low battery warning
+pub fn is_overlay_active() -> bool {
+    OVERLAY_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Returns the last known battery level (in percent) of the device with index `device`
+pub fn get_battery_level(device: u64) -> Option<u8> {
+    MONITOR.lock().battery_level(device)
+}
+
+/// Returns the recorded history of the device with index `device`, oldest samples first
+pub fn get_history(device: u64) -> Vec<PowerStatus> {
+    MONITOR.lock().history(device)
+}
+
+/// Overlay the LED map of the device `make_model_serial` with a pulsing red warning, if the
+/// device is low on battery
+pub fn apply_low_battery_overlay<'a>(
+    make_model_serial: &str,
+    led_map: Cow<'a, [RGBA]>,
+) -> Cow<'a, [RGBA]> {
+    if !OVERLAY_ACTIVE.load(Ordering::SeqCst) {
+        return led_map;
+    }
+
+    let state = match OVERLAY_DEVICES.read().get(make_model_serial) {
+        Some(state) => *state,
+        None => return led_map,
+    };
+
+    let alpha = pulse(state, OVERLAY_EPOCH.elapsed().as_millis() as u64);
+
+    Cow::Owned(led_map.iter().map(|bg| blend_warning(*bg, alpha)).collect())
+}
+
+/// Returns the opacity of the warning overlay, `millis` milliseconds into the animation
+fn pulse(state: BatteryState, millis: u64) -> u8 {
+    let period = match state {
+        BatteryState::Critical => constants::BATTERY_OVERLAY_PERIOD_MILLIS / 2,
+        _ => constants::BATTERY_OVERLAY_PERIOD_MILLIS,
+    };
+
+    let phase = (millis % period) as f64 / period as f64;
+
+    ((1.0 - (phase * 2.0 * PI).cos()) / 2.0 * 255.0).round() as u8
+}
+
+fn blend_warning(bg: RGBA, alpha: u8) -> RGBA {
+    let alpha = alpha as u32;
+    let mix = |bg: u8, fg: u8| ((alpha * fg as u32 + (255 - alpha) * bg as u32) / 255) as u8;
+
+    RGBA {
+        r: mix(bg.r, 255),
+        g: mix(bg.g, 0),
+        b: mix(bg.b, 0),
+        a: bg.a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(pairs: &[(&str, &str)]) -> DeviceStatus {
+        DeviceStatus(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    fn battery(level: u8) -> DeviceStatus {
+        status(&[
+            ("battery-level-percent", &level.to_string()),
+            ("signal-strength-percent", "80"),
+        ])
+    }
+
+    const THRESHOLDS: Thresholds = Thresholds {
+        low: 20,
+        critical: 5,
+        hysteresis: 5,
+    };
+
+    #[test]
+    fn parse_device_status() {
+        let sample = PowerStatus::from_device_status(
+            &status(&[
+                ("battery-level-percent", "75"),
+                ("transceiver-enabled", "true"),
+                ("connected", "true"),
+            ]),
+            42,
+        )
+        .unwrap();
+
+        assert_eq!(sample.timestamp, 42);
+        assert_eq!(sample.battery_level, Some(75));
+        assert_eq!(sample.signal_strength, None);
+        assert_eq!(sample.transceiver_enabled, Some(true));
+
+        // the ELO 7.1 Air reports "unknown" for unexpected values
+        let sample = PowerStatus::from_device_status(
+            &status(&[
+                ("battery-level-percent", "unknown"),
+                ("signal-strength-percent", "90"),
+            ]),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(sample.battery_level, None);
+        assert_eq!(sample.signal_strength, Some(90));
+
+        assert!(PowerStatus::from_device_status(&status(&[("connected", "true")]), 0).is_none());
+    }
+
+    #[test]
+    fn thresholds_use_hysteresis() {
+        use BatteryState::*;
+
+        assert_eq!(THRESHOLDS.classify(50, Normal), Normal);
+        assert_eq!(THRESHOLDS.classify(20, Normal), Low);
+        assert_eq!(THRESHOLDS.classify(5, Normal), Critical);
+
+        // small fluctuations above the threshold do not reset the state
+        assert_eq!(THRESHOLDS.classify(23, Low), Low);
+        assert_eq!(THRESHOLDS.classify(26, Low), Normal);
+        assert_eq!(THRESHOLDS.classify(9, Critical), Critical);
+        assert_eq!(THRESHOLDS.classify(11, Critical), Low);
+        assert_eq!(THRESHOLDS.classify(30, Critical), Normal);
+    }
+
+    #[test]
+    fn events_are_emitted_once_per_threshold() {
+        let mut monitor = Monitor::new(THRESHOLDS);
+
+        assert_eq!(monitor.update(1, &battery(40), 0), None);
+        assert_eq!(
+            monitor.update(1, &battery(20), 1),
+            Some(BatteryEvent {
+                device: 1,
+                level: 20,
+                state: BatteryState::Low
+            })
+        );
+
+        assert_eq!(monitor.update(1, &battery(22), 2), None);
+        assert_eq!(monitor.update(1, &battery(19), 3), None);
+
+        assert_eq!(
+            monitor.update(1, &battery(4), 4).map(|e| e.state),
+            Some(BatteryState::Critical)
+        );
+
+        // charging the device resets the state, so that the next discharge is reported again
+        assert_eq!(monitor.update(1, &battery(100), 5), None);
+        assert_eq!(monitor.battery_state(1), Some(BatteryState::Normal));
+        assert!(monitor.update(1, &battery(15), 6).is_some());
+
+        assert_eq!(monitor.battery_level(1), Some(15));
+        assert_eq!(monitor.battery_level(2), None);
+    }
+
+    #[test]
+    fn history_records_changes_only() {
+        let mut monitor = Monitor::new(THRESHOLDS);
+
+        monitor.update(0, &battery(80), 0);
+        monitor.update(0, &battery(80), 1);
+        monitor.update(0, &battery(79), 2);
+
+        let history = monitor.history(0);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].timestamp, 0);
+        assert_eq!(history[1].battery_level, Some(79));
+
+        for i in 0..(constants::BATTERY_HISTORY_SIZE as u64 + 10) {
+            monitor.update(0, &battery((i % 100) as u8), i);
+        }
+
+        assert_eq!(monitor.history(0).len(), constants::BATTERY_HISTORY_SIZE);
+    }
+
+    #[test]
+    fn overlay_pulses() {
+        let period = constants::BATTERY_OVERLAY_PERIOD_MILLIS;
+
+        assert_eq!(pulse(BatteryState::Low, 0), 0);
+        assert_eq!(pulse(BatteryState::Low, period / 2), 255);
+        assert_eq!(pulse(BatteryState::Critical, period / 4), 255);
+
+        let bg = RGBA {
+            r: 0,
+            g: 200,
+            b: 0,
+            a: 255,
+        };
+
+        assert_eq!(blend_warning(bg, 0), bg);
+        assert_eq!(blend_warning(bg, 255).r, 255);
+        assert_eq!(blend_warning(bg, 255).g, 0);
+    }
+}
@@ -0,0 +1,321 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Device specific color correction
+//!
+//! The LEDs of different devices rarely agree on what "white" looks like. A calibration is
+//! applied to the LED map of a device, just before it is sent to the hardware, so that it does
+//! neither affect the canvas nor the other devices. Each color channel is transformed by:
+//!
+//! `out = 255 * gain * (white_point / 255) * (in / 255) ^ gamma`
+//!
+//! The transformation is precomputed into a lookup table, whenever a calibration is changed.
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::constants;
+use crate::hwdevices::{DeviceTrait, RGBA};
+use crate::layout;
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CalibrationError {
+    #[error("Invalid gamma value, expected: {min}..{max}")]
+    InvalidGamma { min: f64, max: f64 },
+
+    #[error("Invalid gain, expected: 0..{max}")]
+    InvalidGain { max: f64 },
+}
+
+/// Color calibration of a single device
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ColorCalibration {
+    /// Exponent of the gamma curve, 1.0 leaves the curve linear
+    pub gamma: f64,
+
+    /// Gains of the red, green and blue channels
+    pub gains: [f64; 3],
+
+    /// The color that is displayed instead of full white
+    pub white_point: [u8; 3],
+}
+
+impl Default for ColorCalibration {
+    fn default() -> Self {
+        Self {
+            gamma: 1.0,
+            gains: [1.0; 3],
+            white_point: [255; 3],
+        }
+    }
+}
+
+impl ColorCalibration {
+    /// Returns true if the calibration leaves all colors unchanged
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let (min, max) = constants::CALIBRATION_GAMMA_RANGE;
+
+        if !(min..=max).contains(&self.gamma) {
+            return Err(CalibrationError::InvalidGamma { min, max }.into());
+        }
+
+        if self
+            .gains
+            .iter()
+            .any(|gain| !(0.0..=constants::MAX_CALIBRATION_GAIN).contains(gain))
+        {
+            return Err(CalibrationError::InvalidGain {
+                max: constants::MAX_CALIBRATION_GAIN,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn build_lookup_table(&self) -> LookupTable {
+        let mut result = [[0; 256]; 3];
+
+        for (channel, table) in result.iter_mut().enumerate() {
+            let scale = self.gains[channel] * (self.white_point[channel] as f64 / 255.0);
+
+            for (value, out) in table.iter_mut().enumerate() {
+                let level = (value as f64 / 255.0).powf(self.gamma) * scale;
+
+                *out = (level.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+
+        result
+    }
+}
+
+/// Precomputed output values of the red, green and blue channels
+type LookupTable = [[u8; 256]; 3];
+
+struct Calibration {
+    calibration: ColorCalibration,
+    lookup_table: Box<LookupTable>,
+}
+
+/// Set to true while at least one device is calibrated, so that the render thread may skip
+/// the calibration stage without acquiring a lock
+static CALIBRATIONS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Calibrations of the devices, devices without a calibration are not listed here
+    static ref CALIBRATIONS: Arc<RwLock<HashMap<DeviceMakeModelSerial, Calibration>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// Initialize the calibrations from the saved state
+pub fn init_calibrations(calibrations: HashMap<DeviceMakeModelSerial, ColorCalibration>) {
+    for (device, calibration) in calibrations {
+        set_calibration(&device, calibration).unwrap_or_else(|e| {
+            warn!("Ignoring the color calibration of device {}: {}", device, e)
+        });
+    }
+}
+
+/// Returns the calibration of `device`, in `make:model:serial` notation
+pub fn get_calibration(device: &str) -> ColorCalibration {
+    CALIBRATIONS
+        .read()
+        .get(device)
+        .map(|c| c.calibration)
+        .unwrap_or_default()
+}
+
+/// Returns the calibrations of all devices, including devices that are currently not connected
+pub fn get_calibrations() -> HashMap<DeviceMakeModelSerial, ColorCalibration> {
+    CALIBRATIONS
+        .read()
+        .iter()
+        .map(|(device, c)| (device.clone(), c.calibration))
+        .collect()
+}
+
+/// Set the calibration of `device`, in `make:model:serial` notation
+pub fn set_calibration(device: &str, calibration: ColorCalibration) -> Result<()> {
+    calibration.validate()?;
+
+    if calibration.is_identity() {
+        reset_calibration(device);
+    } else {
+        let mut calibrations = CALIBRATIONS.write();
+
+        calibrations.insert(
+            device.to_string(),
+            Calibration {
+                calibration,
+                lookup_table: Box::new(calibration.build_lookup_table()),
+            },
+        );
+
+        CALIBRATIONS_ACTIVE.store(true, Ordering::SeqCst);
+    }
+
+    Ok(())
+}
+
+/// Reset the calibration of `device`. Returns true if the device had been calibrated
+pub fn reset_calibration(device: &str) -> bool {
+    let mut calibrations = CALIBRATIONS.write();
+
+    let result = calibrations.remove(device).is_some();
+
+    CALIBRATIONS_ACTIVE.store(!calibrations.is_empty(), Ordering::SeqCst);
+
+    result
+}
+
+/// Apply the calibration of `device` to its (composed) LED map
+pub fn apply_calibration<'a>(
+    device: &dyn DeviceTrait,
+    led_map: Cow<'a, [RGBA]>,
+) -> Cow<'a, [RGBA]> {
+    if !CALIBRATIONS_ACTIVE.load(Ordering::SeqCst) {
+        return led_map;
+    }
+
+    let key = layout::device_make_model_serial(device);
+
+    match CALIBRATIONS.read().get(&key) {
+        Some(calibration) => Cow::Owned(
+            led_map
+                .iter()
+                .map(|color| apply(&calibration.lookup_table, *color))
+                .collect(),
+        ),
+
+        None => led_map,
+    }
+}
+
+fn apply(lookup_table: &LookupTable, color: RGBA) -> RGBA {
+    RGBA {
+        r: lookup_table[0][color.r as usize],
+        g: lookup_table[1][color.g as usize],
+        b: lookup_table[2][color.b as usize],
+        a: color.a,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn color(r: u8, g: u8, b: u8) -> RGBA {
+        RGBA { r, g, b, a: 255 }
+    }
+
+    #[test]
+    fn identity_calibration() {
+        let lookup_table = ColorCalibration::default().build_lookup_table();
+
+        for value in 0..=255 {
+            assert_eq!(apply(&lookup_table, color(value, value, value)).r, value);
+        }
+    }
+
+    #[test]
+    fn gains_and_white_point() {
+        let calibration = ColorCalibration {
+            gamma: 1.0,
+            gains: [1.0, 0.5, 2.0],
+            white_point: [255, 255, 128],
+        };
+
+        let result = apply(&calibration.build_lookup_table(), color(255, 255, 100));
+
+        assert_eq!(result.r, 255);
+        assert_eq!(result.g, 128);
+        assert_eq!(result.b, 100);
+        assert_eq!(result.a, 255);
+    }
+
+    #[test]
+    fn gamma_curve() {
+        let calibration = ColorCalibration {
+            gamma: 2.0,
+            ..Default::default()
+        };
+
+        let result = apply(&calibration.build_lookup_table(), color(0, 128, 255));
+
+        assert_eq!(result.r, 0);
+        assert_eq!(result.g, 64);
+        assert_eq!(result.b, 255);
+    }
+
+    #[test]
+    fn calibrations_are_validated() {
+        let calibration = ColorCalibration {
+            gamma: 0.0,
+            ..Default::default()
+        };
+
+        assert!(calibration.validate().is_err());
+
+        let calibration = ColorCalibration {
+            gains: [1.0, -0.5, 1.0],
+            ..Default::default()
+        };
+
+        assert!(calibration.validate().is_err());
+        assert!(ColorCalibration::default().validate().is_ok());
+    }
+
+    #[test]
+    fn identity_calibrations_are_not_stored() -> Result<()> {
+        let device = "0x1:0x2:test";
+
+        set_calibration(
+            device,
+            ColorCalibration {
+                gamma: 2.2,
+                ..Default::default()
+            },
+        )?;
+
+        assert_eq!(get_calibration(device).gamma, 2.2);
+
+        set_calibration(device, ColorCalibration::default())?;
+
+        assert!(!get_calibrations().contains_key(device));
+        assert!(!reset_calibration(device));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Push-based stream of the rendered canvas
+//!
+//! Clients connect to the UNIX domain socket `CANVAS_STREAM_SOCKET_NAME`, that is announced by the
+//! `GetCanvasStream` D-Bus method, and receive every frame that has been sent to the devices.
+//! Each frame is encoded as follows (all integers are little endian):
+//!
+//! | Field         | Type                 | Description                                    |
+//! |---------------|----------------------|------------------------------------------------|
+//! | length        | `u32`                | Length of the remainder of the frame, in bytes |
+//! | frame counter | `u64`                | Incremented for each realized frame            |
+//! | timestamp     | `u64`                | Microseconds since the UNIX epoch              |
+//! | num_leds      | `u32`                | Number of LEDs on the canvas                   |
+//! | colors        | `[u8; 4] * num_leds` | RGBA color of each LED                         |
+//!
+//! Gaps in the frame counter indicate frames that have been dropped, because the client could
+//! not keep up. Clients that stall for more than `CANVAS_STREAM_WRITE_TIMEOUT_MILLIS` are
+//! disconnected, so that they can not hold up the other clients.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use flume::{bounded, Sender, TrySendError};
+use lazy_static::lazy_static;
+use log::*;
+use nix::unistd::unlink;
+use parking_lot::Mutex;
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+use crate::util::ratelimited;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The number of frames that have been realized since the daemon has been started
+pub static FRAME_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The number of currently connected clients
+static NUM_CLIENTS: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    /// Connected clients of the canvas stream
+    static ref CLIENTS: Arc<Mutex<Vec<UnixStream>>> = Arc::new(Mutex::new(Vec::new()));
+
+    /// Frames that are waiting to be sent to the clients
+    static ref FRAME_TX: Arc<Mutex<Option<Sender<Arc<Vec<u8>>>>>> = Arc::new(Mutex::new(None));
+}
+
+/// Bind the canvas stream socket and spawn the threads that accept clients and send frames
+pub fn spawn_canvas_stream_threads() -> Result<()> {
+    // unlink any leftover sockets
+    let _result = unlink(constants::CANVAS_STREAM_SOCKET_NAME)
+        .map_err(|e| debug!("Unlink of canvas stream socket failed: {}", e));
+
+    let listener = UnixListener::bind(constants::CANVAS_STREAM_SOCKET_NAME)?;
+
+    let mut perms = fs::metadata(constants::CANVAS_STREAM_SOCKET_NAME)?.permissions();
+    perms.set_mode(0o666);
+    fs::set_permissions(constants::CANVAS_STREAM_SOCKET_NAME, perms)?;
+
+    let (frame_tx, frame_rx) = bounded::<Arc<Vec<u8>>>(constants::CANVAS_STREAM_QUEUE_LEN);
+    FRAME_TX.lock().replace(frame_tx);
+
+    let builder = thread::Builder::new().name("canvas-stream".into());
+    builder.spawn(move || {
+        for client in listener.incoming() {
+            match client {
+                Ok(client) => {
+                    if let Err(e) = client.set_write_timeout(Some(Duration::from_millis(
+                        constants::CANVAS_STREAM_WRITE_TIMEOUT_MILLIS,
+                    ))) {
+                        error!("Could not set up a canvas stream client: {}", e);
+                        continue;
+                    }
+
+                    debug!("Canvas stream client connected");
+
+                    let mut clients = CLIENTS.lock();
+                    clients.push(client);
+
+                    NUM_CLIENTS.store(clients.len(), Ordering::SeqCst);
+                }
+
+                Err(e) => error!("Could not accept a canvas stream client: {}", e),
+            }
+        }
+    })?;
+
+    let builder = thread::Builder::new().name("canvas-stream-io".into());
+    builder.spawn(move || {
+        while let Ok(frame) = frame_rx.recv() {
+            let mut clients = CLIENTS.lock();
+
+            clients.retain_mut(|client| match client.write_all(&frame) {
+                Ok(()) => true,
+
+                Err(e) => {
+                    debug!("Disconnecting canvas stream client: {}", e);
+                    false
+                }
+            });
+
+            NUM_CLIENTS.store(clients.len(), Ordering::SeqCst);
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Publish a frame that has been sent to the devices. Frames are only encoded if at least one
+/// client is connected, and they are dropped instead of blocking the caller if the clients can
+/// not keep up
+pub fn publish_frame(led_map: &[RGBA]) {
+    let frame_counter = FRAME_COUNTER.fetch_add(1, Ordering::SeqCst) + 1;
+
+    if NUM_CLIENTS.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+
+    let frame = Arc::new(encode_frame(frame_counter, timestamp, led_map));
+
+    if let Some(frame_tx) = FRAME_TX.lock().as_ref() {
+        match frame_tx.try_send(frame) {
+            Ok(()) => {}
+
+            Err(TrySendError::Full(_)) => {
+                ratelimited::debug!("Dropped a frame of the canvas stream");
+            }
+
+            Err(TrySendError::Disconnected(_)) => {
+                ratelimited::error!("The canvas stream thread has terminated");
+            }
+        }
+    }
+}
+
+fn encode_frame(frame_counter: u64, timestamp: u64, led_map: &[RGBA]) -> Vec<u8> {
+    let length = 8 + 8 + 4 + led_map.len() * 4;
+
+    let mut result = Vec::with_capacity(4 + length);
+
+    // writes to a Vec<u8> can not fail
+    result.write_u32::<LittleEndian>(length as u32).unwrap();
+    result.write_u64::<LittleEndian>(frame_counter).unwrap();
+    result.write_u64::<LittleEndian>(timestamp).unwrap();
+    result
+        .write_u32::<LittleEndian>(led_map.len() as u32)
+        .unwrap();
+
+    for color in led_map {
+        result.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode_frame;
+    use crate::hwdevices::RGBA;
+
+    #[test]
+    fn encode_canvas_frame() {
+        let led_map = [
+            RGBA {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 4,
+            },
+            RGBA {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255,
+            },
+        ];
+
+        let frame = encode_frame(42, 1_000_000, &led_map);
+
+        assert_eq!(frame.len(), 4 + 8 + 8 + 4 + 2 * 4);
+        assert_eq!(&frame[0..4], &28u32.to_le_bytes());
+        assert_eq!(&frame[4..12], &42u64.to_le_bytes());
+        assert_eq!(&frame[12..20], &1_000_000u64.to_le_bytes());
+        assert_eq!(&frame[20..24], &2u32.to_le_bytes());
+        assert_eq!(&frame[24..], &[1, 2, 3, 4, 255, 0, 0, 255]);
+    }
+}
@@ -24,12 +24,24 @@
 /// Default path of eruption master configuration file
 pub const DEFAULT_CONFIG_FILE: &str = "/etc/eruption/eruption.conf";
 
+/// Default path of the driver registry file (VID/PID aliases and maturity level overrides)
+pub const DEFAULT_DRIVER_REGISTRY_FILE: &str = "/etc/eruption/drivers.toml";
+
 /// Default profile directory
 pub const DEFAULT_PROFILE_DIR: &str = "/var/lib/eruption/profiles/";
 
 /// Default script directory
 pub const DEFAULT_SCRIPT_DIR: &str = "/usr/share/eruption/scripts/";
 
+/// Scripts that have been bundled with an imported profile are stored here
+pub const IMPORTED_SCRIPT_DIR: &str = "/var/lib/eruption/scripts/";
+
+/// Recordings of the canvas are stored here
+pub const RECORDINGS_DIR: &str = "/var/lib/eruption/recordings/";
+
+/// Max. size of a recording of the canvas in bytes, the recording is stopped when it is reached
+pub const MAX_RECORDING_SIZE: u64 = 256 * 1024 * 1024;
+
 /// Default script directory
 pub const DEFAULT_MACRO_DIR: &str = "/usr/share/eruption/scripts/lib/macros";
 
@@ -60,9 +72,65 @@ pub const UNIT_NAME_FX_PROXY: &str = "eruption-fx-proxy.service";
 /// Eruption daemon control UNIX domain socket (SDK support)
 pub const CONTROL_SOCKET_NAME: &str = "/run/eruption/control.sock";
 
+/// Default address of the opt-in SDK network listener
+pub const DEFAULT_SDK_NETWORK_ADDRESS: &str = "127.0.0.1:2359";
+
+/// Default path of the file holding the token that SDK network clients have to present
+pub const DEFAULT_SDK_TOKEN_FILE: &str = "/etc/eruption/sdk.token";
+
+/// Max. size of a single message received from an SDK network client
+pub const MAX_SDK_NETWORK_FRAME_SIZE: usize = 65536;
+
 /// Eruption daemon audio data UNIX domain socket
 pub const AUDIO_SOCKET_NAME: &str = "/run/eruption/audio.sock";
 
+/// Eruption daemon canvas stream UNIX domain socket
+pub const CANVAS_STREAM_SOCKET_NAME: &str = "/run/eruption/canvas.sock";
+
+/// Max. number of frames that are queued for the canvas stream, before frames are dropped
+pub const CANVAS_STREAM_QUEUE_LEN: usize = 8;
+
+/// Clients of the canvas stream that do not accept a frame within n milliseconds are disconnected
+pub const CANVAS_STREAM_WRITE_TIMEOUT_MILLIS: u64 = 100;
+
+/// Eruption daemon metrics UNIX domain socket (OpenMetrics text format)
+pub const METRICS_SOCKET_NAME: &str = "/run/eruption/metrics.sock";
+
+/// Clients of the metrics socket that do not send a request within n milliseconds receive the
+/// metrics in plain OpenMetrics text format, without an HTTP response header
+pub const METRICS_SOCKET_READ_TIMEOUT_MILLIS: u64 = 100;
+
+/// Upper bounds of the buckets of the latency histograms, in microseconds
+pub const METRICS_LATENCY_BUCKETS_MICROS: [u64; 10] =
+    [50, 100, 250, 500, 1000, 2500, 5000, 10000, 25000, 50000];
+
+/// Max. time-to-live of an overlay, submitted via `SetLedColors`
+pub const MAX_OVERLAY_TTL_MILLIS: u32 = 5 * 60 * 1000;
+
+/// Max. number of overlays that a single D-Bus client may submit
+pub const MAX_OVERLAYS_PER_CLIENT: usize = 32;
+
+/// Default battery level (in percent) at or below which a device is considered low on battery
+pub const DEFAULT_BATTERY_LOW_THRESHOLD: u8 = 15;
+
+/// Default battery level (in percent) at or below which the battery of a device is considered critical
+pub const DEFAULT_BATTERY_CRITICAL_THRESHOLD: u8 = 5;
+
+/// The battery level has to rise by n percent above a threshold, before a device is considered recharged
+pub const BATTERY_THRESHOLD_HYSTERESIS: u8 = 5;
+
+/// Max. number of status samples that are kept in the history of each device
+pub const BATTERY_HISTORY_SIZE: usize = 512;
+
+/// Period of the pulsing low battery overlay; the period is halved when the battery level is critical
+pub const BATTERY_OVERLAY_PERIOD_MILLIS: u64 = 2000;
+
+/// Valid range of the gamma value of a device specific color calibration
+pub const CALIBRATION_GAMMA_RANGE: (f64, f64) = (0.1, 4.0);
+
+/// Max. gain of a color channel of a device specific color calibration
+pub const MAX_CALIBRATION_GAIN: f64 = 2.0;
+
 /// Number of slots
 pub const NUM_SLOTS: usize = 4;
 
@@ -84,6 +152,18 @@ pub const PROCESS_SPAWN_WAIT_MILLIS: u64 = 800;
 /// Target frames per second
 pub const TARGET_FPS: u64 = 24;
 
+/// Valid range of the target frame rate of a profile, and of the frame rate limit of a device
+pub const FRAME_RATE_RANGE: (u64, u64) = (1, 60);
+
+/// Default CPU budget of the Lua scripts, in percent of a single CPU core
+pub const DEFAULT_CPU_BUDGET_PERCENT: f64 = 10.0;
+
+/// Scripts that exceed the CPU budget are ticked on every n-th frame at most
+pub const MAX_TICK_DIVISOR: u32 = 8;
+
+/// Account for the CPU time of the scripts over a window of n milliseconds
+pub const SCHEDULER_WINDOW_MILLIS: u64 = 1000;
+
 /// Fade in on profile switch for n milliseconds
 pub const FADE_MILLIS: u64 = 1333;
 
@@ -96,6 +176,9 @@ pub const CANVAS_WIDTH: usize = 22 + 8;
 /// The height of the canvas
 pub const CANVAS_HEIGHT: usize = 6;
 
+/// The number of "pixels" of the mouse zone, located at the end of the canvas
+pub const MOUSE_ZONE_SIZE: usize = 36;
+
 /// The capacity of the buffer used for receiving audio samples
 pub const NET_BUFFER_CAPACITY: usize = 4096;
 
@@ -105,6 +188,9 @@ pub const TIMEOUT_CONDITION_MILLIS: u64 = 25;
 /// Max number of events that will be processed in each iteration of the main loop
 pub const MAX_EVENTS_PER_ITERATION: u64 = 128;
 
+/// Interval in which the input threads of virtual devices check whether they shall terminate
+pub const VIRTUAL_DEVICE_POLL_MILLIS: u64 = 250;
+
 /// Limit event handler upcalls to 1 per `EVENTS_UPCALL_RATE_LIMIT_MILLIS` milliseconds
 pub const EVENTS_UPCALL_RATE_LIMIT_MILLIS: u64 = 25;
 
@@ -20,23 +20,36 @@
 */
 
 use colorgrad::Color;
-use dbus::{ffidisp::BusType, ffidisp::Connection, ffidisp::NameFlag, message::SignalArgs};
+use dbus::arg::{self, ArgType, PropMap, RefArg, Variant};
+use dbus::{
+    ffidisp::BusType, ffidisp::Connection, ffidisp::MsgHandler, ffidisp::MsgHandlerResult,
+    ffidisp::MsgHandlerType, ffidisp::NameFlag, message::MessageType, message::SignalArgs,
+};
 use dbus_tree::{
     Access, MethodErr, Signal, {EmitsChangedSignal, Factory},
 };
 use flume::Sender;
 use log::*;
+use std::collections::HashSet;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use crate::{
+    battery, calibration, canvas_stream,
     color_scheme::ColorScheme,
-    constants, hwdevices,
+    constants,
+    hwdevices::{
+        self, onboard,
+        remap::{self, KeyRemap},
+        schema::{DeviceSchema, Value},
+    },
+    layout, metrics, overlays,
     plugins::{self, audio},
-    profiles, script,
+    profile_manager, profiles, scheduler, script,
     scripting::parameters,
     scripting::parameters_util,
+    state,
 };
 
 /// D-Bus messages and signals that are processed by the main thread
@@ -80,6 +93,7 @@ pub struct DbusApi {
     brightness_changed: Arc<Signal<()>>,
     device_status_changed: Arc<Signal<()>>,
     device_hotplug: Arc<Signal<()>>,
+    battery_level_low: Arc<Signal<()>>,
 }
 
 #[allow(dead_code)]
@@ -91,6 +105,11 @@ impl DbusApi {
         let c = Connection::get_private(BusType::System)?;
         c.register_name("org.eruption", NameFlag::ReplaceExisting as u32)?;
 
+        // get notified when clients disconnect, so that their overlays can be dropped
+        c.add_match(
+            "type='signal',sender='org.freedesktop.DBus',interface='org.freedesktop.DBus',member='NameOwnerChanged'",
+        )?;
+
         let c_clone = Arc::new(c);
         let c_clone2 = c_clone.clone();
         let c_clone3 = c_clone.clone();
@@ -134,6 +153,12 @@ impl DbusApi {
         );
         let device_hotplug_signal_clone = device_hotplug_signal.clone();
 
+        let battery_level_low_signal = Arc::new(
+            f.signal("BatteryLevelLow", ())
+                .sarg::<(u64, u8, bool), _>("battery_info"),
+        );
+        let battery_level_low_signal_clone = battery_level_low_signal.clone();
+
         let active_slot_property = f
             .property::<u64, _>("ActiveSlot", ())
             .emits_changed(EmitsChangedSignal::Const)
@@ -322,13 +347,156 @@ impl DbusApi {
                                 })
                                 .outarg::<Vec<(u8, u8, u8, u8)>, _>("values"),
                             )
-                            // .add_m(
-                            //     f.method("SetLedColors", (), move |m| {
-                            //         *crate::LAST_DBUS_EVENT_TIME.lock() = Instant::now();
-                            //         let s = *script::LED_MAP.read();
-                            //         Ok(vec![m.msg.method_return().append_all(s)])
-                            //     }), // .outarg::<Vec<RGBA>, _>("values"),
-                            // )
+                            .add_m(
+                                f.method("GetCanvasStream", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let socket =
+                                            constants::CANVAS_STREAM_SOCKET_NAME.to_string();
+                                        let frame_counter =
+                                            canvas_stream::FRAME_COUNTER.load(Ordering::SeqCst);
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append2(socket, frame_counter)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<String, _>("socket")
+                                .outarg::<u64, _>("frame_counter"),
+                            )
+                            .add_m(
+                                f.method("GetRenderStats", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let stats = scheduler::get_stats();
+
+                                        let scripts = stats
+                                            .scripts
+                                            .iter()
+                                            .map(|s| {
+                                                (
+                                                    s.script_file.to_string_lossy().to_string(),
+                                                    s.cpu_percent,
+                                                    s.tick_divisor,
+                                                )
+                                            })
+                                            .collect::<Vec<(String, f64, u32)>>();
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append3(
+                                                stats.target_fps,
+                                                stats.fps,
+                                                stats.render_time_percent,
+                                            )
+                                            .append3(
+                                                stats.frames_rendered,
+                                                stats.frames_skipped,
+                                                scripts,
+                                            )])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<u64, _>("target_fps")
+                                .outarg::<f64, _>("fps")
+                                .outarg::<f64, _>("render_time_percent")
+                                .outarg::<u64, _>("frames_rendered")
+                                .outarg::<u64, _>("frames_skipped")
+                                .outarg::<Vec<(String, f64, u32)>, _>("scripts"),
+                            )
+                            .add_m(
+                                f.method("SetLedColors", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (colors, priority, ttl_millis): (
+                                            Vec<(u32, u8, u8, u8, u8)>,
+                                            i32,
+                                            u32,
+                                        ) = m.msg.read3()?;
+
+                                        let colors = colors
+                                            .iter()
+                                            .map(|(index, r, g, b, a)| {
+                                                (
+                                                    *index as usize,
+                                                    hwdevices::RGBA {
+                                                        r: *r,
+                                                        g: *g,
+                                                        b: *b,
+                                                        a: *a,
+                                                    },
+                                                )
+                                            })
+                                            .collect();
+
+                                        let canvas_size = script::LED_MAP.read().len();
+
+                                        let id = overlays::add_overlay(
+                                            &m.msg.sender().unwrap(),
+                                            priority,
+                                            ttl_millis,
+                                            colors,
+                                            canvas_size,
+                                        )
+                                        .map_err(|e| {
+                                            MethodErr::failed(&format!(
+                                                "Could not add the overlay: {}",
+                                                e
+                                            ))
+                                        })?;
+
+                                        script::FRAME_GENERATION_COUNTER
+                                            .fetch_add(1, Ordering::SeqCst);
+
+                                        Ok(vec![m.msg.method_return().append1(id)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<Vec<(u32, u8, u8, u8, u8)>, _>("colors")
+                                .inarg::<i32, _>("priority")
+                                .inarg::<u32, _>("ttl_millis")
+                                .outarg::<u64, _>("overlay_id"),
+                            )
+                            .add_m(
+                                f.method("RemoveOverlay", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let id: u64 = m.msg.read1()?;
+
+                                        let s =
+                                            overlays::remove_overlay(&m.msg.sender().unwrap(), id);
+
+                                        if s {
+                                            script::FRAME_GENERATION_COUNTER
+                                                .fetch_add(1, Ordering::SeqCst);
+                                        }
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("overlay_id")
+                                .outarg::<bool, _>("status"),
+                            )
                             .add_m(
                                 f.method("GetManagedDevices", (), move |m| {
                                     if perms::has_monitor_permission_cached(
@@ -406,6 +574,7 @@ impl DbusApi {
                         f.interface("org.eruption.Device", ())
                             .add_s(device_status_changed_signal_clone)
                             .add_s(device_hotplug_signal_clone)
+                            .add_s(battery_level_low_signal_clone)
                             .add_m(
                                 f.method("SetDeviceConfig", (), move |m| {
                                     if perms::has_settings_permission_cached(
@@ -422,7 +591,7 @@ impl DbusApi {
                                         );
 
                                         apply_device_specific_configuration(device, &param, &value)
-                                            .map_err(|_e| MethodErr::invalid_arg(&param))?;
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
 
                                         Ok(vec![m.msg.method_return().append1(true)])
                                     } else {
@@ -462,6 +631,122 @@ impl DbusApi {
                                 .inarg::<String, _>("param")
                                 .outarg::<String, _>("value"),
                             )
+                            .add_m(
+                                f.method("GetDeviceSchema", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] schema", device);
+
+                                        let result = query_device_schema(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("schema"),
+                            )
+                            .add_m(
+                                f.method("GetOnboardProfiles", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        debug!("Reading onboard profiles of device [{}]", device);
+
+                                        let result = query_onboard_profiles(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("profiles"),
+                            )
+                            .add_m(
+                                f.method("SetOnboardProfiles", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, profiles): (u64, String) = m.msg.read2()?;
+
+                                        debug!("Writing onboard profiles of device [{}]", device);
+
+                                        apply_onboard_profiles(device, &profiles)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<String, _>("profiles")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetHardwareKeyRemap", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying key remappings of device [{}]", device);
+
+                                        let result = query_hardware_key_remap(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("remaps"),
+                            )
+                            .add_m(
+                                f.method("SetHardwareKeyRemap", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, remaps): (u64, String) = m.msg.read2()?;
+
+                                        debug!("Setting key remappings of device [{}]", device);
+
+                                        apply_hardware_key_remap(device, &remaps)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<String, _>("remaps")
+                                .outarg::<bool, _>("status"),
+                            )
                             .add_m(
                                 f.method("GetDeviceStatus", (), move |m| {
                                     if perms::has_monitor_permission_cached(
@@ -484,6 +769,28 @@ impl DbusApi {
                                 .inarg::<u64, _>("device")
                                 .outarg::<String, _>("status"),
                             )
+                            .add_m(
+                                f.method("GetDeviceStatusHistory", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        trace!("Querying device [{}] status history", device);
+
+                                        let result = query_device_status_history(device)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<String, _>("history"),
+                            )
                             .add_m(
                                 f.method("GetManagedDevices", (), move |m| {
                                     if perms::has_monitor_permission_cached(
@@ -561,69 +868,417 @@ impl DbusApi {
                                     "values"
                                 ),
                             )
-                            .add_p(device_status_property_clone),
-                    ),
-            )
-            .add(
-                f.object_path("/org/eruption/config", ())
-                    .introspectable()
-                    .add(
-                        f.interface("org.eruption.Config", ())
-                            .add_s(brightness_changed_signal_clone)
-                            .add_p(enable_sfx_property_clone)
-                            .add_p(brightness_property_clone)
                             .add_m(
-                                f.method("WriteFile", (), move |m| {
-                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
-                                        .unwrap_or(false)
+                                f.method("GetDriverRegistry", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
                                     {
-                                        let (filename, data): (String, String) = m.msg.read2()?;
-
-                                        crate::util::write_file(&PathBuf::from(filename), &data)
-                                            .map_err(|e| {
-                                                MethodErr::failed(&format!(
-                                                    "Error writing file: {}",
-                                                    e
-                                                ))
-                                            })?;
+                                        let drivers: Vec<(
+                                            u16,
+                                            u16,
+                                            String,
+                                            String,
+                                            String,
+                                            String,
+                                            String,
+                                        )> = hwdevices::DRIVERS
+                                            .lock()
+                                            .iter()
+                                            .map(|driver| {
+                                                (
+                                                    driver.get_usb_vid(),
+                                                    driver.get_usb_pid(),
+                                                    driver.get_device_make().to_string(),
+                                                    driver.get_device_name().to_string(),
+                                                    driver.get_driver_family().to_string(),
+                                                    driver.get_maturity_level().to_string(),
+                                                    driver.get_source().to_string(),
+                                                )
+                                            })
+                                            .collect();
 
-                                        let s = true;
-                                        Ok(vec![m.msg.method_return().append1(s)])
+                                        Ok(vec![m.msg.method_return().append1(drivers)])
                                     } else {
                                         Err(MethodErr::failed("Authentication failed"))
                                     }
                                 })
-                                .inarg::<String, _>("filename")
-                                .inarg::<String, _>("data")
-                                .outarg::<bool, _>("status"),
+                                .outarg::<Vec<(
+                                    u16,
+                                    u16,
+                                    String,
+                                    String,
+                                    String,
+                                    String,
+                                    String,
+                                )>, _>(
+                                    "drivers"
+                                ),
                             )
                             .add_m(
-                                f.method("Ping", (), move |m| {
+                                f.method("GetCanvasLayout", (), move |m| {
                                     if perms::has_monitor_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let s = true;
-                                        Ok(vec![m.msg.method_return().append1(s)])
+                                        let layout = layout::LAYOUT.read().clone();
+
+                                        let placements = get_device_placements(&layout)
+                                            .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        Ok(vec![m.msg.method_return().append3(
+                                            layout.width as u64,
+                                            layout.height as u64,
+                                            placements,
+                                        )])
                                     } else {
                                         Err(MethodErr::failed("Authentication failed"))
                                     }
                                 })
-                                .outarg::<bool, _>("status"),
+                                .outarg::<u64, _>("width")
+                                .outarg::<u64, _>("height")
+                                .outarg::<Vec<(u64, u64, u64, u64, u64)>, _>("placements"),
                             )
                             .add_m(
-                                f.method("PingPrivileged", (), move |m| {
-                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
-                                        .unwrap_or(false)
+                                f.method("SetDevicePlacement", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
                                     {
-                                        let s = true;
-                                        Ok(vec![m.msg.method_return().append1(s)])
-                                    } else {
-                                        Err(MethodErr::failed("Authentication failed"))
-                                    }
-                                })
-                                .outarg::<bool, _>("status"),
+                                        let (device, x, y, width, height): (
+                                            u64,
+                                            u64,
+                                            u64,
+                                            u64,
+                                            u64,
+                                        ) = m.msg.read5()?;
+
+                                        debug!(
+                                            "Placing device [{}] at {}x{}+{}+{}",
+                                            device, width, height, x, y
+                                        );
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        layout::set_placement(layout::Placement {
+                                            device: device_make_model_serial,
+                                            x: x as usize,
+                                            y: y as usize,
+                                            width: width as usize,
+                                            height: height as usize,
+                                        })
+                                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<u64, _>("x")
+                                .inarg::<u64, _>("y")
+                                .inarg::<u64, _>("width")
+                                .inarg::<u64, _>("height")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("RemoveDevicePlacement", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        debug!(
+                                            "Removing device [{}] from the canvas layout",
+                                            device
+                                        );
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        let result =
+                                            layout::remove_placement(&device_make_model_serial);
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceCalibration", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        let calibration =
+                                            calibration::get_calibration(&device_make_model_serial);
+
+                                        let [r, g, b] = calibration.gains;
+                                        let gains = (r, g, b);
+
+                                        let [r, g, b] = calibration.white_point;
+                                        let white_point = (r, g, b);
+
+                                        Ok(vec![m.msg.method_return().append3(
+                                            calibration.gamma,
+                                            gains,
+                                            white_point,
+                                        )])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<f64, _>("gamma")
+                                .outarg::<(f64, f64, f64), _>("gains")
+                                .outarg::<(u8, u8, u8), _>("white_point"),
+                            )
+                            .add_m(
+                                f.method("SetDeviceCalibration", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, gamma, gains, white_point): (
+                                            u64,
+                                            f64,
+                                            (f64, f64, f64),
+                                            (u8, u8, u8),
+                                        ) = m.msg.read4()?;
+
+                                        debug!(
+                                            "Setting the color calibration of device [{}] to gamma: {}, gains: {:?}, white point: {:?}",
+                                            device, gamma, gains, white_point
+                                        );
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        calibration::set_calibration(
+                                            &device_make_model_serial,
+                                            calibration::ColorCalibration {
+                                                gamma,
+                                                gains: [gains.0, gains.1, gains.2],
+                                                white_point: [
+                                                    white_point.0,
+                                                    white_point.1,
+                                                    white_point.2,
+                                                ],
+                                            },
+                                        )
+                                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        script::FRAME_GENERATION_COUNTER
+                                            .fetch_add(1, Ordering::SeqCst);
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<f64, _>("gamma")
+                                .inarg::<(f64, f64, f64), _>("gains")
+                                .inarg::<(u8, u8, u8), _>("white_point")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ResetDeviceCalibration", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        debug!(
+                                            "Resetting the color calibration of device [{}]",
+                                            device
+                                        );
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        let result = calibration::reset_calibration(
+                                            &device_make_model_serial,
+                                        );
+
+                                        script::FRAME_GENERATION_COUNTER
+                                            .fetch_add(1, Ordering::SeqCst);
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetDeviceFrameRate", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let device: u64 = m.msg.read1()?;
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        // zero means that the device follows the target frame rate
+                                        let fps = scheduler::get_device_frame_rate(
+                                            &device_make_model_serial,
+                                        )
+                                        .unwrap_or(0);
+
+                                        Ok(vec![m.msg.method_return().append1(fps)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .outarg::<u64, _>("fps"),
+                            )
+                            .add_m(
+                                f.method("SetDeviceFrameRate", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (device, fps): (u64, u64) = m.msg.read2()?;
+
+                                        debug!(
+                                            "Setting the frame rate limit of device [{}] to: {}",
+                                            device, fps
+                                        );
+
+                                        let device_make_model_serial =
+                                            get_device_make_model_serial(device)
+                                                .map_err(|_e| MethodErr::invalid_arg(&device))?;
+
+                                        // zero removes the limit
+                                        scheduler::set_device_frame_rate(
+                                            &device_make_model_serial,
+                                            Some(fps).filter(|fps| *fps > 0),
+                                        )
+                                        .map_err(|e| MethodErr::failed(&format!("{}", e)))?;
+
+                                        state::save_runtime_state().unwrap_or_else(|e| {
+                                            error!("Could not save runtime state: {}", e)
+                                        });
+
+                                        Ok(vec![m.msg.method_return().append1(true)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<u64, _>("device")
+                                .inarg::<u64, _>("fps")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_p(device_status_property_clone),
+                    ),
+            )
+            .add(
+                f.object_path("/org/eruption/config", ())
+                    .introspectable()
+                    .add(
+                        f.interface("org.eruption.Config", ())
+                            .add_s(brightness_changed_signal_clone)
+                            .add_p(enable_sfx_property_clone)
+                            .add_p(brightness_property_clone)
+                            .add_m(
+                                f.method("WriteFile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let (filename, data): (String, String) = m.msg.read2()?;
+
+                                        crate::util::write_file(&PathBuf::from(filename), &data)
+                                            .map_err(|e| {
+                                                MethodErr::failed(&format!(
+                                                    "Error writing file: {}",
+                                                    e
+                                                ))
+                                            })?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("filename")
+                                .inarg::<String, _>("data")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("Ping", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("PingPrivileged", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<bool, _>("status"),
                             )
                             .add_m(
                                 f.method("GetColorSchemes", (), move |m| {
@@ -821,146 +1476,540 @@ impl DbusApi {
                                             &m.msg.sender().unwrap(),
                                         )
                                         .unwrap_or(false)
-                                        {
-                                            let n: Vec<String> = i.read()?;
+                                        {
+                                            let n: Vec<String> = i.read()?;
+
+                                            if n.len() >= constants::NUM_SLOTS {
+                                                *crate::SLOT_NAMES.lock() = n;
+
+                                                Ok(())
+                                            } else {
+                                                Err(MethodErr::failed("Invalid number of elements"))
+                                            }
+                                        } else {
+                                            Err(MethodErr::failed("Authentication failed"))
+                                        }
+                                    }),
+                            ),
+                    ),
+            )
+            .add(
+                f.object_path("/org/eruption/profile", ())
+                    .introspectable()
+                    .add(
+                        f.interface("org.eruption.Profile", ())
+                            .add_s(profiles_changed_signal_clone)
+                            .add_s(active_profile_changed_signal_clone)
+                            .add_p(active_profile_property_clone.clone())
+                            .add_m(
+                                f.method("SwitchProfile", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let n: &str = m.msg.read1()?;
+
+                                        dbus_tx_clone
+                                            .send(Message::SwitchProfile(PathBuf::from(n)))
+                                            .unwrap_or_else(|e| {
+                                                error!(
+                                                    "Could not send a pending D-Bus event: {}",
+                                                    e
+                                                )
+                                            });
+
+                                        // reset the audio backend, it will be enabled again if needed
+                                        plugins::audio::reset_audio_backend();
+
+                                        let mut changed_properties = Vec::new();
+                                        active_profile_property_clone.add_propertieschanged(
+                                            &mut changed_properties,
+                                            &"org.eruption".into(),
+                                            || Box::new(n.to_owned()),
+                                        );
+
+                                        if !changed_properties.is_empty() {
+                                            let msg = changed_properties
+                                                .first()
+                                                .unwrap()
+                                                .to_emit_message(&"/org/eruption/profile".into());
+                                            c_clone3.clone().send(msg).unwrap();
+                                        }
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<&str, _>("filename")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("EnumProfiles", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let mut s: Vec<(String, String)> = profiles::get_profiles()
+                                            .unwrap_or_else(|_| vec![])
+                                            .iter()
+                                            .map(|profile| {
+                                                (
+                                                    profile.name.clone(),
+                                                    profile
+                                                        .profile_file
+                                                        .to_string_lossy()
+                                                        .to_string(),
+                                                )
+                                            })
+                                            .collect();
+
+                                        s.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<Vec<(String, String)>, _>("profiles"),
+                            )
+                            .add_m(
+                                f.method("SetParameter", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (profile_file, script_file, param_name, value): (
+                                            &str,
+                                            &str,
+                                            &str,
+                                            &str,
+                                        ) = m.msg.read4()?;
+
+                                        debug!(
+                                            "Setting parameter {}:{} {} to '{}'",
+                                            &profile_file, &script_file, &param_name, &value
+                                        );
+
+                                        let applied = apply_parameter(
+                                            profile_file,
+                                            script_file,
+                                            param_name,
+                                            value,
+                                        );
+                                        match applied {
+                                            Ok(()) => Ok(vec![m.msg.method_return().append1(true)]),
+                                            Err(err) => {
+                                                debug!("Could not set parameter: {}", err);
+                                                Err(MethodErr::invalid_arg(&value))
+                                            }
+                                        }
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<&str, _>("profile_file")
+                                .inarg::<&str, _>("script_file")
+                                .inarg::<&str, _>("param_name")
+                                .inarg::<&str, _>("value")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("GetParameter", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (profile_file, script_file, param_name): (
+                                            &str,
+                                            &str,
+                                            &str,
+                                        ) = m.msg.read3()?;
+
+                                        match parameters_util::get_parameter(
+                                            profile_file,
+                                            script_file,
+                                            param_name,
+                                        ) {
+                                            Ok(value) => Ok(vec![m
+                                                .msg
+                                                .method_return()
+                                                .append1(value.to_string())]),
+
+                                            Err(err) => {
+                                                debug!("Could not get parameter: {}", err);
+                                                Err(MethodErr::invalid_arg(&param_name))
+                                            }
+                                        }
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<&str, _>("profile_file")
+                                .inarg::<&str, _>("script_file")
+                                .inarg::<&str, _>("param_name")
+                                .outarg::<String, _>("value"),
+                            )
+                            .add_m(
+                                f.method("GetParameters", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let parameters =
+                                            parameters_util::get_active_profile_parameters()
+                                                .map_err(|e| {
+                                                    MethodErr::failed(&format!(
+                                                        "Could not get parameters: {}",
+                                                        e
+                                                    ))
+                                                })?;
+
+                                        let result = parameters
+                                            .iter()
+                                            .map(parameter_info_to_prop_map)
+                                            .collect::<Vec<PropMap>>();
+
+                                        Ok(vec![m.msg.method_return().append1(result)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .outarg::<Vec<PropMap>, _>("parameters"),
+                            )
+                            .add_m(
+                                f.method("SetTypedParameter", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let (profile_file, script_file, param_name, value): (
+                                            &str,
+                                            &str,
+                                            &str,
+                                            Variant<Box<dyn RefArg>>,
+                                        ) = m.msg.read4()?;
+
+                                        let value =
+                                            typed_value_from_variant(&value).ok_or_else(|| {
+                                                MethodErr::from((
+                                                    "org.eruption.Error.TypeMismatch",
+                                                    "Unsupported type of value",
+                                                ))
+                                            })?;
+
+                                        debug!(
+                                            "Setting parameter {}:{} {} to '{}'",
+                                            &profile_file, &script_file, &param_name, &value
+                                        );
+
+                                        parameters_util::apply_typed_parameter(
+                                            profile_file,
+                                            script_file,
+                                            param_name,
+                                            value,
+                                        )
+                                        .map_err(parameter_error_to_method_err)?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<&str, _>("profile_file")
+                                .inarg::<&str, _>("script_file")
+                                .inarg::<&str, _>("param_name")
+                                .inarg::<Variant<Box<dyn RefArg>>, _>("value")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("CreateProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let (name, description, scripts): (
+                                            String,
+                                            String,
+                                            Vec<String>,
+                                        ) = m.msg.read3()?;
+
+                                        let profile_file = profile_manager::create_profile(
+                                            &name,
+                                            &description,
+                                            &scripts,
+                                        )
+                                        .map_err(|e| {
+                                            MethodErr::failed(&format!(
+                                                "Could not create profile: {}",
+                                                e
+                                            ))
+                                        })?;
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append1(profile_file.to_string_lossy().to_string())])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("name")
+                                .inarg::<String, _>("description")
+                                .inarg::<Vec<String>, _>("scripts")
+                                .outarg::<String, _>("profile_file"),
+                            )
+                            .add_m(
+                                f.method("CloneProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let (profile, name): (String, String) = m.msg.read2()?;
+
+                                        let profile_file =
+                                            profile_manager::clone_profile(&profile, &name)
+                                                .map_err(|e| {
+                                                    MethodErr::failed(&format!(
+                                                        "Could not clone profile: {}",
+                                                        e
+                                                    ))
+                                                })?;
+
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append1(profile_file.to_string_lossy().to_string())])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("profile")
+                                .inarg::<String, _>("name")
+                                .outarg::<String, _>("profile_file"),
+                            )
+                            .add_m(
+                                f.method("RenameProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let (profile, name): (String, String) = m.msg.read2()?;
+
+                                        profile_manager::rename_profile(&profile, &name).map_err(
+                                            |e| {
+                                                MethodErr::failed(&format!(
+                                                    "Could not rename profile: {}",
+                                                    e
+                                                ))
+                                            },
+                                        )?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("profile")
+                                .inarg::<String, _>("name")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("DeleteProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let profile: String = m.msg.read1()?;
+
+                                        profile_manager::delete_profile(&profile).map_err(|e| {
+                                            MethodErr::failed(&format!(
+                                                "Could not delete profile: {}",
+                                                e
+                                            ))
+                                        })?;
+
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("profile")
+                                .outarg::<bool, _>("status"),
+                            )
+                            .add_m(
+                                f.method("ExportProfile", (), move |m| {
+                                    if perms::has_settings_permission_cached(
+                                        &m.msg.sender().unwrap(),
+                                    )
+                                    .unwrap_or(false)
+                                    {
+                                        let profile: String = m.msg.read1()?;
+
+                                        let data = profile_manager::export_profile(&profile)
+                                            .map_err(|e| {
+                                                MethodErr::failed(&format!(
+                                                    "Could not export profile: {}",
+                                                    e
+                                                ))
+                                            })?;
+
+                                        Ok(vec![m.msg.method_return().append1(data)])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("profile")
+                                .outarg::<String, _>("data"),
+                            )
+                            .add_m(
+                                f.method("ImportProfile", (), move |m| {
+                                    if perms::has_manage_permission_cached(&m.msg.sender().unwrap())
+                                        .unwrap_or(false)
+                                    {
+                                        let data: String = m.msg.read1()?;
 
-                                            if n.len() >= constants::NUM_SLOTS {
-                                                *crate::SLOT_NAMES.lock() = n;
+                                        let profile_file = profile_manager::import_profile(&data)
+                                            .map_err(|e| {
+                                            MethodErr::failed(&format!(
+                                                "Could not import profile: {}",
+                                                e
+                                            ))
+                                        })?;
 
-                                                Ok(())
-                                            } else {
-                                                Err(MethodErr::failed("Invalid number of elements"))
-                                            }
-                                        } else {
-                                            Err(MethodErr::failed("Authentication failed"))
-                                        }
-                                    }),
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append1(profile_file.to_string_lossy().to_string())])
+                                    } else {
+                                        Err(MethodErr::failed("Authentication failed"))
+                                    }
+                                })
+                                .inarg::<String, _>("data")
+                                .outarg::<String, _>("profile_file"),
                             ),
                     ),
             )
             .add(
-                f.object_path("/org/eruption/profile", ())
+                f.object_path("/org/eruption/metrics", ())
                     .introspectable()
                     .add(
-                        f.interface("org.eruption.Profile", ())
-                            .add_s(profiles_changed_signal_clone)
-                            .add_s(active_profile_changed_signal_clone)
-                            .add_p(active_profile_property_clone.clone())
+                        f.interface("org.eruption.Metrics", ())
                             .add_m(
-                                f.method("SwitchProfile", (), move |m| {
-                                    if perms::has_settings_permission_cached(
+                                f.method("GetMetrics", (), move |m| {
+                                    if perms::has_monitor_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let n: &str = m.msg.read1()?;
+                                        let metrics = metrics::get_metrics();
 
-                                        dbus_tx_clone
-                                            .send(Message::SwitchProfile(PathBuf::from(n)))
-                                            .unwrap_or_else(|e| {
-                                                error!(
-                                                    "Could not send a pending D-Bus event: {}",
-                                                    e
+                                        let script_ticks = metrics
+                                            .script_ticks
+                                            .iter()
+                                            .map(|(script, h)| {
+                                                (
+                                                    script.clone(),
+                                                    h.count,
+                                                    h.mean_millis(),
+                                                    h.max_millis(),
                                                 )
-                                            });
+                                            })
+                                            .collect::<Vec<(String, u64, f64, f64)>>();
 
-                                        // reset the audio backend, it will be enabled again if needed
-                                        plugins::audio::reset_audio_backend();
+                                        let device_writes = metrics
+                                            .device_writes
+                                            .iter()
+                                            .map(|(device, d)| {
+                                                (
+                                                    device.clone(),
+                                                    d.writes.count,
+                                                    d.failures,
+                                                    d.writes.mean_millis(),
+                                                    d.writes.max_millis(),
+                                                )
+                                            })
+                                            .collect::<Vec<(String, u64, u64, f64, f64)>>();
 
-                                        let mut changed_properties = Vec::new();
-                                        active_profile_property_clone.add_propertieschanged(
-                                            &mut changed_properties,
-                                            &"org.eruption".into(),
-                                            || Box::new(n.to_owned()),
-                                        );
+                                        let dbus_calls = metrics
+                                            .dbus_calls
+                                            .into_iter()
+                                            .collect::<Vec<(String, u64)>>();
 
-                                        if !changed_properties.is_empty() {
-                                            let msg = changed_properties
-                                                .first()
-                                                .unwrap()
-                                                .to_emit_message(&"/org/eruption/profile".into());
-                                            c_clone3.clone().send(msg).unwrap();
-                                        }
+                                        let dropped_input_events = metrics
+                                            .dropped_input_events
+                                            .into_iter()
+                                            .collect::<Vec<(String, u64)>>();
 
-                                        let s = true;
-                                        Ok(vec![m.msg.method_return().append1(s)])
+                                        Ok(vec![m
+                                            .msg
+                                            .method_return()
+                                            .append3(
+                                                metrics.since.elapsed().as_secs_f64(),
+                                                metrics.frames_rendered,
+                                                metrics.frames_skipped,
+                                            )
+                                            .append3(
+                                                metrics.frames_dropped,
+                                                metrics.frames_late,
+                                                script_ticks,
+                                            )
+                                            .append3(
+                                                device_writes,
+                                                dbus_calls,
+                                                dropped_input_events,
+                                            )])
                                     } else {
                                         Err(MethodErr::failed("Authentication failed"))
                                     }
                                 })
-                                .inarg::<&str, _>("filename")
-                                .outarg::<bool, _>("status"),
+                                .outarg::<f64, _>("seconds")
+                                .outarg::<u64, _>("frames_rendered")
+                                .outarg::<u64, _>("frames_skipped")
+                                .outarg::<u64, _>("frames_dropped")
+                                .outarg::<u64, _>("frames_late")
+                                .outarg::<Vec<(String, u64, f64, f64)>, _>("script_ticks")
+                                .outarg::<Vec<(String, u64, u64, f64, f64)>, _>("device_writes")
+                                .outarg::<Vec<(String, u64)>, _>("dbus_calls")
+                                .outarg::<Vec<(String, u64)>, _>("dropped_input_events"),
                             )
                             .add_m(
-                                f.method("EnumProfiles", (), move |m| {
+                                f.method("GetOpenMetrics", (), move |m| {
                                     if perms::has_monitor_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let mut s: Vec<(String, String)> = profiles::get_profiles()
-                                            .unwrap_or_else(|_| vec![])
-                                            .iter()
-                                            .map(|profile| {
-                                                (
-                                                    profile.name.clone(),
-                                                    profile
-                                                        .profile_file
-                                                        .to_string_lossy()
-                                                        .to_string(),
-                                                )
-                                            })
-                                            .collect();
-
-                                        s.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+                                        let result = metrics::render_openmetrics();
 
-                                        Ok(vec![m.msg.method_return().append1(s)])
+                                        Ok(vec![m.msg.method_return().append1(result)])
                                     } else {
                                         Err(MethodErr::failed("Authentication failed"))
                                     }
                                 })
-                                .outarg::<Vec<(String, String)>, _>("profiles"),
+                                .outarg::<String, _>("metrics"),
                             )
                             .add_m(
-                                f.method("SetParameter", (), move |m| {
+                                f.method("ResetMetrics", (), move |m| {
                                     if perms::has_settings_permission_cached(
                                         &m.msg.sender().unwrap(),
                                     )
                                     .unwrap_or(false)
                                     {
-                                        let (profile_file, script_file, param_name, value): (
-                                            &str,
-                                            &str,
-                                            &str,
-                                            &str,
-                                        ) = m.msg.read4()?;
-
-                                        debug!(
-                                            "Setting parameter {}:{} {} to '{}'",
-                                            &profile_file, &script_file, &param_name, &value
-                                        );
+                                        metrics::reset();
 
-                                        let applied = apply_parameter(
-                                            profile_file,
-                                            script_file,
-                                            param_name,
-                                            value,
-                                        );
-                                        match applied {
-                                            Ok(()) => Ok(vec![m.msg.method_return().append1(true)]),
-                                            Err(err) => {
-                                                debug!("Could not set parameter: {}", err);
-                                                Err(MethodErr::invalid_arg(&value))
-                                            }
-                                        }
+                                        let s = true;
+                                        Ok(vec![m.msg.method_return().append1(s)])
                                     } else {
                                         Err(MethodErr::failed("Authentication failed"))
                                     }
                                 })
-                                .inarg::<&str, _>("profile_file")
-                                .inarg::<&str, _>("script_file")
-                                .inarg::<&str, _>("param_name")
-                                .inarg::<&str, _>("value")
                                 .outarg::<bool, _>("status"),
                             ),
                     ),
@@ -968,6 +2017,26 @@ impl DbusApi {
 
         tree.set_registered(&c_clone, true)
             .unwrap_or_else(|e| error!("Could not register the tree: {}", e));
+
+        // count the method calls before they are dispatched by the tree
+        let method_call_counter = MethodCallCounter {
+            methods: tree
+                .iter()
+                .flat_map(|path| {
+                    path.iter().flat_map(move |interface| {
+                        interface.iter_m().map(move |method| {
+                            (
+                                path.get_name().to_string(),
+                                interface.get_name().to_string(),
+                                method.get_name().to_string(),
+                            )
+                        })
+                    })
+                })
+                .collect(),
+        };
+
+        c_clone.add_handler(method_call_counter);
         c_clone.add_handler(tree);
 
         Ok(Self {
@@ -978,6 +2047,7 @@ impl DbusApi {
             brightness_changed: brightness_changed_signal,
             device_status_changed: device_status_changed_signal,
             device_hotplug: device_hotplug_signal,
+            battery_level_low: battery_level_low_signal,
         })
     }
 
@@ -1030,6 +2100,21 @@ impl DbusApi {
         Ok(())
     }
 
+    pub fn notify_battery_level_low(&self, device: u64, level: u8, critical: bool) -> Result<()> {
+        let _ = self
+            .connection
+            .as_ref()
+            .unwrap()
+            .send(self.battery_level_low.emit(
+                &"/org/eruption/devices".into(),
+                &"org.eruption.Device".into(),
+                &[(device, level, critical)],
+            ))
+            .map_err(|_| error!("D-Bus error during send call"));
+
+        Ok(())
+    }
+
     pub fn notify_brightness_changed(&self) -> Result<()> {
         let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
@@ -1128,6 +2213,8 @@ impl DbusApi {
                     // implementation of `struct DbusApi`
                     debug!("Message: {:?}", item);
 
+                    process_name_owner_changed(&item);
+
                     Ok(true)
                 } else {
                     trace!("Received a timeout message");
@@ -1148,6 +2235,8 @@ impl DbusApi {
                     // implementation of `struct DbusApi`
                     debug!("Message: {:?}", item);
 
+                    process_name_owner_changed(&item);
+
                     Ok(true)
                 } else {
                     trace!("Received a timeout message");
@@ -1161,6 +2250,48 @@ impl DbusApi {
     }
 }
 
+/// Counts the D-Bus method calls for the metrics, but leaves their handling to the tree
+struct MethodCallCounter {
+    /// The path, interface and name of each method of the tree, calls of any other
+    /// method are rejected by the tree and are not counted
+    methods: HashSet<(String, String, String)>,
+}
+
+impl MsgHandler for MethodCallCounter {
+    fn handler_type(&self) -> MsgHandlerType {
+        MsgHandlerType::MsgType(MessageType::MethodCall)
+    }
+
+    fn handle_msg(&mut self, msg: &dbus::Message) -> Option<MsgHandlerResult> {
+        if let (Some(path), Some(interface), Some(member)) =
+            (msg.path(), msg.interface(), msg.member())
+        {
+            let method = (path.to_string(), interface.to_string(), member.to_string());
+
+            if self.methods.contains(&method) {
+                metrics::dbus_called(&interface, &member);
+            }
+        }
+
+        None
+    }
+}
+
+/// Drop the overlays of clients that have disconnected from the bus
+fn process_name_owner_changed(msg: &dbus::Message) {
+    if msg.msg_type() == MessageType::Signal {
+        if msg.member().as_deref() == Some("NameOwnerChanged") {
+            if let Ok((name, _old_owner, new_owner)) = msg.read3::<&str, &str, &str>() {
+                if new_owner.is_empty() && overlays::remove_overlays_of(name) {
+                    debug!("Dropped the overlays of a disconnected client: {}", name);
+
+                    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
+
 /// Initialize the Eruption D-Bus API support
 pub fn initialize(dbus_tx: Sender<Message>) -> Result<DbusApi> {
     DbusApi::new(dbus_tx)
@@ -1182,6 +2313,122 @@ fn apply_parameter(
     )
 }
 
+/// Convert a parameter to a D-Bus dictionary; `min` and `max` are only present if the script
+/// manifest declares them
+fn parameter_info_to_prop_map(parameter: &parameters_util::ParameterInfo) -> PropMap {
+    let mut result = PropMap::new();
+
+    let mut insert = |key: &str, value: Box<dyn RefArg>| {
+        result.insert(key.to_string(), Variant(value));
+    };
+
+    insert(
+        "script_file",
+        Box::new(parameter.script_file.to_string_lossy().to_string()),
+    );
+    insert("script_name", Box::new(parameter.script_name.clone()));
+    insert("name", Box::new(parameter.name.clone()));
+    insert("description", Box::new(parameter.description.clone()));
+    insert("value", typed_value_to_ref_arg(&parameter.value));
+    insert(
+        "default",
+        typed_value_to_ref_arg(&parameter.manifest.get_default()),
+    );
+
+    let (type_name, min, max): (&str, Option<Box<dyn RefArg>>, Option<Box<dyn RefArg>>) =
+        match &parameter.manifest {
+            parameters::ManifestValue::Int { min, max, .. } => (
+                "int",
+                min.map(|v| Box::new(v) as Box<dyn RefArg>),
+                max.map(|v| Box::new(v) as Box<dyn RefArg>),
+            ),
+
+            parameters::ManifestValue::Float { min, max, .. } => (
+                "float",
+                min.map(|v| Box::new(v) as Box<dyn RefArg>),
+                max.map(|v| Box::new(v) as Box<dyn RefArg>),
+            ),
+
+            parameters::ManifestValue::Bool { .. } => ("bool", None, None),
+
+            parameters::ManifestValue::String { .. } => ("string", None, None),
+
+            parameters::ManifestValue::Color { min, max, .. } => (
+                "color",
+                min.map(|v| Box::new(v) as Box<dyn RefArg>),
+                max.map(|v| Box::new(v) as Box<dyn RefArg>),
+            ),
+        };
+
+    insert("type", Box::new(type_name.to_string()));
+
+    if let Some(min) = min {
+        insert("min", min);
+    }
+
+    if let Some(max) = max {
+        insert("max", max);
+    }
+
+    result
+}
+
+fn typed_value_to_ref_arg(value: &parameters::TypedValue) -> Box<dyn RefArg> {
+    match value {
+        parameters::TypedValue::Int(value) => Box::new(*value),
+        parameters::TypedValue::Float(value) => Box::new(*value),
+        parameters::TypedValue::Bool(value) => Box::new(*value),
+        parameters::TypedValue::String(value) => Box::new(value.clone()),
+        parameters::TypedValue::Color(value) => Box::new(*value),
+    }
+}
+
+/// Convert a D-Bus variant to a typed value; all integer types are converted to `Int`,
+/// they will be coerced to the type that is declared in the script manifest later on
+fn typed_value_from_variant(value: &Variant<Box<dyn RefArg>>) -> Option<parameters::TypedValue> {
+    match value.0.arg_type() {
+        ArgType::Boolean => arg::cast::<bool>(&*value.0).map(|v| parameters::TypedValue::Bool(*v)),
+
+        ArgType::Double => value.0.as_f64().map(parameters::TypedValue::Float),
+
+        ArgType::String => value
+            .0
+            .as_str()
+            .map(|v| parameters::TypedValue::String(v.to_string())),
+
+        ArgType::Byte
+        | ArgType::Int16
+        | ArgType::UInt16
+        | ArgType::Int32
+        | ArgType::UInt32
+        | ArgType::Int64
+        | ArgType::UInt64 => value.0.as_i64().map(parameters::TypedValue::Int),
+
+        _ => None,
+    }
+}
+
+/// Map errors of the parameter API to D-Bus errors that may be told apart by clients
+fn parameter_error_to_method_err(err: eyre::Error) -> MethodErr {
+    let name = match err.downcast_ref::<parameters_util::ParametersUtilError>() {
+        Some(parameters_util::ParametersUtilError::NoSuchParameter {}) => {
+            "org.eruption.Error.NoSuchParameter"
+        }
+
+        Some(parameters_util::ParametersUtilError::TypeMismatch { .. }) => {
+            "org.eruption.Error.TypeMismatch"
+        }
+
+        Some(parameters_util::ParametersUtilError::OutOfRange { .. }) => {
+            "org.eruption.Error.OutOfRange"
+        }
+
+        _ => "org.freedesktop.DBus.Error.Failed",
+    };
+
+    MethodErr::from((name, format!("{}", err)))
+}
+
 /// Query the device specific status from the global status store
 fn query_device_specific_status(device: u64) -> Result<String> {
     let device_status = crate::DEVICE_STATUS.as_ref().lock();
@@ -1192,6 +2439,15 @@ fn query_device_specific_status(device: u64) -> Result<String> {
     }
 }
 
+/// Query the recorded battery level and signal strength samples of a device
+fn query_device_status_history(device: u64) -> Result<String> {
+    if !crate::DEVICE_STATUS.as_ref().lock().contains_key(&device) {
+        return Err(DbusApiError::InvalidDevice {}.into());
+    }
+
+    Ok(serde_json::to_string_pretty(&battery::get_history(device))?)
+}
+
 /// Query the device driver for status information
 /// this will likely cause stuttering when not synchronized with the main loop
 // fn query_device_specific_status_no_cache(device: u64) -> Result<String> {
@@ -1232,14 +2488,108 @@ fn query_device_specific_status(device: u64) -> Result<String> {
 //     Ok(json)
 // }
 
+fn get_device_schema(device: u64) -> Result<DeviceSchema> {
+    let keyboards = crate::KEYBOARD_DEVICES.read();
+    let mice = crate::MOUSE_DEVICES.read();
+    let misc = crate::MISC_DEVICES.read();
+
+    let index = device as usize;
+
+    if index < keyboards.len() {
+        Ok(keyboards[index].read().get_device_schema())
+    } else if index < keyboards.len() + mice.len() {
+        Ok(mice[index - keyboards.len()].read().get_device_schema())
+    } else if index < keyboards.len() + mice.len() + misc.len() {
+        Ok(misc[index - (keyboards.len() + mice.len())]
+            .read()
+            .get_device_schema())
+    } else {
+        Err(DbusApiError::InvalidDevice {}.into())
+    }
+}
+
+fn query_device_schema(device: u64) -> Result<String> {
+    let schema = get_device_schema(device)?;
+    let result = serde_json::to_string_pretty(&schema)?;
+
+    Ok(result)
+}
+
+/// Map a device index to an index into the list of mice
+fn get_mouse_index(device: u64) -> Result<usize> {
+    let keyboards = crate::KEYBOARD_DEVICES.read().len();
+    let mice = crate::MOUSE_DEVICES.read().len();
+
+    let index = device as usize;
+
+    if index >= keyboards && index < keyboards + mice {
+        Ok(index - keyboards)
+    } else {
+        Err(DbusApiError::InvalidDevice {}.into())
+    }
+}
+
+fn query_onboard_profiles(device: u64) -> Result<String> {
+    let index = get_mouse_index(device)?;
+
+    let profiles = onboard::backup(&mut **crate::MOUSE_DEVICES.read()[index].write())?;
+    let result = serde_json::to_string_pretty(&profiles)?;
+
+    Ok(result)
+}
+
+fn apply_onboard_profiles(device: u64, profiles: &str) -> Result<()> {
+    let index = get_mouse_index(device)?;
+    let profiles: onboard::OnboardProfiles = serde_json::from_str(profiles)?;
+
+    onboard::restore(&mut **crate::MOUSE_DEVICES.read()[index].write(), &profiles)?;
+
+    Ok(())
+}
+
+fn query_hardware_key_remap(device: u64) -> Result<String> {
+    if device as usize >= crate::KEYBOARD_DEVICES.read().len() {
+        return Err(DbusApiError::InvalidDevice {}.into());
+    }
+
+    let remaps = remap::get_key_remap(&get_device_make_model_serial(device)?);
+    let result = serde_json::to_string_pretty(&remaps)?;
+
+    Ok(result)
+}
+
+fn apply_hardware_key_remap(device: u64, remaps: &str) -> Result<()> {
+    let remaps: Vec<KeyRemap> = serde_json::from_str(remaps)?;
+
+    let make_model_serial = {
+        let keyboards = crate::KEYBOARD_DEVICES.read();
+        let keyboard = keyboards
+            .get(device as usize)
+            .ok_or(DbusApiError::InvalidDevice {})?;
+
+        keyboard.write().set_hardware_key_remap(&remaps)?;
+
+        let make_model_serial = layout::device_make_model_serial(keyboard.read().as_device());
+
+        make_model_serial
+    };
+
+    remap::set_key_remap(&make_model_serial, &remaps);
+
+    Ok(())
+}
+
 fn apply_device_specific_configuration(device: u64, param: &str, value: &str) -> Result<()> {
+    // validate against the schema of the device before dispatching to the driver,
+    // so that the driver will only ever see well-formed values
+    let value = get_device_schema(device)?.validate_config(param, value)?;
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
-        match param {
-            "brightness" => {
-                let brightness = value.parse::<i32>()?;
-                device.write().set_local_brightness(brightness)?;
+        match (param, value) {
+            ("brightness", Value::Int(brightness)) => {
+                device.write().set_local_brightness(brightness as i32)?;
 
                 script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
 
@@ -1254,52 +2604,45 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
         let index = device as usize - crate::KEYBOARD_DEVICES.read().len();
         let device = &crate::MOUSE_DEVICES.read()[index];
 
-        match param {
-            "profile" => {
-                let profile = value.parse::<i32>()?;
-                device.write().set_profile(profile)?;
+        match (param, value) {
+            ("profile", Value::Int(profile)) => {
+                device.write().set_profile(profile as i32)?;
 
                 Ok(())
             }
 
-            "dpi" => {
-                let dpi = value.parse::<i32>()?;
-                device.write().set_dpi(dpi)?;
+            ("dpi", Value::Int(dpi)) => {
+                device.write().set_dpi(dpi as i32)?;
 
                 Ok(())
             }
 
-            "rate" => {
-                let rate = value.parse::<i32>()?;
-                device.write().set_rate(rate)?;
+            ("rate", Value::Int(rate)) => {
+                device.write().set_rate(rate as i32)?;
 
                 Ok(())
             }
 
-            "dcu" => {
-                let dcu_config = value.parse::<i32>()?;
-                device.write().set_dcu_config(dcu_config)?;
+            ("dcu", Value::Int(dcu_config)) => {
+                device.write().set_dcu_config(dcu_config as i32)?;
 
                 Ok(())
             }
 
-            "angle-snapping" => {
-                let angle_snapping = value.parse::<bool>()?;
+            ("angle-snapping", Value::Bool(angle_snapping)) => {
                 device.write().set_angle_snapping(angle_snapping)?;
 
                 Ok(())
             }
 
-            "debounce" => {
-                let debounce = value.parse::<bool>()?;
+            ("debounce", Value::Bool(debounce)) => {
                 device.write().set_debounce(debounce)?;
 
                 Ok(())
             }
 
-            "brightness" => {
-                let brightness = value.parse::<i32>()?;
-                device.write().set_local_brightness(brightness)?;
+            ("brightness", Value::Int(brightness)) => {
+                device.write().set_local_brightness(brightness as i32)?;
 
                 script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
 
@@ -1317,10 +2660,9 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
             - (crate::KEYBOARD_DEVICES.read().len() + crate::MOUSE_DEVICES.read().len());
         let device = &crate::MISC_DEVICES.read()[index];
 
-        match param {
-            "brightness" => {
-                let brightness = value.parse::<i32>()?;
-                device.write().set_local_brightness(brightness)?;
+        match (param, value) {
+            ("brightness", Value::Int(brightness)) => {
+                device.write().set_local_brightness(brightness as i32)?;
 
                 script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
 
@@ -1335,6 +2677,9 @@ fn apply_device_specific_configuration(device: u64, param: &str, value: &str) ->
 }
 
 fn query_device_specific_configuration(device: u64, param: &str) -> Result<String> {
+    // reject keys that the device does not declare in its schema
+    get_device_schema(device)?.get_config_key(param)?;
+
     if (device as usize) < crate::KEYBOARD_DEVICES.read().len() {
         let device = &crate::KEYBOARD_DEVICES.read()[device as usize];
 
@@ -1523,6 +2868,57 @@ fn get_device_specific_ids(device: u64) -> Result<(u16, u16)> {
     }
 }
 
+fn get_device_make_model_serial(device: u64) -> Result<state::DeviceMakeModelSerial> {
+    let keyboards = crate::KEYBOARD_DEVICES.read();
+    let mice = crate::MOUSE_DEVICES.read();
+    let misc = crate::MISC_DEVICES.read();
+
+    let index = device as usize;
+
+    if index < keyboards.len() {
+        Ok(layout::device_make_model_serial(
+            keyboards[index].read().as_device(),
+        ))
+    } else if index < keyboards.len() + mice.len() {
+        Ok(layout::device_make_model_serial(
+            mice[index - keyboards.len()].read().as_device(),
+        ))
+    } else if index < keyboards.len() + mice.len() + misc.len() {
+        Ok(layout::device_make_model_serial(
+            misc[index - (keyboards.len() + mice.len())]
+                .read()
+                .as_device(),
+        ))
+    } else {
+        Err(DbusApiError::InvalidDevice {}.into())
+    }
+}
+
+/// Returns the placements of all connected devices, as (device, x, y, width, height)
+fn get_device_placements(layout: &layout::Layout) -> Result<Vec<(u64, u64, u64, u64, u64)>> {
+    let num_devices = crate::KEYBOARD_DEVICES.read().len()
+        + crate::MOUSE_DEVICES.read().len()
+        + crate::MISC_DEVICES.read().len();
+
+    let mut result = Vec::new();
+
+    for device in 0..num_devices as u64 {
+        let device_make_model_serial = get_device_make_model_serial(device)?;
+
+        if let Some(placement) = layout.get_placement(&device_make_model_serial) {
+            result.push((
+                device,
+                placement.x as u64,
+                placement.y as u64,
+                placement.width as u64,
+                placement.height as u64,
+            ));
+        }
+    }
+
+    Ok(result)
+}
+
 mod perms {
     use dbus::{arg::RefArg, arg::Variant, blocking::Connection};
     use lazy_static::lazy_static;
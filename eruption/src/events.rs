@@ -20,9 +20,9 @@
 */
 
 use crate::{
-    constants, dbus_interface, events, macros, script, switch_profile, DbusApiEvent,
-    FileSystemEvent, KeyboardDevice, KeyboardHidEvent, MouseDevice, MouseHidEvent, ACTIVE_SLOT,
-    DEVICE_STATUS, FAILED_TXS, KEY_STATES, LUA_TXS, MOUSE_MOTION_BUF,
+    battery, constants, dbus_interface, events, layout, macros, script, switch_profile,
+    DbusApiEvent, FileSystemEvent, KeyboardDevice, KeyboardHidEvent, MouseDevice, MouseHidEvent,
+    ACTIVE_SLOT, DEVICE_STATUS, FAILED_TXS, KEY_STATES, LUA_TXS, MOUSE_MOTION_BUF,
     MOUSE_MOVE_EVENT_LAST_DISPATCHED, REQUEST_FAILSAFE_MODE, REQUEST_PROFILE_RELOAD,
     UPCALL_COMPLETED_ON_KEYBOARD_HID_EVENT, UPCALL_COMPLETED_ON_KEY_DOWN,
     UPCALL_COMPLETED_ON_KEY_UP, UPCALL_COMPLETED_ON_MOUSE_BUTTON_DOWN,
@@ -148,6 +148,9 @@ pub fn process_timer_event() -> Result<()> {
 
     for (index, dev) in crate::KEYBOARD_DEVICES.read().iter().enumerate() {
         let device_status = dev.read().device_status()?;
+        let make_model_serial = layout::device_make_model_serial(dev.read().as_device());
+
+        battery::update_device_status((index + offset) as u64, &make_model_serial, &device_status);
 
         DEVICE_STATUS
             .lock()
@@ -158,6 +161,9 @@ pub fn process_timer_event() -> Result<()> {
 
     for (index, dev) in crate::MOUSE_DEVICES.read().iter().enumerate() {
         let device_status = dev.read().device_status()?;
+        let make_model_serial = layout::device_make_model_serial(dev.read().as_device());
+
+        battery::update_device_status((index + offset) as u64, &make_model_serial, &device_status);
 
         DEVICE_STATUS
             .lock()
@@ -168,6 +174,9 @@ pub fn process_timer_event() -> Result<()> {
 
     for (index, dev) in crate::MISC_DEVICES.read().iter().enumerate() {
         let device_status = dev.read().device_status()?;
+        let make_model_serial = layout::device_make_model_serial(dev.read().as_device());
+
+        battery::update_device_status((index + offset) as u64, &make_model_serial, &device_status);
 
         DEVICE_STATUS
             .lock()
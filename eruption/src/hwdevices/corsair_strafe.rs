@@ -30,6 +30,7 @@ use std::{sync::Arc, thread};
 
 use crate::constants;
 
+use super::schema::{DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
@@ -337,6 +338,10 @@ impl DeviceInfoTrait for CorsairStrafe {
         DeviceCapabilities::from([Capability::Keyboard])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new().status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
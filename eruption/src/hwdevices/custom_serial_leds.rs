@@ -28,6 +28,7 @@ use std::time::Duration;
 
 use crate::hwdevices::DeviceStatus;
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MiscDeviceTrait,
     MouseDeviceTrait, RGBA,
@@ -69,6 +70,12 @@ impl DeviceInfoTrait for CustomSerialLeds {
         DeviceCapabilities::from([])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
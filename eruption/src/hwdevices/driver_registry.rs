@@ -0,0 +1,363 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use log::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{DriverMetadata, DriverSource, MaturityLevel, MiscSerialDriver, DRIVERS};
+
+pub type Result<T> = super::Result<T>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DriverRegistryError {
+    #[error("Could not parse driver registry file: {description}")]
+    ParseError { description: String },
+}
+
+/// An additional VID/PID that shall be handled by an existing driver family
+#[derive(Debug, Clone, Deserialize)]
+pub struct DriverAlias {
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+
+    /// Name of the driver family, e.g. `roccat_kone_pro`
+    pub driver: String,
+
+    /// Defaults to the maturity level of the driver family
+    pub maturity_level: Option<MaturityLevel>,
+}
+
+/// Overrides the maturity level of an existing VID/PID binding
+#[derive(Debug, Clone, Deserialize)]
+pub struct DriverOverride {
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+
+    pub maturity_level: MaturityLevel,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DriverRegistryFile {
+    #[serde(default, rename = "alias")]
+    pub aliases: Vec<DriverAlias>,
+
+    #[serde(default, rename = "override")]
+    pub overrides: Vec<DriverOverride>,
+}
+
+impl DriverRegistryFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)?;
+
+        let result =
+            toml::from_str::<Self>(&data).map_err(|e| DriverRegistryError::ParseError {
+                description: format!("{}", e),
+            })?;
+
+        Ok(result)
+    }
+}
+
+/// Merge the VID/PID aliases and maturity level overrides from `path` into the
+/// registry of built-in drivers
+pub fn load_driver_registry_file(path: &Path) -> Result<()> {
+    if !path.exists() {
+        debug!(
+            "Driver registry file {} does not exist, using built-in drivers only",
+            path.display()
+        );
+
+        return Ok(());
+    }
+
+    info!("Loading driver registry file: {}", path.display());
+
+    let registry_file = DriverRegistryFile::load(path)?;
+
+    merge_driver_registry(&mut DRIVERS.lock(), &registry_file, path);
+
+    Ok(())
+}
+
+fn merge_driver_registry(
+    drivers: &mut Vec<Box<dyn DriverMetadata + Sync + Send + 'static>>,
+    registry_file: &DriverRegistryFile,
+    path: &Path,
+) {
+    for alias in registry_file.aliases.iter() {
+        let template = drivers.iter().find(|d| {
+            d.get_driver_family() == alias.driver && !d.as_any().is::<MiscSerialDriver>()
+        });
+
+        if let Some(template) = template {
+            let driver = template.rebind(
+                alias.usb_vid,
+                alias.usb_pid,
+                alias
+                    .maturity_level
+                    .unwrap_or_else(|| template.get_maturity_level()),
+                DriverSource::Alias(PathBuf::from(path)),
+            );
+
+            if let Some(index) = drivers
+                .iter()
+                .position(|d| d.get_usb_vid() == alias.usb_vid && d.get_usb_pid() == alias.usb_pid)
+            {
+                warn!(
+                    "Alias for 0x{:04x}:0x{:04x} replaces the existing binding to driver '{}'",
+                    alias.usb_vid,
+                    alias.usb_pid,
+                    drivers[index].get_driver_family()
+                );
+
+                drivers[index] = driver;
+            } else {
+                info!(
+                    "Adding alias for 0x{:04x}:0x{:04x} to driver '{}'",
+                    alias.usb_vid, alias.usb_pid, alias.driver
+                );
+
+                drivers.push(driver);
+            }
+        } else {
+            error!(
+                "Alias for 0x{:04x}:0x{:04x} refers to an unknown driver: '{}'",
+                alias.usb_vid, alias.usb_pid, alias.driver
+            );
+        }
+    }
+
+    for entry in registry_file.overrides.iter() {
+        if let Some(index) = drivers
+            .iter()
+            .position(|d| d.get_usb_vid() == entry.usb_vid && d.get_usb_pid() == entry.usb_pid)
+        {
+            info!(
+                "Overriding maturity level of 0x{:04x}:0x{:04x}: {} -> {}",
+                entry.usb_vid,
+                entry.usb_pid,
+                drivers[index].get_maturity_level(),
+                entry.maturity_level
+            );
+
+            // aliases keep their source, since they do not exist in the built-in table
+            let source = match drivers[index].get_source() {
+                DriverSource::Alias(path) => DriverSource::Alias(path.clone()),
+                _ => DriverSource::Override(PathBuf::from(path)),
+            };
+
+            let driver =
+                drivers[index].rebind(entry.usb_vid, entry.usb_pid, entry.maturity_level, source);
+
+            drivers[index] = driver;
+        } else {
+            error!(
+                "Override refers to an unknown device: 0x{:04x}:0x{:04x}",
+                entry.usb_vid, entry.usb_pid
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hwdevices::{
+        roccat_kone_pro, roccat_vulcan_1xx, DeviceClass, KeyboardDriver, MouseDriver,
+    };
+
+    #[test]
+    fn parse_driver_registry_file() -> Result<()> {
+        let data = r#"
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2c8f
+            driver = "roccat_kone_pro"
+
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2c90
+            driver = "roccat_kone_xp"
+            maturity_level = "testing"
+
+            [[override]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2e27
+            maturity_level = "stable"
+        "#;
+
+        let registry_file = toml::from_str::<DriverRegistryFile>(data)?;
+
+        assert_eq!(registry_file.aliases.len(), 2);
+        assert_eq!(registry_file.aliases[0].usb_pid, 0x2c8f);
+        assert_eq!(registry_file.aliases[0].maturity_level, None);
+        assert_eq!(
+            registry_file.aliases[1].maturity_level,
+            Some(MaturityLevel::Testing)
+        );
+
+        assert_eq!(registry_file.overrides.len(), 1);
+        assert_eq!(
+            registry_file.overrides[0].maturity_level,
+            MaturityLevel::Stable
+        );
+
+        Ok(())
+    }
+
+    fn built_in_drivers() -> Vec<Box<dyn DriverMetadata + Sync + Send + 'static>> {
+        vec![
+            KeyboardDriver::register(
+                "ROCCAT",
+                "Vulcan 100/12x",
+                "roccat_vulcan_1xx",
+                0x1e7d,
+                0x3098,
+                &roccat_vulcan_1xx::bind_hiddev,
+                MaturityLevel::Stable,
+            ),
+            MouseDriver::register(
+                "ROCCAT",
+                "Kone Pro",
+                "roccat_kone_pro",
+                0x1e7d,
+                0x2c88,
+                &roccat_kone_pro::bind_hiddev,
+                MaturityLevel::Experimental,
+            ),
+        ]
+    }
+
+    fn find_driver<'a>(
+        drivers: &'a [Box<dyn DriverMetadata + Sync + Send + 'static>],
+        usb_vid: u16,
+        usb_pid: u16,
+    ) -> &'a (dyn DriverMetadata + Sync + Send + 'static) {
+        drivers
+            .iter()
+            .find(|d| d.get_usb_vid() == usb_vid && d.get_usb_pid() == usb_pid)
+            .unwrap()
+            .as_ref()
+    }
+
+    #[test]
+    fn resolve_driver_aliases() -> Result<()> {
+        let path = Path::new("/etc/eruption/drivers.toml");
+
+        let registry_file = toml::from_str::<DriverRegistryFile>(
+            r#"
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2c8f
+            driver = "roccat_kone_pro"
+
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x307b
+            driver = "roccat_vulcan_1xx"
+            maturity_level = "testing"
+
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x9999
+            driver = "no_such_driver"
+
+            [[override]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x307b
+            maturity_level = "experimental"
+        "#,
+        )?;
+
+        let mut drivers = built_in_drivers();
+        merge_driver_registry(&mut drivers, &registry_file, path);
+
+        // aliases to unknown drivers are skipped
+        assert_eq!(drivers.len(), 4);
+
+        // an alias inherits the maturity level of its driver family, unless it declares one
+        let driver = find_driver(&drivers, 0x1e7d, 0x2c8f);
+        assert_eq!(driver.get_driver_family(), "roccat_kone_pro");
+        assert!(matches!(driver.get_device_class(), DeviceClass::Mouse));
+        assert_eq!(driver.get_maturity_level(), MaturityLevel::Experimental);
+        assert_eq!(
+            driver.get_source(),
+            &DriverSource::Alias(PathBuf::from(path))
+        );
+
+        // overriding an alias keeps its source
+        let driver = find_driver(&drivers, 0x1e7d, 0x307b);
+        assert_eq!(driver.get_driver_family(), "roccat_vulcan_1xx");
+        assert!(matches!(driver.get_device_class(), DeviceClass::Keyboard));
+        assert_eq!(driver.get_maturity_level(), MaturityLevel::Experimental);
+        assert_eq!(
+            driver.get_source(),
+            &DriverSource::Alias(PathBuf::from(path))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn user_entries_override_built_in_drivers() -> Result<()> {
+        let path = Path::new("/etc/eruption/drivers.toml");
+
+        let registry_file = toml::from_str::<DriverRegistryFile>(
+            r#"
+            [[alias]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x2c88
+            driver = "roccat_vulcan_1xx"
+
+            [[override]]
+            usb_vid = 0x1e7d
+            usb_pid = 0x3098
+            maturity_level = "testing"
+        "#,
+        )?;
+
+        let mut drivers = built_in_drivers();
+        merge_driver_registry(&mut drivers, &registry_file, path);
+
+        // an alias for a built-in VID/PID replaces the existing binding
+        assert_eq!(drivers.len(), 2);
+
+        let driver = find_driver(&drivers, 0x1e7d, 0x2c88);
+        assert_eq!(driver.get_driver_family(), "roccat_vulcan_1xx");
+        assert_eq!(driver.get_maturity_level(), MaturityLevel::Stable);
+        assert_eq!(
+            driver.get_source(),
+            &DriverSource::Alias(PathBuf::from(path))
+        );
+
+        let driver = find_driver(&drivers, 0x1e7d, 0x3098);
+        assert_eq!(driver.get_driver_family(), "roccat_vulcan_1xx");
+        assert_eq!(driver.get_maturity_level(), MaturityLevel::Testing);
+        assert_eq!(
+            driver.get_source(),
+            &DriverSource::Override(PathBuf::from(path))
+        );
+
+        Ok(())
+    }
+}
@@ -28,6 +28,7 @@ use std::{any::Any, collections::HashMap};
 
 use crate::hwdevices::DeviceStatus;
 
+use super::schema::{DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, KeyboardDevice,
     KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MouseDeviceTrait, RGBA,
@@ -92,6 +93,10 @@ impl DeviceInfoTrait for GenericKeyboard {
         DeviceCapabilities::from([Capability::Keyboard])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new().status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
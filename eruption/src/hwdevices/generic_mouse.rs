@@ -28,6 +28,7 @@ use parking_lot::RwLock;
 
 use crate::hwdevices::DeviceStatus;
 
+use super::schema::{DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice,
     MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -86,6 +87,10 @@ impl DeviceInfoTrait for GenericMouse {
         DeviceCapabilities::from([Capability::Mouse])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new().status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -31,10 +31,17 @@ use std::{any::Any, sync::Arc, thread};
 use std::{path::PathBuf, time::Duration};
 use udev::Enumerator;
 
+use self::onboard::OnboardProfile;
+use self::remap::KeyRemap;
+use self::schema::DeviceSchema;
+
 mod corsair_strafe;
 mod custom_serial_leds;
+pub mod driver_registry;
 mod generic_keyboard;
 mod generic_mouse;
+pub mod onboard;
+pub mod remap;
 mod roccat_aimo_pad;
 mod roccat_burst_pro;
 mod roccat_elo_71_air;
@@ -55,6 +62,8 @@ mod roccat_vulcan_1xx;
 mod roccat_vulcan_pro;
 mod roccat_vulcan_pro_tkl;
 mod roccat_vulcan_tkl;
+pub mod schema;
+pub mod virtual_devices;
 
 pub type KeyboardDevice = Arc<RwLock<Box<dyn KeyboardDeviceTrait + Sync + Send>>>;
 pub type MouseDevice = Arc<RwLock<Box<dyn MouseDeviceTrait + Sync + Send>>>;
@@ -76,78 +85,78 @@ pub type Result<T> = std::result::Result<T, eyre::Error>;
 #[rustfmt::skip]
 lazy_static! {
     // List of supported devices
-    pub static ref DRIVERS: Arc<Mutex<[Box<(dyn DriverMetadata + Sync + Send + 'static)>; 28]>> = Arc::new(Mutex::new([
+    pub static ref DRIVERS: Arc<Mutex<Vec<Box<(dyn DriverMetadata + Sync + Send + 'static)>>>> = Arc::new(Mutex::new(vec![
         // Supported keyboards
 
         // ROCCAT
 
         // Vulcan 100/12x/Pro (TKL) series
-        KeyboardDriver::register("ROCCAT", "Vulcan 100/12x", 0x1e7d, 0x3098, &roccat_vulcan_1xx::bind_hiddev, MaturityLevel::Stable),
-        KeyboardDriver::register("ROCCAT", "Vulcan 100/12x", 0x1e7d, 0x307a, &roccat_vulcan_1xx::bind_hiddev, MaturityLevel::Stable),
+        KeyboardDriver::register("ROCCAT", "Vulcan 100/12x", "roccat_vulcan_1xx", 0x1e7d, 0x3098, &roccat_vulcan_1xx::bind_hiddev, MaturityLevel::Stable),
+        KeyboardDriver::register("ROCCAT", "Vulcan 100/12x", "roccat_vulcan_1xx", 0x1e7d, 0x307a, &roccat_vulcan_1xx::bind_hiddev, MaturityLevel::Stable),
 
-        KeyboardDriver::register("ROCCAT", "Vulcan Pro",     0x1e7d, 0x30f7, &roccat_vulcan_pro::bind_hiddev, MaturityLevel::Experimental),
+        KeyboardDriver::register("ROCCAT", "Vulcan Pro",     "roccat_vulcan_pro", 0x1e7d, 0x30f7, &roccat_vulcan_pro::bind_hiddev, MaturityLevel::Experimental),
 
-        KeyboardDriver::register("ROCCAT", "Vulcan TKL",     0x1e7d, 0x2fee, &roccat_vulcan_tkl::bind_hiddev, MaturityLevel::Experimental),
+        KeyboardDriver::register("ROCCAT", "Vulcan TKL",     "roccat_vulcan_tkl", 0x1e7d, 0x2fee, &roccat_vulcan_tkl::bind_hiddev, MaturityLevel::Experimental),
 
-        KeyboardDriver::register("ROCCAT", "Vulcan Pro TKL", 0x1e7d, 0x311a, &roccat_vulcan_pro_tkl::bind_hiddev, MaturityLevel::Testing),
+        KeyboardDriver::register("ROCCAT", "Vulcan Pro TKL", "roccat_vulcan_pro_tkl", 0x1e7d, 0x311a, &roccat_vulcan_pro_tkl::bind_hiddev, MaturityLevel::Testing),
 
-        KeyboardDriver::register("ROCCAT", "Magma",          0x1e7d, 0x3124, &roccat_magma::bind_hiddev, MaturityLevel::Experimental),
+        KeyboardDriver::register("ROCCAT", "Magma",          "roccat_magma", 0x1e7d, 0x3124, &roccat_magma::bind_hiddev, MaturityLevel::Experimental),
 
         // CORSAIR
 
         // Corsair STRAFE Gaming Keyboard
-        KeyboardDriver::register("Corsair", "Corsair STRAFE Gaming Keyboard", 0x1b1c, 0x1b15, &corsair_strafe::bind_hiddev, MaturityLevel::Experimental),
+        KeyboardDriver::register("Corsair", "Corsair STRAFE Gaming Keyboard", "corsair_strafe", 0x1b1c, 0x1b15, &corsair_strafe::bind_hiddev, MaturityLevel::Experimental),
 
 
         // Supported mice
 
         // ROCCAT
-        MouseDriver::register("ROCCAT", "Kone Aimo",         0x1e7d, 0x2e27, &roccat_kone_aimo::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kone Aimo",         "roccat_kone_aimo", 0x1e7d, 0x2e27, &roccat_kone_aimo::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kone Aimo Remastered", 0x1e7d, 0x2e2c, &roccat_kone_aimo_remastered::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kone Aimo Remastered", "roccat_kone_aimo_remastered", 0x1e7d, 0x2e2c, &roccat_kone_aimo_remastered::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kone XTD Mouse",    0x1e7d, 0x2e22, &roccat_kone_xtd::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kone XTD Mouse",    "roccat_kone_xtd", 0x1e7d, 0x2e22, &roccat_kone_xtd::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kone Pure Ultra",   0x1e7d, 0x2dd2, &roccat_kone_pure_ultra::bind_hiddev, MaturityLevel::Stable),
+        MouseDriver::register("ROCCAT", "Kone Pure Ultra",   "roccat_kone_pure_ultra", 0x1e7d, 0x2dd2, &roccat_kone_pure_ultra::bind_hiddev, MaturityLevel::Stable),
 
-        MouseDriver::register("ROCCAT", "Burst Pro",         0x1e7d, 0x2de1, &roccat_burst_pro::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Burst Pro",         "roccat_burst_pro", 0x1e7d, 0x2de1, &roccat_burst_pro::bind_hiddev, MaturityLevel::Testing),
 
-        MouseDriver::register("ROCCAT", "Kone XP",           0x1e7d, 0x2c8b, &roccat_kone_xp::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kone XP",           "roccat_kone_xp", 0x1e7d, 0x2c8b, &roccat_kone_xp::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kone Pro",          0x1e7d, 0x2c88, &roccat_kone_pro::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kone Pro",          "roccat_kone_pro", 0x1e7d, 0x2c88, &roccat_kone_pro::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kone Pro Air Dongle", 0x1e7d, 0x2c8e, &roccat_kone_pro_air::bind_hiddev, MaturityLevel::Testing),
-        MouseDriver::register("ROCCAT", "Kone Pro Air",        0x1e7d, 0x2c92, &roccat_kone_pro_air::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kone Pro Air Dongle", "roccat_kone_pro_air", 0x1e7d, 0x2c8e, &roccat_kone_pro_air::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kone Pro Air",        "roccat_kone_pro_air", 0x1e7d, 0x2c92, &roccat_kone_pro_air::bind_hiddev, MaturityLevel::Testing),
 
-        MouseDriver::register("ROCCAT", "Kain 100 AIMO",     0x1e7d, 0x2d00, &roccat_kain_100::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Kain 100 AIMO",     "roccat_kain_100", 0x1e7d, 0x2d00, &roccat_kain_100::bind_hiddev, MaturityLevel::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kain 200 AIMO",     0x1e7d, 0x2d5f, &roccat_kain_2xx::bind_hiddev, MaturityLevel::Testing),
-        MouseDriver::register("ROCCAT", "Kain 200 AIMO",     0x1e7d, 0x2d60, &roccat_kain_2xx::bind_hiddev, MaturityLevel::Testing),
-        // MouseDriver::register("ROCCAT", "Kain 202 AIMO",     0x1e7d, 0x2d60, &roccat_kain_2xx::bind_hiddev, Status::Experimental),
+        MouseDriver::register("ROCCAT", "Kain 200 AIMO",     "roccat_kain_2xx", 0x1e7d, 0x2d5f, &roccat_kain_2xx::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kain 200 AIMO",     "roccat_kain_2xx", 0x1e7d, 0x2d60, &roccat_kain_2xx::bind_hiddev, MaturityLevel::Testing),
+        // MouseDriver::register("ROCCAT", "Kain 202 AIMO",     "roccat_kain_2xx", 0x1e7d, 0x2d60, &roccat_kain_2xx::bind_hiddev, Status::Experimental),
 
-        MouseDriver::register("ROCCAT", "Kova AIMO",         0x1e7d, 0x2cf1, &roccat_kova_aimo::bind_hiddev, MaturityLevel::Testing),
-        MouseDriver::register("ROCCAT", "Kova AIMO",         0x1e7d, 0x2cf3, &roccat_kova_aimo::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kova AIMO",         "roccat_kova_aimo", 0x1e7d, 0x2cf1, &roccat_kova_aimo::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kova AIMO",         "roccat_kova_aimo", 0x1e7d, 0x2cf3, &roccat_kova_aimo::bind_hiddev, MaturityLevel::Testing),
 
-        MouseDriver::register("ROCCAT", "Kova 2016",         0x1e7d, 0x2cee, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
-        MouseDriver::register("ROCCAT", "Kova 2016",         0x1e7d, 0x2cef, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
-        MouseDriver::register("ROCCAT", "Kova 2016",         0x1e7d, 0x2cf0, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kova 2016",         "roccat_kova_2016", 0x1e7d, 0x2cee, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kova 2016",         "roccat_kova_2016", 0x1e7d, 0x2cef, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
+        MouseDriver::register("ROCCAT", "Kova 2016",         "roccat_kova_2016", 0x1e7d, 0x2cf0, &roccat_kova_2016::bind_hiddev, MaturityLevel::Testing),
 
-        MouseDriver::register("ROCCAT", "Nyth",              0x1e7d, 0x2e7c, &roccat_nyth::bind_hiddev, MaturityLevel::Experimental),
-        MouseDriver::register("ROCCAT", "Nyth",              0x1e7d, 0x2e7d, &roccat_nyth::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Nyth",              "roccat_nyth", 0x1e7d, 0x2e7c, &roccat_nyth::bind_hiddev, MaturityLevel::Experimental),
+        MouseDriver::register("ROCCAT", "Nyth",              "roccat_nyth", 0x1e7d, 0x2e7d, &roccat_nyth::bind_hiddev, MaturityLevel::Experimental),
 
 
         // Supported miscellaneous devices
 
         // ROCCAT/Turtle Beach
-        MiscDriver::register("ROCCAT/Turtle Beach", "Elo 7.1 Air", 0x1e7d, 0x3a37, &roccat_elo_71_air::bind_hiddev, MaturityLevel::Testing),
+        MiscDriver::register("ROCCAT/Turtle Beach", "Elo 7.1 Air", "roccat_elo_71_air", 0x1e7d, 0x3a37, &roccat_elo_71_air::bind_hiddev, MaturityLevel::Testing),
 
-        MiscDriver::register("ROCCAT", "Aimo Pad Wide", 0x1e7d, 0x343b, &roccat_aimo_pad::bind_hiddev, MaturityLevel::Stable),
+        MiscDriver::register("ROCCAT", "Aimo Pad Wide", "roccat_aimo_pad", 0x1e7d, 0x343b, &roccat_aimo_pad::bind_hiddev, MaturityLevel::Stable),
 
 
         // Misc Serial devices
 
         // Eruption Custom Hardware
-        // MiscSerialDriver::register("Eruption", "Custom Serial LEDs", &custom_serial_leds::bind_serial, Status::Testing),
+        // MiscSerialDriver::register("Eruption", "Custom Serial LEDs", "custom_serial_leds", &custom_serial_leds::bind_serial, Status::Testing),
     ]));
 }
 
@@ -196,12 +205,64 @@ pub enum HwDeviceError {
     MappingError {},
 }
 
+/// Where a driver binding in the registry originated from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DriverSource {
+    /// Compiled into the Eruption daemon
+    BuiltIn,
+
+    /// Additional VID/PID mapping, declared in a driver registry file
+    Alias(PathBuf),
+
+    /// Built-in binding with its maturity level overridden by a driver registry file
+    Override(PathBuf),
+}
+
+impl std::fmt::Display for DriverSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriverSource::BuiltIn => write!(f, "built-in"),
+            DriverSource::Alias(path) => write!(f, "alias ({})", path.display()),
+            DriverSource::Override(path) => write!(f, "override ({})", path.display()),
+        }
+    }
+}
+
+impl std::fmt::Display for MaturityLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaturityLevel::Stable => write!(f, "stable"),
+            MaturityLevel::Testing => write!(f, "testing"),
+            MaturityLevel::Experimental => write!(f, "experimental"),
+        }
+    }
+}
+
 pub trait DriverMetadata {
     fn get_usb_vid(&self) -> u16;
     fn get_usb_pid(&self) -> u16;
 
+    fn get_device_make(&self) -> &str;
+    fn get_device_name(&self) -> &str;
+
+    /// The driver family, e.g. the name of the module that implements the driver
+    fn get_driver_family(&self) -> &str;
+
     fn get_device_class(&self) -> DeviceClass;
 
+    fn get_maturity_level(&self) -> MaturityLevel;
+
+    fn get_source(&self) -> &DriverSource;
+
+    /// Create a copy of this driver, bound to a different USB VID/PID
+    fn rebind(
+        &self,
+        usb_vid: u16,
+        usb_pid: u16,
+        status: MaturityLevel,
+        source: DriverSource,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)>;
+
     fn as_any(&self) -> &(dyn Any);
 }
 
@@ -209,9 +270,11 @@ pub trait SerialDriverMetadata: DriverMetadata {
     fn get_serial_port(&self) -> Option<&str>;
 }
 
+#[derive(Clone)]
 pub struct KeyboardDriver<'a> {
     pub device_make: &'a str,
     pub device_name: &'a str,
+    pub driver_family: &'a str,
 
     pub device_class: DeviceClass,
 
@@ -221,12 +284,14 @@ pub struct KeyboardDriver<'a> {
     pub bind_fn: &'a (dyn Fn(&HidApi, u16, u16, &str) -> Result<KeyboardDevice> + Sync + Send),
 
     pub status: MaturityLevel,
+    pub source: DriverSource,
 }
 
 impl KeyboardDriver<'static> {
     pub fn register(
         device_make: &'static str,
         device_name: &'static str,
+        driver_family: &'static str,
         usb_vid: u16,
         usb_pid: u16,
         bind_fn: &'static (dyn Fn(&HidApi, u16, u16, &str) -> Result<KeyboardDevice> + Sync + Send),
@@ -235,11 +300,13 @@ impl KeyboardDriver<'static> {
         Box::new(KeyboardDriver {
             device_make,
             device_name,
+            driver_family,
             device_class: DeviceClass::Keyboard,
             usb_vid,
             usb_pid,
             bind_fn,
             status,
+            source: DriverSource::BuiltIn,
         })
     }
 }
@@ -260,11 +327,49 @@ impl DriverMetadata for KeyboardDriver<'static> {
     fn get_usb_pid(&self) -> u16 {
         self.usb_pid
     }
+
+    fn get_device_make(&self) -> &str {
+        self.device_make
+    }
+
+    fn get_device_name(&self) -> &str {
+        self.device_name
+    }
+
+    fn get_driver_family(&self) -> &str {
+        self.driver_family
+    }
+
+    fn get_maturity_level(&self) -> MaturityLevel {
+        self.status
+    }
+
+    fn get_source(&self) -> &DriverSource {
+        &self.source
+    }
+
+    fn rebind(
+        &self,
+        usb_vid: u16,
+        usb_pid: u16,
+        status: MaturityLevel,
+        source: DriverSource,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        Box::new(KeyboardDriver {
+            usb_vid,
+            usb_pid,
+            status,
+            source,
+            ..self.clone()
+        })
+    }
 }
 
+#[derive(Clone)]
 pub struct MouseDriver<'a> {
     pub device_make: &'a str,
     pub device_name: &'a str,
+    pub driver_family: &'a str,
 
     pub device_class: DeviceClass,
 
@@ -274,12 +379,14 @@ pub struct MouseDriver<'a> {
     pub bind_fn: &'a (dyn Fn(&HidApi, u16, u16, &str) -> Result<MouseDevice> + Sync + Send),
 
     pub status: MaturityLevel,
+    pub source: DriverSource,
 }
 
 impl MouseDriver<'static> {
     pub fn register(
         device_make: &'static str,
         device_name: &'static str,
+        driver_family: &'static str,
         usb_vid: u16,
         usb_pid: u16,
         bind_fn: &'static (dyn Fn(&HidApi, u16, u16, &str) -> Result<MouseDevice> + Sync + Send),
@@ -288,11 +395,13 @@ impl MouseDriver<'static> {
         Box::new(MouseDriver {
             device_make,
             device_name,
+            driver_family,
             device_class: DeviceClass::Mouse,
             usb_vid,
             usb_pid,
             bind_fn,
             status,
+            source: DriverSource::BuiltIn,
         })
     }
 }
@@ -313,11 +422,49 @@ impl DriverMetadata for MouseDriver<'static> {
     fn get_usb_pid(&self) -> u16 {
         self.usb_pid
     }
+
+    fn get_device_make(&self) -> &str {
+        self.device_make
+    }
+
+    fn get_device_name(&self) -> &str {
+        self.device_name
+    }
+
+    fn get_driver_family(&self) -> &str {
+        self.driver_family
+    }
+
+    fn get_maturity_level(&self) -> MaturityLevel {
+        self.status
+    }
+
+    fn get_source(&self) -> &DriverSource {
+        &self.source
+    }
+
+    fn rebind(
+        &self,
+        usb_vid: u16,
+        usb_pid: u16,
+        status: MaturityLevel,
+        source: DriverSource,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        Box::new(MouseDriver {
+            usb_vid,
+            usb_pid,
+            status,
+            source,
+            ..self.clone()
+        })
+    }
 }
 
+#[derive(Clone)]
 pub struct MiscDriver<'a> {
     pub device_make: &'a str,
     pub device_name: &'a str,
+    pub driver_family: &'a str,
 
     pub device_class: DeviceClass,
 
@@ -327,6 +474,7 @@ pub struct MiscDriver<'a> {
     pub bind_fn: &'a (dyn Fn(&HidApi, u16, u16, &str) -> Result<MiscDevice> + Sync + Send),
 
     pub status: MaturityLevel,
+    pub source: DriverSource,
 }
 
 impl MiscDriver<'static> {
@@ -334,6 +482,7 @@ impl MiscDriver<'static> {
     pub fn register(
         device_make: &'static str,
         device_name: &'static str,
+        driver_family: &'static str,
         usb_vid: u16,
         usb_pid: u16,
         bind_fn: &'static (dyn Fn(&HidApi, u16, u16, &str) -> Result<MiscDevice> + Sync + Send),
@@ -342,11 +491,13 @@ impl MiscDriver<'static> {
         Box::new(MiscDriver {
             device_make,
             device_name,
+            driver_family,
             device_class: DeviceClass::Misc,
             usb_vid,
             usb_pid,
             bind_fn,
             status,
+            source: DriverSource::BuiltIn,
         })
     }
 }
@@ -367,11 +518,49 @@ impl DriverMetadata for MiscDriver<'static> {
     fn get_usb_pid(&self) -> u16 {
         self.usb_pid
     }
+
+    fn get_device_make(&self) -> &str {
+        self.device_make
+    }
+
+    fn get_device_name(&self) -> &str {
+        self.device_name
+    }
+
+    fn get_driver_family(&self) -> &str {
+        self.driver_family
+    }
+
+    fn get_maturity_level(&self) -> MaturityLevel {
+        self.status
+    }
+
+    fn get_source(&self) -> &DriverSource {
+        &self.source
+    }
+
+    fn rebind(
+        &self,
+        usb_vid: u16,
+        usb_pid: u16,
+        status: MaturityLevel,
+        source: DriverSource,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        Box::new(MiscDriver {
+            usb_vid,
+            usb_pid,
+            status,
+            source,
+            ..self.clone()
+        })
+    }
 }
 
+#[derive(Clone)]
 pub struct MiscSerialDriver<'a> {
     pub device_make: &'a str,
     pub device_name: &'a str,
+    pub driver_family: &'a str,
 
     pub device_class: DeviceClass,
 
@@ -380,6 +569,7 @@ pub struct MiscSerialDriver<'a> {
     pub bind_fn: &'a (dyn Fn(&str) -> Result<MiscSerialDevice> + Sync + Send),
 
     pub status: MaturityLevel,
+    pub source: DriverSource,
 }
 
 impl MiscSerialDriver<'static> {
@@ -387,16 +577,19 @@ impl MiscSerialDriver<'static> {
     pub fn register(
         device_make: &'static str,
         device_name: &'static str,
+        driver_family: &'static str,
         bind_fn: &'static (dyn Fn(&str) -> Result<MiscSerialDevice> + Sync + Send),
         status: MaturityLevel,
     ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
         Box::new(MiscSerialDriver {
             device_make,
             device_name,
+            driver_family,
             device_class: DeviceClass::Misc,
             serial_port: None,
             bind_fn,
             status,
+            source: DriverSource::BuiltIn,
         })
     }
 }
@@ -410,10 +603,45 @@ impl DriverMetadata for MiscSerialDriver<'static> {
         0
     }
 
+    fn get_device_make(&self) -> &str {
+        self.device_make
+    }
+
+    fn get_device_name(&self) -> &str {
+        self.device_name
+    }
+
+    fn get_driver_family(&self) -> &str {
+        self.driver_family
+    }
+
     fn get_device_class(&self) -> DeviceClass {
         self.device_class
     }
 
+    fn get_maturity_level(&self) -> MaturityLevel {
+        self.status
+    }
+
+    fn get_source(&self) -> &DriverSource {
+        &self.source
+    }
+
+    fn rebind(
+        &self,
+        _usb_vid: u16,
+        _usb_pid: u16,
+        status: MaturityLevel,
+        source: DriverSource,
+    ) -> Box<(dyn DriverMetadata + Sync + Send + 'static)> {
+        // serial drivers are not bound via USB VID/PID
+        Box::new(MiscSerialDriver {
+            status,
+            source,
+            ..self.clone()
+        })
+    }
+
     fn as_any(&self) -> &(dyn Any) {
         self
     }
@@ -434,7 +662,7 @@ pub enum DeviceClass {
 }
 
 /// Represents an RGBA color value
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct RGBA {
     pub r: u8,
     pub g: u8,
@@ -594,6 +822,18 @@ pub struct NonPnPDevice {
     pub device_file: PathBuf,
 }
 
+/// Virtual (simulated) device, may be declared in .config file
+#[derive(Debug, Clone)]
+pub struct VirtualDeviceConfig {
+    pub class: String,
+    pub name: String,
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+    pub event_script: Option<PathBuf>,
+    pub repeat_events: bool,
+    pub record_file: Option<PathBuf>,
+}
+
 /// Represents the capabilities of a hardware device
 #[derive(Debug, Clone)]
 pub struct DeviceCapabilities(HashSet<Capability>);
@@ -604,6 +844,13 @@ impl<const N: usize> From<[Capability; N]> for DeviceCapabilities {
     }
 }
 
+impl DeviceCapabilities {
+    /// Returns `true` if the device has the capability `cap`
+    pub fn contains(&self, cap: &Capability) -> bool {
+        self.0.contains(cap)
+    }
+}
+
 /// Capabilities that hardware may have
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Capability {
@@ -617,6 +864,7 @@ pub enum Capability {
     // Features
     RgbLighting,
     HardwareProfiles,
+    HardwareRemapping,
     PowerManagement,
 
     DpiSelection,
@@ -630,6 +878,9 @@ pub trait DeviceInfoTrait {
     /// Get device capabilities
     fn get_device_capabilities(&self) -> DeviceCapabilities;
 
+    /// Get the configuration keys supported by the device, and the status keys it reports
+    fn get_device_schema(&self) -> DeviceSchema;
+
     /// Get device specific information
     fn get_device_info(&self) -> Result<DeviceInfo>;
 
@@ -637,21 +888,6 @@ pub trait DeviceInfoTrait {
     fn get_firmware_revision(&self) -> String;
 }
 
-#[allow(unused)]
-#[derive(Debug, Clone)]
-pub enum DeviceConfig {
-    NoOp,
-}
-
-#[allow(unused)]
-pub trait GenericConfiguration {
-    /// Get device specific configuration
-    fn get_device_config(&self, param: &DeviceConfig) -> Result<Box<dyn Any>>;
-
-    /// Set device specific configuration
-    fn set_device_config(&self, param: &DeviceConfig, value: &dyn Any) -> Result<()>;
-}
-
 /// Generic device trait
 pub trait DeviceTrait: DeviceInfoTrait {
     /// Returns the USB path/ID of the device
@@ -743,6 +979,12 @@ pub trait KeyboardDeviceTrait: DeviceTrait {
     /// Get the device specific brightness
     fn get_local_brightness(&self) -> Result<i32>;
 
+    /// Remap keys in the firmware of the device, replacing all previous remappings.
+    /// An empty slice restores the factory assignments
+    fn set_hardware_key_remap(&mut self, _remaps: &[KeyRemap]) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
     /// Send RGBA LED map to the device
     fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()>;
 
@@ -811,6 +1053,27 @@ pub trait MouseDeviceTrait: DeviceTrait {
 
     fn set_debounce(&mut self, debounce: bool) -> Result<()>;
 
+    /// Get the number of onboard profile slots, zero if onboard profiles are not supported
+    fn get_onboard_profile_count(&self) -> u8 {
+        0
+    }
+
+    /// Read the onboard profile in slot `index` from the device
+    fn read_onboard_profile(&self, _index: u8) -> Result<OnboardProfile> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
+    /// Check that all settings of an onboard profile are supported by the device, before
+    /// any of the profiles are written
+    fn validate_onboard_profile(&self, _profile: &OnboardProfile) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
+    /// Write an onboard profile to the device, into the slot that is specified by the profile
+    fn write_onboard_profile(&mut self, _profile: &OnboardProfile) -> Result<()> {
+        Err(HwDeviceError::OpNotSupported {}.into())
+    }
+
     /// Set the device specific brightness
     fn set_local_brightness(&mut self, brightness: i32) -> Result<()>;
 
@@ -901,6 +1164,8 @@ pub fn is_device_blacklisted(vid: u16, pid: u16) -> Result<bool> {
                 }
             } else if table["entry_type"].clone().into_string()? == "device" {
                 /* skip device declarations */
+            } else if table["entry_type"].clone().into_string()? == "virtual" {
+                /* skip virtual device declarations */
             } else {
                 error!("Invalid 'entry_type' specified in the configuration file");
             }
@@ -939,6 +1204,8 @@ pub fn get_non_pnp_devices() -> Result<Vec<NonPnPDevice>> {
                 result.push(device);
             } else if table["entry_type"].clone().into_string()? == "blacklist" {
                 /* skip blacklist entries */
+            } else if table["entry_type"].clone().into_string()? == "virtual" {
+                /* skip virtual device declarations */
             } else {
                 error!("Invalid 'entry_type' specified in the configuration file");
             }
@@ -951,6 +1218,82 @@ pub fn get_non_pnp_devices() -> Result<Vec<NonPnPDevice>> {
     }
 }
 
+/// Returns a Vec of virtual devices declared in eruption.conf
+pub fn get_virtual_devices() -> Result<Vec<VirtualDeviceConfig>> {
+    let mut result = vec![];
+
+    let config = crate::CONFIG.lock();
+
+    if let Some(config) = config.as_ref() {
+        let devices = config.get_array("devices").unwrap_or_else(|_e| vec![]);
+
+        for entry in devices.iter() {
+            let table = entry.clone().into_table()?;
+
+            if table["entry_type"].clone().into_string()? == "virtual" {
+                let class = table["device_class"].clone().into_string()?;
+                let name = table["device_name"].clone().into_string()?;
+
+                let usb_vid = match table.get("vendor_id") {
+                    Some(value) => value.clone().into_int()? as u16,
+                    None => 0,
+                };
+
+                let usb_pid = match table.get("product_id") {
+                    Some(value) => value.clone().into_int()? as u16,
+                    None => 0,
+                };
+
+                let event_script = match table.get("event_script") {
+                    Some(value) => Some(PathBuf::from(value.clone().into_string()?)),
+                    None => None,
+                };
+
+                let repeat_events = match table.get("repeat_events") {
+                    Some(value) => value.clone().into_bool()?,
+                    None => false,
+                };
+
+                let record_file = match table.get("record_file") {
+                    Some(value) => Some(PathBuf::from(value.clone().into_string()?)),
+                    None => None,
+                };
+
+                let device = VirtualDeviceConfig {
+                    class,
+                    name,
+                    usb_vid,
+                    usb_pid,
+                    event_script,
+                    repeat_events,
+                    record_file,
+                };
+
+                result.push(device);
+            } else if table["entry_type"].clone().into_string()? == "blacklist"
+                || table["entry_type"].clone().into_string()? == "device"
+            {
+                /* skip blacklist entries and non-pnp device declarations */
+            } else {
+                error!("Invalid 'entry_type' specified in the configuration file");
+            }
+        }
+
+        Ok(result)
+    } else {
+        // no config available, result will be empty
+        Ok(result)
+    }
+}
+
+/// Returns true if the device is a virtual (simulated) device. Virtual devices
+/// do not have an associated evdev device, so no input threads may be spawned for them
+pub fn is_virtual_device(device: &dyn DeviceTrait) -> bool {
+    device.as_any().is::<virtual_devices::VirtualKeyboard>()
+        || device.as_any().is::<virtual_devices::VirtualMouse>()
+        || device.as_any().is::<virtual_devices::VirtualMisc>()
+}
+
 /// Enumerates all HID devices on the system (and static device declarations
 /// from the .conf file as well), and then returns a tuple of all the supported devices
 pub fn probe_devices() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>, Vec<MiscDevice>)> {
@@ -980,6 +1323,32 @@ pub fn probe_devices() -> Result<(Vec<KeyboardDevice>, Vec<MouseDevice>, Vec<Mis
         }
     }
 
+    // bind all declared virtual devices from configuration file
+    let declared_virtual_devices = get_virtual_devices()?;
+
+    for device in declared_virtual_devices {
+        info!("Binding virtual {} device: {}", device.class, device.name);
+
+        match device.class.as_str() {
+            "keyboard" => match virtual_devices::bind_virtual_keyboard(&device) {
+                Ok(device) => keyboard_devices.push(device),
+                Err(e) => error!("Failed to bind the virtual device: {}", e),
+            },
+
+            "mouse" => match virtual_devices::bind_virtual_mouse(&device) {
+                Ok(device) => mouse_devices.push(device),
+                Err(e) => error!("Failed to bind the virtual device: {}", e),
+            },
+
+            "misc" => match virtual_devices::bind_virtual_misc(&device) {
+                Ok(device) => misc_devices.push(device),
+                Err(e) => error!("Failed to bind the virtual device: {}", e),
+            },
+
+            _ => error!("Unknown device class specified in the configuration file"),
+        }
+    }
+
     let mut bound_devices = vec![];
 
     let hidapi = crate::HIDAPI.read();
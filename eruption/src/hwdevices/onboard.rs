@@ -0,0 +1,331 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Onboard (hardware) profiles of mice
+//!
+//! Mice that declare `Capability::HardwareProfiles` store a set of profiles in their firmware,
+//! each with its own DPI stages, button assignments, poll rate and lighting. This module defines
+//! a device independent model of those profiles, and a portable file format that may be used to
+//! backup the profiles of a mouse and to restore them later on, possibly on another machine.
+//!
+//! Settings that a driver can not map to the portable model are carried along as the raw
+//! feature reports of the device; they are only ever written back to the same make and model.
+
+use serde::{Deserialize, Serialize};
+
+use super::{Capability, MouseDeviceTrait};
+
+pub type Result<T> = super::Result<T>;
+
+/// The version of the portable file format, bump this on incompatible changes
+pub const ONBOARD_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OnboardError {
+    #[error("The device does not support onboard profiles")]
+    NotSupported {},
+
+    #[error("Unsupported file format version: {version}")]
+    UnsupportedVersion { version: u32 },
+
+    #[error("The profiles belong to a different device: {usb_vid:04x}:{usb_pid:04x}")]
+    DeviceMismatch { usb_vid: u16, usb_pid: u16 },
+
+    #[error("Invalid onboard profile index: {index}")]
+    InvalidProfileIndex { index: u8 },
+
+    #[error("The device does not support the setting: {setting}")]
+    UnsupportedSetting { setting: String },
+
+    #[error("Invalid raw report: {id:#04x}")]
+    InvalidRawReport { id: u8 },
+}
+
+/// The action that is assigned to a button of the mouse
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ButtonAction {
+    /// The factory default function of the button
+    Default,
+
+    Disabled,
+
+    /// Emit a mouse button, 1 being the left button
+    MouseButton {
+        button: u8,
+    },
+
+    /// Emit a key, identified by its USB HID usage ID, plus a bitmask of the modifier keys
+    Key {
+        usage: u16,
+        modifiers: u8,
+    },
+
+    DpiCycle,
+    DpiUp,
+    DpiDown,
+
+    ProfileCycle,
+    ProfileUp,
+    ProfileDown,
+
+    /// The device specific code of a function that has no portable equivalent
+    Raw {
+        code: u8,
+    },
+}
+
+/// Assignment of an action to a button
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ButtonAssignment {
+    pub button: u8,
+    pub action: ButtonAction,
+}
+
+/// Lighting settings that are used by the firmware while the daemon is not running
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnboardLighting {
+    /// Brightness in percent
+    pub brightness: u8,
+
+    /// The static color of the LEDs, as RGB
+    pub color: Option<(u8, u8, u8)>,
+}
+
+/// A raw feature report, as read from the device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawReport {
+    pub id: u8,
+    pub data: Vec<u8>,
+}
+
+/// A single onboard profile; settings that are not supported by a device are left empty
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnboardProfile {
+    /// Zero based index of the profile slot
+    pub index: u8,
+
+    #[serde(default)]
+    pub dpi_stages: Vec<u16>,
+    pub active_dpi_stage: Option<u8>,
+
+    /// Poll rate in Hz
+    pub poll_rate: Option<u16>,
+
+    #[serde(default)]
+    pub buttons: Vec<ButtonAssignment>,
+    pub lighting: Option<OnboardLighting>,
+
+    /// Device specific settings without a portable equivalent
+    #[serde(default)]
+    pub raw: Vec<RawReport>,
+}
+
+/// A backup of all onboard profiles of a mouse, this is the portable file format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnboardProfiles {
+    pub format_version: u32,
+
+    pub usb_vid: u16,
+    pub usb_pid: u16,
+    pub firmware: String,
+
+    /// Zero based index of the profile that is active
+    pub active_profile: u8,
+
+    pub profiles: Vec<OnboardProfile>,
+}
+
+impl OnboardProfiles {
+    /// Check that the profiles can be restored to the device `usb_vid:usb_pid`
+    pub fn validate(&self, usb_vid: u16, usb_pid: u16, profile_count: u8) -> Result<()> {
+        if self.format_version != ONBOARD_FORMAT_VERSION {
+            return Err(OnboardError::UnsupportedVersion {
+                version: self.format_version,
+            }
+            .into());
+        }
+
+        if self.usb_vid != usb_vid || self.usb_pid != usb_pid {
+            return Err(OnboardError::DeviceMismatch {
+                usb_vid: self.usb_vid,
+                usb_pid: self.usb_pid,
+            }
+            .into());
+        }
+
+        if self.active_profile >= profile_count {
+            return Err(OnboardError::InvalidProfileIndex {
+                index: self.active_profile,
+            }
+            .into());
+        }
+
+        if let Some(profile) = self.profiles.iter().find(|p| p.index >= profile_count) {
+            return Err(OnboardError::InvalidProfileIndex {
+                index: profile.index,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Read all onboard profiles of the device, and re-select the profile that was active before
+pub fn backup(device: &mut dyn MouseDeviceTrait) -> Result<OnboardProfiles> {
+    if !device
+        .get_device_capabilities()
+        .contains(&Capability::HardwareProfiles)
+    {
+        return Err(OnboardError::NotSupported {}.into());
+    }
+
+    // reading a profile may select it on the device
+    let active_profile = device.get_profile()?;
+
+    let profiles = (0..device.get_onboard_profile_count())
+        .map(|index| device.read_onboard_profile(index))
+        .collect::<Result<Vec<_>>>();
+
+    device.set_profile(active_profile)?;
+
+    Ok(OnboardProfiles {
+        format_version: ONBOARD_FORMAT_VERSION,
+        usb_vid: device.get_usb_vid(),
+        usb_pid: device.get_usb_pid(),
+        firmware: device.get_firmware_revision(),
+        active_profile: active_profile as u8,
+        profiles: profiles?,
+    })
+}
+
+/// Write the onboard profiles to the device, and activate the profile that was active at backup time
+pub fn restore(device: &mut dyn MouseDeviceTrait, profiles: &OnboardProfiles) -> Result<()> {
+    if !device
+        .get_device_capabilities()
+        .contains(&Capability::HardwareProfiles)
+    {
+        return Err(OnboardError::NotSupported {}.into());
+    }
+
+    profiles.validate(
+        device.get_usb_vid(),
+        device.get_usb_pid(),
+        device.get_onboard_profile_count(),
+    )?;
+
+    // validate all profiles first, so that a failed restore leaves the device untouched
+    for profile in profiles.profiles.iter() {
+        device.validate_onboard_profile(profile)?;
+    }
+
+    for profile in profiles.profiles.iter() {
+        device.write_onboard_profile(profile)?;
+    }
+
+    device.set_profile(profiles.active_profile as i32)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_profiles() -> OnboardProfiles {
+        OnboardProfiles {
+            format_version: ONBOARD_FORMAT_VERSION,
+            usb_vid: 0x1e7d,
+            usb_pid: 0x2dd2,
+            firmware: "1.04".to_string(),
+            active_profile: 1,
+            profiles: vec![
+                OnboardProfile {
+                    index: 0,
+                    dpi_stages: vec![400, 800, 1600],
+                    active_dpi_stage: Some(1),
+                    poll_rate: Some(1000),
+                    buttons: vec![ButtonAssignment {
+                        button: 6,
+                        action: ButtonAction::Key {
+                            usage: 0x04,
+                            modifiers: 0x01,
+                        },
+                    }],
+                    lighting: Some(OnboardLighting {
+                        brightness: 80,
+                        color: Some((255, 0, 0)),
+                    }),
+                    raw: vec![RawReport {
+                        id: 0x06,
+                        data: vec![0x06, 0x1f, 0x00],
+                    }],
+                },
+                OnboardProfile {
+                    index: 1,
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn portable_format_round_trip() {
+        let profiles = sample_profiles();
+
+        let json = serde_json::to_string_pretty(&profiles).unwrap();
+        let result: OnboardProfiles = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(result, profiles);
+    }
+
+    #[test]
+    fn missing_settings_are_optional() {
+        let json = r#"{
+            "format_version": 1, "usb_vid": 7805, "usb_pid": 11730, "firmware": "1.04",
+            "active_profile": 0, "profiles": [ { "index": 0 } ]
+        }"#;
+
+        let result: OnboardProfiles = serde_json::from_str(json).unwrap();
+
+        assert!(result.profiles[0].dpi_stages.is_empty());
+        assert_eq!(result.profiles[0].poll_rate, None);
+    }
+
+    #[test]
+    fn validation() {
+        let profiles = sample_profiles();
+
+        assert!(profiles.validate(0x1e7d, 0x2dd2, 5).is_ok());
+
+        // different device
+        assert!(profiles.validate(0x1e7d, 0x2dd3, 5).is_err());
+
+        // too few profile slots
+        assert!(profiles.validate(0x1e7d, 0x2dd2, 1).is_err());
+
+        let mut profiles = sample_profiles();
+        profiles.format_version = ONBOARD_FORMAT_VERSION + 1;
+
+        assert!(profiles.validate(0x1e7d, 0x2dd2, 5).is_err());
+    }
+}
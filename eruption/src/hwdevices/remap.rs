@@ -0,0 +1,279 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Remapping of keys in the firmware of keyboards
+//!
+//! Keyboards that declare `Capability::HardwareRemapping` hold a table in their firmware, that
+//! assigns a USB HID usage ID to each physical key. One-to-one remappings are realized by
+//! rewriting that table, so they do not add any input latency and stay in effect while the
+//! daemon is not running. Remappings are specified with Linux EVDEV key codes, and are persisted
+//! per device in the runtime state, since the table is reset by the firmware on power loss.
+
+use evdev_rs::enums::EV_KEY;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = super::Result<T>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RemapError {
+    #[error("The key can not be remapped in firmware: {code}")]
+    UnmappableKey { code: u32 },
+
+    #[error("The device has no key that emits: {code}")]
+    KeyNotFound { code: u32 },
+
+    #[error("Invalid key assignment report")]
+    InvalidReport {},
+}
+
+/// A one-to-one remapping of a key, as EVDEV key codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeyRemap {
+    /// The key that is remapped
+    pub from: u32,
+
+    /// The key that will be emitted instead
+    pub to: u32,
+}
+
+/// USB HID usage ID of the "Non-US # and ~" key, it is reported as `KEY_BACKSLASH` by Linux
+const HID_USAGE_NON_US_HASH: u8 = 0x32;
+
+/// Map EVDEV key codes to USB HID usage IDs of the keyboard/keypad page
+#[rustfmt::skip]
+const EV_KEY_TO_HID_USAGE: &[(EV_KEY, u8)] = &[
+    (EV_KEY::KEY_A, 0x04), (EV_KEY::KEY_B, 0x05), (EV_KEY::KEY_C, 0x06), (EV_KEY::KEY_D, 0x07),
+    (EV_KEY::KEY_E, 0x08), (EV_KEY::KEY_F, 0x09), (EV_KEY::KEY_G, 0x0a), (EV_KEY::KEY_H, 0x0b),
+    (EV_KEY::KEY_I, 0x0c), (EV_KEY::KEY_J, 0x0d), (EV_KEY::KEY_K, 0x0e), (EV_KEY::KEY_L, 0x0f),
+    (EV_KEY::KEY_M, 0x10), (EV_KEY::KEY_N, 0x11), (EV_KEY::KEY_O, 0x12), (EV_KEY::KEY_P, 0x13),
+    (EV_KEY::KEY_Q, 0x14), (EV_KEY::KEY_R, 0x15), (EV_KEY::KEY_S, 0x16), (EV_KEY::KEY_T, 0x17),
+    (EV_KEY::KEY_U, 0x18), (EV_KEY::KEY_V, 0x19), (EV_KEY::KEY_W, 0x1a), (EV_KEY::KEY_X, 0x1b),
+    (EV_KEY::KEY_Y, 0x1c), (EV_KEY::KEY_Z, 0x1d),
+
+    (EV_KEY::KEY_1, 0x1e), (EV_KEY::KEY_2, 0x1f), (EV_KEY::KEY_3, 0x20), (EV_KEY::KEY_4, 0x21),
+    (EV_KEY::KEY_5, 0x22), (EV_KEY::KEY_6, 0x23), (EV_KEY::KEY_7, 0x24), (EV_KEY::KEY_8, 0x25),
+    (EV_KEY::KEY_9, 0x26), (EV_KEY::KEY_0, 0x27),
+
+    (EV_KEY::KEY_ENTER, 0x28), (EV_KEY::KEY_ESC, 0x29), (EV_KEY::KEY_BACKSPACE, 0x2a),
+    (EV_KEY::KEY_TAB, 0x2b), (EV_KEY::KEY_SPACE, 0x2c), (EV_KEY::KEY_MINUS, 0x2d),
+    (EV_KEY::KEY_EQUAL, 0x2e), (EV_KEY::KEY_LEFTBRACE, 0x2f), (EV_KEY::KEY_RIGHTBRACE, 0x30),
+    (EV_KEY::KEY_BACKSLASH, 0x31), (EV_KEY::KEY_SEMICOLON, 0x33), (EV_KEY::KEY_APOSTROPHE, 0x34),
+    (EV_KEY::KEY_GRAVE, 0x35), (EV_KEY::KEY_COMMA, 0x36), (EV_KEY::KEY_DOT, 0x37),
+    (EV_KEY::KEY_SLASH, 0x38), (EV_KEY::KEY_CAPSLOCK, 0x39),
+
+    (EV_KEY::KEY_F1, 0x3a), (EV_KEY::KEY_F2, 0x3b), (EV_KEY::KEY_F3, 0x3c), (EV_KEY::KEY_F4, 0x3d),
+    (EV_KEY::KEY_F5, 0x3e), (EV_KEY::KEY_F6, 0x3f), (EV_KEY::KEY_F7, 0x40), (EV_KEY::KEY_F8, 0x41),
+    (EV_KEY::KEY_F9, 0x42), (EV_KEY::KEY_F10, 0x43), (EV_KEY::KEY_F11, 0x44), (EV_KEY::KEY_F12, 0x45),
+
+    (EV_KEY::KEY_SYSRQ, 0x46), (EV_KEY::KEY_SCROLLLOCK, 0x47), (EV_KEY::KEY_PAUSE, 0x48),
+    (EV_KEY::KEY_INSERT, 0x49), (EV_KEY::KEY_HOME, 0x4a), (EV_KEY::KEY_PAGEUP, 0x4b),
+    (EV_KEY::KEY_DELETE, 0x4c), (EV_KEY::KEY_END, 0x4d), (EV_KEY::KEY_PAGEDOWN, 0x4e),
+    (EV_KEY::KEY_RIGHT, 0x4f), (EV_KEY::KEY_LEFT, 0x50), (EV_KEY::KEY_DOWN, 0x51),
+    (EV_KEY::KEY_UP, 0x52),
+
+    (EV_KEY::KEY_NUMLOCK, 0x53), (EV_KEY::KEY_KPSLASH, 0x54), (EV_KEY::KEY_KPASTERISK, 0x55),
+    (EV_KEY::KEY_KPMINUS, 0x56), (EV_KEY::KEY_KPPLUS, 0x57), (EV_KEY::KEY_KPENTER, 0x58),
+    (EV_KEY::KEY_KP1, 0x59), (EV_KEY::KEY_KP2, 0x5a), (EV_KEY::KEY_KP3, 0x5b),
+    (EV_KEY::KEY_KP4, 0x5c), (EV_KEY::KEY_KP5, 0x5d), (EV_KEY::KEY_KP6, 0x5e),
+    (EV_KEY::KEY_KP7, 0x5f), (EV_KEY::KEY_KP8, 0x60), (EV_KEY::KEY_KP9, 0x61),
+    (EV_KEY::KEY_KP0, 0x62), (EV_KEY::KEY_KPDOT, 0x63),
+
+    (EV_KEY::KEY_102ND, 0x64), (EV_KEY::KEY_COMPOSE, 0x65),
+
+    (EV_KEY::KEY_LEFTCTRL, 0xe0), (EV_KEY::KEY_LEFTSHIFT, 0xe1), (EV_KEY::KEY_LEFTALT, 0xe2),
+    (EV_KEY::KEY_LEFTMETA, 0xe3), (EV_KEY::KEY_RIGHTCTRL, 0xe4), (EV_KEY::KEY_RIGHTSHIFT, 0xe5),
+    (EV_KEY::KEY_RIGHTALT, 0xe6), (EV_KEY::KEY_RIGHTMETA, 0xe7),
+];
+
+/// Map an EVDEV key code to a USB HID usage ID, returns `None` if the key has no usage on the
+/// keyboard/keypad page, e.g. multimedia keys
+pub fn ev_key_to_hid_usage(code: u32) -> Option<u8> {
+    EV_KEY_TO_HID_USAGE
+        .iter()
+        .find(|(ev_key, _)| *ev_key as u32 == code)
+        .map(|(_, usage)| *usage)
+}
+
+/// Rewrite a key assignment feature report, as used by the ROCCAT Vulcan and Magma series
+///
+/// The report consists of a three byte header, followed by one USB HID usage ID per physical
+/// key, and is terminated by a 16 bit little endian checksum of all preceding bytes. Remappings
+/// are applied to the assignments in `report`, so e.g. swapping two keys works as expected.
+pub fn patch_key_assignments(report: &mut [u8], remaps: &[KeyRemap]) -> Result<()> {
+    if report.len() < 5 || report[1] as usize != report.len() {
+        return Err(RemapError::InvalidReport {}.into());
+    }
+
+    let end = report.len() - 2;
+
+    let defaults = &report[3..end];
+    let mut assignments = defaults.to_vec();
+
+    for remap in remaps.iter() {
+        let from = ev_key_to_hid_usage(remap.from)
+            .ok_or(RemapError::UnmappableKey { code: remap.from })?;
+        let to =
+            ev_key_to_hid_usage(remap.to).ok_or(RemapError::UnmappableKey { code: remap.to })?;
+
+        let mut found = false;
+
+        // ISO keyboards emit "Non-US #" instead, for the key that is mapped to KEY_BACKSLASH
+        for (slot, usage) in assignments.iter_mut().zip(defaults.iter()) {
+            if *usage == from || (from == 0x31 && *usage == HID_USAGE_NON_US_HASH) {
+                *slot = to;
+                found = true;
+            }
+        }
+
+        if !found {
+            return Err(RemapError::KeyNotFound { code: remap.from }.into());
+        }
+    }
+
+    report[3..end].copy_from_slice(&assignments);
+
+    let checksum = report[..end]
+        .iter()
+        .fold(0_u16, |acc, b| acc.wrapping_add(*b as u16));
+
+    report[end..].copy_from_slice(&checksum.to_le_bytes());
+
+    Ok(())
+}
+
+lazy_static! {
+    /// Firmware key remappings of the devices, devices without remappings are not listed here
+    static ref KEY_REMAPS: Arc<RwLock<HashMap<DeviceMakeModelSerial, Vec<KeyRemap>>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// Initialize the key remappings from the saved state
+pub fn init_key_remaps(remaps: HashMap<DeviceMakeModelSerial, Vec<KeyRemap>>) {
+    *KEY_REMAPS.write() = remaps;
+}
+
+/// Returns the key remappings of `device`, in `make:model:serial` notation
+pub fn get_key_remap(device: &str) -> Vec<KeyRemap> {
+    KEY_REMAPS.read().get(device).cloned().unwrap_or_default()
+}
+
+/// Returns the key remappings of all devices, including devices that are currently not connected
+pub fn get_key_remaps() -> HashMap<DeviceMakeModelSerial, Vec<KeyRemap>> {
+    KEY_REMAPS.read().clone()
+}
+
+/// Set the key remappings of `device`, an empty list restores the factory assignments
+pub fn set_key_remap(device: &str, remaps: &[KeyRemap]) {
+    let mut key_remaps = KEY_REMAPS.write();
+
+    if remaps.is_empty() {
+        key_remaps.remove(device);
+    } else {
+        key_remaps.insert(device.to_string(), remaps.to_vec());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use evdev_rs::enums::EV_KEY;
+
+    use super::*;
+
+    /// A truncated key assignment report: Esc, Caps Lock, A, Left Ctrl
+    fn sample_report() -> Vec<u8> {
+        let mut report = vec![0x06, 0x09, 0x00, 0x29, 0x39, 0x04, 0xe0, 0x00, 0x00];
+        patch_key_assignments(&mut report, &[]).unwrap();
+
+        report
+    }
+
+    fn remap(from: EV_KEY, to: EV_KEY) -> KeyRemap {
+        KeyRemap {
+            from: from as u32,
+            to: to as u32,
+        }
+    }
+
+    #[test]
+    fn checksum_is_updated() {
+        let report = sample_report();
+
+        let sum: u16 = report[..7].iter().map(|b| *b as u16).sum();
+        assert_eq!(&report[7..], &sum.to_le_bytes());
+    }
+
+    #[test]
+    fn keys_are_remapped() {
+        let mut report = sample_report();
+
+        patch_key_assignments(
+            &mut report,
+            &[remap(EV_KEY::KEY_CAPSLOCK, EV_KEY::KEY_LEFTCTRL)],
+        )
+        .unwrap();
+
+        assert_eq!(&report[3..7], &[0x29, 0xe0, 0x04, 0xe0]);
+
+        let sum: u16 = report[..7].iter().map(|b| *b as u16).sum();
+        assert_eq!(&report[7..], &sum.to_le_bytes());
+    }
+
+    #[test]
+    fn keys_may_be_swapped() {
+        let mut report = sample_report();
+
+        patch_key_assignments(
+            &mut report,
+            &[
+                remap(EV_KEY::KEY_CAPSLOCK, EV_KEY::KEY_ESC),
+                remap(EV_KEY::KEY_ESC, EV_KEY::KEY_CAPSLOCK),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(&report[3..7], &[0x39, 0x29, 0x04, 0xe0]);
+    }
+
+    #[test]
+    fn invalid_remaps_are_rejected() {
+        let mut report = sample_report();
+
+        // multimedia keys have no usage on the keyboard page
+        assert!(
+            patch_key_assignments(&mut report, &[remap(EV_KEY::KEY_A, EV_KEY::KEY_VOLUMEUP)])
+                .is_err()
+        );
+
+        // there is no such key on the device
+        assert!(
+            patch_key_assignments(&mut report, &[remap(EV_KEY::KEY_B, EV_KEY::KEY_A)]).is_err()
+        );
+
+        // the report has been left unchanged
+        assert_eq!(report, sample_report());
+    }
+}
@@ -30,6 +30,7 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants;
 
+use super::schema::{ConfigKey, DeviceSchema};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
@@ -302,6 +303,10 @@ impl DeviceInfoTrait for RoccatAimoPad {
         ])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new().config(ConfigKey::brightness())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -30,6 +30,7 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MiscDevice, MiscDeviceTrait, MouseDeviceTrait, RGBA,
@@ -428,6 +429,14 @@ impl DeviceInfoTrait for RoccatElo71Air {
         ])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::battery_level())
+            .status(StatusKey::battery_level_raw())
+            .status(StatusKey::transceiver_enabled())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -30,6 +30,7 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants;
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -317,6 +318,12 @@ impl DeviceInfoTrait for RoccatKain100 {
         DeviceCapabilities::from([Capability::Mouse, Capability::RgbLighting])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
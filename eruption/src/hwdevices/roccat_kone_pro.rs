@@ -32,6 +32,7 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants;
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -269,6 +270,13 @@ impl DeviceInfoTrait for RoccatKonePro {
         DeviceCapabilities::from([Capability::Mouse, Capability::RgbLighting])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .config(ConfigKey::profile(5))
+            .status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -32,6 +32,7 @@ use std::{any::Any, thread};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -486,6 +487,16 @@ impl DeviceInfoTrait for RoccatKoneProAir {
         DeviceCapabilities::from([Capability::Mouse, Capability::RgbLighting])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::battery_level())
+            .status(StatusKey::battery_level_raw())
+            .status(StatusKey::signal_strength())
+            .status(StatusKey::signal_strength_raw())
+            .status(StatusKey::transceiver_enabled())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -31,6 +31,10 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants;
 
+use super::onboard::{
+    ButtonAction, ButtonAssignment, OnboardError, OnboardLighting, OnboardProfile, RawReport,
+};
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -42,6 +46,49 @@ pub const SUB_DEVICE: i32 = 2; // USB HID sub-device to bind to
 
 // pub const NUM_BUTTONS: usize = 9;
 
+pub const NUM_ONBOARD_PROFILES: u8 = 5;
+
+/// Feature report that holds the settings of an onboard profile
+const SETTINGS_REPORT_ID: u8 = 0x06;
+const SETTINGS_REPORT_SIZE: usize = 64;
+
+/// Feature report that holds the button assignments of an onboard profile
+const BUTTONS_REPORT_ID: u8 = 0x07;
+const BUTTONS_REPORT_SIZE: usize = 77;
+
+// Onboard profile reports start with their id, their length and the index of the profile,
+// and end with a 16 bit checksum of all preceding bytes. Offsets into the settings report:
+const DPI_STAGES_MASK: usize = 5;
+const ACTIVE_DPI_STAGE: usize = 6;
+const DPI_STAGES_X: usize = 7;
+const DPI_STAGES_Y: usize = 17;
+const LIGHT_BRIGHTNESS: usize = 32;
+const LIGHT_COLOR: usize = 38;
+
+const NUM_DPI_STAGES: usize = 5;
+
+/// The resolution of the sensor is set in steps of 50 DPI
+const DPI_STEP: u16 = 50;
+const MAX_DPI: u16 = 16000;
+
+// The buttons report holds 3 bytes per button: the function, the modifier keys and a key
+const BUTTONS_OFFSET: usize = 3;
+const NUM_BUTTON_SLOTS: usize = 24;
+
+const BUTTON_LEFT: u8 = 0x01;
+const BUTTON_RIGHT: u8 = 0x02;
+const BUTTON_MIDDLE: u8 = 0x03;
+const BUTTON_SHORTCUT: u8 = 0x05;
+const BUTTON_DISABLED: u8 = 0x06;
+const BUTTON_FORWARD: u8 = 0x07;
+const BUTTON_BACKWARD: u8 = 0x08;
+const BUTTON_PROFILE_CYCLE: u8 = 0x10;
+const BUTTON_PROFILE_UP: u8 = 0x11;
+const BUTTON_PROFILE_DOWN: u8 = 0x12;
+const BUTTON_DPI_CYCLE: u8 = 0x14;
+const BUTTON_DPI_UP: u8 = 0x15;
+const BUTTON_DPI_DOWN: u8 = 0x16;
+
 // canvas to LED index mapping
 pub const LED_0: usize = constants::CANVAS_SIZE - 36;
 
@@ -231,6 +278,90 @@ impl RoccatKonePureUltra {
         }
     }
 
+    /// Select the onboard profile `index`, subsequent settings reports will address that profile
+    fn select_onboard_profile(&self, index: u8) -> Result<()> {
+        trace!("Selecting onboard profile {}", index);
+
+        if !self.is_bound {
+            Err(HwDeviceError::DeviceNotBound {}.into())
+        } else if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+            let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+            let buf: [u8; 4] = [0x04, index, 0x00, 0x00];
+
+            match ctrl_dev.send_feature_report(&buf) {
+                Ok(_result) => {
+                    hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                    Ok(())
+                }
+
+                Err(_) => Err(HwDeviceError::InvalidResult {}),
+            }?;
+
+            loop {
+                let mut buf: [u8; 4] = [0; 4];
+                buf[0] = 0x04;
+
+                match ctrl_dev.get_feature_report(&mut buf) {
+                    Ok(_result) => {
+                        hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                        if buf[1] == 0x01 {
+                            return Ok(());
+                        }
+                    }
+
+                    Err(_) => return Err(HwDeviceError::InvalidResult {}.into()),
+                }
+            }
+        }
+    }
+
+    /// Read the onboard profile report `id` of the selected profile
+    fn get_onboard_report(&self, id: u8, size: usize) -> Result<Vec<u8>> {
+        let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+        let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+        let mut buf = vec![0x00_u8; size];
+        buf[0] = id;
+
+        match ctrl_dev.get_feature_report(&mut buf) {
+            Ok(_result) => {
+                hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
+
+                Ok(())
+            }
+
+            Err(_) => Err(HwDeviceError::InvalidResult {}),
+        }?;
+
+        Ok(buf)
+    }
+
+    /// Send the onboard profile report `buf` to the device, and wait until it has been processed
+    fn send_onboard_report(&mut self, buf: &[u8]) -> Result<()> {
+        {
+            let ctrl_dev = self.ctrl_hiddev.as_ref().lock();
+            let ctrl_dev = ctrl_dev.as_ref().unwrap();
+
+            match ctrl_dev.send_feature_report(buf) {
+                Ok(_result) => {
+                    hexdump::hexdump_iter(buf).for_each(|s| trace!("  {}", s));
+
+                    Ok(())
+                }
+
+                Err(_) => Err(HwDeviceError::InvalidResult {}),
+            }?;
+        }
+
+        self.wait_for_ctrl_dev()
+    }
+
     fn wait_for_ctrl_dev(&mut self) -> Result<()> {
         trace!("Waiting for control device to respond...");
 
@@ -264,7 +395,22 @@ impl RoccatKonePureUltra {
 
 impl DeviceInfoTrait for RoccatKonePureUltra {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities::from([Capability::Mouse, Capability::RgbLighting])
+        DeviceCapabilities::from([
+            Capability::Mouse,
+            Capability::RgbLighting,
+            Capability::HardwareProfiles,
+        ])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .config(ConfigKey::profile(5))
+            .config(ConfigKey::dpi_preset(5))
+            .config(ConfigKey::rate(&[125, 250, 500, 1000]))
+            .config(ConfigKey::angle_snapping())
+            .config(ConfigKey::debounce())
+            .status(StatusKey::connected())
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -715,7 +861,17 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
                 Err(_) => Err(HwDeviceError::InvalidResult {}),
             }?;
 
-            buf[29] = rate as u8;
+            buf[29] = match rate {
+                125 => 0,
+
+                250 => 1,
+
+                500 => 2,
+
+                1000 => 3,
+
+                _ => return Err(HwDeviceError::InvalidResult {}.into()),
+            };
 
             match ctrl_dev.send_feature_report(&buf) {
                 Ok(_result) => {
@@ -889,6 +1045,176 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
         }
     }
 
+    fn get_onboard_profile_count(&self) -> u8 {
+        NUM_ONBOARD_PROFILES
+    }
+
+    fn read_onboard_profile(&self, index: u8) -> Result<OnboardProfile> {
+        trace!("Reading onboard profile {}", index);
+
+        if index >= NUM_ONBOARD_PROFILES {
+            return Err(HwDeviceError::InvalidResult {}.into());
+        }
+
+        // the poll rate is a device wide setting, it is shared by all profiles
+        let poll_rate = self.get_rate()? as u16;
+
+        self.select_onboard_profile(index)?;
+
+        let settings = self.get_onboard_report(SETTINGS_REPORT_ID, SETTINGS_REPORT_SIZE)?;
+        let buttons = self.get_onboard_report(BUTTONS_REPORT_ID, BUTTONS_REPORT_SIZE)?;
+
+        let (dpi_stages, active_dpi_stage) = decode_dpi_stages(&settings);
+
+        // the raw reports carry along all settings that have not been decoded
+        Ok(OnboardProfile {
+            index,
+            dpi_stages,
+            active_dpi_stage,
+            poll_rate: Some(poll_rate),
+            buttons: decode_buttons(&buttons),
+            lighting: Some(decode_lighting(&settings)),
+            raw: vec![
+                RawReport {
+                    id: SETTINGS_REPORT_ID,
+                    data: settings,
+                },
+                RawReport {
+                    id: BUTTONS_REPORT_ID,
+                    data: buttons,
+                },
+            ],
+        })
+    }
+
+    fn validate_onboard_profile(&self, profile: &OnboardProfile) -> Result<()> {
+        if profile.index >= NUM_ONBOARD_PROFILES {
+            return Err(OnboardError::InvalidProfileIndex {
+                index: profile.index,
+            }
+            .into());
+        }
+
+        let unsupported = |setting: String| OnboardError::UnsupportedSetting { setting };
+
+        if profile.dpi_stages.len() > NUM_DPI_STAGES {
+            return Err(unsupported(format!("dpi_stages: {:?}", profile.dpi_stages)).into());
+        }
+
+        if let Some(dpi) = profile
+            .dpi_stages
+            .iter()
+            .find(|dpi| !(DPI_STEP..=MAX_DPI).contains(dpi) || *dpi % DPI_STEP != 0)
+        {
+            return Err(unsupported(format!("dpi_stages: {}", dpi)).into());
+        }
+
+        if let Some(dpi_stage) = profile.active_dpi_stage {
+            let num_dpi_stages = if profile.dpi_stages.is_empty() {
+                NUM_DPI_STAGES
+            } else {
+                profile.dpi_stages.len()
+            };
+
+            if dpi_stage as usize >= num_dpi_stages {
+                return Err(unsupported(format!("active_dpi_stage: {}", dpi_stage)).into());
+            }
+        }
+
+        for assignment in profile.buttons.iter() {
+            if assignment.button == 0 || assignment.button as usize > NUM_BUTTON_SLOTS {
+                return Err(unsupported(format!("buttons: {}", assignment.button)).into());
+            }
+
+            encode_button(&assignment.action)?;
+        }
+
+        if let Some(lighting) = &profile.lighting {
+            if lighting.brightness > 100 {
+                return Err(
+                    unsupported(format!("lighting.brightness: {}", lighting.brightness)).into(),
+                );
+            }
+        }
+
+        if let Some(poll_rate) = profile.poll_rate {
+            if ![125, 250, 500, 1000].contains(&poll_rate) {
+                return Err(unsupported(format!("poll_rate: {}", poll_rate)).into());
+            }
+        }
+
+        for report in profile.raw.iter() {
+            let size = match report.id {
+                SETTINGS_REPORT_ID => SETTINGS_REPORT_SIZE,
+                BUTTONS_REPORT_ID => BUTTONS_REPORT_SIZE,
+
+                _ => return Err(OnboardError::InvalidRawReport { id: report.id }.into()),
+            };
+
+            if report.data.len() != size || report.data[0] != report.id {
+                return Err(OnboardError::InvalidRawReport { id: report.id }.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_onboard_profile(&mut self, profile: &OnboardProfile) -> Result<()> {
+        trace!("Writing onboard profile {}", profile.index);
+
+        self.validate_onboard_profile(profile)?;
+
+        // start from the current settings, if the profile does not carry the raw reports
+        let raw_report = |id: u8| {
+            profile
+                .raw
+                .iter()
+                .find(|r| r.id == id)
+                .map(|r| r.data.clone())
+        };
+
+        let (mut settings, mut buttons) = match (
+            raw_report(SETTINGS_REPORT_ID),
+            raw_report(BUTTONS_REPORT_ID),
+        ) {
+            (Some(settings), Some(buttons)) => (settings, buttons),
+
+            (settings, buttons) => {
+                let current = self.read_onboard_profile(profile.index)?;
+
+                (
+                    settings.unwrap_or_else(|| current.raw[0].data.clone()),
+                    buttons.unwrap_or_else(|| current.raw[1].data.clone()),
+                )
+            }
+        };
+
+        settings[2] = profile.index;
+        buttons[2] = profile.index;
+
+        encode_dpi_stages(&mut settings, &profile.dpi_stages, profile.active_dpi_stage);
+
+        if let Some(lighting) = &profile.lighting {
+            encode_lighting(&mut settings, lighting);
+        }
+
+        encode_buttons(&mut buttons, &profile.buttons)?;
+
+        update_checksum(&mut settings);
+        update_checksum(&mut buttons);
+
+        self.select_onboard_profile(profile.index)?;
+
+        self.send_onboard_report(&settings)?;
+        self.send_onboard_report(&buttons)?;
+
+        if let Some(poll_rate) = profile.poll_rate {
+            self.set_rate(poll_rate as i32)?;
+        }
+
+        Ok(())
+    }
+
     fn get_local_brightness(&self) -> Result<i32> {
         trace!("Querying device specific brightness");
 
@@ -1191,3 +1517,397 @@ impl MouseDeviceTrait for RoccatKonePureUltra {
         false
     }
 }
+
+/// Update the checksum in the last two bytes of the onboard profile report `buf`
+fn update_checksum(buf: &mut [u8]) {
+    // the length of the report, as declared by the report itself
+    let length = (buf[1] as usize).min(buf.len());
+
+    let checksum = buf[..length - 2]
+        .iter()
+        .fold(0_u16, |sum, b| sum.wrapping_add(*b as u16));
+
+    buf[length - 2..length].copy_from_slice(&checksum.to_le_bytes());
+}
+
+/// Returns the enabled DPI stages, and the index of the active stage among them
+fn decode_dpi_stages(settings: &[u8]) -> (Vec<u16>, Option<u8>) {
+    let mut dpi_stages = Vec::new();
+    let mut active_dpi_stage = None;
+
+    for stage in 0..NUM_DPI_STAGES {
+        if settings[DPI_STAGES_MASK] & (1 << stage) != 0 {
+            if settings[ACTIVE_DPI_STAGE] as usize == stage {
+                active_dpi_stage = Some(dpi_stages.len() as u8);
+            }
+
+            let offset = DPI_STAGES_X + stage * 2;
+            let value = u16::from_le_bytes([settings[offset], settings[offset + 1]]);
+
+            dpi_stages.push(value.saturating_mul(DPI_STEP));
+        }
+    }
+
+    (dpi_stages, active_dpi_stage)
+}
+
+fn encode_dpi_stages(settings: &mut [u8], dpi_stages: &[u16], active_dpi_stage: Option<u8>) {
+    if !dpi_stages.is_empty() {
+        for (stage, dpi) in dpi_stages.iter().enumerate() {
+            let value = (dpi / DPI_STEP).to_le_bytes();
+
+            let x = DPI_STAGES_X + stage * 2;
+            let y = DPI_STAGES_Y + stage * 2;
+
+            // the Y resolution may differ from the X resolution, it is only
+            // overwritten along with the X resolution of a stage that changed
+            if settings[x..x + 2] != value {
+                settings[x..x + 2].copy_from_slice(&value);
+                settings[y..y + 2].copy_from_slice(&value);
+            }
+        }
+
+        settings[DPI_STAGES_MASK] = (1 << dpi_stages.len()) - 1;
+    }
+
+    // the active stage is an index into the enabled stages
+    if let Some(dpi_stage) = active_dpi_stage {
+        if let Some(stage) = (0..NUM_DPI_STAGES)
+            .filter(|stage| settings[DPI_STAGES_MASK] & (1 << stage) != 0)
+            .nth(dpi_stage as usize)
+        {
+            settings[ACTIVE_DPI_STAGE] = stage as u8;
+        }
+    }
+}
+
+fn decode_lighting(settings: &[u8]) -> OnboardLighting {
+    OnboardLighting {
+        brightness: ((settings[LIGHT_BRIGHTNESS] as u32 * 100 + 127) / 255) as u8,
+        color: Some((
+            settings[LIGHT_COLOR],
+            settings[LIGHT_COLOR + 1],
+            settings[LIGHT_COLOR + 2],
+        )),
+    }
+}
+
+fn encode_lighting(settings: &mut [u8], lighting: &OnboardLighting) {
+    // keep the exact value, if it already maps to the requested brightness
+    if decode_lighting(settings).brightness != lighting.brightness {
+        settings[LIGHT_BRIGHTNESS] = ((lighting.brightness as u32 * 255 + 50) / 100) as u8;
+    }
+
+    if let Some((r, g, b)) = lighting.color {
+        settings[LIGHT_COLOR..LIGHT_COLOR + 3].copy_from_slice(&[r, g, b]);
+    }
+}
+
+/// The device uses a bitmask of Shift, Ctrl, Alt and Win, the portable model
+/// uses the USB HID modifier bits of the left and right hand keys
+fn decode_modifiers(modifiers: u8) -> u8 {
+    (modifiers & 0x01) << 1 | (modifiers & 0x02) >> 1 | (modifiers & 0x0c)
+}
+
+fn encode_modifiers(modifiers: u8) -> u8 {
+    let modifiers = (modifiers | modifiers >> 4) & 0x0f;
+
+    (modifiers & 0x01) << 1 | (modifiers & 0x02) >> 1 | (modifiers & 0x0c)
+}
+
+fn decode_buttons(buttons: &[u8]) -> Vec<ButtonAssignment> {
+    (0..NUM_BUTTON_SLOTS)
+        .map(|slot| {
+            let offset = BUTTONS_OFFSET + slot * 3;
+
+            let action = match buttons[offset] {
+                BUTTON_LEFT => ButtonAction::MouseButton { button: 1 },
+                BUTTON_RIGHT => ButtonAction::MouseButton { button: 2 },
+                BUTTON_MIDDLE => ButtonAction::MouseButton { button: 3 },
+                BUTTON_BACKWARD => ButtonAction::MouseButton { button: 4 },
+                BUTTON_FORWARD => ButtonAction::MouseButton { button: 5 },
+
+                BUTTON_SHORTCUT => ButtonAction::Key {
+                    usage: buttons[offset + 2] as u16,
+                    modifiers: decode_modifiers(buttons[offset + 1]),
+                },
+
+                BUTTON_DISABLED => ButtonAction::Disabled,
+
+                BUTTON_DPI_CYCLE => ButtonAction::DpiCycle,
+                BUTTON_DPI_UP => ButtonAction::DpiUp,
+                BUTTON_DPI_DOWN => ButtonAction::DpiDown,
+
+                BUTTON_PROFILE_CYCLE => ButtonAction::ProfileCycle,
+                BUTTON_PROFILE_UP => ButtonAction::ProfileUp,
+                BUTTON_PROFILE_DOWN => ButtonAction::ProfileDown,
+
+                code => ButtonAction::Raw { code },
+            };
+
+            ButtonAssignment {
+                button: slot as u8 + 1,
+                action,
+            }
+        })
+        .collect()
+}
+
+/// Returns the function code of the button action, and its modifiers and key, if any
+fn encode_button(action: &ButtonAction) -> Result<(u8, Option<(u8, u8)>)> {
+    let unsupported = || OnboardError::UnsupportedSetting {
+        setting: format!("buttons: {:?}", action),
+    };
+
+    let result = match *action {
+        ButtonAction::MouseButton { button: 1 } => (BUTTON_LEFT, None),
+        ButtonAction::MouseButton { button: 2 } => (BUTTON_RIGHT, None),
+        ButtonAction::MouseButton { button: 3 } => (BUTTON_MIDDLE, None),
+        ButtonAction::MouseButton { button: 4 } => (BUTTON_BACKWARD, None),
+        ButtonAction::MouseButton { button: 5 } => (BUTTON_FORWARD, None),
+
+        ButtonAction::Key { usage, modifiers } => {
+            let usage = u8::try_from(usage).map_err(|_| unsupported())?;
+
+            (BUTTON_SHORTCUT, Some((encode_modifiers(modifiers), usage)))
+        }
+
+        ButtonAction::Disabled => (BUTTON_DISABLED, None),
+
+        ButtonAction::DpiCycle => (BUTTON_DPI_CYCLE, None),
+        ButtonAction::DpiUp => (BUTTON_DPI_UP, None),
+        ButtonAction::DpiDown => (BUTTON_DPI_DOWN, None),
+
+        ButtonAction::ProfileCycle => (BUTTON_PROFILE_CYCLE, None),
+        ButtonAction::ProfileUp => (BUTTON_PROFILE_UP, None),
+        ButtonAction::ProfileDown => (BUTTON_PROFILE_DOWN, None),
+
+        // the modifiers and the key of raw functions are carried along by the raw report
+        ButtonAction::Raw { code } => (code, None),
+
+        // the factory defaults are not known to the driver
+        ButtonAction::Default | ButtonAction::MouseButton { .. } => {
+            return Err(unsupported().into())
+        }
+    };
+
+    Ok(result)
+}
+
+fn encode_buttons(buttons: &mut [u8], assignments: &[ButtonAssignment]) -> Result<()> {
+    for assignment in assignments.iter() {
+        let offset = BUTTONS_OFFSET + (assignment.button as usize - 1) * 3;
+
+        let (code, key) = encode_button(&assignment.action)?;
+
+        if let Some((modifiers, usage)) = key {
+            buttons[offset + 1] = modifiers;
+            buttons[offset + 2] = usage;
+        } else if code != buttons[offset] {
+            buttons[offset + 1] = 0x00;
+            buttons[offset + 2] = 0x00;
+        }
+
+        buttons[offset] = code;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A settings report, as read from the device
+    const SETTINGS: [u8; SETTINGS_REPORT_SIZE] = [
+        0x06, 0x3f, 0x00, 0x06, 0x06, 0x1f, 0x01, 0x08, 0x00, 0x10, 0x00, 0x18, 0x00, 0x20, 0x00,
+        0x40, 0x00, 0x08, 0x00, 0x10, 0x00, 0x18, 0x00, 0x20, 0x00, 0x40, 0x00, 0x00, 0x00, 0x03,
+        0x09, 0x06, 0xff, 0x0f, 0x00, 0x00, 0x14, 0xff, 0xff, 0x00, 0x00, 0x14, 0xff, 0x00, 0x48,
+        0xff, 0x14, 0xff, 0x00, 0x48, 0xff, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xf7, 0x09, 0x00,
+    ];
+
+    fn buttons_report() -> Vec<u8> {
+        let mut buttons = vec![0x00; BUTTONS_REPORT_SIZE];
+
+        buttons[..3].copy_from_slice(&[BUTTONS_REPORT_ID, BUTTONS_REPORT_SIZE as u8, 0x00]);
+
+        let slots = [
+            [BUTTON_LEFT, 0x00, 0x00],
+            [BUTTON_RIGHT, 0x00, 0x00],
+            [BUTTON_MIDDLE, 0x00, 0x00],
+            [BUTTON_SHORTCUT, 0x01, 0x06],
+            [BUTTON_DPI_CYCLE, 0x00, 0x00],
+            [0x30, 0x12, 0x34],
+        ];
+
+        for (slot, bytes) in slots.iter().enumerate() {
+            let offset = BUTTONS_OFFSET + slot * 3;
+            buttons[offset..offset + 3].copy_from_slice(bytes);
+        }
+
+        for slot in slots.len()..NUM_BUTTON_SLOTS {
+            buttons[BUTTONS_OFFSET + slot * 3] = BUTTON_DISABLED;
+        }
+
+        update_checksum(&mut buttons);
+
+        buttons
+    }
+
+    #[test]
+    fn checksum() {
+        let mut settings = SETTINGS;
+        settings[61..63].copy_from_slice(&[0x00, 0x00]);
+
+        update_checksum(&mut settings);
+
+        assert_eq!(settings, SETTINGS);
+    }
+
+    #[test]
+    fn decode_settings() {
+        assert_eq!(
+            decode_dpi_stages(&SETTINGS),
+            (vec![400, 800, 1200, 1600, 3200], Some(1))
+        );
+
+        assert_eq!(
+            decode_lighting(&SETTINGS),
+            OnboardLighting {
+                brightness: 100,
+                color: Some((0xff, 0x00, 0x00)),
+            }
+        );
+    }
+
+    #[test]
+    fn encoding_decoded_settings_is_lossless() {
+        let mut settings = SETTINGS;
+
+        let (dpi_stages, active_dpi_stage) = decode_dpi_stages(&settings);
+        encode_dpi_stages(&mut settings, &dpi_stages, active_dpi_stage);
+        encode_lighting(&mut settings, &decode_lighting(&SETTINGS));
+        update_checksum(&mut settings);
+
+        assert_eq!(settings, SETTINGS);
+    }
+
+    #[test]
+    fn encode_settings() {
+        let mut settings = SETTINGS;
+
+        encode_dpi_stages(&mut settings, &[800, 1600, 2400], Some(2));
+        encode_lighting(
+            &mut settings,
+            &OnboardLighting {
+                brightness: 50,
+                color: Some((0x00, 0x80, 0xff)),
+            },
+        );
+
+        assert_eq!(settings[DPI_STAGES_MASK], 0x07);
+        assert_eq!(settings[ACTIVE_DPI_STAGE], 2);
+        assert_eq!(
+            &settings[DPI_STAGES_X..DPI_STAGES_X + 6],
+            &[16, 0, 32, 0, 48, 0]
+        );
+        assert_eq!(
+            &settings[DPI_STAGES_Y..DPI_STAGES_Y + 6],
+            &[16, 0, 32, 0, 48, 0]
+        );
+        assert_eq!(settings[LIGHT_BRIGHTNESS], 128);
+
+        assert_eq!(
+            decode_dpi_stages(&settings),
+            (vec![800, 1600, 2400], Some(2))
+        );
+
+        assert_eq!(
+            decode_lighting(&settings),
+            OnboardLighting {
+                brightness: 50,
+                color: Some((0x00, 0x80, 0xff)),
+            }
+        );
+    }
+
+    #[test]
+    fn decode_button_assignments() {
+        let buttons = decode_buttons(&buttons_report());
+
+        assert_eq!(buttons.len(), NUM_BUTTON_SLOTS);
+
+        assert_eq!(
+            buttons[..6]
+                .iter()
+                .map(|assignment| assignment.action.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                ButtonAction::MouseButton { button: 1 },
+                ButtonAction::MouseButton { button: 2 },
+                ButtonAction::MouseButton { button: 3 },
+                ButtonAction::Key {
+                    usage: 0x06,
+                    modifiers: 0x02,
+                },
+                ButtonAction::DpiCycle,
+                ButtonAction::Raw { code: 0x30 },
+            ]
+        );
+
+        assert_eq!(buttons[6].button, 7);
+        assert_eq!(buttons[6].action, ButtonAction::Disabled);
+    }
+
+    #[test]
+    fn encoding_decoded_buttons_is_lossless() {
+        let report = buttons_report();
+        let mut buttons = report.clone();
+
+        encode_buttons(&mut buttons, &decode_buttons(&report)).unwrap();
+
+        assert_eq!(buttons, report);
+    }
+
+    #[test]
+    fn encode_button_assignments() {
+        let mut buttons = buttons_report();
+
+        encode_buttons(
+            &mut buttons,
+            &[
+                ButtonAssignment {
+                    button: 1,
+                    action: ButtonAction::Key {
+                        usage: 0x04,
+                        // right shift
+                        modifiers: 0x20,
+                    },
+                },
+                ButtonAssignment {
+                    button: 6,
+                    action: ButtonAction::ProfileUp,
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(&buttons[3..6], &[BUTTON_SHORTCUT, 0x01, 0x04]);
+        assert_eq!(&buttons[18..21], &[BUTTON_PROFILE_UP, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn reject_unsupported_buttons() {
+        for action in [
+            ButtonAction::Default,
+            ButtonAction::MouseButton { button: 6 },
+            ButtonAction::Key {
+                usage: 0x100,
+                modifiers: 0x00,
+            },
+        ] {
+            assert!(encode_button(&action).is_err(), "{:?}", action);
+        }
+    }
+}
@@ -28,6 +28,7 @@ use std::{any::Any, collections::HashMap, mem::size_of, sync::Arc};
 
 use crate::{constants, hwdevices::DeviceStatus};
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, MouseDevice,
     MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -261,6 +262,12 @@ impl DeviceInfoTrait for RoccatKovaAimo {
         DeviceCapabilities::from([Capability::Mouse, Capability::RgbLighting])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
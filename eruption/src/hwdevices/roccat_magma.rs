@@ -30,6 +30,8 @@ use std::{sync::Arc, thread};
 
 use crate::constants;
 
+use super::remap::{self, KeyRemap};
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
@@ -115,6 +117,7 @@ pub struct RoccatMagma {
 
     // device specific configuration options
     pub brightness: i32,
+    pub key_remaps: Vec<KeyRemap>,
 }
 
 impl RoccatMagma {
@@ -138,6 +141,7 @@ impl RoccatMagma {
             dial_mode: Arc::new(Mutex::new(DialMode::Brightness)),
 
             brightness: 100,
+            key_remaps: vec![],
         }
     }
 
@@ -299,7 +303,7 @@ impl RoccatMagma {
                 }
 
                 0x06 => {
-                    let buf: [u8; 150] = [
+                    let mut buf: [u8; 150] = [
                         0x06, 0x96, 0x00, 0x01, 0x90, 0x62, 0x1a, 0x26, 0x2c, 0x00, 0x00, 0x91,
                         0x00, 0x5d, 0xe5, 0x00, 0xe0, 0x00, 0x00, 0x24, 0x47, 0x00, 0x00, 0x59,
                         0xe1, 0xe6, 0x42, 0x1f, 0x57, 0x5c, 0x58, 0x00, 0x55, 0x23, 0x4a, 0x4d,
@@ -315,6 +319,8 @@ impl RoccatMagma {
                         0xf1, 0x00, 0xe4, 0x1e, 0x4c, 0x1f,
                     ];
 
+                    remap::patch_key_assignments(&mut buf, &self.key_remaps)?;
+
                     match ctrl_dev.send_feature_report(&buf) {
                         Ok(_result) => {
                             hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
@@ -400,7 +406,17 @@ impl RoccatMagma {
 
 impl DeviceInfoTrait for RoccatMagma {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities::from([Capability::Keyboard, Capability::RgbLighting])
+        DeviceCapabilities::from([
+            Capability::Keyboard,
+            Capability::RgbLighting,
+            Capability::HardwareRemapping,
+        ])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -715,6 +731,28 @@ impl KeyboardDeviceTrait for RoccatMagma {
         Ok(self.brightness)
     }
 
+    fn set_hardware_key_remap(&mut self, remaps: &[KeyRemap]) -> Result<()> {
+        trace!("Setting hardware key remappings");
+
+        let previous = std::mem::replace(&mut self.key_remaps, remaps.to_vec());
+
+        // the key assignments are sent as part of the init sequence, so only
+        // an already initialized device needs to be updated right away
+        if self.is_initialized {
+            let result = self
+                .send_ctrl_report(0x06)
+                .and_then(|_| self.wait_for_ctrl_dev());
+
+            if result.is_err() {
+                self.key_remaps = previous;
+            }
+
+            result
+        } else {
+            Ok(())
+        }
+    }
+
     #[inline]
     fn get_next_event(&self) -> Result<KeyboardHidEvent> {
         self.get_next_event_timeout(-1)
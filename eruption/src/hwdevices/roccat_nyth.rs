@@ -32,6 +32,7 @@ use std::{mem::size_of, sync::Arc};
 
 use crate::constants;
 
+use super::schema::{DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     MouseDevice, MouseDeviceTrait, MouseHidEvent, RGBA,
@@ -211,6 +212,10 @@ impl DeviceInfoTrait for RoccatNyth {
         DeviceCapabilities::from([Capability::Mouse])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new().status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
@@ -30,6 +30,7 @@ use std::{sync::Arc, thread};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
@@ -337,6 +338,12 @@ impl DeviceInfoTrait for RoccatVulcanProTKL {
         DeviceCapabilities::from([Capability::Keyboard, Capability::RgbLighting])
     }
 
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+    }
+
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
         trace!("Querying the device for information...");
 
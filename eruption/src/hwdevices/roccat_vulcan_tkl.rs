@@ -29,6 +29,8 @@ use std::{sync::Arc, thread};
 
 use crate::constants::{self, DEVICE_SETTLE_MILLIS};
 
+use super::remap::{self, KeyRemap};
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
 use super::{
     Capability, DeviceCapabilities, DeviceInfoTrait, DeviceStatus, DeviceTrait, HwDeviceError,
     KeyboardDevice, KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind,
@@ -116,6 +118,7 @@ pub struct RoccatVulcanTKL {
 
     // device specific configuration options
     pub brightness: i32,
+    pub key_remaps: Vec<KeyRemap>,
 }
 
 impl RoccatVulcanTKL {
@@ -139,6 +142,7 @@ impl RoccatVulcanTKL {
             dial_mode: Arc::new(Mutex::new(DialMode::Brightness)),
 
             brightness: 100,
+            key_remaps: vec![],
         }
     }
 
@@ -277,7 +281,7 @@ impl RoccatVulcanTKL {
                 }
 
                 0x06 => {
-                    let buf: [u8; 133] = [
+                    let mut buf: [u8; 133] = [
                         0x06, 0x85, 0x00, 0x3a, 0x29, 0x35, 0x1e, 0x2b, 0x39, 0xe1, 0xe0, 0x3b,
                         0x1f, 0x14, 0x1a, 0x04, 0x64, 0x00, 0x00, 0x3d, 0x3c, 0x20, 0x21, 0x08,
                         0x16, 0x1d, 0xe2, 0x3e, 0x23, 0x22, 0x15, 0x07, 0x1b, 0x06, 0x8b, 0x3f,
@@ -292,6 +296,8 @@ impl RoccatVulcanTKL {
                         0x24,
                     ];
 
+                    remap::patch_key_assignments(&mut buf, &self.key_remaps)?;
+
                     match ctrl_dev.send_feature_report(&buf) {
                         Ok(_result) => {
                             hexdump::hexdump_iter(&buf).for_each(|s| trace!("  {}", s));
@@ -432,7 +438,17 @@ impl RoccatVulcanTKL {
 
 impl DeviceInfoTrait for RoccatVulcanTKL {
     fn get_device_capabilities(&self) -> DeviceCapabilities {
-        DeviceCapabilities::from([Capability::Keyboard, Capability::RgbLighting])
+        DeviceCapabilities::from([
+            Capability::Keyboard,
+            Capability::RgbLighting,
+            Capability::HardwareRemapping,
+        ])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
     }
 
     fn get_device_info(&self) -> Result<super::DeviceInfo> {
@@ -766,6 +782,28 @@ impl KeyboardDeviceTrait for RoccatVulcanTKL {
         Ok(self.brightness)
     }
 
+    fn set_hardware_key_remap(&mut self, remaps: &[KeyRemap]) -> Result<()> {
+        trace!("Setting hardware key remappings");
+
+        let previous = std::mem::replace(&mut self.key_remaps, remaps.to_vec());
+
+        // the key assignments are sent as part of the init sequence, so only
+        // an already initialized device needs to be updated right away
+        if self.is_initialized {
+            let result = self
+                .send_ctrl_report(0x06)
+                .and_then(|_| self.wait_for_ctrl_dev());
+
+            if result.is_err() {
+                self.key_remaps = previous;
+            }
+
+            result
+        } else {
+            Ok(())
+        }
+    }
+
     #[inline]
     fn get_next_event(&self) -> Result<KeyboardHidEvent> {
         self.get_next_event_timeout(-1)
@@ -0,0 +1,497 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Typed schema of the device specific configuration and status
+//!
+//! Each driver declares the configuration keys that it supports, with their type, valid range
+//! and unit, as well as the status keys that it reports. The schema is published via the
+//! `GetDeviceSchema` method of `org.eruption.Device`, so that clients do not need to guess which
+//! parameters a device supports. Values that are submitted via `SetDeviceConfig` are validated
+//! against the schema, before they are passed on to the driver.
+
+use serde::Serialize;
+use std::fmt;
+
+use super::DeviceStatus;
+
+pub type Result<T> = super::Result<T>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchemaError {
+    #[error("The device does not support the configuration key: {key}")]
+    UnsupportedKey { key: String },
+
+    #[error("The configuration key is read-only: {key}")]
+    ReadOnlyKey { key: String },
+
+    #[error("Invalid value for {key}: {description}")]
+    InvalidValue { key: String, description: String },
+}
+
+/// The type and the valid range of a configuration or status value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ValueType {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+
+    /// One of a set of discrete values, like e.g. the supported poll rates
+    Choice {
+        values: Vec<i64>,
+    },
+
+    Text,
+}
+
+/// A typed configuration or status value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl ValueType {
+    /// Parse `value` and verify that it lies within the valid range
+    pub fn parse(&self, key: &str, value: &str) -> Result<Value> {
+        let invalid = |description: String| SchemaError::InvalidValue {
+            key: key.to_string(),
+            description,
+        };
+
+        let parse_int = || {
+            value
+                .trim()
+                .parse::<i64>()
+                .map_err(|_| invalid(format!("expected an integer, got '{}'", value)))
+        };
+
+        match self {
+            ValueType::Bool => value
+                .trim()
+                .parse::<bool>()
+                .map(Value::Bool)
+                .map_err(|_| invalid(format!("expected true or false, got '{}'", value)).into()),
+
+            ValueType::Int { min, max } => {
+                let i = parse_int()?;
+
+                if (*min..=*max).contains(&i) {
+                    Ok(Value::Int(i))
+                } else {
+                    Err(invalid(format!("expected {}..{}, got {}", min, max, i)).into())
+                }
+            }
+
+            ValueType::Choice { values } => {
+                let i = parse_int()?;
+
+                if values.contains(&i) {
+                    Ok(Value::Int(i))
+                } else {
+                    Err(invalid(format!("expected one of {:?}, got {}", values, i)).into())
+                }
+            }
+
+            ValueType::Text => Ok(Value::Text(value.to_string())),
+        }
+    }
+}
+
+/// A device specific configuration parameter
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigKey {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value_type: ValueType,
+    pub unit: Option<&'static str>,
+    pub writable: bool,
+}
+
+impl ConfigKey {
+    /// Descriptive information about the device, like e.g. the firmware revision
+    pub fn info() -> Self {
+        Self {
+            name: "info",
+            description: "Information about the device",
+            value_type: ValueType::Text,
+            unit: None,
+            writable: false,
+        }
+    }
+
+    pub fn firmware() -> Self {
+        Self {
+            name: "firmware",
+            description: "Firmware revision",
+            value_type: ValueType::Text,
+            unit: None,
+            writable: false,
+        }
+    }
+
+    pub fn brightness() -> Self {
+        Self {
+            name: "brightness",
+            description: "Device specific brightness of the LEDs",
+            value_type: ValueType::Int { min: 0, max: 100 },
+            unit: Some("%"),
+            writable: true,
+        }
+    }
+
+    /// The active onboard profile, `count` profiles are stored on the device
+    pub fn profile(count: i64) -> Self {
+        Self {
+            name: "profile",
+            description: "Active onboard profile",
+            value_type: ValueType::Int {
+                min: 0,
+                max: count - 1,
+            },
+            unit: None,
+            writable: true,
+        }
+    }
+
+    /// The active DPI preset of the onboard profile, `count` presets are available
+    pub fn dpi_preset(count: i64) -> Self {
+        Self {
+            name: "dpi",
+            description: "Active DPI preset",
+            value_type: ValueType::Int {
+                min: 0,
+                max: count - 1,
+            },
+            unit: None,
+            writable: true,
+        }
+    }
+
+    /// The sensor resolution, for devices that accept arbitrary values
+    pub fn dpi(min: i64, max: i64) -> Self {
+        Self {
+            name: "dpi",
+            description: "Sensor resolution",
+            value_type: ValueType::Int { min, max },
+            unit: Some("dpi"),
+            writable: true,
+        }
+    }
+
+    pub fn rate(values: &[i64]) -> Self {
+        Self {
+            name: "rate",
+            description: "USB poll rate",
+            value_type: ValueType::Choice {
+                values: values.to_vec(),
+            },
+            unit: Some("Hz"),
+            writable: true,
+        }
+    }
+
+    pub fn dcu(min: i64, max: i64) -> Self {
+        Self {
+            name: "dcu",
+            description: "Distance control unit (lift-off distance)",
+            value_type: ValueType::Int { min, max },
+            unit: None,
+            writable: true,
+        }
+    }
+
+    pub fn angle_snapping() -> Self {
+        Self {
+            name: "angle-snapping",
+            description: "Angle snapping",
+            value_type: ValueType::Bool,
+            unit: None,
+            writable: true,
+        }
+    }
+
+    pub fn debounce() -> Self {
+        Self {
+            name: "debounce",
+            description: "Debounce of the buttons",
+            value_type: ValueType::Bool,
+            unit: None,
+            writable: true,
+        }
+    }
+
+    /// Parse and validate a value that shall be written to the device
+    pub fn validate(&self, value: &str) -> Result<Value> {
+        if !self.writable {
+            return Err(SchemaError::ReadOnlyKey {
+                key: self.name.to_string(),
+            }
+            .into());
+        }
+
+        self.value_type.parse(self.name, value)
+    }
+}
+
+/// A key of the status map that is reported by a device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusKey {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub value_type: ValueType,
+    pub unit: Option<&'static str>,
+}
+
+impl StatusKey {
+    pub fn connected() -> Self {
+        Self {
+            name: "connected",
+            description: "The device is connected",
+            value_type: ValueType::Bool,
+            unit: None,
+        }
+    }
+
+    pub fn battery_level() -> Self {
+        Self {
+            name: "battery-level-percent",
+            description: "Battery level",
+            value_type: ValueType::Int { min: 0, max: 100 },
+            unit: Some("%"),
+        }
+    }
+
+    pub fn battery_level_raw() -> Self {
+        Self {
+            name: "battery-level-raw",
+            description: "Battery level, as reported by the device",
+            value_type: ValueType::Int { min: 0, max: 255 },
+            unit: None,
+        }
+    }
+
+    pub fn signal_strength() -> Self {
+        Self {
+            name: "signal-strength-percent",
+            description: "Signal strength of the wireless connection",
+            value_type: ValueType::Int { min: 0, max: 100 },
+            unit: Some("%"),
+        }
+    }
+
+    pub fn signal_strength_raw() -> Self {
+        Self {
+            name: "signal-strength-raw",
+            description: "Signal strength, as reported by the device",
+            value_type: ValueType::Int { min: 0, max: 255 },
+            unit: None,
+        }
+    }
+
+    pub fn transceiver_enabled() -> Self {
+        Self {
+            name: "transceiver-enabled",
+            description: "The wireless transceiver is enabled",
+            value_type: ValueType::Bool,
+            unit: None,
+        }
+    }
+
+    pub fn is_virtual() -> Self {
+        Self {
+            name: "virtual",
+            description: "The device is simulated",
+            value_type: ValueType::Bool,
+            unit: None,
+        }
+    }
+
+    pub fn frames_recorded() -> Self {
+        Self {
+            name: "frames-recorded",
+            description: "Number of LED frames recorded by the simulated device",
+            value_type: ValueType::Int {
+                min: 0,
+                max: i64::MAX,
+            },
+            unit: None,
+        }
+    }
+}
+
+/// The configuration keys that a device supports and the status keys that it reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeviceSchema {
+    pub config: Vec<ConfigKey>,
+    pub status: Vec<StatusKey>,
+}
+
+impl Default for DeviceSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DeviceSchema {
+    /// Returns a schema with the read-only keys that are supported by all devices
+    pub fn new() -> Self {
+        Self {
+            config: vec![ConfigKey::info(), ConfigKey::firmware()],
+            status: vec![],
+        }
+    }
+
+    pub fn config(mut self, key: ConfigKey) -> Self {
+        self.config.push(key);
+        self
+    }
+
+    pub fn status(mut self, key: StatusKey) -> Self {
+        self.status.push(key);
+        self
+    }
+
+    pub fn get_config_key(&self, name: &str) -> Result<&ConfigKey> {
+        self.config.iter().find(|k| k.name == name).ok_or_else(|| {
+            SchemaError::UnsupportedKey {
+                key: name.to_string(),
+            }
+            .into()
+        })
+    }
+
+    /// Parse and validate a value of the configuration key `name`
+    pub fn validate_config(&self, name: &str, value: &str) -> Result<Value> {
+        self.get_config_key(name)?.validate(value)
+    }
+
+    /// Convert the status map that has been reported by the driver to typed values. Values that
+    /// are not declared in the schema, or that do not match their declared type are omitted
+    pub fn typed_status(&self, status: &DeviceStatus) -> Vec<(&'static str, Value)> {
+        self.status
+            .iter()
+            .filter_map(|key| {
+                status
+                    .get(key.name)
+                    .and_then(|v| key.value_type.parse(key.name, v).ok())
+                    .map(|v| (key.name, v))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn mouse_schema() -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .config(ConfigKey::profile(5))
+            .config(ConfigKey::rate(&[125, 250, 500, 1000]))
+            .config(ConfigKey::debounce())
+            .status(StatusKey::connected())
+            .status(StatusKey::battery_level())
+    }
+
+    #[test]
+    fn config_values_are_validated() -> Result<()> {
+        let schema = mouse_schema();
+
+        assert_eq!(schema.validate_config("brightness", "80")?, Value::Int(80));
+        assert_eq!(schema.validate_config("profile", "4")?, Value::Int(4));
+        assert_eq!(schema.validate_config("rate", "1000")?, Value::Int(1000));
+        assert_eq!(
+            schema.validate_config("debounce", "true")?,
+            Value::Bool(true)
+        );
+
+        assert!(schema.validate_config("brightness", "101").is_err());
+        assert!(schema.validate_config("brightness", "bright").is_err());
+        assert!(schema.validate_config("profile", "5").is_err());
+        assert!(schema.validate_config("rate", "300").is_err());
+        assert!(schema.validate_config("debounce", "1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_and_read_only_keys_are_rejected() {
+        let schema = mouse_schema();
+
+        let error = schema
+            .validate_config("angle-snapping", "true")
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SchemaError>(),
+            Some(SchemaError::UnsupportedKey { .. })
+        ));
+
+        let error = schema.validate_config("firmware", "1.0").unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<SchemaError>(),
+            Some(SchemaError::ReadOnlyKey { .. })
+        ));
+    }
+
+    #[test]
+    fn status_is_converted_to_typed_values() {
+        let schema = mouse_schema();
+
+        let status = DeviceStatus(HashMap::from([
+            ("connected".to_string(), "true".to_string()),
+            ("battery-level-percent".to_string(), "unknown".to_string()),
+            ("undeclared".to_string(), "1".to_string()),
+        ]));
+
+        assert_eq!(
+            schema.typed_status(&status),
+            vec![("connected", Value::Bool(true))]
+        );
+    }
+
+    #[test]
+    fn schema_is_serialized_with_tagged_types() -> Result<()> {
+        let json = serde_json::to_value(ConfigKey::rate(&[500, 1000]))?;
+
+        assert_eq!(json["name"], "rate");
+        assert_eq!(json["value_type"]["type"], "choice");
+        assert_eq!(json["value_type"]["values"][1], 1000);
+        assert_eq!(json["unit"], "Hz");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1392 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{any::Any, collections::HashMap, sync::Arc};
+
+use evdev_rs::enums::EV_KEY;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+
+use crate::constants;
+use crate::hwdevices::DeviceStatus;
+
+use super::onboard::{ButtonAction, ButtonAssignment, OnboardLighting, OnboardProfile};
+use super::schema::{ConfigKey, DeviceSchema, StatusKey};
+use super::{
+    Capability, DeviceCapabilities, DeviceInfoTrait, DeviceTrait, HwDeviceError, KeyboardDevice,
+    KeyboardDeviceTrait, KeyboardHidEvent, KeyboardHidEventCode, LedKind, MiscDevice,
+    MiscDeviceTrait, MouseDevice, MouseDeviceTrait, MouseHidEvent, VirtualDeviceConfig, RGBA,
+};
+
+// the virtual keyboard mimics the layout of a ROCCAT Vulcan 100/12x
+use super::roccat_vulcan_1xx::{COLS_TOPOLOGY, NUM_COLS, NUM_KEYS, NUM_ROWS, ROWS_TOPOLOGY};
+
+/// Number of frames that will be kept in memory by the frame recorder
+pub const MAX_RECORDED_FRAMES: usize = 1024;
+
+pub const NUM_MOUSE_LEDS: usize = 36;
+pub const MOUSE_ZONE_OFFSET: usize = constants::CANVAS_SIZE - NUM_MOUSE_LEDS;
+pub const NUM_MISC_LEDS: usize = 80;
+
+/// Number of onboard profile slots of the virtual mouse
+pub const NUM_ONBOARD_PROFILES: u8 = 5;
+
+pub type Result<T> = super::Result<T>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VirtualDeviceError {
+    #[error("Syntax error in event script on line {line}: {description}")]
+    EventScriptError { line: usize, description: String },
+}
+
+/// Binds a virtual keyboard, as declared in eruption.conf
+pub fn bind_virtual_keyboard(config: &VirtualDeviceConfig) -> Result<KeyboardDevice> {
+    Ok(Arc::new(RwLock::new(Box::new(VirtualKeyboard::bind(
+        config,
+    )?))))
+}
+
+/// Binds a virtual mouse, as declared in eruption.conf
+pub fn bind_virtual_mouse(config: &VirtualDeviceConfig) -> Result<MouseDevice> {
+    Ok(Arc::new(RwLock::new(Box::new(VirtualMouse::bind(config)?))))
+}
+
+/// Binds a virtual misc device, as declared in eruption.conf
+pub fn bind_virtual_misc(config: &VirtualDeviceConfig) -> Result<MiscDevice> {
+    Ok(Arc::new(RwLock::new(Box::new(VirtualMisc::bind(config)?))))
+}
+
+/// An input event that may be replayed by a virtual device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScriptedEvent {
+    Keyboard(KeyboardHidEvent),
+    Mouse(MouseHidEvent),
+}
+
+/// An input event, and the delay after which it shall be delivered,
+/// relative to the previous event of the script
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ScriptedEventEntry {
+    pub delay: Duration,
+    pub event: ScriptedEvent,
+}
+
+/// Parses an event script
+///
+/// Each line consists of a delay in milliseconds, relative to the previous event,
+/// followed by the name of an event and an optional argument, e.g.: `500 key_down 42`.
+/// Empty lines and lines starting with `#` are ignored
+pub fn parse_event_script(source: &str) -> Result<Vec<ScriptedEventEntry>> {
+    let mut result = vec![];
+
+    for (line_no, line) in source.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let syntax_error = |description: &str| VirtualDeviceError::EventScriptError {
+            line: line_no + 1,
+            description: description.to_owned(),
+        };
+
+        let tokens = line.split_whitespace().collect::<Vec<_>>();
+
+        let delay = tokens[0]
+            .parse::<u64>()
+            .map_err(|_e| syntax_error("Invalid delay"))?;
+
+        let name = tokens
+            .get(1)
+            .ok_or_else(|| syntax_error("Missing event name"))?;
+
+        let arg = match tokens.get(2) {
+            Some(arg) => Some(
+                arg.parse::<u8>()
+                    .map_err(|_e| syntax_error("Invalid argument"))?,
+            ),
+
+            None => None,
+        };
+
+        let require_arg = || arg.ok_or_else(|| syntax_error("Missing argument"));
+
+        let event = match *name {
+            "key_down" => ScriptedEvent::Keyboard(KeyboardHidEvent::KeyDown {
+                code: KeyboardHidEventCode::Unknown(require_arg()?),
+            }),
+
+            "key_up" => ScriptedEvent::Keyboard(KeyboardHidEvent::KeyUp {
+                code: KeyboardHidEventCode::Unknown(require_arg()?),
+            }),
+
+            "next_slot" => ScriptedEvent::Keyboard(KeyboardHidEvent::NextSlot),
+            "previous_slot" => ScriptedEvent::Keyboard(KeyboardHidEvent::PreviousSlot),
+
+            "brightness_up" => ScriptedEvent::Keyboard(KeyboardHidEvent::BrightnessUp),
+            "brightness_down" => ScriptedEvent::Keyboard(KeyboardHidEvent::BrightnessDown),
+            "set_brightness" => {
+                ScriptedEvent::Keyboard(KeyboardHidEvent::SetBrightness(require_arg()?))
+            }
+
+            "button_down" => ScriptedEvent::Mouse(MouseHidEvent::ButtonDown(require_arg()?)),
+            "button_up" => ScriptedEvent::Mouse(MouseHidEvent::ButtonUp(require_arg()?)),
+            "dpi_change" => ScriptedEvent::Mouse(MouseHidEvent::DpiChange(require_arg()?)),
+
+            _ => return Err(syntax_error("Unknown event").into()),
+        };
+
+        result.push(ScriptedEventEntry {
+            delay: Duration::from_millis(delay),
+            event,
+        });
+    }
+
+    Ok(result)
+}
+
+/// Replays the events of an event script, honoring the specified delays
+#[derive(Debug)]
+pub struct EventPlayer {
+    events: Vec<ScriptedEventEntry>,
+    position: usize,
+    last_event_time: Instant,
+    repeat: bool,
+}
+
+impl EventPlayer {
+    pub fn new(events: Vec<ScriptedEventEntry>, repeat: bool) -> Self {
+        Self {
+            events,
+            position: 0,
+            last_event_time: Instant::now(),
+            repeat,
+        }
+    }
+
+    /// Restart playback from the beginning of the script
+    pub fn rewind(&mut self) {
+        self.position = 0;
+        self.last_event_time = Instant::now();
+    }
+
+    /// Returns the next event, if it is due within `millis` milliseconds.
+    /// A negative value of `millis` waits until the next event is due
+    pub fn next_event_timeout(&mut self, millis: i32) -> Option<ScriptedEvent> {
+        if self.position >= self.events.len() {
+            if self.repeat && !self.events.is_empty() {
+                self.position = 0;
+            } else {
+                return None;
+            }
+        }
+
+        let entry = self.events[self.position];
+
+        let elapsed = self.last_event_time.elapsed();
+
+        if elapsed < entry.delay {
+            let remaining = entry.delay - elapsed;
+
+            if millis < 0 || remaining <= Duration::from_millis(millis as u64) {
+                thread::sleep(remaining);
+            } else {
+                if millis > 0 {
+                    thread::sleep(Duration::from_millis(millis as u64));
+                }
+
+                return None;
+            }
+        }
+
+        self.position += 1;
+        self.last_event_time = Instant::now();
+
+        Some(entry.event)
+    }
+}
+
+/// Records the LED maps that have been sent to a virtual device
+pub struct FrameRecorder {
+    frames: VecDeque<Vec<RGBA>>,
+    frame_count: u64,
+    record_file: Option<File>,
+}
+
+impl FrameRecorder {
+    pub fn new(record_file: Option<&Path>) -> Result<Self> {
+        let record_file = match record_file {
+            Some(path) => Some(
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .open(path)?,
+            ),
+
+            None => None,
+        };
+
+        Ok(Self {
+            frames: VecDeque::with_capacity(MAX_RECORDED_FRAMES),
+            frame_count: 0,
+            record_file,
+        })
+    }
+
+    /// Record a frame. If a record file has been specified, the frame will be appended
+    /// to it as well, as a line of hex encoded `RRGGBBAA` values
+    pub fn record(&mut self, led_map: &[RGBA]) -> Result<()> {
+        if self.frames.len() >= MAX_RECORDED_FRAMES {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(led_map.to_vec());
+        self.frame_count += 1;
+
+        if let Some(file) = self.record_file.as_mut() {
+            let line = led_map
+                .iter()
+                .map(|c| format!("{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            writeln!(file, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    /// The total number of frames that have been recorded
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// The most recently recorded frames, oldest first
+    #[allow(dead_code)]
+    pub fn frames(&self) -> &VecDeque<Vec<RGBA>> {
+        &self.frames
+    }
+
+    /// The most recently recorded frame
+    #[allow(dead_code)]
+    pub fn last_frame(&self) -> Option<&Vec<RGBA>> {
+        self.frames.back()
+    }
+}
+
+fn load_event_player(config: &VirtualDeviceConfig) -> Result<EventPlayer> {
+    let events = match &config.event_script {
+        Some(path) => parse_event_script(&fs::read_to_string(path)?)?,
+
+        None => vec![],
+    };
+
+    Ok(EventPlayer::new(events, config.repeat_events))
+}
+
+fn virtual_device_status(recorder: &FrameRecorder) -> DeviceStatus {
+    let mut table = HashMap::new();
+
+    table.insert("connected".to_owned(), format!("{}", true));
+    table.insert("virtual".to_owned(), format!("{}", true));
+    table.insert(
+        "frames-recorded".to_owned(),
+        format!("{}", recorder.frame_count()),
+    );
+
+    DeviceStatus(table)
+}
+
+/// A simulated keyboard device, used to run Eruption without hardware attached
+pub struct VirtualKeyboard {
+    pub config: VirtualDeviceConfig,
+
+    pub is_opened: bool,
+    pub is_initialized: bool,
+
+    pub has_failed: bool,
+
+    // device specific configuration options
+    pub brightness: i32,
+
+    pub player: Arc<Mutex<EventPlayer>>,
+    pub recorder: Arc<Mutex<FrameRecorder>>,
+}
+
+impl VirtualKeyboard {
+    /// Binds the driver to the declared virtual device
+    pub fn bind(config: &VirtualDeviceConfig) -> Result<Self> {
+        info!("Bound driver: Virtual Keyboard ({})", config.name);
+
+        Ok(Self {
+            config: config.clone(),
+            is_opened: false,
+            is_initialized: false,
+            has_failed: false,
+            brightness: 100,
+            player: Arc::new(Mutex::new(load_event_player(config)?)),
+            recorder: Arc::new(Mutex::new(FrameRecorder::new(
+                config.record_file.as_deref(),
+            )?)),
+        })
+    }
+}
+
+impl DeviceInfoTrait for VirtualKeyboard {
+    fn get_device_capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::from([Capability::Keyboard, Capability::RgbLighting])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+            .status(StatusKey::is_virtual())
+            .status(StatusKey::frames_recorded())
+    }
+
+    fn get_device_info(&self) -> Result<super::DeviceInfo> {
+        trace!("Querying the device for information...");
+
+        let result = super::DeviceInfo::new(0);
+        Ok(result)
+    }
+
+    fn get_firmware_revision(&self) -> String {
+        "<virtual>".to_string()
+    }
+}
+
+impl DeviceTrait for VirtualKeyboard {
+    fn get_usb_path(&self) -> String {
+        "<virtual>".to_string()
+    }
+
+    fn get_usb_vid(&self) -> u16 {
+        self.config.usb_vid
+    }
+
+    fn get_usb_pid(&self) -> u16 {
+        self.config.usb_pid
+    }
+
+    fn get_serial(&self) -> Option<&str> {
+        Some(&self.config.name)
+    }
+
+    fn get_support_script_file(&self) -> String {
+        "keyboards/roccat_vulcan_1xx".to_string()
+    }
+
+    fn open(&mut self, _api: &hidapi::HidApi) -> Result<()> {
+        trace!("Opening virtual device now...");
+
+        self.player.lock().rewind();
+        self.is_opened = true;
+
+        Ok(())
+    }
+
+    fn close_all(&mut self) -> Result<()> {
+        trace!("Closing virtual device now...");
+
+        self.is_opened = false;
+        self.is_initialized = false;
+
+        Ok(())
+    }
+
+    fn send_init_sequence(&mut self) -> Result<()> {
+        trace!("Sending device init sequence...");
+
+        if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            self.is_initialized = true;
+
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> Result<bool> {
+        Ok(self.is_initialized)
+    }
+
+    fn has_failed(&self) -> Result<bool> {
+        Ok(self.has_failed)
+    }
+
+    fn fail(&mut self) -> Result<()> {
+        self.has_failed = true;
+        Ok(())
+    }
+
+    fn write_data_raw(&self, _buf: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_data_raw(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.resize(size, 0);
+
+        Ok(buf)
+    }
+
+    fn device_status(&self) -> Result<DeviceStatus> {
+        Ok(virtual_device_status(&self.recorder.lock()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_device(&self) -> &dyn DeviceTrait {
+        self
+    }
+
+    fn as_device_mut(&mut self) -> &mut dyn DeviceTrait {
+        self
+    }
+
+    fn as_mouse_device(&self) -> Option<&dyn MouseDeviceTrait> {
+        None
+    }
+
+    fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
+        None
+    }
+}
+
+impl KeyboardDeviceTrait for VirtualKeyboard {
+    fn set_status_led(&self, _led_kind: LedKind, _on: bool) -> Result<()> {
+        trace!("Setting status LED state");
+
+        Ok(())
+    }
+
+    #[inline]
+    fn get_next_event(&self) -> Result<KeyboardHidEvent> {
+        self.get_next_event_timeout(-1)
+    }
+
+    fn get_next_event_timeout(&self, millis: i32) -> Result<KeyboardHidEvent> {
+        trace!("Querying control device for next event");
+
+        if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else {
+            match self.player.lock().next_event_timeout(millis) {
+                Some(ScriptedEvent::Keyboard(event)) => Ok(event),
+
+                // skip events that are destined for other device classes
+                Some(_) | None => Ok(KeyboardHidEvent::Unknown),
+            }
+        }
+    }
+
+    fn ev_key_to_key_index(&self, _key: EV_KEY) -> u8 {
+        0
+    }
+
+    fn hid_event_code_to_key_index(&self, code: &KeyboardHidEventCode) -> u8 {
+        match code {
+            // scripted events carry the key index directly
+            KeyboardHidEventCode::Unknown(index) => *index,
+
+            _ => 0,
+        }
+    }
+
+    fn hid_event_code_to_report(&self, _code: &KeyboardHidEventCode) -> u8 {
+        0
+    }
+
+    fn set_local_brightness(&mut self, brightness: i32) -> Result<()> {
+        trace!("Setting device specific brightness");
+
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    fn get_local_brightness(&self) -> Result<i32> {
+        trace!("Querying device specific brightness");
+
+        Ok(self.brightness)
+    }
+
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        trace!("Setting LEDs from supplied map...");
+
+        if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else if led_map.len() < NUM_KEYS {
+            Err(HwDeviceError::LedMapError {}.into())
+        } else {
+            self.recorder.lock().record(&led_map[0..NUM_KEYS])
+        }
+    }
+
+    fn set_led_init_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED init pattern...");
+
+        let led_map = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; NUM_KEYS];
+
+        self.send_led_map(&led_map)
+    }
+
+    fn set_led_off_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED off pattern...");
+
+        let led_map = [RGBA {
+            r: 0x00,
+            g: 0x00,
+            b: 0x00,
+            a: 0x00,
+        }; NUM_KEYS];
+
+        self.send_led_map(&led_map)
+    }
+
+    /// Returns the number of keys
+    fn get_num_keys(&self) -> usize {
+        NUM_KEYS
+    }
+
+    /// Returns the number of rows (vertical number of keys)
+    fn get_num_rows(&self) -> usize {
+        NUM_ROWS
+    }
+
+    /// Returns the number of columns (horizontal number of keys)
+    fn get_num_cols(&self) -> usize {
+        NUM_COLS
+    }
+
+    /// Returns the indices of the keys in row `row`
+    fn get_row_topology(&self, row: usize) -> &'static [u8] {
+        let idx = row * NUM_COLS;
+        &ROWS_TOPOLOGY[idx..(idx + NUM_COLS + 1)]
+    }
+
+    /// Returns the indices of the keys in column `col`
+    fn get_col_topology(&self, col: usize) -> &'static [u8] {
+        let idx = col * NUM_ROWS;
+        &COLS_TOPOLOGY[idx..(idx + NUM_ROWS + 1)]
+    }
+}
+
+/// A simulated mouse device, used to run Eruption without hardware attached
+pub struct VirtualMouse {
+    pub config: VirtualDeviceConfig,
+
+    pub is_opened: bool,
+    pub is_initialized: bool,
+
+    pub has_failed: bool,
+
+    // device specific configuration options
+    pub brightness: i32,
+    pub profile: i32,
+    pub dpi: i32,
+    pub rate: i32,
+    pub dcu: i32,
+    pub angle_snapping: bool,
+    pub debounce: bool,
+
+    // simulated onboard profiles
+    pub onboard_profiles: Vec<OnboardProfile>,
+
+    pub player: Arc<Mutex<EventPlayer>>,
+    pub recorder: Arc<Mutex<FrameRecorder>>,
+}
+
+impl VirtualMouse {
+    /// Binds the driver to the declared virtual device
+    pub fn bind(config: &VirtualDeviceConfig) -> Result<Self> {
+        info!("Bound driver: Virtual Mouse ({})", config.name);
+
+        Ok(Self {
+            config: config.clone(),
+            is_opened: false,
+            is_initialized: false,
+            has_failed: false,
+            brightness: 100,
+            profile: 0,
+            dpi: 800,
+            rate: 1000,
+            dcu: 0,
+            angle_snapping: false,
+            debounce: false,
+            onboard_profiles: (0..NUM_ONBOARD_PROFILES)
+                .map(default_onboard_profile)
+                .collect(),
+            player: Arc::new(Mutex::new(load_event_player(config)?)),
+            recorder: Arc::new(Mutex::new(FrameRecorder::new(
+                config.record_file.as_deref(),
+            )?)),
+        })
+    }
+}
+
+/// The factory default settings of an onboard profile of the virtual mouse
+fn default_onboard_profile(index: u8) -> OnboardProfile {
+    OnboardProfile {
+        index,
+        dpi_stages: vec![400, 800, 1600, 3200, 6400],
+        active_dpi_stage: Some(1),
+        poll_rate: Some(1000),
+        buttons: (1..=5)
+            .map(|button| ButtonAssignment {
+                button,
+                action: ButtonAction::Default,
+            })
+            .collect(),
+        lighting: Some(OnboardLighting {
+            brightness: 100,
+            color: None,
+        }),
+        raw: vec![],
+    }
+}
+
+impl DeviceInfoTrait for VirtualMouse {
+    fn get_device_capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::from([
+            Capability::Mouse,
+            Capability::RgbLighting,
+            Capability::HardwareProfiles,
+            Capability::DpiSelection,
+            Capability::Debounce,
+            Capability::AngleSnapping,
+        ])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .config(ConfigKey::profile(5))
+            .config(ConfigKey::dpi(100, 16000))
+            .config(ConfigKey::rate(&[125, 250, 500, 1000]))
+            .config(ConfigKey::dcu(0, 3))
+            .config(ConfigKey::angle_snapping())
+            .config(ConfigKey::debounce())
+            .status(StatusKey::connected())
+            .status(StatusKey::is_virtual())
+            .status(StatusKey::frames_recorded())
+    }
+
+    fn get_device_info(&self) -> Result<super::DeviceInfo> {
+        trace!("Querying the device for information...");
+
+        let result = super::DeviceInfo::new(0);
+        Ok(result)
+    }
+
+    fn get_firmware_revision(&self) -> String {
+        "<virtual>".to_string()
+    }
+}
+
+impl DeviceTrait for VirtualMouse {
+    fn get_usb_path(&self) -> String {
+        "<virtual>".to_string()
+    }
+
+    fn get_usb_vid(&self) -> u16 {
+        self.config.usb_vid
+    }
+
+    fn get_usb_pid(&self) -> u16 {
+        self.config.usb_pid
+    }
+
+    fn get_serial(&self) -> Option<&str> {
+        Some(&self.config.name)
+    }
+
+    fn get_support_script_file(&self) -> String {
+        "mice/generic_mouse".to_string()
+    }
+
+    fn open(&mut self, _api: &hidapi::HidApi) -> Result<()> {
+        trace!("Opening virtual device now...");
+
+        self.player.lock().rewind();
+        self.is_opened = true;
+
+        Ok(())
+    }
+
+    fn close_all(&mut self) -> Result<()> {
+        trace!("Closing virtual device now...");
+
+        self.is_opened = false;
+        self.is_initialized = false;
+
+        Ok(())
+    }
+
+    fn send_init_sequence(&mut self) -> Result<()> {
+        trace!("Sending device init sequence...");
+
+        if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            self.is_initialized = true;
+
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> Result<bool> {
+        Ok(self.is_initialized)
+    }
+
+    fn has_failed(&self) -> Result<bool> {
+        Ok(self.has_failed)
+    }
+
+    fn fail(&mut self) -> Result<()> {
+        self.has_failed = true;
+        Ok(())
+    }
+
+    fn write_data_raw(&self, _buf: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_data_raw(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.resize(size, 0);
+
+        Ok(buf)
+    }
+
+    fn device_status(&self) -> Result<DeviceStatus> {
+        Ok(virtual_device_status(&self.recorder.lock()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_device(&self) -> &dyn DeviceTrait {
+        self
+    }
+
+    fn as_device_mut(&mut self) -> &mut dyn DeviceTrait {
+        self
+    }
+
+    fn as_mouse_device(&self) -> Option<&dyn MouseDeviceTrait> {
+        Some(self as &dyn MouseDeviceTrait)
+    }
+
+    fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
+        Some(self as &mut dyn MouseDeviceTrait)
+    }
+}
+
+impl MouseDeviceTrait for VirtualMouse {
+    fn get_profile(&self) -> Result<i32> {
+        trace!("Querying device profile config");
+
+        Ok(self.profile)
+    }
+
+    fn set_profile(&mut self, profile: i32) -> Result<()> {
+        trace!("Setting device profile config");
+
+        self.profile = profile;
+
+        Ok(())
+    }
+
+    fn get_dpi(&self) -> Result<i32> {
+        trace!("Querying device DPI config");
+
+        Ok(self.dpi)
+    }
+
+    fn set_dpi(&mut self, dpi: i32) -> Result<()> {
+        trace!("Setting device DPI config");
+
+        self.dpi = dpi;
+
+        Ok(())
+    }
+
+    fn get_rate(&self) -> Result<i32> {
+        trace!("Querying device poll rate config");
+
+        Ok(self.rate)
+    }
+
+    fn set_rate(&mut self, rate: i32) -> Result<()> {
+        trace!("Setting device poll rate config");
+
+        self.rate = rate;
+
+        Ok(())
+    }
+
+    fn get_dcu_config(&self) -> Result<i32> {
+        trace!("Querying device DCU config");
+
+        Ok(self.dcu)
+    }
+
+    fn set_dcu_config(&mut self, dcu: i32) -> Result<()> {
+        trace!("Setting device DCU config");
+
+        self.dcu = dcu;
+
+        Ok(())
+    }
+
+    fn get_angle_snapping(&self) -> Result<bool> {
+        trace!("Querying device angle-snapping config");
+
+        Ok(self.angle_snapping)
+    }
+
+    fn set_angle_snapping(&mut self, angle_snapping: bool) -> Result<()> {
+        trace!("Setting device angle-snapping config");
+
+        self.angle_snapping = angle_snapping;
+
+        Ok(())
+    }
+
+    fn get_debounce(&self) -> Result<bool> {
+        trace!("Querying device debounce config");
+
+        Ok(self.debounce)
+    }
+
+    fn set_debounce(&mut self, debounce: bool) -> Result<()> {
+        trace!("Setting device debounce config");
+
+        self.debounce = debounce;
+
+        Ok(())
+    }
+
+    fn get_onboard_profile_count(&self) -> u8 {
+        NUM_ONBOARD_PROFILES
+    }
+
+    fn read_onboard_profile(&self, index: u8) -> Result<OnboardProfile> {
+        trace!("Reading onboard profile {}", index);
+
+        self.onboard_profiles
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| HwDeviceError::InvalidResult {}.into())
+    }
+
+    fn validate_onboard_profile(&self, profile: &OnboardProfile) -> Result<()> {
+        if profile.index < NUM_ONBOARD_PROFILES {
+            Ok(())
+        } else {
+            Err(HwDeviceError::InvalidResult {}.into())
+        }
+    }
+
+    fn write_onboard_profile(&mut self, profile: &OnboardProfile) -> Result<()> {
+        trace!("Writing onboard profile {}", profile.index);
+
+        match self.onboard_profiles.get_mut(profile.index as usize) {
+            Some(slot) => {
+                *slot = profile.clone();
+
+                Ok(())
+            }
+
+            None => Err(HwDeviceError::InvalidResult {}.into()),
+        }
+    }
+
+    fn get_local_brightness(&self) -> Result<i32> {
+        trace!("Querying device specific brightness");
+
+        Ok(self.brightness)
+    }
+
+    fn set_local_brightness(&mut self, brightness: i32) -> Result<()> {
+        trace!("Setting device specific brightness");
+
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    #[inline]
+    fn get_next_event(&self) -> Result<MouseHidEvent> {
+        self.get_next_event_timeout(-1)
+    }
+
+    fn get_next_event_timeout(&self, millis: i32) -> Result<MouseHidEvent> {
+        trace!("Querying control device for next event");
+
+        if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else {
+            match self.player.lock().next_event_timeout(millis) {
+                Some(ScriptedEvent::Mouse(event)) => Ok(event),
+
+                // skip events that are destined for other device classes
+                Some(_) | None => Ok(MouseHidEvent::Unknown),
+            }
+        }
+    }
+
+    fn ev_key_to_button_index(&self, code: EV_KEY) -> Result<u8> {
+        match code {
+            EV_KEY::KEY_RESERVED => Ok(0),
+
+            EV_KEY::BTN_LEFT => Ok(1),
+            EV_KEY::BTN_MIDDLE => Ok(2),
+            EV_KEY::BTN_RIGHT => Ok(3),
+
+            EV_KEY::BTN_EXTRA => Ok(4),
+            EV_KEY::BTN_SIDE => Ok(5),
+            EV_KEY::BTN_FORWARD => Ok(6),
+            EV_KEY::BTN_BACK => Ok(7),
+            EV_KEY::BTN_TASK => Ok(8),
+
+            _ => Err(HwDeviceError::MappingError {}.into()),
+        }
+    }
+
+    fn button_index_to_ev_key(&self, index: u32) -> Result<EV_KEY> {
+        match index {
+            0 => Ok(EV_KEY::KEY_RESERVED),
+
+            1 => Ok(EV_KEY::BTN_LEFT),
+            2 => Ok(EV_KEY::BTN_MIDDLE),
+            3 => Ok(EV_KEY::BTN_RIGHT),
+
+            4 => Ok(EV_KEY::BTN_EXTRA),
+            5 => Ok(EV_KEY::BTN_SIDE),
+            6 => Ok(EV_KEY::BTN_FORWARD),
+            7 => Ok(EV_KEY::BTN_BACK),
+            8 => Ok(EV_KEY::BTN_TASK),
+
+            _ => Err(HwDeviceError::MappingError {}.into()),
+        }
+    }
+
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        trace!("Setting LEDs from supplied map...");
+
+        if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else if led_map.len() < constants::CANVAS_SIZE {
+            Err(HwDeviceError::LedMapError {}.into())
+        } else {
+            self.recorder
+                .lock()
+                .record(&led_map[MOUSE_ZONE_OFFSET..constants::CANVAS_SIZE])
+        }
+    }
+
+    fn set_led_init_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED init pattern...");
+
+        Ok(())
+    }
+
+    fn set_led_off_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED off pattern...");
+
+        Ok(())
+    }
+
+    fn has_secondary_device(&self) -> bool {
+        false
+    }
+}
+
+/// A simulated misc device, e.g. a LED strip, used to run Eruption without hardware attached
+pub struct VirtualMisc {
+    pub config: VirtualDeviceConfig,
+
+    pub is_opened: bool,
+    pub is_initialized: bool,
+
+    pub has_failed: bool,
+
+    // device specific configuration options
+    pub brightness: i32,
+
+    pub recorder: Arc<Mutex<FrameRecorder>>,
+}
+
+impl VirtualMisc {
+    /// Binds the driver to the declared virtual device
+    pub fn bind(config: &VirtualDeviceConfig) -> Result<Self> {
+        info!("Bound driver: Virtual Misc Device ({})", config.name);
+
+        Ok(Self {
+            config: config.clone(),
+            is_opened: false,
+            is_initialized: false,
+            has_failed: false,
+            brightness: 100,
+            recorder: Arc::new(Mutex::new(FrameRecorder::new(
+                config.record_file.as_deref(),
+            )?)),
+        })
+    }
+}
+
+impl DeviceInfoTrait for VirtualMisc {
+    fn get_device_capabilities(&self) -> DeviceCapabilities {
+        DeviceCapabilities::from([Capability::Misc, Capability::RgbLighting])
+    }
+
+    fn get_device_schema(&self) -> DeviceSchema {
+        DeviceSchema::new()
+            .config(ConfigKey::brightness())
+            .status(StatusKey::connected())
+            .status(StatusKey::is_virtual())
+            .status(StatusKey::frames_recorded())
+    }
+
+    fn get_device_info(&self) -> Result<super::DeviceInfo> {
+        trace!("Querying the device for information...");
+
+        let result = super::DeviceInfo::new(0);
+        Ok(result)
+    }
+
+    fn get_firmware_revision(&self) -> String {
+        "<virtual>".to_string()
+    }
+}
+
+impl DeviceTrait for VirtualMisc {
+    fn get_usb_path(&self) -> String {
+        "<virtual>".to_string()
+    }
+
+    fn get_usb_vid(&self) -> u16 {
+        self.config.usb_vid
+    }
+
+    fn get_usb_pid(&self) -> u16 {
+        self.config.usb_pid
+    }
+
+    fn get_serial(&self) -> Option<&str> {
+        Some(&self.config.name)
+    }
+
+    fn get_support_script_file(&self) -> String {
+        "misc/custom_serial_leds".to_string()
+    }
+
+    fn open(&mut self, _api: &hidapi::HidApi) -> Result<()> {
+        trace!("Opening virtual device now...");
+
+        self.is_opened = true;
+
+        Ok(())
+    }
+
+    fn close_all(&mut self) -> Result<()> {
+        trace!("Closing virtual device now...");
+
+        self.is_opened = false;
+        self.is_initialized = false;
+
+        Ok(())
+    }
+
+    fn send_init_sequence(&mut self) -> Result<()> {
+        trace!("Sending device init sequence...");
+
+        if !self.is_opened {
+            Err(HwDeviceError::DeviceNotOpened {}.into())
+        } else {
+            self.is_initialized = true;
+
+            Ok(())
+        }
+    }
+
+    fn is_initialized(&self) -> Result<bool> {
+        Ok(self.is_initialized)
+    }
+
+    fn has_failed(&self) -> Result<bool> {
+        Ok(self.has_failed)
+    }
+
+    fn fail(&mut self) -> Result<()> {
+        self.has_failed = true;
+        Ok(())
+    }
+
+    fn write_data_raw(&self, _buf: &[u8]) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_data_raw(&self, size: usize) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        buf.resize(size, 0);
+
+        Ok(buf)
+    }
+
+    fn device_status(&self) -> Result<DeviceStatus> {
+        Ok(virtual_device_status(&self.recorder.lock()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn as_device(&self) -> &dyn DeviceTrait {
+        self
+    }
+
+    fn as_device_mut(&mut self) -> &mut dyn DeviceTrait {
+        self
+    }
+
+    fn as_mouse_device(&self) -> Option<&dyn MouseDeviceTrait> {
+        None
+    }
+
+    fn as_mouse_device_mut(&mut self) -> Option<&mut dyn MouseDeviceTrait> {
+        None
+    }
+}
+
+impl MiscDeviceTrait for VirtualMisc {
+    fn has_input_device(&self) -> bool {
+        false
+    }
+
+    fn set_local_brightness(&mut self, brightness: i32) -> Result<()> {
+        trace!("Setting device specific brightness");
+
+        self.brightness = brightness;
+
+        Ok(())
+    }
+
+    fn get_local_brightness(&self) -> Result<i32> {
+        trace!("Querying device specific brightness");
+
+        Ok(self.brightness)
+    }
+
+    fn send_led_map(&mut self, led_map: &[RGBA]) -> Result<()> {
+        trace!("Setting LEDs from supplied map...");
+
+        if !self.is_initialized {
+            Err(HwDeviceError::DeviceNotInitialized {}.into())
+        } else if led_map.len() < NUM_MISC_LEDS {
+            Err(HwDeviceError::LedMapError {}.into())
+        } else {
+            self.recorder.lock().record(&led_map[0..NUM_MISC_LEDS])
+        }
+    }
+
+    fn set_led_init_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED init pattern...");
+
+        Ok(())
+    }
+
+    fn set_led_off_pattern(&mut self) -> Result<()> {
+        trace!("Setting LED off pattern...");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{
+        parse_event_script, EventPlayer, FrameRecorder, ScriptedEvent, ScriptedEventEntry,
+    };
+    use crate::hwdevices::{KeyboardHidEvent, KeyboardHidEventCode, MouseHidEvent, RGBA};
+
+    #[test]
+    fn event_script_parsing() {
+        let source = r#"
+            # press and release a key
+            0 key_down 42
+            100 key_up 42
+
+            250 button_down 1
+            10 next_slot
+        "#;
+
+        let events = parse_event_script(source).unwrap();
+
+        assert_eq!(
+            events,
+            vec![
+                ScriptedEventEntry {
+                    delay: Duration::from_millis(0),
+                    event: ScriptedEvent::Keyboard(KeyboardHidEvent::KeyDown {
+                        code: KeyboardHidEventCode::Unknown(42)
+                    }),
+                },
+                ScriptedEventEntry {
+                    delay: Duration::from_millis(100),
+                    event: ScriptedEvent::Keyboard(KeyboardHidEvent::KeyUp {
+                        code: KeyboardHidEventCode::Unknown(42)
+                    }),
+                },
+                ScriptedEventEntry {
+                    delay: Duration::from_millis(250),
+                    event: ScriptedEvent::Mouse(MouseHidEvent::ButtonDown(1)),
+                },
+                ScriptedEventEntry {
+                    delay: Duration::from_millis(10),
+                    event: ScriptedEvent::Keyboard(KeyboardHidEvent::NextSlot),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn event_script_syntax_errors() {
+        assert!(parse_event_script("abc key_down 1").is_err());
+        assert!(parse_event_script("10 key_down").is_err());
+        assert!(parse_event_script("10 explode 1").is_err());
+        assert!(parse_event_script("10").is_err());
+    }
+
+    #[test]
+    fn event_player_honors_delays() {
+        let events = parse_event_script("0 key_down 1\n60000 key_up 1").unwrap();
+        let mut player = EventPlayer::new(events, false);
+
+        assert_eq!(
+            player.next_event_timeout(0),
+            Some(ScriptedEvent::Keyboard(KeyboardHidEvent::KeyDown {
+                code: KeyboardHidEventCode::Unknown(1)
+            }))
+        );
+
+        // the second event is not due yet
+        assert_eq!(player.next_event_timeout(0), None);
+    }
+
+    #[test]
+    fn onboard_profiles_backup_and_restore() {
+        use crate::hwdevices::onboard::{self, ButtonAction};
+        use crate::hwdevices::{MouseDeviceTrait, VirtualDeviceConfig};
+
+        let config = VirtualDeviceConfig {
+            class: "mouse".to_string(),
+            name: "Test".to_string(),
+            usb_vid: 0xffff,
+            usb_pid: 0x0002,
+            event_script: None,
+            repeat_events: false,
+            record_file: None,
+        };
+
+        let mut mouse = super::VirtualMouse::bind(&config).unwrap();
+
+        let mut backup = onboard::backup(&mut mouse).unwrap();
+        assert_eq!(backup.profiles.len(), super::NUM_ONBOARD_PROFILES as usize);
+
+        backup.active_profile = 2;
+        backup.profiles[2].dpi_stages = vec![1200, 2400];
+        backup.profiles[2].buttons[4].action = ButtonAction::DpiCycle;
+
+        onboard::restore(&mut mouse, &backup).unwrap();
+
+        assert_eq!(onboard::backup(&mut mouse).unwrap(), backup);
+        assert_eq!(mouse.get_profile().unwrap(), 2);
+
+        // profiles of a different device are rejected
+        backup.usb_pid = 0x0003;
+        assert!(onboard::restore(&mut mouse, &backup).is_err());
+    }
+
+    #[test]
+    fn frame_recorder_keeps_the_latest_frames() {
+        let mut recorder = FrameRecorder::new(None).unwrap();
+
+        for i in 0..(super::MAX_RECORDED_FRAMES + 10) {
+            let frame = [RGBA {
+                r: (i % 256) as u8,
+                g: 0,
+                b: 0,
+                a: 255,
+            }; 4];
+
+            recorder.record(&frame).unwrap();
+        }
+
+        assert_eq!(
+            recorder.frame_count(),
+            (super::MAX_RECORDED_FRAMES + 10) as u64
+        );
+        assert_eq!(recorder.frames().len(), super::MAX_RECORDED_FRAMES);
+        assert_eq!(
+            recorder.last_frame().unwrap()[0].r,
+            ((super::MAX_RECORDED_FRAMES + 9) % 256) as u8
+        );
+    }
+}
@@ -0,0 +1,413 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Composition of the devices on a virtual canvas
+//!
+//! Without a layout, all devices share the "legacy" canvas: keyboards read their keys from the
+//! start of the canvas, mice and misc devices read their LEDs from the "mouse zone" at the end of
+//! it. As soon as a device has been placed on the layout, the device is assigned a rectangle of the
+//! virtual canvas instead, and the LED map that is sent to the device will be composed from that
+//! rectangle. Since the composed LED map uses the legacy format, the hardware drivers do not
+//! need to be aware of the layout at all.
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::constants;
+use crate::hwdevices::{DeviceTrait, KeyboardDeviceTrait, RGBA};
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LayoutError {
+    #[error("The canvas is too small, it needs to hold at least {min} LEDs")]
+    CanvasTooSmall { min: usize },
+
+    #[error("The placement of the device does not fit on the canvas: {device}")]
+    InvalidPlacement { device: String },
+}
+
+lazy_static! {
+    /// The layout of the virtual canvas
+    pub static ref LAYOUT: Arc<RwLock<Layout>> = Arc::new(RwLock::new(Layout::default()));
+
+    /// Cached mappings from the legacy LED map of a device to the indices on the virtual canvas
+    static ref DEVICE_MAPPINGS: Arc<RwLock<HashMap<DeviceMakeModelSerial, Arc<Vec<usize>>>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+}
+
+/// A rectangle on the virtual canvas, occupied by a single device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    /// The device in `make:model:serial` notation, as used in the state file
+    pub device: DeviceMakeModelSerial,
+
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Placement {
+    /// Returns the index on the virtual canvas of the cell at (`x`, `y`), relative to the placement
+    fn cell_index(&self, x: usize, y: usize, canvas_width: usize) -> usize {
+        (self.y + y.min(self.height - 1)) * canvas_width + self.x + x.min(self.width - 1)
+    }
+}
+
+/// The dimensions of the virtual canvas and the placements of the devices on it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Layout {
+    pub width: usize,
+    pub height: usize,
+
+    #[serde(default)]
+    pub placements: Vec<Placement>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            width: constants::CANVAS_WIDTH,
+            height: constants::CANVAS_HEIGHT,
+            placements: vec![],
+        }
+    }
+}
+
+impl Layout {
+    /// Returns the number of "pixels" on the virtual canvas
+    pub fn canvas_size(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Returns true if at least one device has been placed on the virtual canvas
+    pub fn is_active(&self) -> bool {
+        !self.placements.is_empty()
+    }
+
+    pub fn get_placement(&self, device: &str) -> Option<&Placement> {
+        self.placements.iter().find(|p| p.device == device)
+    }
+
+    /// Verify that the virtual canvas is able to hold the legacy canvas
+    pub fn validate_dimensions(&self) -> Result<()> {
+        if self.canvas_size() < constants::CANVAS_SIZE {
+            Err(LayoutError::CanvasTooSmall {
+                min: constants::CANVAS_SIZE,
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Verify that `placement` is non-empty and lies completely within the virtual canvas
+    pub fn validate_placement(&self, placement: &Placement) -> Result<()> {
+        if placement.width == 0
+            || placement.height == 0
+            || placement.x + placement.width > self.width
+            || placement.y + placement.height > self.height
+        {
+            Err(LayoutError::InvalidPlacement {
+                device: placement.device.clone(),
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Place a device on the virtual canvas, replacing a previous placement of that device
+    pub fn set_placement(&mut self, placement: Placement) -> Result<()> {
+        self.validate_placement(&placement)?;
+
+        self.placements.retain(|p| p.device != placement.device);
+        self.placements.push(placement);
+
+        Ok(())
+    }
+
+    /// Remove a device from the virtual canvas, returns true if the device had been placed
+    pub fn remove_placement(&mut self, device: &str) -> bool {
+        let len = self.placements.len();
+
+        self.placements.retain(|p| p.device != device);
+
+        len != self.placements.len()
+    }
+}
+
+/// Returns the identifier of `device`, in `make:model:serial` notation
+pub fn device_make_model_serial(device: &dyn DeviceTrait) -> DeviceMakeModelSerial {
+    format!(
+        "0x{:x}:0x{:x}:{}",
+        device.get_usb_vid(),
+        device.get_usb_pid(),
+        device.get_serial().unwrap_or("")
+    )
+}
+
+/// Returns the number of "pixels" on the virtual canvas
+pub fn canvas_size() -> usize {
+    LAYOUT.read().canvas_size()
+}
+
+/// Returns the width of the virtual canvas
+pub fn canvas_width() -> usize {
+    LAYOUT.read().width
+}
+
+/// Returns the height of the virtual canvas
+pub fn canvas_height() -> usize {
+    LAYOUT.read().height
+}
+
+/// Returns true if at least one device has been placed on the virtual canvas
+pub fn is_active() -> bool {
+    LAYOUT.read().is_active()
+}
+
+/// Initialize the layout from the saved state. This needs to happen before the canvas is
+/// allocated, since the dimensions of the virtual canvas can not be changed at runtime
+pub fn init_layout(mut layout: Layout) {
+    if let Err(e) = layout.validate_dimensions() {
+        warn!("Invalid canvas layout: {}, using the default dimensions", e);
+
+        layout.width = constants::CANVAS_WIDTH;
+        layout.height = constants::CANVAS_HEIGHT;
+    }
+
+    let placements = std::mem::take(&mut layout.placements);
+    for placement in placements {
+        layout
+            .set_placement(placement)
+            .unwrap_or_else(|e| warn!("Ignoring the placement of a device: {}", e));
+    }
+
+    *LAYOUT.write() = layout;
+
+    DEVICE_MAPPINGS.write().clear();
+}
+
+/// Place a device on the virtual canvas
+pub fn set_placement(placement: Placement) -> Result<()> {
+    LAYOUT.write().set_placement(placement)?;
+
+    DEVICE_MAPPINGS.write().clear();
+
+    Ok(())
+}
+
+/// Remove a device from the virtual canvas, it will use the legacy canvas afterwards
+pub fn remove_placement(device: &str) -> bool {
+    let result = LAYOUT.write().remove_placement(device);
+
+    DEVICE_MAPPINGS.write().clear();
+
+    result
+}
+
+/// Returns the LED map for `device`, in the legacy format that is expected by the hardware drivers.
+/// Keyboards should supply `keyboard`, so that their keys may be placed using their topology
+pub fn compose_led_map<'a>(
+    device: &dyn DeviceTrait,
+    keyboard: Option<&dyn KeyboardDeviceTrait>,
+    canvas: &'a [RGBA],
+) -> Cow<'a, [RGBA]> {
+    if !is_active() {
+        return Cow::Borrowed(canvas);
+    }
+
+    let key = device_make_model_serial(device);
+
+    let mapping = DEVICE_MAPPINGS.read().get(&key).cloned();
+    let mapping = match mapping {
+        Some(mapping) => mapping,
+
+        None => {
+            let layout = LAYOUT.read();
+
+            match layout.get_placement(&key) {
+                Some(placement) => {
+                    let topology = keyboard.map(keyboard_topology).unwrap_or_default();
+                    let mapping = Arc::new(build_mapping(placement, layout.width, &topology));
+
+                    DEVICE_MAPPINGS.write().insert(key, mapping.clone());
+
+                    mapping
+                }
+
+                // devices without a placement use the legacy canvas
+                None => return Cow::Borrowed(canvas),
+            }
+        }
+    };
+
+    Cow::Owned(
+        mapping
+            .iter()
+            .map(|index| {
+                canvas.get(*index).copied().unwrap_or(RGBA {
+                    r: 0,
+                    g: 0,
+                    b: 0,
+                    a: 0,
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Returns the key indices of each row of the keyboard
+fn keyboard_topology(keyboard: &dyn KeyboardDeviceTrait) -> Vec<Vec<u8>> {
+    (0..keyboard.get_num_rows())
+        .map(|row| keyboard.get_row_topology(row).to_vec())
+        .collect()
+}
+
+/// Map each LED of the legacy canvas to a cell of `placement` on the virtual canvas. The LEDs of
+/// the mouse zone and all other LEDs are laid out row by row, keys are laid out using the rows
+/// of the keyboard `topology`, scaled to the size of the placement
+fn build_mapping(placement: &Placement, canvas_width: usize, topology: &[Vec<u8>]) -> Vec<usize> {
+    let mouse_zone_start = constants::CANVAS_SIZE - constants::MOUSE_ZONE_SIZE;
+
+    let mut result: Vec<usize> = (0..constants::CANVAS_SIZE)
+        .map(|index| {
+            let n = if index >= mouse_zone_start {
+                index - mouse_zone_start
+            } else {
+                index
+            };
+
+            placement.cell_index(
+                n % placement.width,
+                (n / placement.width) % placement.height,
+                canvas_width,
+            )
+        })
+        .collect();
+
+    for (row, keys) in topology.iter().enumerate() {
+        for (col, key) in keys.iter().enumerate() {
+            let key = *key as usize;
+
+            // unused slots of the topology tables are marked with 0xff
+            if key < constants::CANVAS_SIZE {
+                let x = col * placement.width / keys.len();
+                let y = row * placement.height / topology.len();
+
+                result[key] = placement.cell_index(x, y, canvas_width);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(x: usize, y: usize, width: usize, height: usize) -> Placement {
+        Placement {
+            device: "0x1e7d:0x2dcd:".to_string(),
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn default_layout_is_the_legacy_canvas() {
+        let layout = Layout::default();
+
+        assert_eq!(layout.canvas_size(), constants::CANVAS_SIZE);
+        assert!(!layout.is_active());
+        assert!(layout.validate_dimensions().is_ok());
+    }
+
+    #[test]
+    fn placements_must_fit_on_the_canvas() {
+        let mut layout = Layout {
+            width: 40,
+            height: 10,
+            placements: vec![],
+        };
+
+        assert!(layout.set_placement(placement(0, 0, 24, 6)).is_ok());
+        assert!(layout.set_placement(placement(30, 0, 11, 6)).is_err());
+        assert!(layout.set_placement(placement(0, 8, 4, 0)).is_err());
+
+        // a device has at most one placement
+        assert!(layout.set_placement(placement(36, 8, 4, 2)).is_ok());
+        assert_eq!(layout.placements.len(), 1);
+
+        assert!(layout.remove_placement("0x1e7d:0x2dcd:"));
+        assert!(!layout.is_active());
+    }
+
+    #[test]
+    fn canvas_must_hold_the_legacy_canvas() {
+        let layout = Layout {
+            width: 10,
+            height: 10,
+            placements: vec![],
+        };
+
+        assert!(layout.validate_dimensions().is_err());
+    }
+
+    #[test]
+    fn mouse_zone_is_mapped_into_the_placement() {
+        let mapping = build_mapping(&placement(36, 8, 2, 2), 40, &[]);
+        let mouse_zone_start = constants::CANVAS_SIZE - constants::MOUSE_ZONE_SIZE;
+
+        assert_eq!(mapping.len(), constants::CANVAS_SIZE);
+
+        assert_eq!(mapping[mouse_zone_start], 8 * 40 + 36);
+        assert_eq!(mapping[mouse_zone_start + 1], 8 * 40 + 37);
+        assert_eq!(mapping[mouse_zone_start + 2], 9 * 40 + 36);
+        assert_eq!(mapping[mouse_zone_start + 3], 9 * 40 + 37);
+
+        // wraps around
+        assert_eq!(mapping[mouse_zone_start + 4], 8 * 40 + 36);
+    }
+
+    #[test]
+    fn keys_are_mapped_using_the_topology() {
+        let topology = vec![vec![0x00, 0x02, 0xff], vec![0x01, 0x03, 0x04]];
+        let mapping = build_mapping(&placement(4, 1, 6, 2), 40, &topology);
+
+        assert_eq!(mapping[0x00], 40 + 4);
+        assert_eq!(mapping[0x02], 40 + 6);
+        assert_eq!(mapping[0x01], 2 * 40 + 4);
+        assert_eq!(mapping[0x03], 2 * 40 + 6);
+        assert_eq!(mapping[0x04], 2 * 40 + 8);
+    }
+}
@@ -59,13 +59,22 @@ mod util;
 mod hwdevices;
 use hwdevices::{KeyboardDevice, KeyboardHidEvent, MiscDevice, MouseDevice, MouseHidEvent};
 
+mod battery;
+mod calibration;
+mod canvas_stream;
 mod color_scheme;
 mod constants;
 mod dbus_interface;
 mod events;
+mod layout;
+mod metrics;
+mod overlays;
 mod plugin_manager;
 mod plugins;
+mod profile_manager;
 mod profiles;
+mod recording;
+mod scheduler;
 mod scripting;
 mod state;
 
@@ -439,16 +448,25 @@ pub fn switch_profile(
 
         let profile = Profile::new_fail_safe();
 
+        scheduler::set_profile_frame_rate(profile.target_fps);
+
         // spawn a new set of Lua VMs, with scripts from the failsafe profile
-        for (thread_idx, manifest) in profile.manifests.values().enumerate() {
+        for (thread_idx, (manifest, layer)) in profile.get_layer_stack().into_iter().enumerate() {
             let (lua_tx, lua_rx) = unbounded();
             let parameters = &manifest.get_merged_parameters(&profile);
-            threads::spawn_lua_thread(thread_idx, lua_rx, &manifest.script_file, parameters)
-                .unwrap_or_else(|e| {
-                    errors_present = true;
+            threads::spawn_lua_thread(
+                thread_idx,
+                lua_rx,
+                &manifest.script_file,
+                parameters,
+                &manifest.capabilities,
+                layer,
+            )
+            .unwrap_or_else(|e| {
+                errors_present = true;
 
-                    error!("Could not spawn a thread: {}", e);
-                });
+                error!("Could not spawn a thread: {}", e);
+            });
 
             let mut tx = LuaTx::new(manifest.script_file.to_owned(), lua_tx);
 
@@ -541,14 +559,19 @@ pub fn switch_profile(
 
                 let mut num_vms = 0; // only valid if no errors occurred
 
-                // now spawn a new set of Lua VMs, with scripts from the new profile
-                for (thread_idx, manifest) in profile.manifests.values().enumerate() {
+                // now spawn a new set of Lua VMs, with scripts from the new profile,
+                // in the order of the layer stack, from the bottom to the top
+                for (thread_idx, (manifest, layer)) in
+                    profile.get_layer_stack().into_iter().enumerate()
+                {
                     let (lua_tx, lua_rx) = unbounded();
                     if let Err(e) = threads::spawn_lua_thread(
                         thread_idx,
                         lua_rx,
                         &manifest.script_file,
                         &manifest.get_merged_parameters(&profile),
+                        &manifest.capabilities,
+                        layer,
                     ) {
                         errors_present = true;
 
@@ -585,7 +608,10 @@ pub fn switch_profile(
                         .unwrap()
                         .get_int("global.profile_fade_milliseconds")
                         .unwrap_or(constants::FADE_MILLIS as i64);
-                    let fade_frames = (fade_millis * constants::TARGET_FPS as i64 / 1000) as isize;
+                    scheduler::set_profile_frame_rate(profile.target_fps);
+
+                    let fade_frames =
+                        (fade_millis * scheduler::target_fps() as i64 / 1000) as isize;
                     crate::BRIGHTNESS_FADER.store(fade_frames, Ordering::SeqCst);
                     crate::BRIGHTNESS_FADER_BASE.store(fade_frames, Ordering::SeqCst);
 
@@ -1014,11 +1040,14 @@ fn run_main_loop(
             // use 'device status poll' code to detect failed/disconnected devices as well,
             // by forcing a write to the device. This is required for hotplug to work correctly in
             // case we didn't transfer data to the device for an extended period of time
-            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+            scheduler::refresh_devices();
         }
 
+        // may have been changed by switching profiles
+        let target_fps = scheduler::target_fps();
+
         // now, process events from all available sources...
-        let result = sel.wait_timeout(Duration::from_millis(1000 / (constants::TARGET_FPS * 2)));
+        let result = sel.wait_timeout(Duration::from_millis(1000 / (target_fps * 2)));
 
         let timedout = if let Err(result) = result {
             match result {
@@ -1078,9 +1107,7 @@ fn run_main_loop(
             return Err(MainError::DeviceFailed {}.into());
         }
 
-        if delay_time_hid_poll.elapsed()
-            >= Duration::from_millis(1000 / (constants::TARGET_FPS * 8))
-        {
+        if delay_time_hid_poll.elapsed() >= Duration::from_millis(1000 / (target_fps * 8)) {
             #[cfg(feature = "profiling")]
             coz::scope!("HID events polling");
 
@@ -1101,29 +1128,50 @@ fn run_main_loop(
         }
 
         if !device_has_failed
-            && delay_time_render.elapsed() >= Duration::from_millis(1000 / constants::TARGET_FPS)
+            && delay_time_render.elapsed() >= Duration::from_millis(1000 / target_fps)
         {
             #[cfg(feature = "profiling")]
             coz::scope!("render code");
 
-            let delta =
-                (delay_time_render.elapsed().as_millis() as u64 / constants::TARGET_FPS) as u32;
+            // the number of frames that have passed, at the target frame rate
+            let delta = ((delay_time_render.elapsed().as_millis() as u64 * target_fps + 500) / 1000)
+                .max(1) as u32;
 
             delay_time_render = Instant::now();
 
-            // send timer tick events to the Lua VMs
-            for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                // if this tx failed previously, then skip it completely
-                if !FAILED_TXS.read().contains(&index) {
-                    lua_tx
-                        .send(script::Message::Tick(delta))
-                        .unwrap_or_else(|e| {
-                            error!("Send error during timer tick event: {}", e);
-                            FAILED_TXS.write().insert(index);
-                        });
+            {
+                let lua_txs = LUA_TXS.read();
+
+                // scripts that exceed the CPU budget may be skipped on this frame
+                let deltas = scheduler::schedule_ticks(
+                    lua_txs.iter().map(|tx| tx.script_file.as_path()),
+                    delta,
+                );
+
+                // send timer tick events to the Lua VMs
+                for (index, (lua_tx, delta)) in lua_txs.iter().zip(deltas).enumerate() {
+                    // if this tx failed previously, then skip it completely
+                    if let Some(delta) = delta.filter(|_| !FAILED_TXS.read().contains(&index)) {
+                        lua_tx
+                            .send(script::Message::Tick(delta))
+                            .unwrap_or_else(|e| {
+                                error!("Send error during timer tick event: {}", e);
+                                FAILED_TXS.write().insert(index);
+                            });
+                    }
                 }
             }
 
+            // repaint the canvas after overlays have expired
+            if overlays::expire_overlays() {
+                script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+            }
+
+            // keep the low battery warning pulsing, even if the effects are static
+            if battery::is_overlay_active() {
+                script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+            }
+
             // finally, update the LEDs if necessary
             DEV_IO_TX
                 .lock()
@@ -1147,14 +1195,16 @@ fn run_main_loop(
         }
 
         let elapsed_after_sleep = start_time.elapsed().as_millis();
-        if elapsed_after_sleep > (1000 / constants::TARGET_FPS + 82_u64).into() {
+        if elapsed_after_sleep > (1000 / target_fps + 82_u64).into() {
             warn!("More than 82 milliseconds of jitter detected!");
             warn!("This means that we dropped at least one frame");
             warn!(
                 "Loop took: {} milliseconds, goal: {}",
                 elapsed_after_sleep,
-                1000 / constants::TARGET_FPS
+                1000 / target_fps
             );
+
+            metrics::frame_late();
         } /* else if elapsed_after_sleep < 5_u128 {
               warn!("Short loop detected");
               warn!(
@@ -1277,6 +1327,11 @@ fn remove_failed_devices() -> Result<bool> {
             .unwrap_or_else(|e| error!("Could not send a pending dbus API event: {}", e));
     }
 
+    if result {
+        // the indices of the devices have changed
+        battery::reset();
+    }
+
     Ok(result)
 }
 
@@ -1435,6 +1490,19 @@ pub fn init_keyboard_device(keyboard_device: &KeyboardDevice) {
         );
     });
 
+    // firmware key remappings are sent as part of the initialization handshake
+    let key_remaps = hwdevices::remap::get_key_remap(&layout::device_make_model_serial(
+        keyboard_device.read().as_device(),
+    ));
+
+    if !key_remaps.is_empty() {
+        info!("Applying firmware key remappings...");
+        keyboard_device
+            .write()
+            .set_hardware_key_remap(&key_remaps)
+            .unwrap_or_else(|e| error!("Could not remap keys: {}", e));
+    }
+
     // send initialization handshake
     info!("Initializing keyboard device...");
     keyboard_device
@@ -1663,6 +1731,14 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
         }
     }
 
+    // merge additional VID/PID aliases and maturity level overrides into the driver registry
+    let driver_registry_file = config
+        .get::<String>("global.driver_registry_file")
+        .unwrap_or_else(|_| constants::DEFAULT_DRIVER_REGISTRY_FILE.to_string());
+
+    hwdevices::driver_registry::load_driver_registry_file(&PathBuf::from(driver_registry_file))
+        .unwrap_or_else(|e| error!("Could not load the driver registry file: {}", e));
+
     // load and initialize global runtime state
     info!("Loading saved state...");
     state::init_global_runtime_state()
@@ -1708,14 +1784,20 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                     info!("Spawning keyboard input thread...");
 
                     let (kbd_tx, kbd_rx) = unbounded();
-                    threads::spawn_keyboard_input_thread(
-                        kbd_tx.clone(),
-                        device.clone(),
-                        index,
-                        usb_vid,
-                        usb_pid,
-                    )
-                    .unwrap_or_else(|e| {
+
+                    let result = if hwdevices::is_virtual_device(device.read().as_device()) {
+                        threads::spawn_virtual_input_thread(kbd_tx.clone(), index)
+                    } else {
+                        threads::spawn_keyboard_input_thread(
+                            kbd_tx.clone(),
+                            device.clone(),
+                            index,
+                            usb_vid,
+                            usb_pid,
+                        )
+                    };
+
+                    result.unwrap_or_else(|e| {
                         error!("Could not spawn a thread: {}", e);
                         panic!()
                     });
@@ -1739,14 +1821,19 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
                         // spawn a thread to handle mouse input
                         info!("Spawning mouse input thread...");
 
-                        spawn_mouse_input_thread(
-                            mouse_tx.clone(),
-                            device.clone(),
-                            index,
-                            usb_vid,
-                            usb_pid,
-                        )
-                        .unwrap_or_else(|e| {
+                        let result = if hwdevices::is_virtual_device(device.read().as_device()) {
+                            threads::spawn_virtual_input_thread(mouse_tx.clone(), index)
+                        } else {
+                            spawn_mouse_input_thread(
+                                mouse_tx.clone(),
+                                device.clone(),
+                                index,
+                                usb_vid,
+                                usb_pid,
+                            )
+                        };
+
+                        result.unwrap_or_else(|e| {
                             error!("Could not spawn a thread: {}", e);
                             panic!()
                         });
@@ -1843,6 +1930,15 @@ pub async fn async_main() -> std::result::Result<(), eyre::Error> {
 
                 *DBUS_API_TX.lock() = Some(dbus_api_tx.clone());
 
+                // initialize the canvas stream
+                info!("Initializing canvas stream...");
+                canvas_stream::spawn_canvas_stream_threads()
+                    .unwrap_or_else(|e| error!("Could not initialize the canvas stream: {}", e));
+
+                // initialize the metrics socket, if enabled
+                metrics::spawn_metrics_socket_thread()
+                    .unwrap_or_else(|e| error!("Could not initialize the metrics socket: {}", e));
+
                 let (fsevents_tx, fsevents_rx) = unbounded();
                 register_filesystem_watcher(fsevents_tx, PathBuf::from(&config_file))
                     .unwrap_or_else(|e| error!("Could not register file changes watcher: {}", e));
@@ -0,0 +1,504 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Metrics of the daemon
+//!
+//! Counts the frames that have been rendered, skipped, dropped or that have been late, the
+//! duration of the `on_tick()` handler of each script, the latency and failures of the HID
+//! writes to each device, the D-Bus method calls and the input events that have been dropped.
+//!
+//! The metrics are available via the `org.eruption.Metrics` D-Bus interface, and optionally via
+//! the UNIX domain socket `METRICS_SOCKET_NAME`, in OpenMetrics text format. The socket accepts
+//! plain HTTP `GET` requests, e.g. `curl --unix-socket /run/eruption/metrics.sock http://localhost/metrics`,
+//! clients that do not send a request receive the bare metrics.
+
+use lazy_static::lazy_static;
+use log::*;
+use nix::unistd::unlink;
+use parking_lot::Mutex;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Content type of the OpenMetrics text format
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// A histogram of durations, with the buckets of `METRICS_LATENCY_BUCKETS_MICROS`
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    /// Cumulative counts, i.e. each bucket includes all observations of the buckets below it
+    buckets: [u64; constants::METRICS_LATENCY_BUCKETS_MICROS.len()],
+
+    pub count: u64,
+    pub sum_micros: u64,
+    pub max_micros: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let micros = duration.as_micros().min(u64::MAX as u128) as u64;
+
+        for (bucket, upper_bound) in self
+            .buckets
+            .iter_mut()
+            .zip(constants::METRICS_LATENCY_BUCKETS_MICROS.iter())
+        {
+            if micros <= *upper_bound {
+                *bucket += 1;
+            }
+        }
+
+        self.count += 1;
+        self.sum_micros = self.sum_micros.saturating_add(micros);
+        self.max_micros = self.max_micros.max(micros);
+    }
+
+    /// The mean of all observations, in milliseconds
+    pub fn mean_millis(&self) -> f64 {
+        if self.count > 0 {
+            self.sum_micros as f64 / self.count as f64 / 1000.0
+        } else {
+            0.0
+        }
+    }
+
+    /// The max. of all observations, in milliseconds
+    pub fn max_millis(&self) -> f64 {
+        self.max_micros as f64 / 1000.0
+    }
+}
+
+/// HID writes to a single device
+#[derive(Debug, Clone, Default)]
+pub struct DeviceMetrics {
+    pub writes: Histogram,
+    pub failures: u64,
+}
+
+/// A snapshot of the metrics
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    /// The time at which the metrics have been reset last
+    pub since: Instant,
+
+    pub frames_rendered: u64,
+    pub frames_skipped: u64,
+    pub frames_dropped: u64,
+    pub frames_late: u64,
+
+    /// Duration of the `on_tick()` handler, by name of the script
+    pub script_ticks: BTreeMap<String, Histogram>,
+
+    /// HID writes, by make, model and serial of the device
+    pub device_writes: BTreeMap<String, DeviceMetrics>,
+
+    /// D-Bus method calls, by interface and member
+    pub dbus_calls: BTreeMap<String, u64>,
+
+    /// Dropped input events, by the kind of the source device
+    pub dropped_input_events: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            since: Instant::now(),
+            frames_rendered: 0,
+            frames_skipped: 0,
+            frames_dropped: 0,
+            frames_late: 0,
+            script_ticks: BTreeMap::new(),
+            device_writes: BTreeMap::new(),
+            dbus_calls: BTreeMap::new(),
+            dropped_input_events: BTreeMap::new(),
+        }
+    }
+}
+
+lazy_static! {
+    /// The metrics that have been collected since the start of the daemon, or since the last reset
+    static ref METRICS: Arc<Mutex<Metrics>> = Arc::new(Mutex::new(Metrics::new()));
+}
+
+/// A frame has been sent to the devices
+pub fn frame_rendered() {
+    METRICS.lock().frames_rendered += 1;
+}
+
+/// A frame has not been rendered, since no script submitted a new color map
+pub fn frame_skipped() {
+    METRICS.lock().frames_skipped += 1;
+}
+
+/// A frame has been dropped, e.g. because a Lua VM did not realize its color map in time
+pub fn frame_dropped() {
+    METRICS.lock().frames_dropped += 1;
+}
+
+/// An iteration of the main loop took considerably longer than the frame time
+pub fn frame_late() {
+    METRICS.lock().frames_late += 1;
+}
+
+/// Record the duration of a call to the `on_tick()` handler of a script
+pub fn script_ticked(script_file: &Path, duration: Duration) {
+    let script = script_file
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| script_file.to_string_lossy().to_string());
+
+    METRICS
+        .lock()
+        .script_ticks
+        .entry(script)
+        .or_default()
+        .observe(duration);
+}
+
+/// Record the duration of a HID write to a device, and whether it failed
+pub fn device_written(device: &str, duration: Duration, failed: bool) {
+    let mut metrics = METRICS.lock();
+
+    let device_metrics = metrics.device_writes.entry(device.to_string()).or_default();
+    device_metrics.writes.observe(duration);
+
+    if failed {
+        device_metrics.failures += 1;
+    }
+}
+
+/// Count a D-Bus method call
+pub fn dbus_called(interface: &str, member: &str) {
+    *METRICS
+        .lock()
+        .dbus_calls
+        .entry(format!("{}.{}", interface, member))
+        .or_default() += 1;
+}
+
+/// Count an input event that has been dropped, e.g. by the kernel or by a full queue
+pub fn input_event_dropped(source: &str) {
+    *METRICS
+        .lock()
+        .dropped_input_events
+        .entry(source.to_string())
+        .or_default() += 1;
+}
+
+/// Get a snapshot of the metrics
+pub fn get_metrics() -> Metrics {
+    METRICS.lock().clone()
+}
+
+/// Reset all metrics
+pub fn reset() {
+    *METRICS.lock() = Metrics::new();
+}
+
+/// Render the metrics in OpenMetrics text format
+pub fn render_openmetrics() -> String {
+    render(&METRICS.lock())
+}
+
+fn render(metrics: &Metrics) -> String {
+    let mut result = String::new();
+
+    let _ = writeln!(result, "# TYPE eruption_frames counter");
+    let _ = writeln!(result, "# HELP eruption_frames Frames of the render loop");
+
+    for (state, value) in [
+        ("rendered", metrics.frames_rendered),
+        ("skipped", metrics.frames_skipped),
+        ("dropped", metrics.frames_dropped),
+        ("late", metrics.frames_late),
+    ] {
+        let _ = writeln!(
+            result,
+            "eruption_frames_total{{state=\"{}\"}} {}",
+            state, value
+        );
+    }
+
+    let _ = writeln!(result, "# TYPE eruption_script_tick_seconds histogram");
+    let _ = writeln!(
+        result,
+        "# HELP eruption_script_tick_seconds Duration of the on_tick() handler of the scripts"
+    );
+
+    for (script, histogram) in metrics.script_ticks.iter() {
+        let labels = format!("script=\"{}\"", escape_label_value(script));
+        render_histogram(
+            &mut result,
+            "eruption_script_tick_seconds",
+            &labels,
+            histogram,
+        );
+    }
+
+    let _ = writeln!(result, "# TYPE eruption_device_write_seconds histogram");
+    let _ = writeln!(
+        result,
+        "# HELP eruption_device_write_seconds Latency of the HID writes to the devices"
+    );
+
+    for (device, device_metrics) in metrics.device_writes.iter() {
+        let labels = format!("device=\"{}\"", escape_label_value(device));
+        render_histogram(
+            &mut result,
+            "eruption_device_write_seconds",
+            &labels,
+            &device_metrics.writes,
+        );
+    }
+
+    let _ = writeln!(result, "# TYPE eruption_device_write_failures counter");
+    let _ = writeln!(
+        result,
+        "# HELP eruption_device_write_failures Failed HID writes to the devices"
+    );
+
+    for (device, device_metrics) in metrics.device_writes.iter() {
+        let _ = writeln!(
+            result,
+            "eruption_device_write_failures_total{{device=\"{}\"}} {}",
+            escape_label_value(device),
+            device_metrics.failures
+        );
+    }
+
+    let _ = writeln!(result, "# TYPE eruption_dbus_calls counter");
+    let _ = writeln!(result, "# HELP eruption_dbus_calls D-Bus method calls");
+
+    for (method, value) in metrics.dbus_calls.iter() {
+        let _ = writeln!(
+            result,
+            "eruption_dbus_calls_total{{method=\"{}\"}} {}",
+            escape_label_value(method),
+            value
+        );
+    }
+
+    let _ = writeln!(result, "# TYPE eruption_dropped_input_events counter");
+    let _ = writeln!(
+        result,
+        "# HELP eruption_dropped_input_events Input events that have been dropped"
+    );
+
+    for (source, value) in metrics.dropped_input_events.iter() {
+        let _ = writeln!(
+            result,
+            "eruption_dropped_input_events_total{{source=\"{}\"}} {}",
+            escape_label_value(source),
+            value
+        );
+    }
+
+    let _ = writeln!(result, "# EOF");
+
+    result
+}
+
+fn render_histogram(result: &mut String, name: &str, labels: &str, histogram: &Histogram) {
+    for (bucket, upper_bound) in histogram
+        .buckets
+        .iter()
+        .zip(constants::METRICS_LATENCY_BUCKETS_MICROS.iter())
+    {
+        let _ = writeln!(
+            result,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            name,
+            labels,
+            *upper_bound as f64 / 1_000_000.0,
+            bucket
+        );
+    }
+
+    let _ = writeln!(
+        result,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        name, labels, histogram.count
+    );
+    let _ = writeln!(result, "{}_count{{{}}} {}", name, labels, histogram.count);
+    let _ = writeln!(
+        result,
+        "{}_sum{{{}}} {}",
+        name,
+        labels,
+        histogram.sum_micros as f64 / 1_000_000.0
+    );
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Bind the metrics socket and spawn the thread that serves the metrics, but only if the socket
+/// has been enabled in the configuration file
+pub fn spawn_metrics_socket_thread() -> Result<()> {
+    let enabled = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get::<bool>("metrics.enable_socket").ok())
+        .unwrap_or(false);
+
+    if !enabled {
+        return Ok(());
+    }
+
+    // unlink any leftover sockets
+    let _result = unlink(constants::METRICS_SOCKET_NAME)
+        .map_err(|e| debug!("Unlink of metrics socket failed: {}", e));
+
+    let listener = UnixListener::bind(constants::METRICS_SOCKET_NAME)?;
+
+    let mut perms = fs::metadata(constants::METRICS_SOCKET_NAME)?.permissions();
+    perms.set_mode(0o666);
+    fs::set_permissions(constants::METRICS_SOCKET_NAME, perms)?;
+
+    let builder = thread::Builder::new().name("metrics".into());
+    builder.spawn(move || {
+        for client in listener.incoming() {
+            match client {
+                Ok(client) => {
+                    serve_client(client)
+                        .unwrap_or_else(|e| debug!("Could not serve a metrics client: {}", e));
+                }
+
+                Err(e) => error!("Could not accept a metrics client: {}", e),
+            }
+        }
+    })?;
+
+    Ok(())
+}
+
+fn serve_client(mut client: UnixStream) -> Result<()> {
+    let timeout = Duration::from_millis(constants::METRICS_SOCKET_READ_TIMEOUT_MILLIS);
+
+    client.set_read_timeout(Some(timeout))?;
+    client.set_write_timeout(Some(timeout))?;
+
+    // clients that do not send an HTTP request within the timeout receive the bare metrics
+    let mut buf = [0_u8; 1024];
+    let is_http = matches!(client.read(&mut buf), Ok(len) if buf[..len].starts_with(b"GET "));
+
+    let body = render_openmetrics();
+
+    if is_http {
+        write!(
+            client,
+            "HTTP/1.0 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            OPENMETRICS_CONTENT_TYPE,
+            body.len()
+        )?;
+    }
+
+    client.write_all(body.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::default();
+
+        histogram.observe(Duration::from_micros(75));
+        histogram.observe(Duration::from_micros(2000));
+        histogram.observe(Duration::from_secs(1));
+
+        assert_eq!(histogram.buckets[0], 0);
+        assert_eq!(histogram.buckets[1], 1);
+        assert_eq!(histogram.buckets[5], 2);
+        assert_eq!(histogram.buckets[9], 2);
+
+        assert_eq!(histogram.count, 3);
+        assert_eq!(histogram.max_micros, 1_000_000);
+        assert!((histogram.mean_millis() - 334.025).abs() < 0.001);
+    }
+
+    #[test]
+    fn render_openmetrics_text() {
+        let mut metrics = Metrics::new();
+        metrics.frames_rendered = 42;
+
+        metrics
+            .script_ticks
+            .entry("organic.lua".to_string())
+            .or_default()
+            .observe(Duration::from_micros(300));
+
+        let device_metrics = metrics
+            .device_writes
+            .entry("0x1e7d:0x311a:".to_string())
+            .or_default();
+        device_metrics.writes.observe(Duration::from_millis(3));
+        device_metrics.failures = 1;
+
+        metrics
+            .dbus_calls
+            .insert("org.eruption.Status.GetRenderStats".to_string(), 7);
+
+        let text = render(&metrics);
+
+        assert!(text.contains("eruption_frames_total{state=\"rendered\"} 42\n"));
+        assert!(text.contains(
+            "eruption_script_tick_seconds_bucket{script=\"organic.lua\",le=\"0.00025\"} 0\n"
+        ));
+        assert!(text.contains(
+            "eruption_script_tick_seconds_bucket{script=\"organic.lua\",le=\"0.0005\"} 1\n"
+        ));
+        assert!(text.contains("eruption_script_tick_seconds_count{script=\"organic.lua\"} 1\n"));
+        assert!(
+            text.contains("eruption_device_write_seconds_sum{device=\"0x1e7d:0x311a:\"} 0.003\n")
+        );
+        assert!(
+            text.contains("eruption_device_write_failures_total{device=\"0x1e7d:0x311a:\"} 1\n")
+        );
+        assert!(text.contains(
+            "eruption_dbus_calls_total{method=\"org.eruption.Status.GetRenderStats\"} 7\n"
+        ));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
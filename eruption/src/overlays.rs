@@ -0,0 +1,270 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Overlays that are composited on top of the effect pipeline
+//!
+//! D-Bus clients submit a sparse set of LED colors with a priority and a time-to-live via
+//! `SetLedColors`, e.g. to flash a notification. Overlays of higher priority are blended on top
+//! of overlays of lower priority; overlays of equal priority are blended in the order they have
+//! been submitted. Overlays are dropped as soon as they expire, or when the D-Bus client that
+//! submitted them disconnects from the bus.
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OverlayError {
+    #[error("LED index {index} is out of range, the canvas holds {size} LEDs")]
+    InvalidIndex { index: usize, size: usize },
+
+    #[error("Invalid time-to-live, expected: 1..{max} milliseconds")]
+    InvalidTtl { max: u32 },
+
+    #[error("Too many overlays, a client may submit up to {max} overlays")]
+    TooManyOverlays { max: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub id: u64,
+
+    /// The unique D-Bus name of the client that submitted the overlay
+    pub owner: String,
+
+    pub priority: i32,
+    pub expires: Instant,
+
+    /// Sparse set of LED colors, as pairs of the index on the canvas and the color
+    pub colors: Vec<(usize, RGBA)>,
+}
+
+/// The id of the next overlay
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Set to true while at least one overlay is active, so that the render thread may skip
+/// the overlays without acquiring a lock
+static OVERLAYS_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Active overlays, ordered by ascending priority
+    static ref OVERLAYS: Arc<RwLock<Vec<Overlay>>> = Arc::new(RwLock::new(Vec::new()));
+}
+
+/// Submit a new overlay on behalf of the D-Bus client `owner`. Returns the id of the overlay
+pub fn add_overlay(
+    owner: &str,
+    priority: i32,
+    ttl_millis: u32,
+    colors: Vec<(usize, RGBA)>,
+    canvas_size: usize,
+) -> Result<u64> {
+    if ttl_millis == 0 || ttl_millis > constants::MAX_OVERLAY_TTL_MILLIS {
+        return Err(OverlayError::InvalidTtl {
+            max: constants::MAX_OVERLAY_TTL_MILLIS,
+        }
+        .into());
+    }
+
+    if let Some((index, _)) = colors.iter().find(|(index, _)| *index >= canvas_size) {
+        return Err(OverlayError::InvalidIndex {
+            index: *index,
+            size: canvas_size,
+        }
+        .into());
+    }
+
+    let mut overlays = OVERLAYS.write();
+
+    if overlays.iter().filter(|o| o.owner == owner).count() >= constants::MAX_OVERLAYS_PER_CLIENT {
+        return Err(OverlayError::TooManyOverlays {
+            max: constants::MAX_OVERLAYS_PER_CLIENT,
+        }
+        .into());
+    }
+
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+
+    let overlay = Overlay {
+        id,
+        owner: owner.to_string(),
+        priority,
+        expires: Instant::now() + Duration::from_millis(ttl_millis as u64),
+        colors,
+    };
+
+    // keep the overlays ordered by priority, after overlays of equal priority
+    let position = overlays.partition_point(|o| o.priority <= priority);
+    overlays.insert(position, overlay);
+
+    OVERLAYS_ACTIVE.store(true, Ordering::SeqCst);
+
+    Ok(id)
+}
+
+/// Remove the overlay `id`, if it has been submitted by `owner`
+pub fn remove_overlay(owner: &str, id: u64) -> bool {
+    remove_overlays_where(|o| o.id == id && o.owner == owner)
+}
+
+/// Remove all overlays of the D-Bus client `owner`, e.g. after the client disconnected
+pub fn remove_overlays_of(owner: &str) -> bool {
+    remove_overlays_where(|o| o.owner == owner)
+}
+
+/// Remove all expired overlays. Returns true if at least one overlay has been removed
+pub fn expire_overlays() -> bool {
+    if !OVERLAYS_ACTIVE.load(Ordering::SeqCst) {
+        return false;
+    }
+
+    let now = Instant::now();
+
+    remove_overlays_where(|o| o.expires <= now)
+}
+
+fn remove_overlays_where<F>(f: F) -> bool
+where
+    F: Fn(&Overlay) -> bool,
+{
+    let mut overlays = OVERLAYS.write();
+
+    let len = overlays.len();
+    overlays.retain(|o| !f(o));
+
+    OVERLAYS_ACTIVE.store(!overlays.is_empty(), Ordering::SeqCst);
+
+    overlays.len() != len
+}
+
+/// Returns true if at least one overlay is active
+pub fn is_active() -> bool {
+    OVERLAYS_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Blend all active overlays onto the canvas `led_map`. The colors of the overlays are scaled
+/// by `brightness` (in percent)
+pub fn blend_overlays(led_map: &mut [RGBA], brightness: isize) {
+    let brightness = brightness.clamp(0, 100) as u32;
+
+    for overlay in OVERLAYS.read().iter() {
+        for (index, fg) in overlay.colors.iter() {
+            if let Some(bg) = led_map.get_mut(*index) {
+                *bg = blend(*bg, *fg, brightness);
+            }
+        }
+    }
+}
+
+fn blend(bg: RGBA, fg: RGBA, brightness: u32) -> RGBA {
+    let alpha = fg.a as u32;
+
+    let mix = |bg: u8, fg: u8| -> u8 {
+        let fg = fg as u32 * brightness / 100;
+
+        ((alpha * fg + (255 - alpha) * bg as u32) / 255) as u8
+    };
+
+    RGBA {
+        r: mix(bg.r, fg.r),
+        g: mix(bg.g, fg.g),
+        b: mix(bg.b, fg.b),
+        a: bg.a.max(fg.a),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: RGBA = RGBA {
+        r: 0,
+        g: 0,
+        b: 0,
+        a: 255,
+    };
+
+    fn color(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA { r, g, b, a }
+    }
+
+    #[test]
+    fn overlays_are_validated() {
+        assert!(add_overlay(":1.100", 0, 0, vec![], 8).is_err());
+        assert!(add_overlay(":1.100", 0, u32::MAX, vec![], 8).is_err());
+        assert!(add_overlay(":1.100", 0, 1000, vec![(8, BLACK)], 8).is_err());
+    }
+
+    #[test]
+    fn overlays_are_blended_by_priority() -> Result<()> {
+        let red = color(255, 0, 0, 255);
+        let green = color(0, 255, 0, 255);
+
+        add_overlay(":1.101", 10, 60_000, vec![(0, red), (1, red)], 4)?;
+        add_overlay(":1.102", 0, 60_000, vec![(0, green), (2, green)], 4)?;
+
+        let mut led_map = vec![BLACK; 4];
+        blend_overlays(&mut led_map, 100);
+
+        assert_eq!(led_map[0].r, 255);
+        assert_eq!(led_map[0].g, 0);
+        assert_eq!(led_map[1].r, 255);
+        assert_eq!(led_map[2].g, 255);
+        assert_eq!(led_map[3].r + led_map[3].g + led_map[3].b, 0);
+
+        assert!(remove_overlays_of(":1.101"));
+        assert!(remove_overlays_of(":1.102"));
+        assert!(!remove_overlays_of(":1.102"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn overlays_are_only_removed_by_their_owner() -> Result<()> {
+        let id = add_overlay(":1.103", 0, 60_000, vec![(0, BLACK)], 4)?;
+
+        assert!(!remove_overlay(":1.104", id));
+        assert!(remove_overlay(":1.103", id));
+
+        Ok(())
+    }
+
+    #[test]
+    fn blend_with_alpha_and_brightness() {
+        let bg = color(0, 0, 200, 255);
+        let fg = color(255, 0, 0, 128);
+
+        let result = blend(bg, fg, 100);
+        assert_eq!(result.r, 128);
+        assert_eq!(result.b, 99);
+
+        let result = blend(bg, color(200, 0, 0, 255), 50);
+        assert_eq!(result.r, 100);
+        assert_eq!(result.b, 0);
+    }
+}
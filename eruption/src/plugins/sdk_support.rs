@@ -26,7 +26,7 @@ use crate::{
 };
 use flume::unbounded;
 use lazy_static::lazy_static;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use mlua::prelude::*;
 use nix::poll::{poll, PollFd, PollFlags};
 use nix::unistd::unlink;
@@ -34,8 +34,9 @@ use parking_lot::{Mutex, RwLock};
 use prost::Message;
 use socket2::{Domain, SockAddr, Socket, Type};
 use std::any::Any;
-use std::io::Cursor;
+use std::io::{Cursor, ErrorKind, Read, Write};
 use std::mem::MaybeUninit;
+use std::net::{TcpListener, TcpStream};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
@@ -46,7 +47,9 @@ use std::{fs, thread};
 
 use crate::{
     hwdevices::RGBA,
+    layout,
     plugins::{self, Plugin},
+    recording,
     scripting::parameters,
     scripting::parameters_util,
 };
@@ -70,7 +73,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; layout::canvas_size()]));
 }
 
 lazy_static! {
@@ -296,6 +299,512 @@ pub fn claim_hotplugged_devices(_hotplug_info: &HotplugInfo) -> Result<()> {
     Ok(())
 }
 
+/// The events an SDK client has subscribed to, and the state it has last been notified about
+#[derive(Debug)]
+struct Subscription {
+    request: protocol::SubscribeRequest,
+
+    profile_file: String,
+    slot: usize,
+    brightness: isize,
+}
+
+impl Subscription {
+    fn new(request: protocol::SubscribeRequest) -> Self {
+        Self {
+            request,
+            profile_file: active_profile_file(),
+            slot: crate::ACTIVE_SLOT.load(Ordering::SeqCst),
+            brightness: crate::BRIGHTNESS.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Compare the current state against the last notified one and generate notifications for all changes
+    fn poll_notifications(&mut self) -> Vec<protocol::Response> {
+        let mut result = Vec::new();
+
+        let profile_file = active_profile_file();
+        if profile_file != self.profile_file {
+            if self.request.profile_changed {
+                result.push(protocol::Notification {
+                    event: Some(protocol::notification::Event::ProfileChanged(
+                        protocol::ProfileChangedEvent {
+                            profile_file: profile_file.clone(),
+                        },
+                    )),
+                });
+            }
+
+            self.profile_file = profile_file;
+        }
+
+        let slot = crate::ACTIVE_SLOT.load(Ordering::SeqCst);
+        if slot != self.slot {
+            if self.request.slot_changed {
+                result.push(protocol::Notification {
+                    event: Some(protocol::notification::Event::SlotChanged(
+                        protocol::SlotChangedEvent { slot: slot as u64 },
+                    )),
+                });
+            }
+
+            self.slot = slot;
+        }
+
+        let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+        if brightness != self.brightness {
+            if self.request.brightness_changed {
+                result.push(protocol::Notification {
+                    event: Some(protocol::notification::Event::BrightnessChanged(
+                        protocol::BrightnessChangedEvent {
+                            brightness: brightness as i64,
+                        },
+                    )),
+                });
+            }
+
+            self.brightness = brightness;
+        }
+
+        result
+            .into_iter()
+            .map(|notification| protocol::Response {
+                response_message: Some(protocol::response::ResponseMessage::Notification(
+                    notification,
+                )),
+            })
+            .collect()
+    }
+}
+
+fn active_profile_file() -> String {
+    let active_profile = &*crate::ACTIVE_PROFILE.lock();
+    match active_profile {
+        Some(active_profile) => active_profile.profile_file.to_string_lossy().to_string(),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Returns an error response, the connection of the client is kept open
+fn error_response(description: String) -> protocol::response::ResponseMessage {
+    warn!("{}", description);
+
+    protocol::response::ResponseMessage::Error(protocol::ErrorResponse { description })
+}
+
+/// Process a single request of an SDK client, returns the response that shall be sent, if any
+fn handle_request(
+    request: protocol::Request,
+    subscription: &mut Option<Subscription>,
+) -> Result<Option<protocol::Response>> {
+    let response_message = match request.request_message {
+        Some(protocol::request::RequestMessage::Noop(_message)) => {
+            /* Do nothing */
+
+            trace!("NOOP");
+
+            None
+        }
+
+        Some(protocol::request::RequestMessage::Status(_message)) => {
+            trace!("Get Status");
+
+            Some(protocol::response::ResponseMessage::Status(
+                protocol::StatusResponse {
+                    description: "Eruption".to_string(),
+                },
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::ActiveProfile(_message)) => {
+            trace!("Get Active Profile");
+
+            let profile_file = active_profile_file();
+
+            Some(protocol::response::ResponseMessage::ActiveProfile(
+                protocol::ActiveProfileResponse { profile_file },
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::SwitchProfile(message)) => {
+            trace!("Switch Profile");
+
+            let profile_file = PathBuf::from(message.profile_file);
+            let switched = crate::switch_profile_please(Some(&profile_file))?;
+
+            Some(protocol::response::ResponseMessage::SwitchProfile(
+                protocol::SwitchProfileResponse {
+                    switched: switched == SwitchProfileResult::Switched,
+                },
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::SetParameters(message)) => {
+            let parameter_values: Vec<parameters::UntypedParameter> = message
+                .parameter_values
+                .iter()
+                .map(|map| parameters::UntypedParameter {
+                    name: map.0.to_string(),
+                    value: map.1.to_string(),
+                })
+                .collect();
+            parameters_util::apply_parameters(
+                &message.profile_file,
+                &message.script_file,
+                &parameter_values,
+            )?;
+
+            Some(protocol::response::ResponseMessage::SetParameters(
+                protocol::SetParametersResponse {},
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::SetCanvas(message)) => {
+            trace!("Set canvas");
+
+            let payload_map = message.canvas;
+
+            // clients that are not aware of the layout paint the legacy canvas
+            if payload_map.len() != layout::canvas_size() * 4
+                && payload_map.len() != constants::CANVAS_SIZE * 4
+            {
+                return Ok(Some(protocol::Response {
+                    response_message: Some(error_response(format!(
+                        "Invalid canvas, expected {} bytes, found {}",
+                        layout::canvas_size() * 4,
+                        payload_map.len()
+                    ))),
+                }));
+            }
+
+            let led_map: Vec<RGBA> = payload_map
+                .chunks_exact(4)
+                .map(|c| RGBA {
+                    r: c[0],
+                    g: c[1],
+                    b: c[2],
+                    a: c[3],
+                })
+                .collect();
+
+            LED_MAP.write()[..led_map.len()].copy_from_slice(&led_map);
+
+            SDK_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
+
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+            Some(protocol::response::ResponseMessage::SetCanvas(
+                protocol::SetCanvasResponse {},
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::NotifyHotplug(message)) => {
+            trace!("Notify hotplug");
+
+            let payload_hotplug_info = message.payload;
+
+            let config = bincode::config::standard();
+            let hotplug_info: HotplugInfo =
+                bincode::decode_from_slice(&payload_hotplug_info, config)?.0;
+
+            info!("Hotplug event received, trying to claim newly added devices now...");
+
+            claim_hotplugged_devices(&hotplug_info)?;
+
+            // this is required for hotplug to work correctly in case we didn't transfer
+            // data to the device for an extended period of time
+            script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+
+            Some(protocol::response::ResponseMessage::NotifyHotplug(
+                protocol::NotifyHotplugResponse {},
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::Authenticate(_message)) => {
+            trace!("Authenticate");
+
+            // local clients are trusted, network clients are authenticated before
+            // their requests are dispatched here
+            Some(protocol::response::ResponseMessage::Authenticate(
+                protocol::AuthenticateResponse {
+                    authenticated: true,
+                },
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::Subscribe(message)) => {
+            trace!("Subscribe");
+
+            *subscription = Some(Subscription::new(message));
+
+            Some(protocol::response::ResponseMessage::Subscribe(
+                protocol::SubscribeResponse {},
+            ))
+        }
+
+        Some(protocol::request::RequestMessage::StartRecording(message)) => {
+            trace!("Start recording");
+
+            match recording::start_recording(&message.file_name, layout::canvas_size()) {
+                Ok(_path) => Some(protocol::response::ResponseMessage::StartRecording(
+                    protocol::StartRecordingResponse {},
+                )),
+
+                Err(e) => Some(error_response(format!(
+                    "Could not start the recording: {}",
+                    e
+                ))),
+            }
+        }
+
+        Some(protocol::request::RequestMessage::StopRecording(_message)) => {
+            trace!("Stop recording");
+
+            match recording::stop_recording() {
+                Ok(num_frames) => Some(protocol::response::ResponseMessage::StopRecording(
+                    protocol::StopRecordingResponse { num_frames },
+                )),
+
+                Err(e) => Some(error_response(format!(
+                    "Could not stop the recording: {}",
+                    e
+                ))),
+            }
+        }
+
+        Some(protocol::request::RequestMessage::StartPlayback(message)) => {
+            trace!("Start playback");
+
+            let result = recording::get_recording(&message.file_name).and_then(|recording| {
+                let num_frames = recording.frames.len() as u64;
+                let duration_millis = recording.duration().as_millis() as u64;
+
+                recording::start_playback(recording, message.looped, |colors| {
+                    {
+                        // the canvas may have grown since the recording has been made
+                        let mut led_map = LED_MAP.write();
+                        let len = led_map.len().min(colors.len());
+
+                        led_map[..len].copy_from_slice(&colors[..len]);
+                    }
+
+                    SDK_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
+
+                    script::FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+                })?;
+
+                Ok((num_frames, duration_millis))
+            });
+
+            match result {
+                Ok((num_frames, duration_millis)) => {
+                    Some(protocol::response::ResponseMessage::StartPlayback(
+                        protocol::StartPlaybackResponse {
+                            num_frames,
+                            duration_millis,
+                        },
+                    ))
+                }
+
+                Err(e) => Some(error_response(format!(
+                    "Could not start the playback: {}",
+                    e
+                ))),
+            }
+        }
+
+        Some(protocol::request::RequestMessage::StopPlayback(_message)) => {
+            trace!("Stop playback");
+
+            let stopped = recording::stop_playback();
+
+            Some(protocol::response::ResponseMessage::StopPlayback(
+                protocol::StopPlaybackResponse { stopped },
+            ))
+        }
+
+        None => {
+            // not sure how this can happen
+            error!("Protocol error: No message in message payload");
+            return Err(SdkPluginError::PluginError {
+                description: "No message is message payload".to_owned(),
+            }
+            .into());
+        }
+    };
+
+    Ok(response_message.map(|response_message| protocol::Response {
+        response_message: Some(response_message),
+    }))
+}
+
+/// Serve a client of the network listener, the first request has to be a valid authentication
+fn run_network_client(mut stream: TcpStream, token: &str) -> Result<()> {
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(Duration::from_millis(constants::SLEEP_TIME_TIMEOUT)))?;
+
+    let mut frames = FrameReader::default();
+
+    let request = protocol::Request::decode(&frames.read_frame(&mut stream)?[..])?;
+
+    let authenticated = matches!(
+        &request.request_message,
+        Some(protocol::request::RequestMessage::Authenticate(message))
+            if tokens_equal(&message.token, token)
+    );
+
+    write_frame(
+        &mut stream,
+        &protocol::Response {
+            response_message: Some(protocol::response::ResponseMessage::Authenticate(
+                protocol::AuthenticateResponse { authenticated },
+            )),
+        },
+    )?;
+
+    if !authenticated {
+        warn!(
+            "Eruption SDK network client failed to authenticate: {}",
+            stream
+                .peer_addr()
+                .map(|a| a.to_string())
+                .unwrap_or_default()
+        );
+
+        return Ok(());
+    }
+
+    debug!("Eruption SDK network client authenticated");
+
+    // use a short timeout, so that notifications may be sent in between requests
+    stream.set_read_timeout(Some(Duration::from_millis(15)))?;
+
+    let mut subscription: Option<Subscription> = None;
+
+    loop {
+        if crate::QUIT.load(Ordering::SeqCst) {
+            break;
+        }
+
+        match frames.read_frame(&mut stream) {
+            Ok(frame) => {
+                let request = protocol::Request::decode(&frame[..])?;
+
+                if let Some(response) = handle_request(request, &mut subscription)? {
+                    write_frame(&mut stream, &response)?;
+                }
+            }
+
+            Err(e)
+                if matches!(
+                    e.downcast_ref::<std::io::Error>().map(|e| e.kind()),
+                    Some(ErrorKind::WouldBlock) | Some(ErrorKind::TimedOut)
+                ) => {}
+
+            Err(e) => {
+                debug!("Eruption SDK network client disconnected: {}", e);
+
+                break;
+            }
+        }
+
+        // notify a subscribed client about state changes
+        if let Some(subscription) = subscription.as_mut() {
+            for response in subscription.poll_notifications() {
+                write_frame(&mut stream, &response)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_frame(stream: &mut TcpStream, response: &protocol::Response) -> Result<()> {
+    let mut buf = Vec::new();
+    response.encode_length_delimited(&mut buf)?;
+
+    stream.write_all(&buf)?;
+
+    Ok(())
+}
+
+/// Compare tokens in constant time
+fn tokens_equal(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Splits a TCP byte stream into length delimited frames, partially received frames are
+/// kept until the remaining data arrives
+#[derive(Debug, Default)]
+struct FrameReader {
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    /// Read the next frame, honoring the read timeout of the stream
+    fn read_frame<R: Read>(&mut self, stream: &mut R) -> Result<Vec<u8>> {
+        loop {
+            if let Some(frame) = self.take_frame()? {
+                return Ok(frame);
+            }
+
+            let mut tmp = [0_u8; constants::NET_BUFFER_CAPACITY];
+
+            match stream.read(&mut tmp) {
+                Ok(0) => {
+                    return Err(SdkPluginError::PluginError {
+                        description: "Lost connection to Eruption SDK client".to_owned(),
+                    }
+                    .into())
+                }
+
+                Ok(n) => self.buf.extend_from_slice(&tmp[..n]),
+
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn take_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+
+        match prost::decode_length_delimiter(&self.buf[..]) {
+            Ok(len) if len > constants::MAX_SDK_NETWORK_FRAME_SIZE => {
+                Err(SdkPluginError::PluginError {
+                    description: format!("Message too large: {} bytes", len),
+                }
+                .into())
+            }
+
+            Ok(len) => {
+                let start = prost::length_delimiter_len(len);
+
+                if self.buf.len() >= start + len {
+                    let frame = self.buf[start..start + len].to_vec();
+                    self.buf.drain(..start + len);
+
+                    Ok(Some(frame))
+                } else {
+                    Ok(None)
+                }
+            }
+
+            // the length delimiter (a varint of up to 10 bytes) has not been received completely yet
+            Err(_e) if self.buf.len() < 10 => Ok(None),
+
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
 ///
 pub struct SdkSupportPlugin {}
 
@@ -345,6 +854,81 @@ impl SdkSupportPlugin {
         Ok(())
     }
 
+    /// Start the network listener, if it has been enabled in the configuration file
+    pub fn start_network_thread() -> Result<()> {
+        let (enabled, address, token_file) = {
+            let config = crate::CONFIG.lock();
+            let config = config.as_ref().unwrap();
+
+            (
+                config
+                    .get::<bool>("sdk.enable_network_listener")
+                    .unwrap_or(false),
+                config
+                    .get::<String>("sdk.network_listener_address")
+                    .unwrap_or_else(|_| constants::DEFAULT_SDK_NETWORK_ADDRESS.to_string()),
+                config
+                    .get::<String>("sdk.network_token_file")
+                    .unwrap_or_else(|_| constants::DEFAULT_SDK_TOKEN_FILE.to_string()),
+            )
+        };
+
+        if !enabled {
+            return Ok(());
+        }
+
+        let token = fs::read_to_string(&token_file)
+            .map(|t| t.trim().to_string())
+            .unwrap_or_default();
+
+        if token.is_empty() {
+            return Err(SdkPluginError::PluginError {
+                description: format!(
+                    "The SDK network listener requires a token, please create the file {}",
+                    token_file
+                ),
+            }
+            .into());
+        }
+
+        let listener = TcpListener::bind(&address)?;
+
+        info!("The SDK network listener is listening on {}", address);
+
+        let builder = thread::Builder::new().name("sdk-network".into());
+        builder.spawn(move || {
+            for stream in listener.incoming() {
+                if crate::QUIT.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                match stream {
+                    Ok(stream) => {
+                        debug!("Eruption SDK network client connected");
+
+                        let token = token.clone();
+
+                        let builder = thread::Builder::new().name("sdk-client".into());
+                        builder
+                            .spawn(move || {
+                                run_network_client(stream, &token).unwrap_or_else(|e| {
+                                    error!("Eruption SDK network client error: {}", e);
+                                });
+                            })
+                            .unwrap_or_else(|e| {
+                                error!("Could not spawn a thread: {}", e);
+                                panic!()
+                            });
+                    }
+
+                    Err(e) => error!("Could not accept a connection: {}", e),
+                }
+            }
+        })?;
+
+        Ok(())
+    }
+
     pub fn run_io_loop() -> Result<()> {
         unsafe fn assume_init(buf: &[MaybeUninit<u8>]) -> &[u8] {
             &*(buf as *const [MaybeUninit<u8>] as *const [u8])
@@ -366,6 +950,8 @@ impl SdkSupportPlugin {
                         socket.set_send_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
                         socket.set_recv_buffer_size(constants::NET_BUFFER_CAPACITY * 2)?;
 
+                        let mut subscription: Option<Subscription> = None;
+
                         // connection successful, enter event loop now
                         'EVENT_LOOP: loop {
                             if crate::QUIT.load(Ordering::SeqCst) {
@@ -435,300 +1021,18 @@ impl SdkSupportPlugin {
                                             }
                                         };
 
-                                        match request.request_message {
-                                            Some(protocol::request::RequestMessage::Noop(
-                                                _message,
-                                            )) => {
-                                                /* Do nothing */
-
-                                                trace!("NOOP");
-                                            }
-
-                                            Some(protocol::request::RequestMessage::Status(
-                                                _message,
-                                            )) => {
-                                                trace!("Get Status");
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::Status(
-                                                            protocol::StatusResponse {
-                                                                description: "Eruption".to_string(),
-                                                            },
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
+                                        if let Some(response) =
+                                            handle_request(request, &mut subscription)?
+                                        {
+                                            let mut buf = Vec::new();
+                                            response.encode_length_delimited(&mut buf)?;
 
-                                            Some(
-                                                protocol::request::RequestMessage::ActiveProfile(
-                                                    _message,
-                                                ),
-                                            ) => {
-                                                trace!("Get Active Profile");
-
-                                                let profile_file = {
-                                                    let active_profile =
-                                                        &*crate::ACTIVE_PROFILE.lock();
-                                                    match active_profile {
-                                                        Some(active_profile) => active_profile
-                                                            .profile_file
-                                                            .to_string_lossy()
-                                                            .to_string(),
-                                                        None => "Unknown".to_string(),
-                                                    }
-                                                };
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::ActiveProfile(
-                                                            protocol::ActiveProfileResponse {
-                                                                profile_file
-                                                            },
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
-
-                                            Some(
-                                                protocol::request::RequestMessage::SwitchProfile(
-                                                    message,
-                                                ),
-                                            ) => {
-                                                trace!("Switch Profile");
-
-                                                let profile_file =
-                                                    PathBuf::from(message.profile_file);
-                                                let switched = crate::switch_profile_please(Some(
-                                                    &profile_file,
-                                                ))?;
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::SwitchProfile(
-                                                            protocol::SwitchProfileResponse {
-                                                                switched: switched == SwitchProfileResult::Switched
-                                                            },
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
-
-                                            Some(
-                                                protocol::request::RequestMessage::SetParameters(
-                                                    message,
-                                                ),
-                                            ) => {
-                                                let parameter_values: Vec<
-                                                    parameters::UntypedParameter,
-                                                > = message
-                                                    .parameter_values
-                                                    .iter()
-                                                    .map(|map| parameters::UntypedParameter {
-                                                        name: map.0.to_string(),
-                                                        value: map.1.to_string(),
-                                                    })
-                                                    .collect();
-                                                parameters_util::apply_parameters(
-                                                    &message.profile_file,
-                                                    &message.script_file,
-                                                    &parameter_values,
-                                                )?;
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::SetParameters(
-                                                            protocol::SetParametersResponse {},
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
-
-                                            Some(protocol::request::RequestMessage::SetCanvas(
-                                                message,
-                                            )) => {
-                                                trace!("Set canvas");
-
-                                                let payload_map = message.canvas;
-
-                                                let mut led_map = [RGBA {
-                                                    r: 0,
-                                                    g: 0,
-                                                    b: 0,
-                                                    a: 0,
-                                                };
-                                                    constants::CANVAS_SIZE];
-
-                                                let mut i = 0;
-                                                let mut cntr = 0;
-
-                                                loop {
-                                                    led_map[cntr] = RGBA {
-                                                        r: payload_map[i],
-                                                        g: payload_map[i + 1],
-                                                        b: payload_map[i + 2],
-                                                        a: payload_map[i + 3],
-                                                    };
-
-                                                    i += 4;
-                                                    cntr += 1;
-
-                                                    if cntr >= led_map.len()
-                                                        || i >= payload_map.len()
-                                                    {
-                                                        break;
-                                                    }
-                                                }
-
-                                                LED_MAP.write().copy_from_slice(&led_map);
-
-                                                SDK_SUPPORT_ACTIVE.store(true, Ordering::SeqCst);
-
-                                                script::FRAME_GENERATION_COUNTER
-                                                    .fetch_add(1, Ordering::SeqCst);
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::SetCanvas(
-                                                            protocol::SetCanvasResponse {},
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
-
-                                            Some(
-                                                protocol::request::RequestMessage::NotifyHotplug(
-                                                    message,
-                                                ),
-                                            ) => {
-                                                trace!("Notify hotplug");
-
-                                                let payload_hotplug_info = message.payload;
-
-                                                let config = bincode::config::standard();
-                                                let hotplug_info: HotplugInfo =
-                                                    bincode::decode_from_slice(
-                                                        &payload_hotplug_info,
-                                                        config,
-                                                    )?
-                                                    .0;
-
-                                                info!("Hotplug event received, trying to claim newly added devices now...");
-
-                                                claim_hotplugged_devices(&hotplug_info)?;
-
-                                                // this is required for hotplug to work correctly in case we didn't transfer
-                                                // data to the device for an extended period of time
-                                                script::FRAME_GENERATION_COUNTER
-                                                    .fetch_add(1, Ordering::SeqCst);
-
-                                                let response = protocol::Response {
-                                                    response_message: Some(
-                                                        protocol::response::ResponseMessage::NotifyHotplug(
-                                                            protocol::NotifyHotplugResponse {},
-                                                        ),
-                                                    ),
-                                                };
-
-                                                let mut buf = Vec::new();
-                                                response.encode_length_delimited(&mut buf)?;
-
-                                                // send data
-                                                match socket.send(&buf) {
-                                                    Ok(_n) => {}
-
-                                                    Err(_e) => {
-                                                        return Err(SdkPluginError::PluginError {
-                                                            description: "Lost connection to Eruption SDK client".to_owned(),
-                                                        }
-                                                            .into());
-                                                    }
-                                                }
-                                            }
-
-                                            None => {
-                                                // not sure how this can happen
-                                                error!(
-                                                    "Protocol error: No message in message payload"
-                                                );
+                                            // send data
+                                            if socket.send(&buf).is_err() {
                                                 return Err(SdkPluginError::PluginError {
-                                                    description: "No message is message payload"
-                                                        .to_owned(),
+                                                    description:
+                                                        "Lost connection to Eruption SDK client"
+                                                            .to_owned(),
                                                 }
                                                 .into());
                                             }
@@ -737,6 +1041,22 @@ impl SdkSupportPlugin {
                                 }
                             }
 
+                            // notify a subscribed client about state changes
+                            if let Some(subscription) = subscription.as_mut() {
+                                for response in subscription.poll_notifications() {
+                                    let mut buf = Vec::new();
+                                    response.encode_length_delimited(&mut buf)?;
+
+                                    if socket.send(&buf).is_err() {
+                                        return Err(SdkPluginError::PluginError {
+                                            description: "Lost connection to Eruption SDK client"
+                                                .to_owned(),
+                                        }
+                                        .into());
+                                    }
+                                }
+                            }
+
                             if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
                                 thread::sleep(Duration::from_millis(1));
                             } else {
@@ -773,6 +1093,10 @@ impl Plugin for SdkSupportPlugin {
         Self::initialize_socket()?;
         Self::start_control_thread()?;
 
+        Self::start_network_thread().unwrap_or_else(|e| {
+            error!("Could not start the SDK network listener: {}", e);
+        });
+
         // events::register_observer(|event: &events::Event| {
         //     match event {
         //         events::Event::KeyDown(_index) => {}
@@ -810,3 +1134,198 @@ impl Plugin for SdkSupportPlugin {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send_request(stream: &mut TcpStream, request: protocol::request::RequestMessage) {
+        let mut buf = Vec::new();
+        protocol::Request {
+            request_message: Some(request),
+        }
+        .encode_length_delimited(&mut buf)
+        .unwrap();
+
+        stream.write_all(&buf).unwrap();
+    }
+
+    fn receive_response(
+        stream: &mut TcpStream,
+        frames: &mut FrameReader,
+    ) -> Option<protocol::response::ResponseMessage> {
+        protocol::Response::decode(&frames.read_frame(stream).unwrap()[..])
+            .unwrap()
+            .response_message
+    }
+
+    fn spawn_client_handler() -> (TcpStream, thread::JoinHandle<Result<()>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _address) = listener.accept()?;
+            run_network_client(stream, "secret")
+        });
+
+        (TcpStream::connect(address).unwrap(), handle)
+    }
+
+    #[test]
+    fn network_client_authentication() {
+        let (mut stream, handle) = spawn_client_handler();
+        let mut frames = FrameReader::default();
+
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Authenticate(protocol::AuthenticateRequest {
+                token: "secret".to_string(),
+            }),
+        );
+
+        match receive_response(&mut stream, &mut frames) {
+            Some(protocol::response::ResponseMessage::Authenticate(response)) => {
+                assert!(response.authenticated)
+            }
+            response => panic!("Unexpected response: {:?}", response),
+        }
+
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Status(protocol::StatusRequest {}),
+        );
+
+        match receive_response(&mut stream, &mut frames) {
+            Some(protocol::response::ResponseMessage::Status(response)) => {
+                assert_eq!(response.description, "Eruption")
+            }
+            response => panic!("Unexpected response: {:?}", response),
+        }
+
+        drop(stream);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn network_client_invalid_token() {
+        let (mut stream, handle) = spawn_client_handler();
+        let mut frames = FrameReader::default();
+
+        // any other request than an authentication will be rejected as well
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Authenticate(protocol::AuthenticateRequest {
+                token: "guessed".to_string(),
+            }),
+        );
+
+        match receive_response(&mut stream, &mut frames) {
+            Some(protocol::response::ResponseMessage::Authenticate(response)) => {
+                assert!(!response.authenticated)
+            }
+            response => panic!("Unexpected response: {:?}", response),
+        }
+
+        handle.join().unwrap().unwrap();
+
+        // the connection has been closed by the daemon
+        assert!(frames.read_frame(&mut stream).is_err());
+    }
+
+    #[test]
+    fn reject_malformed_canvas() {
+        let (mut stream, handle) = spawn_client_handler();
+        let mut frames = FrameReader::default();
+
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Authenticate(protocol::AuthenticateRequest {
+                token: "secret".to_string(),
+            }),
+        );
+
+        assert!(matches!(
+            receive_response(&mut stream, &mut frames),
+            Some(protocol::response::ResponseMessage::Authenticate(_))
+        ));
+
+        for canvas in [
+            vec![],
+            vec![0xff; 5],
+            vec![0xff; (layout::canvas_size() + 1) * 4],
+        ] {
+            send_request(
+                &mut stream,
+                protocol::request::RequestMessage::SetCanvas(protocol::SetCanvasRequest { canvas }),
+            );
+
+            match receive_response(&mut stream, &mut frames) {
+                Some(protocol::response::ResponseMessage::Error(response)) => {
+                    assert!(response.description.contains("Invalid canvas"))
+                }
+                response => panic!("Unexpected response: {:?}", response),
+            }
+        }
+
+        drop(stream);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn report_recording_errors() {
+        let (mut stream, handle) = spawn_client_handler();
+        let mut frames = FrameReader::default();
+
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Authenticate(protocol::AuthenticateRequest {
+                token: "secret".to_string(),
+            }),
+        );
+
+        assert!(matches!(
+            receive_response(&mut stream, &mut frames),
+            Some(protocol::response::ResponseMessage::Authenticate(_))
+        ));
+
+        for request in [
+            protocol::request::RequestMessage::StartRecording(protocol::StartRecordingRequest {
+                file_name: "../recording".to_string(),
+            }),
+            protocol::request::RequestMessage::StopRecording(protocol::StopRecordingRequest {}),
+            protocol::request::RequestMessage::StartPlayback(protocol::StartPlaybackRequest {
+                file_name: "../recording".to_string(),
+                looped: false,
+            }),
+        ] {
+            send_request(&mut stream, request);
+
+            match receive_response(&mut stream, &mut frames) {
+                Some(protocol::response::ResponseMessage::Error(_)) => {}
+                response => panic!("Unexpected response: {:?}", response),
+            }
+        }
+
+        // the connection is still open
+        send_request(
+            &mut stream,
+            protocol::request::RequestMessage::Status(protocol::StatusRequest {}),
+        );
+
+        assert!(matches!(
+            receive_response(&mut stream, &mut frames),
+            Some(protocol::response::ResponseMessage::Status(_))
+        ));
+
+        drop(stream);
+        handle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn compare_tokens() {
+        assert!(tokens_equal("secret", "secret"));
+        assert!(!tokens_equal("secret", "secreT"));
+        assert!(!tokens_equal("secret", "secret2"));
+        assert!(!tokens_equal("", "secret"));
+    }
+}
@@ -0,0 +1,495 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Management of profile files on behalf of D-Bus clients
+//!
+//! Profiles are addressed either by their UUID or by the path of their profile file; only
+//! files that reside in one of the profile directories are ever modified or deleted.
+//!
+//! An exported profile is a single JSON document that holds the profile file, its persisted
+//! state and every active script that is not available from the script directories, so that
+//! the profile may be imported on another installation of Eruption. Bundled scripts are
+//! written to the `IMPORTED_SCRIPT_DIR` on import.
+
+use log::*;
+use same_file::is_same_file;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::constants;
+use crate::profiles::{self, Profile};
+use crate::scripting::manifest::Manifest;
+use crate::util;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// The version of the archive format, written by `export_profile()`
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileManagerError {
+    #[error("Could not find profile: {profile}")]
+    NotFound { profile: String },
+
+    #[error("Invalid profile name")]
+    InvalidName {},
+
+    #[error("The profile is assigned to slot {slot}")]
+    AssignedToSlot { slot: usize },
+
+    #[error("Could not find script: {script}")]
+    ScriptNotFound { script: String },
+
+    #[error("Unsupported archive version: {version}")]
+    UnsupportedVersion { version: u32 },
+
+    #[error("Invalid script file name in archive: {file_name}")]
+    InvalidScriptFileName { file_name: String },
+
+    #[error("A different script with the same name is already installed: {path}")]
+    ScriptConflict { path: String },
+}
+
+/// A profile, bundled with its state and its non-stock scripts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileArchive {
+    pub version: u32,
+
+    /// The contents of the profile file
+    pub profile: String,
+
+    /// The contents of the `.profile.state` file, if any
+    #[serde(default)]
+    pub state: Option<String>,
+
+    #[serde(default)]
+    pub scripts: Vec<ArchivedScript>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedScript {
+    pub file_name: String,
+    pub source: String,
+    pub manifest: String,
+}
+
+/// Find the profile file of `profile`, that may either be a UUID or the path of a profile file
+pub fn resolve_profile(profile: &str) -> Result<PathBuf> {
+    let result = match Uuid::parse_str(profile) {
+        Ok(uuid) => profiles::find_path_by_uuid(uuid),
+
+        Err(_) => profiles::get_profile_files()?
+            .into_iter()
+            .find(|p| is_same_file(p, profile).unwrap_or(false)),
+    };
+
+    result.ok_or_else(|| {
+        ProfileManagerError::NotFound {
+            profile: profile.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Create a new profile from a list of scripts. Returns the path of the new profile file
+pub fn create_profile(name: &str, description: &str, scripts: &[String]) -> Result<PathBuf> {
+    let active_scripts = scripts.iter().map(PathBuf::from).collect::<Vec<_>>();
+
+    verify_scripts(&active_scripts)?;
+
+    let profile = Profile {
+        id: Uuid::new_v4(),
+        profile_file: new_profile_file(name)?,
+        name: name.to_string(),
+        description: description.to_string(),
+        active_scripts,
+        ..Default::default()
+    };
+
+    profile.save()?;
+
+    info!("Created profile: {}", profile.profile_file.display());
+
+    Ok(profile.profile_file)
+}
+
+/// Clone the profile `profile` under a new UUID, including its persisted state.
+/// Returns the path of the new profile file
+pub fn clone_profile(profile: &str, name: &str) -> Result<PathBuf> {
+    let profile_file = resolve_profile(profile)?;
+
+    let mut profile = Profile::load_file_only(&profile_file)?;
+
+    profile.id = Uuid::new_v4();
+    profile.name = name.to_string();
+    profile.profile_file = new_profile_file(name)?;
+
+    profile.save()?;
+
+    let state_file = profile_file.with_extension("profile.state");
+    if state_file.exists() {
+        fs::copy(
+            &state_file,
+            profile.profile_file.with_extension("profile.state"),
+        )?;
+    }
+
+    info!(
+        "Cloned profile {} to: {}",
+        profile_file.display(),
+        profile.profile_file.display()
+    );
+
+    Ok(profile.profile_file)
+}
+
+/// Change the name of the profile `profile`; the profile file keeps its path, so that slot
+/// assignments and rules that refer to it stay valid
+pub fn rename_profile(profile: &str, name: &str) -> Result<()> {
+    if name.trim().is_empty() {
+        return Err(ProfileManagerError::InvalidName {}.into());
+    }
+
+    let profile_file = resolve_profile(profile)?;
+
+    let mut profile = Profile::load_file_only(&profile_file)?;
+    profile.name = name.to_string();
+
+    profile.save()?;
+
+    Ok(())
+}
+
+/// Delete the profile `profile` along with its persisted state. Profiles that are assigned
+/// to a slot may not be deleted
+pub fn delete_profile(profile: &str) -> Result<()> {
+    let profile_file = resolve_profile(profile)?;
+
+    if let Some(slot_profiles) = crate::SLOT_PROFILES.lock().as_ref() {
+        if let Some(slot) = slot_profiles
+            .iter()
+            .position(|p| is_same_file(p, &profile_file).unwrap_or(false))
+        {
+            return Err(ProfileManagerError::AssignedToSlot { slot: slot + 1 }.into());
+        }
+    }
+
+    let state_file = profile_file.with_extension("profile.state");
+    if state_file.exists() {
+        fs::remove_file(&state_file)?;
+    }
+
+    fs::remove_file(&profile_file)?;
+
+    info!("Deleted profile: {}", profile_file.display());
+
+    Ok(())
+}
+
+/// Export the profile `profile` as a single JSON document, see `ProfileArchive`
+pub fn export_profile(profile: &str) -> Result<String> {
+    let profile_file = resolve_profile(profile)?;
+    let profile = Profile::load_file_only(&profile_file)?;
+
+    let state_file = profile_file.with_extension("profile.state");
+    let state = if state_file.exists() {
+        Some(fs::read_to_string(&state_file)?)
+    } else {
+        None
+    };
+
+    let mut scripts = Vec::new();
+
+    for script in profile.active_scripts.iter() {
+        let manifest = Manifest::load(script).map_err(|_| ProfileManagerError::ScriptNotFound {
+            script: script.display().to_string(),
+        })?;
+
+        if !is_stock_script(&manifest.script_file) {
+            let file_name = manifest
+                .script_file
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+
+            scripts.push(ArchivedScript {
+                file_name,
+                source: fs::read_to_string(&manifest.script_file)?,
+                manifest: fs::read_to_string(util::get_manifest_for(&manifest.script_file))?,
+            });
+        }
+    }
+
+    let archive = ProfileArchive {
+        version: ARCHIVE_VERSION,
+        profile: fs::read_to_string(&profile_file)?,
+        state,
+        scripts,
+    };
+
+    Ok(serde_json::to_string_pretty(&archive)?)
+}
+
+/// Import a profile that has been exported by `export_profile()`. If a profile with the same
+/// UUID already exists, the imported profile is assigned a new UUID. Returns the path of the
+/// new profile file
+pub fn import_profile(data: &str) -> Result<PathBuf> {
+    let archive: ProfileArchive = serde_json::from_str(data)?;
+
+    if archive.version != ARCHIVE_VERSION {
+        return Err(ProfileManagerError::UnsupportedVersion {
+            version: archive.version,
+        }
+        .into());
+    }
+
+    let mut profile: Profile = toml::de::from_str(&archive.profile)?;
+
+    // validate the archive before anything gets written
+    for script in archive.scripts.iter() {
+        if !is_valid_script_file_name(&script.file_name) {
+            return Err(ProfileManagerError::InvalidScriptFileName {
+                file_name: script.file_name.clone(),
+            }
+            .into());
+        }
+
+        let script_file = Path::new(constants::IMPORTED_SCRIPT_DIR).join(&script.file_name);
+
+        if script_file.exists() && fs::read_to_string(&script_file)? != script.source {
+            return Err(ProfileManagerError::ScriptConflict {
+                path: script_file.display().to_string(),
+            }
+            .into());
+        }
+    }
+
+    // the imported scripts are not available from the script directories,
+    // so refer to them by their absolute path
+    let bundled_scripts: Vec<(PathBuf, &ArchivedScript, String)> = archive
+        .scripts
+        .iter()
+        .map(|script| {
+            let script_file = Path::new(constants::IMPORTED_SCRIPT_DIR).join(&script.file_name);
+            let manifest = strip_capabilities(&script.manifest).map_err(|_| {
+                ProfileManagerError::ScriptNotFound {
+                    script: script_file.display().to_string(),
+                }
+            })?;
+
+            Ok((script_file, script, manifest))
+        })
+        .collect::<Result<_>>()?;
+
+    for (script_file, _script, _manifest) in bundled_scripts.iter() {
+        for active_script in profile.active_scripts.iter_mut() {
+            if active_script.file_name() == script_file.file_name() {
+                *active_script = script_file.clone();
+            }
+        }
+    }
+
+    // bundled scripts are verified in memory, so that a failed import leaves no files behind
+    for active_script in profile.active_scripts.iter() {
+        match bundled_scripts
+            .iter()
+            .find(|(script_file, _script, _manifest)| script_file == active_script)
+        {
+            Some((_script_file, _script, manifest)) => {
+                toml::de::from_str::<Manifest>(manifest).map_err(|_| {
+                    ProfileManagerError::ScriptNotFound {
+                        script: active_script.display().to_string(),
+                    }
+                })?;
+            }
+
+            None => verify_scripts(std::slice::from_ref(active_script))?,
+        }
+    }
+
+    if profiles::find_path_by_uuid(profile.id).is_some() {
+        profile.id = Uuid::new_v4();
+    }
+
+    profile.profile_file = new_profile_file(&profile.name)?;
+
+    if !bundled_scripts.is_empty() {
+        fs::create_dir_all(constants::IMPORTED_SCRIPT_DIR)?;
+    }
+
+    for (script_file, script, manifest) in bundled_scripts.iter() {
+        let manifest_file = util::get_manifest_for(script_file);
+
+        // keep the manifest of a script that is already installed, since it may hold
+        // capabilities that have been granted to the script
+        if script_file.exists() && manifest_file.exists() {
+            continue;
+        }
+
+        fs::write(script_file, &script.source)?;
+        fs::write(manifest_file, manifest)?;
+    }
+
+    profile.save()?;
+
+    if let Some(state) = archive.state {
+        fs::write(profile.profile_file.with_extension("profile.state"), state)?;
+    }
+
+    info!("Imported profile: {}", profile.profile_file.display());
+
+    Ok(profile.profile_file)
+}
+
+/// Make sure that all scripts exist and have a valid manifest
+fn verify_scripts(scripts: &[PathBuf]) -> Result<()> {
+    for script in scripts.iter() {
+        Manifest::load(script).map_err(|_| ProfileManagerError::ScriptNotFound {
+            script: script.display().to_string(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Remove the capabilities from the manifest of an imported script. A profile from an untrusted
+/// source must not be able to lift the sandbox of its scripts, capabilities have to be granted
+/// by editing the manifest of the installed script
+fn strip_capabilities(manifest: &str) -> Result<String> {
+    let mut manifest: toml::Table = toml::de::from_str(manifest)?;
+
+    if let Some(capabilities) = manifest.remove("capabilities") {
+        warn!(
+            "Removed the capabilities {} from the manifest of an imported script",
+            capabilities
+        );
+    }
+
+    Ok(toml::to_string(&manifest)?)
+}
+
+/// Stock scripts are available from the script directories of every installation of Eruption
+fn is_stock_script(script_file: &Path) -> bool {
+    util::get_script_dirs()
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .any(|dir| script_file.starts_with(dir))
+}
+
+fn is_valid_script_file_name(file_name: &str) -> bool {
+    let path = Path::new(file_name);
+
+    path.file_name().map(|f| f == file_name).unwrap_or(false)
+        && path.extension().map(|e| e == "lua").unwrap_or(false)
+}
+
+/// Derive the file name of a profile from its name, e.g. "My Profile" -> "my-profile.profile"
+fn profile_file_name(name: &str, index: usize) -> String {
+    let stem = name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+
+    if index > 1 {
+        format!("{}-{}.profile", stem, index)
+    } else {
+        format!("{}.profile", stem)
+    }
+}
+
+/// Returns a path in the first profile directory that is not in use yet
+fn new_profile_file(name: &str) -> Result<PathBuf> {
+    if profile_file_name(name, 1) == ".profile" {
+        return Err(ProfileManagerError::InvalidName {}.into());
+    }
+
+    let profile_dir = profiles::get_profile_dirs()
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| PathBuf::from(constants::DEFAULT_PROFILE_DIR));
+
+    let result = (1..)
+        .map(|index| profile_dir.join(profile_file_name(name, index)))
+        .find(|path| !path.exists())
+        .unwrap();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid_script_file_name, profile_file_name, strip_capabilities};
+    use crate::scripting::manifest::Manifest;
+
+    #[test]
+    fn derive_profile_file_names() {
+        assert_eq!(profile_file_name("My Profile", 1), "my-profile.profile");
+        assert_eq!(profile_file_name("  Red / Wave! ", 1), "red-wave.profile");
+        assert_eq!(profile_file_name("Red Wave", 3), "red-wave-3.profile");
+        assert_eq!(profile_file_name("???", 1), ".profile");
+    }
+
+    #[test]
+    fn imported_scripts_are_not_granted_capabilities() {
+        let manifest = r#"
+            name = "Macros"
+            description = "Bundled with a profile"
+            version = "0.0.1"
+            author = "Someone"
+            min_supported_version = "0.1.1"
+            tags = ["Demo"]
+            capabilities = ["system", "os", "io", "network", "inject_input"]
+
+            [[config]]
+            type = "int"
+            name = "speed"
+            description = "Speed"
+            default = 5
+        "#;
+
+        let stripped: Manifest =
+            toml::de::from_str(&strip_capabilities(manifest).unwrap()).unwrap();
+        let original: Manifest = toml::de::from_str(manifest).unwrap();
+
+        assert!(stripped.capabilities.is_empty());
+        assert_eq!(stripped.name, original.name);
+        assert_eq!(stripped.config, original.config);
+
+        assert!(strip_capabilities("capabilities = [").is_err());
+    }
+
+    #[test]
+    fn validate_script_file_names() {
+        assert!(is_valid_script_file_name("custom.lua"));
+
+        assert!(!is_valid_script_file_name("custom.lua.manifest"));
+        assert!(!is_valid_script_file_name("../custom.lua"));
+        assert!(!is_valid_script_file_name(
+            "/usr/share/eruption/scripts/custom.lua"
+        ));
+        assert!(!is_valid_script_file_name(""));
+    }
+}
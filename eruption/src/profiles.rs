@@ -33,6 +33,7 @@ use std::{collections::BTreeMap, ffi::OsStr};
 use std::{fs, io};
 use uuid::Uuid;
 
+use crate::scripting::layers::Layer;
 use crate::scripting::manifest::Manifest;
 use crate::scripting::parameters::{
     ProfileConfiguration, ProfileParameter, ProfileScriptParameters, TypedValue,
@@ -40,6 +41,12 @@ use crate::scripting::parameters::{
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
+#[cfg(test)]
+lazy_static::lazy_static! {
+    /// The configuration is global state, tests that replace it have to hold this lock
+    pub static ref CONFIG_LOCK: parking_lot::Mutex<()> = parking_lot::Mutex::new(());
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ProfileError {
     #[error("Could not open profile file for reading")]
@@ -90,6 +97,10 @@ pub struct Profile {
 
     #[serde(default = "default_script_file")]
     pub active_scripts: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_fps: Option<u64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub layers: Vec<Layer>,
     #[serde(default)]
     pub config: ProfileConfiguration,
 
@@ -167,6 +178,8 @@ impl Profile {
             active_scripts: vec![PathBuf::from(
                 "/usr/share/eruption/scripts/lib/failsafe.lua",
             )],
+            target_fps: None,
+            layers: vec![],
             config: ProfileConfiguration::new(),
             manifests: IndexMap::new(),
         };
@@ -246,6 +259,56 @@ impl Profile {
         Ok(())
     }
 
+    /// Returns the manifests of the active scripts along with their layers, ordered from the
+    /// bottom to the top of the layer stack. Scripts without a layer are placed at the bottom
+    pub fn get_layer_stack(&self) -> Vec<(&Manifest, Layer)> {
+        let mut result: Vec<(&Manifest, Layer)> = self
+            .manifests
+            .values()
+            .filter(|manifest| {
+                !self
+                    .layers
+                    .iter()
+                    .any(|layer| layer.matches(&manifest.script_file))
+            })
+            .map(|manifest| {
+                (
+                    manifest,
+                    Layer {
+                        script: manifest.script_file.clone(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        for layer in self.layers.iter() {
+            match self
+                .manifests
+                .values()
+                .find(|manifest| layer.matches(&manifest.script_file))
+            {
+                Some(manifest) => {
+                    if result.iter().any(|(m, _)| m.name == manifest.name) {
+                        warn!(
+                            "Ignoring duplicate layer for script: {}",
+                            layer.script.display()
+                        );
+                    } else {
+                        result.push((manifest, layer.clone()));
+                    }
+                }
+
+                None => warn!(
+                    "Ignoring layer for a script that is not active: {}",
+                    layer.script.display()
+                ),
+            }
+        }
+
+        result
+    }
+
     fn merge_parameters(&mut self) {
         for manifest in self.manifests.values() {
             let profile_script_parameters = self.config.get_parameters_mut(&manifest.name);
@@ -384,6 +447,8 @@ impl Default for Profile {
             name: "Default".into(),
             description: "Auto-generated profile".into(),
             active_scripts: vec![PathBuf::from(constants::DEFAULT_EFFECT_SCRIPT)],
+            target_fps: None,
+            layers: vec![],
             config: ProfileConfiguration::new(),
             manifests: IndexMap::new(),
         }
@@ -664,6 +729,9 @@ mod tests {
 
         let profile_path = assets_path.join("manifest_test.profile").canonicalize()?;
 
+        // the golden frames tests replace the configuration as well
+        let _guard = super::CONFIG_LOCK.lock();
+
         let config = config::Config::builder()
             .set_override(
                 "global.script_dirs",
@@ -794,6 +862,8 @@ mod tests {
                 PathBuf::from("mno"),
                 PathBuf::from("pqr"),
             ],
+            target_fps: None,
+            layers: vec![],
             config: [
                 (
                     "123".to_string(),
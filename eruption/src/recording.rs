@@ -0,0 +1,675 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Recording and playback of the realized canvas
+//!
+//! The recorder captures every frame that has been sent to the devices, along with its timing.
+//! Recordings are stored in `RECORDINGS_DIR` and may be replayed by the `playback.lua` script
+//! or by SDK clients. All integers are little endian:
+//!
+//! | Field    | Type      | Description                                             |
+//! |----------|-----------|---------------------------------------------------------|
+//! | magic    | `[u8; 4]` | `ERCR`                                                  |
+//! | version  | `u16`     | Version of the file format, currently 1                 |
+//! | num_leds | `u32`     | Number of LEDs on the canvas                            |
+//! | frames   | ...       | Frames, until the end of the file                       |
+//!
+//! To keep recordings compact, each frame only holds the spans of LEDs that have changed since
+//! the previous frame (the initial canvas is fully transparent black):
+//!
+//! | Field     | Type      | Description                                            |
+//! |-----------|-----------|--------------------------------------------------------|
+//! | delay     | `u32`     | Microseconds since the previous frame                  |
+//! | num_spans | `u32`     | Number of spans that follow                            |
+//! | start     | `u32`     | Index of the first LED of the span                     |
+//! | len       | `u32`     | Number of LEDs in the span                             |
+//! | colors    | `[u8; 4]` | RGBA color of each LED of the span                     |
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::hwdevices::RGBA;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+const MAGIC: &[u8; 4] = b"ERCR";
+const VERSION: u16 = 1;
+
+/// Runs of unchanged LEDs shorter than this are included in the surrounding span, since
+/// starting a new span costs as much as storing two LEDs
+const MIN_SPAN_GAP: usize = 3;
+
+/// Interval in which the playback thread checks whether it shall terminate
+const PLAYBACK_POLL_MILLIS: u64 = 50;
+
+/// Max. number of decoded recordings that are kept in memory
+const MAX_CACHED_RECORDINGS: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RecordingError {
+    #[error("Invalid file name of a recording: {file_name}")]
+    InvalidFileName { file_name: String },
+
+    #[error("A recording is already in progress")]
+    AlreadyRecording {},
+
+    #[error("No recording is in progress")]
+    NotRecording {},
+
+    #[error("Invalid recording: {description}")]
+    InvalidRecording { description: String },
+
+    #[error("The size of the canvas has changed, expected {expected} LEDs, found {found}")]
+    CanvasSizeMismatch { expected: usize, found: usize },
+
+    #[error("The recording exceeds the max. size of {limit} bytes")]
+    SizeLimitExceeded { limit: u64 },
+}
+
+/// A single decoded frame
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// Offset from the start of the recording
+    pub timestamp: Duration,
+    pub colors: Vec<RGBA>,
+}
+
+/// A decoded recording
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub num_leds: usize,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::decode(
+            &mut BufReader::new(File::open(path)?),
+            crate::layout::canvas_size(),
+        )
+    }
+
+    /// Decode a recording of at most `max_leds` LEDs
+    pub fn decode<R: Read>(reader: &mut R, max_leds: usize) -> Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(RecordingError::InvalidRecording {
+                description: "Not a recording of the canvas".to_string(),
+            }
+            .into());
+        }
+
+        let version = reader.read_u16::<LittleEndian>()?;
+        if version != VERSION {
+            return Err(RecordingError::InvalidRecording {
+                description: format!("Unsupported version: {}", version),
+            }
+            .into());
+        }
+
+        let num_leds = reader.read_u32::<LittleEndian>()? as usize;
+        if num_leds > max_leds {
+            return Err(RecordingError::InvalidRecording {
+                description: format!("{} LEDs exceed the canvas of {} LEDs", num_leds, max_leds),
+            }
+            .into());
+        }
+
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut colors = vec![RGBA::default(); num_leds];
+        let mut timestamp = Duration::ZERO;
+
+        loop {
+            match decode_frame(reader, &mut colors) {
+                Ok(delay) => {
+                    timestamp += Duration::from_micros(delay as u64);
+
+                    frames.push(Frame {
+                        timestamp,
+                        colors: colors.clone(),
+                    });
+                }
+
+                // the recording ends with the last complete frame, the file may have been
+                // truncated if the daemon has been terminated while recording
+                Err(e)
+                    if e.downcast_ref::<io::Error>().map(|e| e.kind())
+                        == Some(io::ErrorKind::UnexpectedEof) =>
+                {
+                    break
+                }
+
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Self { num_leds, frames })
+    }
+
+    /// Returns the offset of the last frame
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map(|frame| frame.timestamp)
+            .unwrap_or_default()
+    }
+
+    /// Returns the frame that is visible at `time`. Looped recordings start over after their
+    /// last frame, otherwise the last frame stays visible
+    pub fn frame_at(&self, time: Duration, looped: bool) -> Option<&Frame> {
+        let duration = self.duration();
+
+        let time = if looped && !duration.is_zero() {
+            Duration::from_nanos((time.as_nanos() % duration.as_nanos()) as u64)
+        } else {
+            time
+        };
+
+        let index = self
+            .frames
+            .partition_point(|frame| frame.timestamp <= time)
+            .saturating_sub(1);
+
+        self.frames.get(index)
+    }
+}
+
+struct Recorder {
+    writer: BufWriter<File>,
+    previous: Vec<RGBA>,
+    last_frame: Option<Instant>,
+    num_frames: u64,
+    /// Number of bytes written so far
+    size: u64,
+}
+
+impl Recorder {
+    fn create(path: &Path, num_leds: usize) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_u16::<LittleEndian>(VERSION)?;
+        writer.write_u32::<LittleEndian>(num_leds as u32)?;
+
+        Ok(Self {
+            writer,
+            previous: vec![RGBA::default(); num_leds],
+            last_frame: None,
+            num_frames: 0,
+            size: (MAGIC.len() + 2 + 4) as u64,
+        })
+    }
+
+    fn write_frame(&mut self, led_map: &[RGBA]) -> Result<()> {
+        if led_map.len() != self.previous.len() {
+            return Err(RecordingError::CanvasSizeMismatch {
+                expected: self.previous.len(),
+                found: led_map.len(),
+            }
+            .into());
+        }
+
+        let now = Instant::now();
+
+        let delay = self
+            .last_frame
+            .map(|last_frame| now.duration_since(last_frame).as_micros())
+            .unwrap_or(0)
+            .min(u32::MAX as u128) as u32;
+
+        let mut frame = Vec::new();
+        encode_frame(&mut frame, delay, &self.previous, led_map)?;
+
+        // the recording ends with the last frame that fits
+        if self.size + frame.len() as u64 > constants::MAX_RECORDING_SIZE {
+            return Err(RecordingError::SizeLimitExceeded {
+                limit: constants::MAX_RECORDING_SIZE,
+            }
+            .into());
+        }
+
+        self.writer.write_all(&frame)?;
+
+        self.previous.copy_from_slice(led_map);
+        self.last_frame = Some(now);
+        self.num_frames += 1;
+        self.size += frame.len() as u64;
+
+        Ok(())
+    }
+}
+
+/// Set to true while a recording is in progress, so that the render thread may skip the recorder
+/// without acquiring a lock
+static RECORDING_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Incremented each time a playback is started or stopped, to terminate the previous playback
+static PLAYBACK_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+static PLAYBACK_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RECORDER: Arc<Mutex<Option<Recorder>>> = Arc::new(Mutex::new(None));
+
+    /// Recordings that have been decoded for playback, by file name
+    static ref RECORDINGS: Arc<Mutex<HashMap<String, Arc<Recording>>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Returns the path of the recording `file_name`. Recordings are confined to `RECORDINGS_DIR`
+pub fn recording_path(file_name: &str) -> Result<PathBuf> {
+    if file_name.is_empty()
+        || file_name.starts_with('.')
+        || file_name.contains(std::path::MAIN_SEPARATOR)
+    {
+        return Err(RecordingError::InvalidFileName {
+            file_name: file_name.to_string(),
+        }
+        .into());
+    }
+
+    Ok(PathBuf::from(constants::RECORDINGS_DIR).join(file_name))
+}
+
+/// Start recording the realized canvas to the file `file_name`
+pub fn start_recording(file_name: &str, num_leds: usize) -> Result<PathBuf> {
+    let path = recording_path(file_name)?;
+
+    let mut recorder = RECORDER.lock();
+
+    if recorder.is_some() {
+        return Err(RecordingError::AlreadyRecording {}.into());
+    }
+
+    fs::create_dir_all(constants::RECORDINGS_DIR)?;
+
+    *recorder = Some(Recorder::create(&path, num_leds)?);
+
+    // the file will be overwritten, so drop the stale decoded version
+    RECORDINGS.lock().remove(file_name);
+
+    RECORDING_ACTIVE.store(true, Ordering::SeqCst);
+
+    info!("Recording the canvas to: {}", path.display());
+
+    Ok(path)
+}
+
+/// Stop the recording that is in progress. Returns the number of recorded frames
+pub fn stop_recording() -> Result<u64> {
+    let mut recorder = RECORDER.lock();
+
+    RECORDING_ACTIVE.store(false, Ordering::SeqCst);
+
+    match recorder.take() {
+        Some(mut recorder) => {
+            recorder.writer.flush()?;
+
+            info!("Recorded {} frames", recorder.num_frames);
+
+            Ok(recorder.num_frames)
+        }
+
+        None => Err(RecordingError::NotRecording {}.into()),
+    }
+}
+
+/// Record a frame that has been sent to the devices, if a recording is in progress
+pub fn record_frame(led_map: &[RGBA]) {
+    if !RECORDING_ACTIVE.load(Ordering::SeqCst) {
+        return;
+    }
+
+    let mut recorder = RECORDER.lock();
+
+    if let Some(r) = recorder.as_mut() {
+        if let Err(e) = r.write_frame(led_map) {
+            error!("Could not record a frame, stopping the recording: {}", e);
+
+            recorder.take();
+            RECORDING_ACTIVE.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Returns the decoded recording `file_name`, it will be loaded on first use
+pub fn get_recording(file_name: &str) -> Result<Arc<Recording>> {
+    let mut recordings = RECORDINGS.lock();
+
+    if let Some(recording) = recordings.get(file_name) {
+        return Ok(recording.clone());
+    }
+
+    let recording = Arc::new(Recording::load(&recording_path(file_name)?)?);
+
+    if recordings.len() >= MAX_CACHED_RECORDINGS {
+        recordings.clear();
+    }
+
+    recordings.insert(file_name.to_string(), recording.clone());
+
+    Ok(recording)
+}
+
+/// Replay `recording` on a separate thread, `sink` is invoked with the colors of each frame
+/// when the frame is due. A playback that is already in progress will be stopped
+pub fn start_playback<F>(recording: Arc<Recording>, looped: bool, mut sink: F) -> Result<()>
+where
+    F: FnMut(&[RGBA]) + Send + 'static,
+{
+    let generation = PLAYBACK_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let is_current = move || {
+        PLAYBACK_GENERATION.load(Ordering::SeqCst) == generation
+            && !crate::QUIT.load(Ordering::SeqCst)
+    };
+
+    PLAYBACK_ACTIVE.store(true, Ordering::SeqCst);
+
+    thread::Builder::new()
+        .name("canvas-playback".into())
+        .spawn(move || {
+            'playback: loop {
+                let start = Instant::now();
+
+                for frame in recording.frames.iter() {
+                    loop {
+                        if !is_current() {
+                            break 'playback;
+                        }
+
+                        let elapsed = start.elapsed();
+                        if elapsed >= frame.timestamp {
+                            break;
+                        }
+
+                        thread::sleep(
+                            (frame.timestamp - elapsed)
+                                .min(Duration::from_millis(PLAYBACK_POLL_MILLIS)),
+                        );
+                    }
+
+                    sink(&frame.colors);
+                }
+
+                if !looped {
+                    break;
+                }
+
+                if recording.duration().is_zero() {
+                    // nothing to animate, but do not spin
+                    thread::sleep(Duration::from_millis(PLAYBACK_POLL_MILLIS));
+                }
+            }
+
+            if is_current() {
+                PLAYBACK_ACTIVE.store(false, Ordering::SeqCst);
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Stop the playback that is in progress. Returns true if a playback has been stopped
+pub fn stop_playback() -> bool {
+    PLAYBACK_GENERATION.fetch_add(1, Ordering::SeqCst);
+
+    PLAYBACK_ACTIVE.swap(false, Ordering::SeqCst)
+}
+
+/// Decode a single frame into `colors`, returns the delay of the frame in microseconds
+fn decode_frame<R: Read>(reader: &mut R, colors: &mut [RGBA]) -> Result<u32> {
+    let delay = reader.read_u32::<LittleEndian>()?;
+    let num_spans = reader.read_u32::<LittleEndian>()?;
+
+    for _ in 0..num_spans {
+        let start = reader.read_u32::<LittleEndian>()? as usize;
+        let len = reader.read_u32::<LittleEndian>()? as usize;
+
+        let span = colors
+            .get_mut(start..start.saturating_add(len))
+            .ok_or_else(|| RecordingError::InvalidRecording {
+                description: format!("Span {}+{} exceeds the canvas", start, len),
+            })?;
+
+        for color in span.iter_mut() {
+            let mut rgba = [0; 4];
+            reader.read_exact(&mut rgba)?;
+
+            *color = RGBA {
+                r: rgba[0],
+                g: rgba[1],
+                b: rgba[2],
+                a: rgba[3],
+            };
+        }
+    }
+
+    Ok(delay)
+}
+
+fn encode_frame<W: Write>(
+    writer: &mut W,
+    delay: u32,
+    previous: &[RGBA],
+    led_map: &[RGBA],
+) -> io::Result<()> {
+    let spans = changed_spans(previous, led_map);
+
+    writer.write_u32::<LittleEndian>(delay)?;
+    writer.write_u32::<LittleEndian>(spans.len() as u32)?;
+
+    for (start, end) in spans {
+        writer.write_u32::<LittleEndian>(start as u32)?;
+        writer.write_u32::<LittleEndian>((end - start) as u32)?;
+
+        for color in &led_map[start..end] {
+            writer.write_all(&[color.r, color.g, color.b, color.a])?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the ranges of LEDs that differ between `previous` and `current`
+fn changed_spans(previous: &[RGBA], current: &[RGBA]) -> Vec<(usize, usize)> {
+    let mut result: Vec<(usize, usize)> = Vec::new();
+
+    for (index, color) in current.iter().enumerate() {
+        if previous.get(index) == Some(color) {
+            continue;
+        }
+
+        match result.last_mut() {
+            Some((_, end)) if index - *end < MIN_SPAN_GAP => *end = index + 1,
+
+            _ => result.push((index, index + 1)),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn color(r: u8, g: u8, b: u8) -> RGBA {
+        RGBA { r, g, b, a: 255 }
+    }
+
+    fn encode(frames: &[(u32, Vec<RGBA>)]) -> Vec<u8> {
+        let num_leds = frames[0].1.len();
+
+        let mut result = Vec::new();
+        result.extend_from_slice(MAGIC);
+        result.write_u16::<LittleEndian>(VERSION).unwrap();
+        result.write_u32::<LittleEndian>(num_leds as u32).unwrap();
+
+        let mut previous = vec![RGBA::default(); num_leds];
+
+        for (delay, led_map) in frames {
+            encode_frame(&mut result, *delay, &previous, led_map).unwrap();
+            previous.copy_from_slice(led_map);
+        }
+
+        result
+    }
+
+    #[test]
+    fn spans_of_changed_leds() {
+        let black = color(0, 0, 0);
+        let red = color(255, 0, 0);
+
+        let previous = vec![black; 10];
+        let mut current = previous.clone();
+        current[1] = red;
+        current[3] = red;
+        current[8] = red;
+
+        assert_eq!(changed_spans(&previous, &current), vec![(1, 4), (8, 9)]);
+        assert!(changed_spans(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn recordings_round_trip() -> Result<()> {
+        let black = color(0, 0, 0);
+        let red = color(255, 0, 0);
+        let blue = color(0, 0, 255);
+
+        let frames = vec![
+            (0, vec![red, black, black, black]),
+            (40_000, vec![red, blue, black, black]),
+            (40_000, vec![red, blue, black, black]),
+            (20_000, vec![black, black, black, red]),
+        ];
+
+        let recording =
+            Recording::decode(&mut Cursor::new(encode(&frames)), constants::CANVAS_SIZE)?;
+
+        assert_eq!(recording.num_leds, 4);
+        assert_eq!(recording.frames.len(), 4);
+        assert_eq!(recording.duration(), Duration::from_millis(100));
+
+        for (frame, (_, led_map)) in recording.frames.iter().zip(frames.iter()) {
+            assert_eq!(&frame.colors, led_map);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_recordings_end_with_the_last_complete_frame() -> Result<()> {
+        let frames = vec![(0, vec![color(1, 2, 3)]), (1000, vec![color(4, 5, 6)])];
+
+        let mut data = encode(&frames);
+        data.truncate(data.len() - 2);
+
+        let recording = Recording::decode(&mut Cursor::new(data), constants::CANVAS_SIZE)?;
+        assert_eq!(recording.frames.len(), 1);
+        assert_eq!(recording.frames[0].colors[0].r, 1);
+
+        assert!(
+            Recording::decode(&mut Cursor::new(b"ERCX\x01\x00"), constants::CANVAS_SIZE).is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn recordings_larger_than_the_canvas_are_rejected() {
+        let frames = vec![(0, vec![color(1, 2, 3); 8])];
+
+        let err = Recording::decode(&mut Cursor::new(encode(&frames)), 4).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<RecordingError>(),
+            Some(RecordingError::InvalidRecording { .. })
+        ));
+    }
+
+    #[test]
+    fn canvas_size_mismatch_while_recording() -> Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("eruption-recording-{}.canvas", std::process::id()));
+
+        let mut recorder = Recorder::create(&path, 4)?;
+        let result = recorder.write_frame(&[color(1, 2, 3); 8]);
+
+        fs::remove_file(&path)?;
+
+        assert!(matches!(
+            result.unwrap_err().downcast_ref::<RecordingError>(),
+            Some(RecordingError::CanvasSizeMismatch {
+                expected: 4,
+                found: 8
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_at_time() -> Result<()> {
+        let frames = vec![
+            (0, vec![color(1, 0, 0)]),
+            (100_000, vec![color(2, 0, 0)]),
+            (100_000, vec![color(3, 0, 0)]),
+        ];
+
+        let recording =
+            Recording::decode(&mut Cursor::new(encode(&frames)), constants::CANVAS_SIZE)?;
+
+        let red_at = |millis: u64, looped: bool| {
+            recording
+                .frame_at(Duration::from_millis(millis), looped)
+                .map(|frame| frame.colors[0].r)
+        };
+
+        assert_eq!(red_at(0, false), Some(1));
+        assert_eq!(red_at(150, false), Some(2));
+        assert_eq!(red_at(500, false), Some(3));
+        assert_eq!(red_at(250, true), Some(1));
+        assert_eq!(red_at(350, true), Some(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_names_are_confined() {
+        assert!(recording_path("effect.canvas").is_ok());
+        assert!(recording_path("../eruption.state").is_err());
+        assert!(recording_path(".hidden").is_err());
+        assert!(recording_path("").is_err());
+    }
+}
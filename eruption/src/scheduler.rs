@@ -0,0 +1,545 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Scheduling of the render loop
+//!
+//! The main loop ticks the Lua VMs and requests a new frame at the target frame rate, which is
+//! taken from the active profile, or from `global.target_fps` of the configuration file. The
+//! frame rate may additionally be limited per device.
+//!
+//! The time that each script spends in its `on_tick()` handler is accounted for. While the scripts
+//! exceed the CPU budget (`global.cpu_budget_percent`, relative to a single core), the heaviest
+//! script is ticked less often. The skipped ticks are accumulated into the `delta` argument of
+//! its next `on_tick()` call, so that animations keep their speed. The frame rate of a throttled
+//! script is raised again, as soon as the budget allows it.
+
+use lazy_static::lazy_static;
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::constants;
+use crate::state::DeviceMakeModelSerial;
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchedulerError {
+    #[error("Invalid frame rate, expected: {min}..{max}")]
+    InvalidFrameRate { min: u64, max: u64 },
+}
+
+/// The target frame rate of the active profile
+static TARGET_FPS: AtomicU64 = AtomicU64::new(constants::TARGET_FPS);
+
+/// Statistics of a single script of the active profile
+#[derive(Debug, Clone)]
+pub struct ScriptStats {
+    pub script_file: PathBuf,
+
+    /// Time spent in `on_tick()` during the last accounting window, in percent of a CPU core
+    pub cpu_percent: f64,
+
+    /// The script is ticked on every n-th frame only
+    pub tick_divisor: u32,
+
+    cpu_time: Duration,
+    pending_delta: u32,
+    pending_frames: u32,
+}
+
+impl ScriptStats {
+    fn new(script_file: &Path) -> Self {
+        Self {
+            script_file: script_file.to_path_buf(),
+            cpu_percent: 0.0,
+            tick_divisor: 1,
+            cpu_time: Duration::ZERO,
+            pending_delta: 0,
+            pending_frames: 0,
+        }
+    }
+}
+
+/// Statistics of the render loop, measured over the last accounting window
+#[derive(Debug, Clone)]
+pub struct RenderStats {
+    pub target_fps: u64,
+
+    /// Number of frames that have actually been rendered per second
+    pub fps: f64,
+
+    /// Time spent realizing the canvas and sending it to the devices, in percent
+    pub render_time_percent: f64,
+
+    /// Total number of rendered frames
+    pub frames_rendered: u64,
+
+    /// Total number of frames that have been skipped, since nothing changed
+    pub frames_skipped: u64,
+
+    pub scripts: Vec<ScriptStats>,
+}
+
+struct Scheduler {
+    cpu_budget_percent: f64,
+    scripts: Vec<ScriptStats>,
+
+    window_start: Instant,
+    window_frames: u32,
+    window_render_time: Duration,
+
+    fps: f64,
+    render_time_percent: f64,
+    frames_rendered: u64,
+    frames_skipped: u64,
+}
+
+impl Scheduler {
+    fn new(cpu_budget_percent: f64) -> Self {
+        Self {
+            cpu_budget_percent,
+            scripts: vec![],
+            window_start: Instant::now(),
+            window_frames: 0,
+            window_render_time: Duration::ZERO,
+            fps: 0.0,
+            render_time_percent: 0.0,
+            frames_rendered: 0,
+            frames_skipped: 0,
+        }
+    }
+
+    fn schedule_ticks<'a>(
+        &mut self,
+        script_files: impl Iterator<Item = &'a Path>,
+        delta: u32,
+    ) -> Vec<Option<u32>> {
+        if self.window_start.elapsed() >= Duration::from_millis(constants::SCHEDULER_WINDOW_MILLIS)
+        {
+            self.close_window();
+        }
+
+        let mut result = vec![];
+
+        for (index, script_file) in script_files.enumerate() {
+            if self
+                .scripts
+                .get(index)
+                .map(|stats| stats.script_file != script_file)
+                .unwrap_or(true)
+            {
+                // the layer stack has changed, e.g. after switching profiles
+                self.scripts.truncate(index);
+                self.scripts.push(ScriptStats::new(script_file));
+            }
+
+            let stats = &mut self.scripts[index];
+
+            stats.pending_delta += delta;
+            stats.pending_frames += 1;
+
+            if stats.pending_frames >= stats.tick_divisor {
+                result.push(Some(stats.pending_delta));
+
+                stats.pending_delta = 0;
+                stats.pending_frames = 0;
+            } else {
+                result.push(None);
+            }
+        }
+
+        self.scripts.truncate(result.len());
+
+        result
+    }
+
+    fn close_window(&mut self) {
+        let elapsed = self.window_start.elapsed().as_secs_f64();
+
+        for stats in self.scripts.iter_mut() {
+            stats.cpu_percent = stats.cpu_time.as_secs_f64() / elapsed * 100.0;
+            stats.cpu_time = Duration::ZERO;
+        }
+
+        self.fps = self.window_frames as f64 / elapsed;
+        self.render_time_percent = self.window_render_time.as_secs_f64() / elapsed * 100.0;
+
+        self.window_start = Instant::now();
+        self.window_frames = 0;
+        self.window_render_time = Duration::ZERO;
+
+        self.adjust_tick_divisors();
+    }
+
+    /// Throttle or un-throttle at most one script per accounting window
+    fn adjust_tick_divisors(&mut self) {
+        let load: f64 = self.scripts.iter().map(|stats| stats.cpu_percent).sum();
+
+        if load > self.cpu_budget_percent {
+            if let Some(stats) = self
+                .scripts
+                .iter_mut()
+                .filter(|stats| stats.tick_divisor < constants::MAX_TICK_DIVISOR)
+                .max_by(|a, b| a.cpu_percent.total_cmp(&b.cpu_percent))
+            {
+                stats.tick_divisor = (stats.tick_divisor * 2).min(constants::MAX_TICK_DIVISOR);
+
+                debug!(
+                    "CPU budget exceeded ({:.1}%): Ticking {} on every {} frames",
+                    load,
+                    stats.script_file.display(),
+                    stats.tick_divisor
+                );
+            }
+        } else if let Some(stats) = self
+            .scripts
+            .iter_mut()
+            .filter(|stats| stats.tick_divisor > 1)
+            .max_by_key(|stats| stats.tick_divisor)
+        {
+            // halving the divisor will roughly double the load caused by the script
+            if load + stats.cpu_percent < self.cpu_budget_percent {
+                stats.tick_divisor /= 2;
+
+                debug!(
+                    "Within CPU budget ({:.1}%): Ticking {} on every {} frames",
+                    load,
+                    stats.script_file.display(),
+                    stats.tick_divisor
+                );
+            }
+        }
+    }
+}
+
+struct DeviceState {
+    frame_generation: usize,
+    last_frame: Instant,
+}
+
+lazy_static! {
+    static ref SCHEDULER: Arc<Mutex<Scheduler>> = {
+        let cpu_budget_percent = crate::CONFIG
+            .lock()
+            .as_ref()
+            .and_then(|config| config.get_float("global.cpu_budget_percent").ok())
+            .unwrap_or(constants::DEFAULT_CPU_BUDGET_PERCENT);
+
+        Arc::new(Mutex::new(Scheduler::new(cpu_budget_percent)))
+    };
+
+    /// Frame rate limits of the devices, devices without a limit are not listed here
+    static ref DEVICE_FRAME_RATES: Arc<RwLock<HashMap<DeviceMakeModelSerial, u64>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+
+    /// The frames that have last been sent to each of the devices
+    static ref DEVICES: Arc<Mutex<HashMap<DeviceMakeModelSerial, DeviceState>>> =
+        Arc::new(Mutex::new(HashMap::new()));
+}
+
+/// Returns the target frame rate of the active profile
+pub fn target_fps() -> u64 {
+    TARGET_FPS.load(Ordering::SeqCst)
+}
+
+/// Set the target frame rate from the active profile. Falls back to the frame rate of the
+/// configuration file, if the profile does not specify one
+pub fn set_profile_frame_rate(target_fps: Option<u64>) {
+    let default_fps = crate::CONFIG
+        .lock()
+        .as_ref()
+        .and_then(|config| config.get_int("global.target_fps").ok())
+        .map(|fps| fps as u64)
+        .unwrap_or(constants::TARGET_FPS);
+
+    let target_fps = target_fps.unwrap_or(default_fps);
+    let (min, max) = constants::FRAME_RATE_RANGE;
+
+    if !(min..=max).contains(&target_fps) {
+        warn!(
+            "Invalid target frame rate: {}, expected: {}..{}",
+            target_fps, min, max
+        );
+    }
+
+    TARGET_FPS.store(target_fps.clamp(min, max), Ordering::SeqCst);
+}
+
+/// Decide which of the Lua VMs shall be ticked on this frame. Returns the `delta` value of
+/// the tick for each of the scripts, or `None` if the script is throttled on this frame
+pub fn schedule_ticks<'a>(
+    script_files: impl Iterator<Item = &'a Path>,
+    delta: u32,
+) -> Vec<Option<u32>> {
+    SCHEDULER.lock().schedule_ticks(script_files, delta)
+}
+
+/// Account for the time that the script with index `index` spent in its `on_tick()` handler
+pub fn record_script_time(index: usize, elapsed: Duration) {
+    if let Some(stats) = SCHEDULER.lock().scripts.get_mut(index) {
+        stats.cpu_time += elapsed;
+    }
+}
+
+/// Account for a frame that has been rendered in `elapsed` time
+pub fn frame_rendered(elapsed: Duration) {
+    let mut scheduler = SCHEDULER.lock();
+
+    scheduler.frames_rendered += 1;
+    scheduler.window_frames += 1;
+    scheduler.window_render_time += elapsed;
+}
+
+/// Account for a frame that has been skipped, since nothing changed
+pub fn frame_skipped() {
+    SCHEDULER.lock().frames_skipped += 1;
+}
+
+/// Returns the statistics of the render loop
+pub fn get_stats() -> RenderStats {
+    let scheduler = SCHEDULER.lock();
+
+    RenderStats {
+        target_fps: target_fps(),
+        fps: scheduler.fps,
+        render_time_percent: scheduler.render_time_percent,
+        frames_rendered: scheduler.frames_rendered,
+        frames_skipped: scheduler.frames_skipped,
+        scripts: scheduler.scripts.clone(),
+    }
+}
+
+/// Initialize the frame rate limits of the devices from the saved state
+pub fn init_device_frame_rates(frame_rates: HashMap<DeviceMakeModelSerial, u64>) {
+    for (device, fps) in frame_rates {
+        set_device_frame_rate(&device, Some(fps))
+            .unwrap_or_else(|e| warn!("Ignoring the frame rate limit of device {}: {}", device, e));
+    }
+}
+
+/// Returns the frame rate limit of `device`, in `make:model:serial` notation
+pub fn get_device_frame_rate(device: &str) -> Option<u64> {
+    DEVICE_FRAME_RATES.read().get(device).copied()
+}
+
+/// Returns the frame rate limits of all devices, including devices that are currently not connected
+pub fn get_device_frame_rates() -> HashMap<DeviceMakeModelSerial, u64> {
+    DEVICE_FRAME_RATES.read().clone()
+}
+
+/// Limit the frame rate of `device`, in `make:model:serial` notation. `None` removes the limit
+pub fn set_device_frame_rate(device: &str, fps: Option<u64>) -> Result<()> {
+    match fps {
+        Some(fps) => {
+            let (min, max) = constants::FRAME_RATE_RANGE;
+
+            if !(min..=max).contains(&fps) {
+                return Err(SchedulerError::InvalidFrameRate { min, max }.into());
+            }
+
+            DEVICE_FRAME_RATES.write().insert(device.to_string(), fps);
+        }
+
+        None => {
+            DEVICE_FRAME_RATES.write().remove(device);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if the frame `frame_generation` shall be sent to `device` now
+pub fn is_device_due(device: &str, frame_generation: usize) -> bool {
+    match DEVICES.lock().get(device) {
+        Some(state) if state.frame_generation >= frame_generation => false,
+
+        Some(state) => match get_device_frame_rate(device) {
+            Some(fps) => state.last_frame.elapsed() >= Duration::from_millis(1000 / fps),
+            None => true,
+        },
+
+        None => true,
+    }
+}
+
+/// Record that the frame `frame_generation` has been sent to `device`
+pub fn device_rendered(device: &str, frame_generation: usize) {
+    DEVICES.lock().insert(
+        device.to_string(),
+        DeviceState {
+            frame_generation,
+            last_frame: Instant::now(),
+        },
+    );
+}
+
+/// Returns true if at least one device is lagging behind the frame `frame_generation`,
+/// e.g. due to its frame rate limit
+pub fn has_pending_devices(frame_generation: usize) -> bool {
+    DEVICES
+        .lock()
+        .values()
+        .any(|state| state.frame_generation < frame_generation)
+}
+
+/// Send the current frame to all devices again, even if nothing changed
+pub fn refresh_devices() {
+    for state in DEVICES.lock().values_mut() {
+        state.frame_generation = 0;
+    }
+}
+
+/// Forget about devices that are not connected anymore
+pub fn retain_devices(devices: &HashSet<DeviceMakeModelSerial>) {
+    DEVICES.lock().retain(|device, _| devices.contains(device));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(scheduler: &mut Scheduler, script_files: &[&Path]) -> Vec<Option<u32>> {
+        scheduler.schedule_ticks(script_files.iter().copied(), 1)
+    }
+
+    #[test]
+    fn scripts_are_ticked_on_every_frame() {
+        let mut scheduler = Scheduler::new(100.0);
+        let scripts = [Path::new("a.lua"), Path::new("b.lua")];
+
+        for _ in 0..4 {
+            assert_eq!(ticks(&mut scheduler, &scripts), vec![Some(1), Some(1)]);
+        }
+    }
+
+    #[test]
+    fn throttled_scripts_accumulate_delta() {
+        let mut scheduler = Scheduler::new(100.0);
+        let scripts = [Path::new("a.lua"), Path::new("b.lua")];
+
+        ticks(&mut scheduler, &scripts);
+        scheduler.scripts[1].tick_divisor = 4;
+
+        let result = (0..8)
+            .map(|_| ticks(&mut scheduler, &scripts)[1])
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            result,
+            vec![None, None, None, Some(4), None, None, None, Some(4)]
+        );
+    }
+
+    #[test]
+    fn heaviest_script_is_throttled() {
+        let mut scheduler = Scheduler::new(10.0);
+        let scripts = [Path::new("a.lua"), Path::new("b.lua")];
+
+        ticks(&mut scheduler, &scripts);
+        scheduler.scripts[0].cpu_percent = 2.0;
+        scheduler.scripts[1].cpu_percent = 12.0;
+
+        scheduler.adjust_tick_divisors();
+
+        assert_eq!(scheduler.scripts[0].tick_divisor, 1);
+        assert_eq!(scheduler.scripts[1].tick_divisor, 2);
+
+        // still above budget, but the script may not be throttled any further
+        scheduler.scripts[1].tick_divisor = constants::MAX_TICK_DIVISOR;
+        scheduler.adjust_tick_divisors();
+
+        assert_eq!(scheduler.scripts[0].tick_divisor, 2);
+        assert_eq!(
+            scheduler.scripts[1].tick_divisor,
+            constants::MAX_TICK_DIVISOR
+        );
+    }
+
+    #[test]
+    fn throttling_is_lifted_within_budget() {
+        let mut scheduler = Scheduler::new(10.0);
+        let scripts = [Path::new("a.lua")];
+
+        ticks(&mut scheduler, &scripts);
+        scheduler.scripts[0].tick_divisor = 4;
+
+        // doubling the load would exceed the budget
+        scheduler.scripts[0].cpu_percent = 6.0;
+        scheduler.adjust_tick_divisors();
+
+        assert_eq!(scheduler.scripts[0].tick_divisor, 4);
+
+        scheduler.scripts[0].cpu_percent = 4.0;
+        scheduler.adjust_tick_divisors();
+
+        assert_eq!(scheduler.scripts[0].tick_divisor, 2);
+    }
+
+    #[test]
+    fn stats_are_reset_when_the_layer_stack_changes() {
+        let mut scheduler = Scheduler::new(100.0);
+
+        ticks(&mut scheduler, &[Path::new("a.lua"), Path::new("b.lua")]);
+        scheduler.scripts[0].tick_divisor = 2;
+        scheduler.scripts[1].tick_divisor = 2;
+
+        ticks(&mut scheduler, &[Path::new("a.lua"), Path::new("c.lua")]);
+
+        assert_eq!(scheduler.scripts.len(), 2);
+        assert_eq!(scheduler.scripts[0].tick_divisor, 2);
+        assert_eq!(scheduler.scripts[1].tick_divisor, 1);
+        assert_eq!(scheduler.scripts[1].script_file, Path::new("c.lua"));
+
+        ticks(&mut scheduler, &[Path::new("a.lua")]);
+
+        assert_eq!(scheduler.scripts.len(), 1);
+    }
+
+    #[test]
+    fn device_frame_rates_are_limited() -> Result<()> {
+        let device = "0x1:0x2:scheduler-test";
+
+        assert!(set_device_frame_rate(device, Some(0)).is_err());
+
+        set_device_frame_rate(device, Some(1))?;
+
+        assert!(is_device_due(device, 1));
+        device_rendered(device, 1);
+
+        // the same frame is never sent twice
+        assert!(!is_device_due(device, 1));
+
+        // a newer frame has to wait for the frame rate limit
+        assert!(!is_device_due(device, 2));
+        assert!(has_pending_devices(2));
+
+        set_device_frame_rate(device, None)?;
+
+        assert!(is_device_due(device, 2));
+
+        Ok(())
+    }
+}
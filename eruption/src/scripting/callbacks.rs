@@ -21,12 +21,15 @@
 
 /// These functions are intended to be used from within Lua scripts
 use byteorder::{ByteOrder, LittleEndian};
+use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
 use noise::NoiseFn;
 use palette::convert::FromColor;
 use palette::{Hsl, Srgb};
-use rand::Rng;
+use parking_lot::RwLock;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::convert::TryFrom;
 use std::fmt::Write;
 use std::sync::atomic::Ordering;
@@ -36,16 +39,18 @@ use std::vec::Vec;
 use std::{cell::RefCell, thread};
 
 use crate::{
-    constants,
+    battery, constants,
     hwdevices::RGBA,
-    plugin_manager,
+    layout, plugin_manager,
     plugins::macros,
+    recording, scheduler,
     script::ScriptingError,
     script::{
         ALLOCATED_GRADIENTS, FRAME_GENERATION_COUNTER, LED_MAP, LOCAL_LED_MAP,
         LOCAL_LED_MAP_MODIFIED,
     },
     scripting::callbacks,
+    util::ratelimited,
 };
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -59,9 +64,19 @@ pub enum CallbacksError {
     ParseParamError {},
 }
 
+lazy_static! {
+    /// If set, the random number and noise generators of newly started Lua VMs will be
+    /// seeded with this value instead of the current time, to get reproducible results
+    pub static ref DETERMINISTIC_SEED: RwLock<Option<u32>> = RwLock::new(None);
+}
+
 fn seed() -> u32 {
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    if let Some(seed) = *DETERMINISTIC_SEED.read() {
+        return seed;
+    }
+
     let start = SystemTime::now();
     let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap_or_default();
 
@@ -69,6 +84,8 @@ fn seed() -> u32 {
 }
 
 thread_local! {
+    pub static RNG: RefCell<StdRng> = RefCell::new(StdRng::seed_from_u64(seed() as u64));
+
     pub static PERLIN_NOISE: RefCell<noise::Perlin> = {
         let noise = noise::Perlin::new(seed());
 
@@ -179,7 +196,7 @@ pub(crate) fn stringify(mut string: &mut String, value: mlua::Value) -> Option<(
 
 /// Returns the target framerate
 pub(crate) fn get_target_fps() -> u64 {
-    constants::TARGET_FPS
+    scheduler::target_fps()
 }
 
 /// Returns the Lua support scripts for all connected devices
@@ -203,17 +220,22 @@ pub(crate) fn get_support_script_files() -> Vec<String> {
 
 /// Returns the number of "pixels" on the canvas
 pub(crate) fn get_canvas_size() -> usize {
-    constants::CANVAS_SIZE
+    layout::canvas_size()
 }
 
 /// Returns the height of the canvas
 pub(crate) fn get_canvas_height() -> usize {
-    constants::CANVAS_HEIGHT
+    layout::canvas_height()
 }
 
 /// Returns the width of the canvas
 pub(crate) fn get_canvas_width() -> usize {
-    constants::CANVAS_WIDTH
+    layout::canvas_width()
+}
+
+/// Returns true if devices have been placed on the canvas layout
+pub(crate) fn is_canvas_layout_active() -> bool {
+    layout::is_active()
 }
 
 /// Inject a key on the eruption virtual keyboard.
@@ -691,7 +713,7 @@ pub(crate) fn get_color_map() -> Vec<u32> {
         })
         .collect::<Vec<u32>>();
 
-    assert!(result.len() == constants::CANVAS_SIZE);
+    assert!(result.len() == layout::canvas_size());
 
     result
 }
@@ -710,12 +732,15 @@ pub(crate) fn submit_color_map(map: &[u32]) -> Result<()> {
     //     )
     // );
 
-    let mut led_map = [RGBA {
-        r: 0,
-        g: 0,
-        b: 0,
-        a: 0,
-    }; constants::CANVAS_SIZE];
+    let mut led_map = vec![
+        RGBA {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        layout::canvas_size()
+    ];
 
     let mut i = 0;
     loop {
@@ -740,6 +765,41 @@ pub(crate) fn submit_color_map(map: &[u32]) -> Result<()> {
     Ok(())
 }
 
+/// Submit the frame of the recording `file_name` that is visible `millis` milliseconds into
+/// the playback. Returns false if the recording could not be loaded
+pub(crate) fn submit_recording_frame(file_name: &str, millis: u64, looped: bool) -> bool {
+    let recording = match recording::get_recording(file_name) {
+        Ok(recording) => recording,
+
+        Err(e) => {
+            ratelimited::warn!("Could not load the recording {}: {}", file_name, e);
+
+            return false;
+        }
+    };
+
+    if let Some(frame) = recording.frame_at(Duration::from_millis(millis), looped) {
+        LOCAL_LED_MAP.with(|local_map| {
+            let mut local_map = local_map.borrow_mut();
+            let len = local_map.len().min(frame.colors.len());
+
+            local_map[..len].copy_from_slice(&frame.colors[..len]);
+        });
+
+        LOCAL_LED_MAP_MODIFIED.with(|f| *f.borrow_mut() = true);
+
+        FRAME_GENERATION_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+
+    true
+}
+
+/// Returns the battery level (in percent) of the device with index `device`, or `nil`
+/// if the device does not report a battery level
+pub(crate) fn get_device_battery_level(device: u64) -> Option<u8> {
+    battery::get_battery_level(device)
+}
+
 pub(crate) fn get_brightness() -> isize {
     crate::BRIGHTNESS.load(Ordering::SeqCst)
 }
@@ -814,6 +874,10 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     let get_canvas_height = lua_ctx.create_function(|_, ()| Ok(callbacks::get_canvas_height()))?;
     globals.set("get_canvas_height", get_canvas_height)?;
 
+    let is_canvas_layout_active =
+        lua_ctx.create_function(|_, ()| Ok(callbacks::is_canvas_layout_active()))?;
+    globals.set("is_canvas_layout_active", is_canvas_layout_active)?;
+
     // math library
     let max = lua_ctx.create_function(|_, (f1, f2): (f64, f64)| Ok(f1.max(f2)))?;
     globals.set("max", max)?;
@@ -857,7 +921,7 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
 
     let rand = lua_ctx.create_function(|_, (l, h): (i64, i64)| {
         if h - l > 0 {
-            Ok(rand::thread_rng().gen_range(l..h))
+            Ok(RNG.with(|rng| rng.borrow_mut().gen_range(l..h)))
         } else {
             Ok(0)
         }
@@ -1048,6 +1112,10 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     let get_num_keys = lua_ctx.create_function(move |_, ()| Ok(callbacks::get_num_keys()))?;
     globals.set("get_num_keys", get_num_keys)?;
 
+    let get_device_battery_level = lua_ctx
+        .create_function(move |_, device: u64| Ok(callbacks::get_device_battery_level(device)))?;
+    globals.set("get_device_battery_level", get_device_battery_level)?;
+
     let get_color_map = lua_ctx.create_function(move |_, ()| Ok(callbacks::get_color_map()))?;
     globals.set("get_color_map", get_color_map)?;
 
@@ -1057,6 +1125,12 @@ pub fn register_support_funcs(lua_ctx: &Lua) -> mlua::Result<()> {
     })?;
     globals.set("submit_color_map", submit_color_map)?;
 
+    let submit_recording_frame =
+        lua_ctx.create_function(move |_, (file, millis, looped): (String, u64, bool)| {
+            Ok(callbacks::submit_recording_frame(&file, millis, looped))
+        })?;
+    globals.set("submit_recording_frame", submit_recording_frame)?;
+
     let get_brightness = lua_ctx.create_function(move |_, ()| Ok(callbacks::get_brightness()))?;
     globals.set("get_brightness", get_brightness)?;
 
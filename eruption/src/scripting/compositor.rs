@@ -0,0 +1,168 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Blending of the color maps of the layers of a profile onto the canvas
+
+use crate::hwdevices::RGBA;
+
+use super::layers::{BlendMode, Layer};
+
+/// Combine a single color channel of the layer with the canvas beneath it
+fn mix(blend_mode: BlendMode, bg: u8, fg: u8) -> f32 {
+    let (bg, fg) = (bg as f32, fg as f32);
+
+    match blend_mode {
+        BlendMode::Normal | BlendMode::Replace => fg,
+        BlendMode::Add => (bg + fg).min(255.0),
+        BlendMode::Multiply => bg * fg / 255.0,
+        BlendMode::Screen => 255.0 - (255.0 - bg) * (255.0 - fg) / 255.0,
+        BlendMode::Max => bg.max(fg),
+    }
+}
+
+/// Blend the color map `foreground` of `layer` onto the `canvas`, and apply the `brightness`
+pub fn blend(layer: &Layer, canvas: &mut [RGBA], foreground: &[RGBA], brightness: f32) {
+    let opacity = layer.opacity.clamp(0.0, 1.0) as f32;
+
+    for (idx, (background, fg)) in canvas.iter_mut().zip(foreground.iter()).enumerate() {
+        let bg = *background;
+
+        let alpha = if layer.mask.map(|mask| mask.contains(idx)).unwrap_or(true) {
+            match layer.blend_mode {
+                BlendMode::Replace => 255.0 * opacity,
+                _ => fg.a as f32 * opacity,
+            }
+        } else {
+            0.0
+        };
+
+        #[rustfmt::skip]
+        let color = RGBA {
+            r: ((((alpha * mix(layer.blend_mode, bg.r, fg.r)) + (255.0 - alpha) * bg.r as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+            g: ((((alpha * mix(layer.blend_mode, bg.g, fg.g)) + (255.0 - alpha) * bg.g as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+            b: ((((alpha * mix(layer.blend_mode, bg.b, fg.b)) + (255.0 - alpha) * bg.b as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
+            a: fg.a,
+        };
+
+        *background = color;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripting::layers::Mask;
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA { r, g, b, a }
+    }
+
+    fn blend_one(layer: &Layer, bg: RGBA, fg: RGBA) -> RGBA {
+        let mut canvas = [bg];
+        blend(layer, &mut canvas, &[fg], 100.0);
+
+        canvas[0]
+    }
+
+    fn layer(blend_mode: BlendMode) -> Layer {
+        Layer {
+            blend_mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn normal_blending_matches_plain_alpha_blending() {
+        let bg = rgba(200, 100, 50, 255);
+        let fg = rgba(10, 20, 250, 128);
+
+        let brightness = 85.0;
+
+        let mut canvas = [bg];
+        blend(&Layer::default(), &mut canvas, &[fg], brightness);
+
+        let expected = |fg: u8, bg: u8| {
+            ((((fg as f32 * 128.0) + (255 - 128) as f32 * bg as f32).floor() * brightness / 100.0)
+                as u32
+                >> 8) as u8
+        };
+
+        assert_eq!(canvas[0].r, expected(fg.r, bg.r));
+        assert_eq!(canvas[0].g, expected(fg.g, bg.g));
+        assert_eq!(canvas[0].b, expected(fg.b, bg.b));
+        assert_eq!(canvas[0].a, fg.a);
+    }
+
+    #[test]
+    fn blend_modes() {
+        let bg = rgba(200, 100, 0, 255);
+        let fg = rgba(100, 100, 100, 255);
+
+        let c = blend_one(&layer(BlendMode::Add), bg, fg);
+        assert_eq!((c.r, c.g, c.b), (254, 199, 99));
+
+        let c = blend_one(&layer(BlendMode::Multiply), bg, fg);
+        assert_eq!((c.r, c.g, c.b), (78, 39, 0));
+
+        let c = blend_one(&layer(BlendMode::Screen), bg, fg);
+        assert_eq!((c.r, c.g, c.b), (220, 160, 99));
+
+        let c = blend_one(&layer(BlendMode::Max), bg, fg);
+        assert_eq!((c.r, c.g, c.b), (199, 99, 99));
+    }
+
+    #[test]
+    fn replace_ignores_the_alpha_channel() {
+        let c = blend_one(
+            &layer(BlendMode::Replace),
+            rgba(200, 200, 200, 255),
+            rgba(0, 0, 0, 0),
+        );
+
+        assert_eq!((c.r, c.g, c.b), (0, 0, 0));
+    }
+
+    #[test]
+    fn opacity_and_mask() {
+        let bg = rgba(200, 200, 200, 255);
+        let fg = rgba(0, 0, 0, 255);
+
+        let transparent = Layer {
+            opacity: 0.0,
+            ..layer(BlendMode::Replace)
+        };
+
+        let c = blend_one(&transparent, bg, fg);
+        assert_eq!((c.r, c.g, c.b), (199, 199, 199));
+
+        let masked = Layer {
+            mask: Some(Mask { start: 1, end: 2 }),
+            ..layer(BlendMode::Replace)
+        };
+
+        let mut canvas = [bg, bg, bg];
+        blend(&masked, &mut canvas, &[fg, fg, fg], 100.0);
+
+        assert_eq!(canvas[0].r, 199);
+        assert_eq!(canvas[1].r, 0);
+        assert_eq!(canvas[2].r, 199);
+    }
+}
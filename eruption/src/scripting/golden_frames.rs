@@ -0,0 +1,584 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Headless test harness for Lua effect scripts
+//!
+//! Loads a profile and its scripts, drives the Lua VMs with a deterministic
+//! clock and seeded random number generators, and compares the rendered canvas
+//! against stored golden frames. Golden frames use the same format as the
+//! `record_file` of virtual devices: one line per frame, each LED encoded as
+//! hex `RRGGBBAA`, separated by spaces.
+
+use flume::{bounded, unbounded, Sender};
+use log::*;
+use parking_lot::MutexGuard;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{
+    constants,
+    hwdevices::{self, VirtualDeviceConfig, RGBA},
+    plugin_manager, plugins,
+    profiles::{self, Profile},
+    scripting::callbacks,
+    scripting::script,
+};
+
+pub type Result<T> = std::result::Result<T, eyre::Error>;
+
+/// Max. time to wait for a Lua VM to process its pending messages
+const TIMEOUT_MILLIS: u64 = 5000;
+
+/// Set this environment variable to (re-)record all golden frames
+pub const BLESS_ENV_VAR: &str = "ERUPTION_BLESS_GOLDEN_FRAMES";
+
+#[derive(Debug, thiserror::Error)]
+pub enum GoldenFramesError {
+    #[error("Lua VM of script {script} is not responding")]
+    ScriptError { script: String },
+
+    #[error(
+        "Golden frames file {path} does not exist, set ERUPTION_BLESS_GOLDEN_FRAMES=1 to record it"
+    )]
+    MissingGoldenFile { path: String },
+
+    #[error("Invalid golden frames file: {description}")]
+    ParseError { description: String },
+
+    #[error("Expected {expected} frames, got {actual}")]
+    FrameCountMismatch { expected: usize, actual: usize },
+
+    #[error("Frame {frame}, LED {index}: expected {expected}, got {actual}")]
+    FrameMismatch {
+        frame: usize,
+        index: usize,
+        expected: String,
+        actual: String,
+    },
+}
+
+struct LuaVm {
+    script_file: String,
+    tx: Sender<script::Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Runs the scripts of a profile headless, one frame at a time
+pub struct FrameHarness {
+    vms: Vec<LuaVm>,
+    pending_events: Vec<script::Message>,
+    frame: usize,
+
+    /// The canvas, the device lists and the configuration are global state, so only one
+    /// harness may be active at a time
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl FrameHarness {
+    /// Load the profile `profile_file` and start a Lua VM for each of its scripts, which are
+    /// looked up in `script_dirs`. All random number and noise generators will be seeded with `seed`
+    pub fn load(profile_file: &Path, script_dirs: &[PathBuf], seed: u32) -> Result<Self> {
+        let guard = profiles::CONFIG_LOCK.lock();
+
+        let config = config::Config::builder()
+            .set_override(
+                "global.script_dirs",
+                script_dirs
+                    .iter()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .collect::<Vec<_>>(),
+            )?
+            .build()?;
+
+        *crate::CONFIG.lock() = Some(config);
+
+        *callbacks::DETERMINISTIC_SEED.write() = Some(seed);
+
+        crate::BRIGHTNESS.store(100, Ordering::SeqCst);
+        crate::BRIGHTNESS_FADER.store(0, Ordering::SeqCst);
+
+        crate::KEY_STATES.write().fill(false);
+        crate::BUTTON_STATES.write().fill(false);
+
+        // attach a virtual keyboard, so that the device specific support scripts will be loaded
+        let keyboard = hwdevices::virtual_devices::bind_virtual_keyboard(&VirtualDeviceConfig {
+            class: "keyboard".to_string(),
+            name: "Virtual Keyboard".to_string(),
+            usb_vid: 0,
+            usb_pid: 0,
+            event_script: None,
+            repeat_events: false,
+            record_file: None,
+        })?;
+
+        *crate::KEYBOARD_DEVICES.write() = vec![keyboard];
+
+        register_plugins();
+
+        let profile = Profile::load_fully(profile_file)?;
+
+        let mut vms = Vec::new();
+
+        for (index, (manifest, layer)) in profile.get_layer_stack().into_iter().enumerate() {
+            let (tx, rx) = unbounded();
+
+            let script_file = manifest.script_file.clone();
+            let capabilities = manifest.capabilities.clone();
+            let mut parameter_values: BTreeMap<String, _> = manifest
+                .get_merged_parameters(&profile)
+                .into_iter()
+                .map(|pv| (pv.name.clone(), pv))
+                .collect();
+
+            let thread = thread::Builder::new()
+                .name(format!("golden:{}", index))
+                .spawn(move || {
+                    script::LAYER.with(|l| *l.borrow_mut() = layer);
+
+                    match script::run_script(
+                        &script_file,
+                        &mut parameter_values,
+                        &capabilities,
+                        &rx,
+                    ) {
+                        Ok(script::RunScriptResult::TerminatedGracefully) => (),
+
+                        _ => error!("Script {} terminated with errors", script_file.display()),
+                    }
+                })?;
+
+            vms.push(LuaVm {
+                script_file: manifest.script_file.display().to_string(),
+                tx,
+                thread: Some(thread),
+            });
+        }
+
+        let harness = Self {
+            vms,
+            pending_events: Vec::new(),
+            frame: 0,
+            _guard: guard,
+        };
+
+        // wait for all scripts to complete their `on_startup` handler
+        harness.sync()?;
+
+        Ok(harness)
+    }
+
+    /// The number of frames that have been rendered so far
+    #[allow(dead_code)]
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Queue a key down event, that will be delivered with the next frame
+    pub fn key_down(&mut self, key_index: u8) {
+        if let Some(v) = crate::KEY_STATES.write().get_mut(key_index as usize) {
+            *v = true;
+        }
+
+        self.pending_events
+            .push(script::Message::KeyDown(key_index));
+    }
+
+    /// Queue a key up event, that will be delivered with the next frame
+    pub fn key_up(&mut self, key_index: u8) {
+        if let Some(v) = crate::KEY_STATES.write().get_mut(key_index as usize) {
+            *v = false;
+        }
+
+        self.pending_events.push(script::Message::KeyUp(key_index));
+    }
+
+    /// Queue a mouse move event, that will be delivered with the next frame
+    #[allow(dead_code)]
+    pub fn mouse_move(&mut self, rel_x: i32, rel_y: i32, rel_z: i32) {
+        self.pending_events
+            .push(script::Message::MouseMove(rel_x, rel_y, rel_z));
+    }
+
+    /// Deliver all pending events, advance the clock by one tick and render the canvas
+    pub fn step(&mut self) -> Result<Vec<RGBA>> {
+        let events = std::mem::take(&mut self.pending_events);
+
+        // process the VMs one after another, so that scripts reading the canvas
+        // always observe the same state
+        for vm in self.vms.iter() {
+            for event in events.iter() {
+                vm.send(event.clone())?;
+            }
+
+            vm.send(script::Message::Tick(1))?;
+            vm.sync()?;
+        }
+
+        let frame = self.render()?;
+
+        self.frame += 1;
+
+        Ok(frame)
+    }
+
+    /// Render `count` frames
+    pub fn run(&mut self, count: usize) -> Result<Vec<Vec<RGBA>>> {
+        let mut result = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            result.push(self.step()?);
+        }
+
+        Ok(result)
+    }
+
+    fn sync(&self) -> Result<()> {
+        for vm in self.vms.iter() {
+            vm.sync()?;
+        }
+
+        Ok(())
+    }
+
+    /// Blend the color maps of all VMs in order, just like the device I/O thread does
+    fn render(&self) -> Result<Vec<RGBA>> {
+        script::LED_MAP.write().copy_from_slice(
+            &[RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }; constants::CANVAS_SIZE],
+        );
+
+        *crate::COLOR_MAPS_READY_CONDITION.0.lock() = self.vms.len();
+
+        for vm in self.vms.iter() {
+            let mut pending = crate::COLOR_MAPS_READY_CONDITION.0.lock();
+
+            vm.send(script::Message::RealizeColorMap)?;
+
+            let result = crate::COLOR_MAPS_READY_CONDITION
+                .1
+                .wait_for(&mut pending, Duration::from_millis(TIMEOUT_MILLIS));
+
+            if result.timed_out() {
+                return Err(GoldenFramesError::ScriptError {
+                    script: vm.script_file.clone(),
+                }
+                .into());
+            }
+        }
+
+        let result = script::LED_MAP.read().clone();
+
+        Ok(result)
+    }
+}
+
+impl Drop for FrameHarness {
+    fn drop(&mut self) {
+        for vm in self.vms.iter_mut() {
+            vm.tx.send(script::Message::Unload).ok();
+
+            if let Some(thread) = vm.thread.take() {
+                thread.join().ok();
+            }
+        }
+
+        crate::KEYBOARD_DEVICES.write().clear();
+        crate::KEY_STATES.write().fill(false);
+
+        *callbacks::DETERMINISTIC_SEED.write() = None;
+    }
+}
+
+impl LuaVm {
+    fn send(&self, message: script::Message) -> Result<()> {
+        self.tx
+            .send(message)
+            .map_err(|_e| GoldenFramesError::ScriptError {
+                script: self.script_file.clone(),
+            })?;
+
+        Ok(())
+    }
+
+    /// Wait until the VM has processed all previously queued messages
+    fn sync(&self) -> Result<()> {
+        let (tx, rx) = bounded(1);
+
+        self.send(script::Message::Sync(tx))?;
+
+        rx.recv_timeout(Duration::from_millis(TIMEOUT_MILLIS))
+            .map_err(|_e| GoldenFramesError::ScriptError {
+                script: self.script_file.clone(),
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Register the plugins that do not require any hardware or system services
+fn register_plugins() {
+    let mut plugin_manager = plugin_manager::PLUGIN_MANAGER.write();
+
+    if plugin_manager
+        .find_plugin_by_name("Keyboard".to_string())
+        .is_none()
+    {
+        let _ = plugin_manager.register_plugin(Box::new(plugins::KeyboardPlugin::new()));
+    }
+
+    if plugin_manager
+        .find_plugin_by_name("Mouse".to_string())
+        .is_none()
+    {
+        let _ = plugin_manager.register_plugin(Box::new(plugins::MousePlugin::new()));
+    }
+}
+
+/// Encode frames in the golden frames file format
+pub fn format_frames(frames: &[Vec<RGBA>]) -> String {
+    let mut result = String::new();
+
+    for frame in frames {
+        let line = frame
+            .iter()
+            .map(|c| format!("{:02x}{:02x}{:02x}{:02x}", c.r, c.g, c.b, c.a))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        result.push_str(&line);
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Decode frames from the golden frames file format
+pub fn parse_frames(data: &str) -> Result<Vec<Vec<RGBA>>> {
+    let mut result = Vec::new();
+
+    for (line_nr, line) in data.lines().enumerate() {
+        let mut frame = Vec::new();
+
+        for value in line.split_whitespace() {
+            let v = u32::from_str_radix(value, 16)
+                .ok()
+                .filter(|_| value.len() == 8)
+                .ok_or_else(|| GoldenFramesError::ParseError {
+                    description: format!("line {}: invalid color '{}'", line_nr + 1, value),
+                })?;
+
+            frame.push(RGBA {
+                r: (v >> 24) as u8,
+                g: (v >> 16) as u8,
+                b: (v >> 8) as u8,
+                a: v as u8,
+            });
+        }
+
+        result.push(frame);
+    }
+
+    Ok(result)
+}
+
+/// Compare rendered frames against golden frames
+pub fn compare_frames(actual: &[Vec<RGBA>], expected: &[Vec<RGBA>]) -> Result<()> {
+    if actual.len() != expected.len() {
+        return Err(GoldenFramesError::FrameCountMismatch {
+            expected: expected.len(),
+            actual: actual.len(),
+        }
+        .into());
+    }
+
+    for (frame, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+        if a.len() != e.len() {
+            return Err(GoldenFramesError::ParseError {
+                description: format!(
+                    "frame {}: expected {} LEDs, got {}",
+                    frame,
+                    e.len(),
+                    a.len()
+                ),
+            }
+            .into());
+        }
+
+        for (index, (a, e)) in a.iter().zip(e.iter()).enumerate() {
+            if (a.r, a.g, a.b, a.a) != (e.r, e.g, e.b, e.a) {
+                return Err(GoldenFramesError::FrameMismatch {
+                    frame,
+                    index,
+                    expected: format!("{:02x}{:02x}{:02x}{:02x}", e.r, e.g, e.b, e.a),
+                    actual: format!("{:02x}{:02x}{:02x}{:02x}", a.r, a.g, a.b, a.a),
+                }
+                .into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Compare rendered frames against the golden frames file `golden_file`.
+///
+/// If the environment variable `ERUPTION_BLESS_GOLDEN_FRAMES` is set, the rendered
+/// frames will be recorded instead
+pub fn verify_golden_frames(golden_file: &Path, frames: &[Vec<RGBA>]) -> Result<()> {
+    if std::env::var_os(BLESS_ENV_VAR).is_some() {
+        warn!("Recording golden frames: {}", golden_file.display());
+
+        fs::write(golden_file, format_frames(frames))?;
+
+        Ok(())
+    } else if !golden_file.exists() {
+        Err(GoldenFramesError::MissingGoldenFile {
+            path: golden_file.display().to_string(),
+        }
+        .into())
+    } else {
+        let expected = parse_frames(&fs::read_to_string(golden_file)?)?;
+
+        compare_frames(frames, &expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn golden_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../support/tests/golden/")
+    }
+
+    fn script_dirs() -> Vec<PathBuf> {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+        vec![
+            manifest_dir.join("src/scripts"),
+            manifest_dir.join("../support/tests/assets"),
+        ]
+    }
+
+    fn render_profile(profile: &str, seed: u32, count: usize) -> Result<Vec<Vec<RGBA>>> {
+        let mut harness = FrameHarness::load(&golden_path().join(profile), &script_dirs(), seed)?;
+
+        harness.run(count)
+    }
+
+    #[test]
+    fn parse_and_format_frames() -> Result<()> {
+        let data = "ff000080 00ff00ff\n0000ff00 ffffffff\n";
+        let frames = parse_frames(data)?;
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0][0].r, 0xff);
+        assert_eq!(frames[0][0].a, 0x80);
+        assert_eq!(frames[1][0].b, 0xff);
+
+        assert_eq!(format_frames(&frames), data);
+
+        assert!(parse_frames("ff0000").is_err());
+        assert!(parse_frames("gg000000").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn compare_frames_reports_first_mismatch() -> Result<()> {
+        let expected = parse_frames("ff0000ff ff0000ff\n")?;
+        let actual = parse_frames("ff0000ff fe0000ff\n")?;
+
+        let err = compare_frames(&actual, &expected).unwrap_err();
+
+        match err.downcast_ref::<GoldenFramesError>() {
+            Some(GoldenFramesError::FrameMismatch { frame, index, .. }) => {
+                assert_eq!(*frame, 0);
+                assert_eq!(*index, 1);
+            }
+
+            _ => panic!("Unexpected error: {}", err),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_golden_file_is_an_error() {
+        if std::env::var_os(BLESS_ENV_VAR).is_some() {
+            return;
+        }
+
+        let frames = parse_frames("ff0000ff\n").unwrap();
+        let err = verify_golden_frames(&golden_path().join("missing.frames"), &frames).unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<GoldenFramesError>(),
+            Some(GoldenFramesError::MissingGoldenFile { .. })
+        ));
+    }
+
+    #[test]
+    fn golden_solid() -> Result<()> {
+        let frames = render_profile("solid.profile", 42, 10)?;
+
+        verify_golden_frames(&golden_path().join("solid.frames"), &frames)
+    }
+
+    #[test]
+    fn golden_fire() -> Result<()> {
+        let frames = render_profile("fire.profile", 42, 30)?;
+
+        verify_golden_frames(&golden_path().join("fire.frames"), &frames)
+    }
+
+    #[test]
+    fn golden_shockwave() -> Result<()> {
+        let mut harness =
+            FrameHarness::load(&golden_path().join("shockwave.profile"), &script_dirs(), 42)?;
+
+        let mut frames = harness.run(2)?;
+
+        harness.key_down(42);
+        frames.extend(harness.run(2)?);
+
+        harness.key_up(42);
+        frames.extend(harness.run(26)?);
+
+        verify_golden_frames(&golden_path().join("shockwave.frames"), &frames)
+    }
+
+    #[test]
+    fn rendering_is_deterministic() -> Result<()> {
+        let first = render_profile("fire.profile", 1337, 20)?;
+        let second = render_profile("fire.profile", 1337, 20)?;
+
+        compare_frames(&second, &first)
+    }
+}
@@ -0,0 +1,121 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! The layer stack of a profile
+//!
+//! Every script of a profile renders into a layer of its own. On each frame, the layers are
+//! blended onto the canvas one after another, from the bottom to the top of the layer stack.
+//! See the `compositor` module for the blending itself.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Specifies how the colors of a layer are combined with the colors beneath it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BlendMode {
+    /// Plain alpha blending
+    Normal,
+
+    Add,
+    Multiply,
+    Screen,
+    Max,
+
+    /// Paint over the canvas, ignoring the alpha channel of the layer
+    Replace,
+}
+
+impl Default for BlendMode {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+/// A zone of the canvas, from index `start` up to, but not including, index `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mask {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Mask {
+    pub fn contains(&self, index: usize) -> bool {
+        (self.start..self.end).contains(&index)
+    }
+}
+
+fn default_opacity() -> f64 {
+    1.0
+}
+
+/// The compositing settings of a script
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    /// The script file, as specified in `active_scripts`
+    pub script: PathBuf,
+
+    #[serde(default)]
+    pub blend_mode: BlendMode,
+
+    /// The opacity of the layer, from 0.0 to 1.0
+    #[serde(default = "default_opacity")]
+    pub opacity: f64,
+
+    /// The layer is transparent outside of the mask
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask: Option<Mask>,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            script: PathBuf::new(),
+            blend_mode: BlendMode::default(),
+            opacity: default_opacity(),
+            mask: None,
+        }
+    }
+}
+
+impl Layer {
+    /// Returns true if this layer applies to the script `script_file`
+    pub fn matches(&self, script_file: &Path) -> bool {
+        self.script.file_name().is_some() && self.script.file_name() == script_file.file_name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_match_scripts_by_file_name() {
+        let layer = Layer {
+            script: PathBuf::from("macros.lua"),
+            ..Default::default()
+        };
+
+        assert!(layer.matches(Path::new("/usr/share/eruption/scripts/macros.lua")));
+        assert!(!layer.matches(Path::new("/usr/share/eruption/scripts/netfx.lua")));
+        assert!(!Layer::default().matches(Path::new("macros.lua")));
+    }
+}
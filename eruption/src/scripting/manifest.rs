@@ -65,6 +65,8 @@ pub struct Manifest {
     pub min_supported_version: String,
     pub tags: Option<Vec<ScriptTag>>,
     #[serde(default)]
+    pub capabilities: Vec<Capability>,
+    #[serde(default)]
     pub config: ManifestConfiguration,
 }
 
@@ -268,6 +270,49 @@ pub fn get_scripts() -> Result<Vec<Manifest>> {
     Ok(result)
 }
 
+/// Privileged functionality that a script needs to declare in its manifest, before it
+/// is made available to the script
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// The Lua `io` library, `dofile()` and `loadfile()`
+    Io,
+
+    /// The functions of the Lua `os` library that access the system, e.g. `os.execute()`
+    Os,
+
+    /// Execution of external commands and termination of the daemon
+    System,
+
+    /// Injection of synthesized keyboard and mouse events
+    InjectInput,
+
+    /// Network connections
+    Network,
+}
+
+impl Capability {
+    pub fn get_name(&self) -> &'static str {
+        match *self {
+            Capability::Io => "io",
+            Capability::Os => "os",
+            Capability::System => "system",
+            Capability::InjectInput => "inject_input",
+            Capability::Network => "network",
+        }
+    }
+
+    pub fn get_description(&self) -> String {
+        match *self {
+            Capability::Io => "Read and write arbitrary files".into(),
+            Capability::Os => "Access the operating system, including running commands".into(),
+            Capability::System => "Run commands and terminate the daemon".into(),
+            Capability::InjectInput => "Inject keyboard and mouse events".into(),
+            Capability::Network => "Connect to hosts on the network".into(),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, PartialOrd, Eq, Ord)]
 pub enum ScriptTag {
     // Script "classes"
@@ -20,8 +20,13 @@
 */
 
 pub mod callbacks;
+pub mod compositor;
 pub mod constants;
+#[cfg(test)]
+pub mod golden_frames;
+pub mod layers;
 pub mod manifest;
 pub mod parameters;
 pub mod parameters_util;
+pub mod sandbox;
 pub mod script;
@@ -45,6 +45,30 @@ pub enum ParametersUtilError {
 
     #[error("Script manifest does not reference the parameter")]
     NoSuchParameter {},
+
+    #[error("Invalid type of value, expected: {expected}")]
+    TypeMismatch { expected: String },
+
+    #[error("Value {value} is out of range, expected: {min}..{max}")]
+    OutOfRange {
+        value: String,
+        min: String,
+        max: String,
+    },
+}
+
+/// A parameter of a script of the active profile, merged from the script manifest
+/// and the profile
+#[derive(Debug, Clone)]
+pub struct ParameterInfo {
+    pub script_file: PathBuf,
+    pub script_name: String,
+    pub name: String,
+    pub description: String,
+    pub manifest: ManifestValue,
+
+    /// The current value of the parameter
+    pub value: TypedValue,
 }
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -99,6 +123,203 @@ pub fn apply_parameters(
     Ok(())
 }
 
+/// Get the current value of a parameter, or the default value from the manifest if the
+/// profile does not override it
+pub fn get_parameter(
+    profile_file: &str,
+    script_file: &str,
+    param_name: &str,
+) -> Result<TypedValue> {
+    let profile_path = PathBuf::from(&profile_file);
+    let script_path = PathBuf::from(&script_file);
+
+    let manifest = match Manifest::load(&script_path) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!("Could not open manifest file: {}", e);
+            return Err(ParametersUtilError::OpenError {}.into());
+        }
+    };
+
+    let is_active_profile = crate::ACTIVE_PROFILE
+        .lock()
+        .as_ref()
+        .map(|active_profile| is_same_file(&active_profile.profile_file, &profile_path))
+        .unwrap_or(false);
+
+    let profile_parameter = if is_active_profile {
+        crate::ACTIVE_PROFILE
+            .lock()
+            .as_ref()
+            .and_then(|profile| profile.config.get_parameter(&manifest.name, param_name))
+            .cloned()
+    } else {
+        match Profile::load_file_and_state_only(&profile_path) {
+            Ok(profile) => profile
+                .config
+                .get_parameter(&manifest.name, param_name)
+                .cloned(),
+
+            Err(e) => {
+                error!("Could not open profile file: {}", e);
+                return Err(ParametersUtilError::OpenError {}.into());
+            }
+        }
+    };
+
+    match profile_parameter {
+        Some(profile_parameter) => Ok(profile_parameter.value),
+
+        None => match manifest.config.get_parameter(param_name) {
+            Some(manifest_parameter) => Ok(manifest_parameter.get_default()),
+            None => Err(ParametersUtilError::NoSuchParameter {}.into()),
+        },
+    }
+}
+
+/// Get all parameters of all scripts of the active profile, along with their current values
+pub fn get_active_profile_parameters() -> Result<Vec<ParameterInfo>> {
+    let mut result = Vec::new();
+
+    if let Some(profile) = crate::ACTIVE_PROFILE.lock().as_ref() {
+        for manifest in profile.manifests.values() {
+            for manifest_parameter in manifest.config.iter() {
+                let value = profile
+                    .config
+                    .get_parameter(&manifest.name, &manifest_parameter.name)
+                    .map(|p| p.value.clone())
+                    .unwrap_or_else(|| manifest_parameter.get_default());
+
+                result.push(ParameterInfo {
+                    script_file: manifest.script_file.clone(),
+                    script_name: manifest.name.clone(),
+                    name: manifest_parameter.name.clone(),
+                    description: manifest_parameter.description.clone(),
+                    manifest: manifest_parameter.manifest.clone(),
+                    value,
+                });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Set a parameter to a typed value. Unlike `apply_parameters`, the value is validated against
+/// the script manifest
+pub fn apply_typed_parameter(
+    profile_file: &str,
+    script_file: &str,
+    param_name: &str,
+    value: TypedValue,
+) -> Result<()> {
+    let manifest = match Manifest::load(&PathBuf::from(&script_file)) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            error!("Could not open manifest file: {}", e);
+            return Err(ParametersUtilError::OpenError {}.into());
+        }
+    };
+
+    let manifest_parameter = manifest
+        .config
+        .get_parameter(param_name)
+        .ok_or(ParametersUtilError::NoSuchParameter {})?;
+
+    let value = validate_value(&manifest_parameter.manifest, value)?;
+
+    apply_parameters(
+        profile_file,
+        script_file,
+        &[UntypedParameter {
+            name: param_name.to_string(),
+            value: value.to_string(),
+        }],
+    )
+}
+
+/// Verify that `value` is of the type that is declared in the manifest, and that it is within
+/// the declared range. Integers are accepted for float and color parameters
+pub fn validate_value(manifest: &ManifestValue, value: TypedValue) -> Result<TypedValue> {
+    fn check_range<T: PartialOrd + ToString>(
+        value: T,
+        min: Option<T>,
+        max: Option<T>,
+    ) -> Result<()> {
+        let below = min.as_ref().map(|min| value < *min).unwrap_or(false);
+        let above = max.as_ref().map(|max| value > *max).unwrap_or(false);
+
+        if below || above {
+            Err(ParametersUtilError::OutOfRange {
+                value: value.to_string(),
+                min: min.map(|v| v.to_string()).unwrap_or_default(),
+                max: max.map(|v| v.to_string()).unwrap_or_default(),
+            }
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    let type_mismatch = |expected: &str| -> eyre::Error {
+        ParametersUtilError::TypeMismatch {
+            expected: expected.to_string(),
+        }
+        .into()
+    };
+
+    let result = match (manifest, value) {
+        (ManifestValue::Int { min, max, .. }, TypedValue::Int(value)) => {
+            check_range(value, *min, *max)?;
+            TypedValue::Int(value)
+        }
+
+        (ManifestValue::Float { min, max, .. }, TypedValue::Float(value)) => {
+            // NaN compares false to any bound, so it would pass the range check
+            if !value.is_finite() {
+                return Err(ParametersUtilError::OutOfRange {
+                    value: value.to_string(),
+                    min: min.map(|v| v.to_string()).unwrap_or_default(),
+                    max: max.map(|v| v.to_string()).unwrap_or_default(),
+                }
+                .into());
+            }
+
+            check_range(value, *min, *max)?;
+            TypedValue::Float(value)
+        }
+
+        (ManifestValue::Float { min, max, .. }, TypedValue::Int(value)) => {
+            check_range(value as f64, *min, *max)?;
+            TypedValue::Float(value as f64)
+        }
+
+        (ManifestValue::Bool { .. }, TypedValue::Bool(value)) => TypedValue::Bool(value),
+
+        (ManifestValue::String { .. }, TypedValue::String(value)) => TypedValue::String(value),
+
+        (ManifestValue::Color { min, max, .. }, TypedValue::Color(value)) => {
+            check_range(value, *min, *max)?;
+            TypedValue::Color(value)
+        }
+
+        (ManifestValue::Color { min, max, .. }, TypedValue::Int(value)) => {
+            let value = u32::try_from(value).map_err(|_| type_mismatch("color"))?;
+
+            check_range(value, *min, *max)?;
+            TypedValue::Color(value)
+        }
+
+        (ManifestValue::Int { .. }, _) => return Err(type_mismatch("int")),
+        (ManifestValue::Float { .. }, _) => return Err(type_mismatch("float")),
+        (ManifestValue::Bool { .. }, _) => return Err(type_mismatch("bool")),
+        (ManifestValue::String { .. }, _) => return Err(type_mismatch("string")),
+        (ManifestValue::Color { .. }, _) => return Err(type_mismatch("color")),
+    };
+
+    Ok(result)
+}
+
 fn update_profile_and_state_file(
     profile: &mut Profile,
     script_path: &Path,
@@ -197,3 +418,76 @@ fn parse_new_profile_parameter(
         manifest: Some(manifest_parameter.manifest.to_owned()),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_value, ParametersUtilError};
+    use crate::scripting::parameters::{ManifestValue, TypedValue};
+
+    #[test]
+    fn validate_parameter_ranges() {
+        let manifest = ManifestValue::Int {
+            default: 5,
+            min: Some(1),
+            max: Some(10),
+        };
+
+        assert_eq!(
+            validate_value(&manifest, TypedValue::Int(10)).unwrap(),
+            TypedValue::Int(10)
+        );
+
+        let err = validate_value(&manifest, TypedValue::Int(11)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ParametersUtilError>(),
+            Some(ParametersUtilError::OutOfRange { .. })
+        ));
+
+        let manifest = ManifestValue::Float {
+            default: 0.5,
+            min: Some(0.0),
+            max: None,
+        };
+
+        assert_eq!(
+            validate_value(&manifest, TypedValue::Int(2)).unwrap(),
+            TypedValue::Float(2.0)
+        );
+        assert!(validate_value(&manifest, TypedValue::Float(-0.1)).is_err());
+
+        // a missing bound does not admit non-finite values
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let err = validate_value(&manifest, TypedValue::Float(value)).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ParametersUtilError>(),
+                Some(ParametersUtilError::OutOfRange { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn validate_parameter_types() {
+        let manifest = ManifestValue::Color {
+            default: 0xff0000,
+            min: None,
+            max: None,
+        };
+
+        assert_eq!(
+            validate_value(&manifest, TypedValue::Int(0x00ff00)).unwrap(),
+            TypedValue::Color(0x00ff00)
+        );
+
+        for value in [
+            TypedValue::Int(-1),
+            TypedValue::Bool(true),
+            TypedValue::String("#00ff00".to_string()),
+        ] {
+            let err = validate_value(&manifest, value).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ParametersUtilError>(),
+                Some(ParametersUtilError::TypeMismatch { .. })
+            ));
+        }
+    }
+}
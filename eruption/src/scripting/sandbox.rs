@@ -0,0 +1,247 @@
+/*  SPDX-License-Identifier: GPL-3.0-or-later  */
+
+/*
+    This file is part of Eruption.
+
+    Eruption is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Eruption is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with Eruption.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Enforcement of the capabilities that a script declares in its manifest
+//!
+//! Lua VMs are created in safe mode, so scripts can neither load C modules nor use the
+//! `debug` library. Only source code may be loaded, since Lua does not verify precompiled
+//! chunks. The `io` library is only loaded if the respective capability has been granted,
+//! and all other privileged functions are replaced by stubs that raise an error when called.
+
+use mlua::prelude::*;
+use mlua::{LuaOptions, StdLib};
+
+use super::manifest::Capability;
+
+/// The Lua standard libraries that are available to every script
+fn default_std_libs() -> StdLib {
+    StdLib::COROUTINE
+        | StdLib::TABLE
+        | StdLib::STRING
+        | StdLib::UTF8
+        | StdLib::MATH
+        | StdLib::OS
+        | StdLib::PACKAGE
+}
+
+/// Restricts the loading of code to Lua source code, and `require()` to Lua modules
+/// in the directories on `package.path`
+const RESTRICT_CODE_LOADING: &str = r#"
+local load, loadfile, searchpath = load, loadfile, package.searchpath
+
+_G.load = function(chunk, chunkname, _mode, ...)
+    return load(chunk, chunkname, "t", ...)
+end
+
+_G.loadfile = function(filename, _mode, ...)
+    return loadfile(filename, "t", ...)
+end
+
+_G.dofile = function(filename)
+    return assert(loadfile(filename, "t"))()
+end
+
+string.dump = nil
+
+package.path = ""
+package.cpath = ""
+package.loadlib = nil
+package.searchers = {
+    package.searchers[1],
+    function(name)
+        local filename, err = searchpath(name, package.path)
+        if filename == nil then
+            return err
+        end
+
+        return assert(loadfile(filename, "t")), filename
+    end,
+}
+"#;
+
+impl Capability {
+    /// The Lua standard library that is loaded, only if the capability has been granted
+    fn std_lib(&self) -> StdLib {
+        match *self {
+            Capability::Io => StdLib::IO,
+            Capability::Os | Capability::System | Capability::InjectInput | Capability::Network => {
+                StdLib::NONE
+            }
+        }
+    }
+
+    /// The functions that are revoked, unless the capability has been granted
+    fn functions(&self) -> &'static [&'static str] {
+        match *self {
+            Capability::Io => &["dofile", "loadfile"],
+            Capability::Os => &[
+                "os.execute",
+                "os.exit",
+                "os.getenv",
+                "os.remove",
+                "os.rename",
+                "os.setlocale",
+                "os.tmpname",
+            ],
+            Capability::System => &["system", "exit"],
+            Capability::InjectInput => &[
+                "inject_key",
+                "inject_key_with_delay",
+                "inject_mouse_button",
+                "inject_mouse_wheel",
+            ],
+            // reserved, no function requires network access yet
+            Capability::Network => &[],
+        }
+    }
+}
+
+const ALL_CAPABILITIES: [Capability; 5] = [
+    Capability::Io,
+    Capability::Os,
+    Capability::System,
+    Capability::InjectInput,
+    Capability::Network,
+];
+
+/// Create a new Lua VM, with the standard libraries permitted by `capabilities`
+pub fn new_vm(capabilities: &[Capability]) -> LuaResult<Lua> {
+    let std_libs = capabilities
+        .iter()
+        .fold(default_std_libs(), |libs, capability| {
+            libs | capability.std_lib()
+        });
+
+    let lua_ctx = Lua::new_with(std_libs, LuaOptions::default())?;
+
+    lua_ctx.load(RESTRICT_CODE_LOADING).exec()?;
+
+    Ok(lua_ctx)
+}
+
+/// Replace all registered functions that require a capability not contained in `capabilities`
+pub fn revoke_capabilities(lua_ctx: &Lua, capabilities: &[Capability]) -> LuaResult<()> {
+    let globals = lua_ctx.globals();
+
+    for capability in ALL_CAPABILITIES
+        .iter()
+        .filter(|capability| !capabilities.contains(capability))
+    {
+        for name in capability.functions() {
+            let message = format!(
+                "{}() requires the '{}' capability, which is not granted by the script manifest",
+                name,
+                capability.get_name()
+            );
+
+            let stub = lua_ctx.create_function(move |_, ()| -> LuaResult<()> {
+                Err(LuaError::RuntimeError(message.clone()))
+            })?;
+
+            match name.split_once('.') {
+                Some((table, name)) => globals.get::<_, LuaTable>(table)?.set(name, stub)?,
+                None => globals.set(*name, stub)?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn libraries_require_capabilities() -> LuaResult<()> {
+        let lua_ctx = new_vm(&[])?;
+
+        assert!(lua_ctx
+            .load("return io == nil and debug == nil")
+            .eval::<bool>()?);
+        assert!(lua_ctx
+            .load("return string ~= nil and math ~= nil and os.clock ~= nil")
+            .eval::<bool>()?);
+
+        let lua_ctx = new_vm(&[Capability::Io])?;
+
+        assert!(lua_ctx.load("return io ~= nil").eval::<bool>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn functions_are_revoked_unless_granted() -> LuaResult<()> {
+        let lua_ctx = new_vm(&[])?;
+        let globals = lua_ctx.globals();
+
+        globals.set("exit", lua_ctx.create_function(|_, ()| Ok(true))?)?;
+        globals.set("inject_key", lua_ctx.create_function(|_, ()| Ok(true))?)?;
+
+        revoke_capabilities(&lua_ctx, &[Capability::InjectInput])?;
+
+        let error = lua_ctx.load("exit()").exec().unwrap_err();
+        assert!(error.to_string().contains("'system' capability"));
+
+        let error = lua_ctx.load("os.execute('true')").exec().unwrap_err();
+        assert!(error.to_string().contains("'os' capability"));
+
+        assert!(lua_ctx.load("return inject_key(30, true)").eval::<bool>()?);
+        assert!(lua_ctx.load("dofile('/etc/passwd')").exec().is_err());
+        assert!(lua_ctx.load("return os.time() > 0").eval::<bool>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn only_source_code_may_be_loaded() -> LuaResult<()> {
+        let lua_ctx = new_vm(&[Capability::Io])?;
+
+        assert!(lua_ctx.load("return string.dump == nil").eval::<bool>()?);
+        assert!(lua_ctx
+            .load(r#"return load("\27Lua\84\0", "chunk", "b") == nil"#)
+            .eval::<bool>()?);
+
+        // loading source code still works, with and without an environment
+        assert!(lua_ctx
+            .load("return load('return 1')() == 1")
+            .eval::<bool>()?);
+        assert!(lua_ctx
+            .load("return load('return x', 'chunk', 't', { x = 2 })() == 2")
+            .eval::<bool>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn native_modules_can_not_be_loaded() -> LuaResult<()> {
+        let lua_ctx = new_vm(&[])?;
+
+        assert!(lua_ctx
+            .load(
+                "return package.loadlib == nil and package.cpath == '' and #package.searchers == 2"
+            )
+            .eval::<bool>()?);
+        assert!(lua_ctx.load("require('socket')").exec().is_err());
+
+        Ok(())
+    }
+}
@@ -19,7 +19,7 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
-use flume::Receiver;
+use flume::{Receiver, Sender};
 use lazy_static::lazy_static;
 use log::*;
 use mlua::prelude::*;
@@ -34,15 +34,20 @@ use std::fs;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
 use std::vec::Vec;
 
 use crate::{
-    constants, hwdevices::KeyboardHidEvent, hwdevices::MouseHidEvent, hwdevices::RGBA,
-    scripting::callbacks, scripting::constants::*,
+    constants, hwdevices::KeyboardHidEvent, hwdevices::MouseHidEvent, hwdevices::RGBA, layout,
+    metrics, scheduler, scripting::callbacks, scripting::constants::*,
 };
 
+use super::compositor;
+use super::layers::Layer;
+use super::manifest::Capability;
 use super::parameters::PlainParameter;
 use super::parameters::TypedValue;
+use super::sandbox;
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
 
@@ -76,6 +81,10 @@ pub enum Message {
     SetParameters {
         parameter_values: Vec<PlainParameter>,
     },
+
+    /// Acknowledge on the supplied channel, as soon as all previously queued messages have been processed
+    #[allow(dead_code)]
+    Sync(Sender<()>),
 }
 
 lazy_static! {
@@ -85,7 +94,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; layout::canvas_size()]));
 
     /// The last successfully rendered canvas
     pub static ref LAST_RENDERED_LED_MAP: Arc<RwLock<Vec<RGBA>>> = Arc::new(RwLock::new(vec![RGBA {
@@ -93,7 +102,7 @@ lazy_static! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]));
+    }; layout::canvas_size()]));
 
     /// Frame generation counter, used to detect if we need to submit the LED_MAP to the hardware
     pub static ref FRAME_GENERATION_COUNTER: AtomicUsize = AtomicUsize::new(0);
@@ -106,11 +115,17 @@ thread_local! {
         g: 0x00,
         b: 0x00,
         a: 0x00,
-    }; constants::CANVAS_SIZE]);
+    }; layout::canvas_size()]);
 
     /// True, if LED color map was modified at least once in this thread
     pub static LOCAL_LED_MAP_MODIFIED: RefCell<bool> = RefCell::new(false);
 
+    /// The layer of the script running in this thread, e.g. its blend mode, opacity and mask
+    pub static LAYER: RefCell<Layer> = RefCell::new(Layer::default());
+
+    /// Index of the script running in this thread, in the layer stack of the active profile
+    pub static THREAD_INDEX: RefCell<usize> = RefCell::new(0);
+
     /// Vec of allocated gradient objects
     pub static ALLOCATED_GRADIENTS: RefCell<HashMap<usize, colorgrad::Gradient>> = RefCell::new(HashMap::new());
 }
@@ -236,18 +251,30 @@ impl<'lua> RunningScriptCallHelper<'lua> {
 pub fn run_script(
     script_file: &Path,
     parameter_values: &mut BTreeMap<String, PlainParameter>,
+    capabilities: &[Capability],
     rx: &Receiver<Message>,
 ) -> Result<RunScriptResult> {
     match fs::read_to_string(script_file) {
         Ok(script) => {
-            let lua_ctx =
-                unsafe { Lua::unsafe_new_with(mlua::StdLib::ALL, mlua::LuaOptions::default()) };
+            let lua_ctx = match sandbox::new_vm(capabilities) {
+                Ok(lua_ctx) => lua_ctx,
+                Err(e) => {
+                    error!("Could not create a Lua VM: {}", e);
+                    return Ok(RunScriptResult::TerminatedWithErrors);
+                }
+            };
 
             // Prepare the Lua environment and eval the script
             let prepared = register_support_globals(&lua_ctx)
                 .and_then(|()| register_support_funcs(&lua_ctx))
+                .and_then(|()| sandbox::revoke_capabilities(&lua_ctx, capabilities))
                 .and_then(|()| set_parameter_values(&lua_ctx, parameter_values.values()))
-                .and_then(|()| lua_ctx.load(&script).eval::<()>());
+                .and_then(|()| {
+                    lua_ctx
+                        .load(&script)
+                        .set_mode(mlua::ChunkMode::Text)
+                        .eval::<()>()
+                });
 
             if let Err(e) = prepared {
                 error!(
@@ -315,16 +342,23 @@ fn register_support_globals(lua_ctx: &Lua) -> mlua::Result<()> {
         .get::<Vec<String>>("global.script_dirs")
         .unwrap_or_else(|_| vec![constants::DEFAULT_SCRIPT_DIR.to_string()]);
 
-    let mut path_spec = String::from("package.path = package.path .. '");
-
-    for script_dir in script_dirs {
-        path_spec += &format!(";{0}/lib/?;{0}/lib/?.lua", &script_dir);
-    }
-
-    path_spec += "'";
+    // modules are only ever loaded from the script directories
+    let path_spec = format!(
+        "package.path = '{}'",
+        script_dirs
+            .iter()
+            .map(|script_dir| format!("{0}/lib/?;{0}/lib/?.lua", script_dir))
+            .collect::<Vec<_>>()
+            .join(";")
+    );
 
     lua_ctx.load(&path_spec).exec().unwrap();
 
+    // make `math.random()` reproducible as well
+    if let Some(seed) = *callbacks::DETERMINISTIC_SEED.read() {
+        lua_ctx.load(&format!("math.randomseed({})", seed)).exec()?;
+    }
+
     let mut config: BTreeMap<&str, &str> = BTreeMap::new();
     config.insert("daemon_name", "eruption");
     config.insert("daemon_version", env!("CARGO_PKG_VERSION"));
@@ -385,6 +419,11 @@ fn process_message(
         Message::SetParameters { parameter_values } => {
             on_apply_parameters(call_helper, parameter_values)
         }
+        Message::Sync(tx) => {
+            tx.send(()).ok();
+
+            Ok(RunningScriptResult::Continue)
+        }
     }
 }
 
@@ -412,7 +451,14 @@ fn on_tick(call_helper: &mut RunningScriptCallHelper, param: u32) -> Result<Runn
     let called = if call_helper.skip_on_tick {
         Ok(RunningScriptCallHelperResult::NoHandler)
     } else {
-        call_helper.call(FUNCTION_ON_TICK, param)
+        let start_time = Instant::now();
+        let result = call_helper.call(FUNCTION_ON_TICK, param);
+        let elapsed = start_time.elapsed();
+
+        THREAD_INDEX.with(|index| scheduler::record_script_time(*index.borrow(), elapsed));
+        metrics::script_ticked(Path::new(&call_helper.file_name), elapsed);
+
+        result
     };
 
     continue_if_ok(called)
@@ -432,22 +478,14 @@ fn realize_color_map() -> Result<RunningScriptResult> {
                 brightness as f32
             };
 
-            for chunks in LED_MAP.write().chunks_exact_mut(constants::CANVAS_SIZE) {
-                for (idx, background) in chunks.iter_mut().enumerate() {
-                    let bg = &background;
-                    let fg = foreground.borrow()[idx];
-
-                    #[rustfmt::skip]
-                    let color = RGBA {
-                        r: ((((fg.a as f32) * fg.r as f32 + (255 - fg.a) as f32 * bg.r as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        g: ((((fg.a as f32) * fg.g as f32 + (255 - fg.a) as f32 * bg.g as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        b: ((((fg.a as f32) * fg.b as f32 + (255 - fg.a) as f32 * bg.b as f32).floor() * brightness / 100.0) as u32 >> 8) as u8,
-                        a: fg.a,
-                    };
-
-                    *background = color;
-                }
-            }
+            LAYER.with(|layer| {
+                compositor::blend(
+                    &layer.borrow(),
+                    &mut LED_MAP.write(),
+                    &foreground.borrow(),
+                    brightness,
+                )
+            });
         });
     }
 
@@ -30,8 +30,12 @@ canvas_width = get_canvas_width()
 keyboard_zone_start = 1
 keyboard_zone_end = get_num_keys()
 
-mouse_zone_start = get_canvas_size() - 36
-mouse_zone_end = get_canvas_size() + 36
+-- the mouse zone is located at the end of the legacy canvas area, that is
+-- used by all devices which have not been placed on the canvas layout
+legacy_canvas_size = 144 + 36
+
+mouse_zone_start = legacy_canvas_size - 36
+mouse_zone_end = legacy_canvas_size + 36
 
 -- Keyboard topology maps --
 -- use 'table_offset = 0' for the ISO model
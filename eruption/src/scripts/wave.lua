@@ -33,7 +33,33 @@ end
 function on_tick(delta)
     ticks = ticks + delta
 
-    if horizontal then
+    if is_canvas_layout_active() then
+        -- devices have been placed on the canvas layout, so let the
+        -- wave flow across the whole canvas, instead of the keyboard only
+        local num_lines = canvas_height
+        local line_length = canvas_width
+
+        if horizontal then
+            num_lines = canvas_width
+            line_length = canvas_height
+        end
+
+        for i = num_lines - 1, 0, -1 do
+            local alpha = (sin(i / wave_length +
+                                   (ticks * direction / speed_divisor)) + 1) *
+                              scale_factor
+            local r, g, b = color_to_rgb(color_wave)
+
+            for j = 0, line_length - 1 do
+                local index = i * canvas_width + j + 1
+
+                if horizontal then index = j * canvas_width + i + 1 end
+
+                color_map[index] = rgba_to_color(r, g, b,
+                                                 clamp(alpha * opacity, 0, 255))
+            end
+        end
+    elseif horizontal then
         for i = num_cols, 0, -1 do
             for j = 1, max_keys_per_col do
                 local alpha = (sin(i / wave_length +
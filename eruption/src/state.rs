@@ -30,8 +30,12 @@ use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
+use crate::calibration::{self, ColorCalibration};
 use crate::color_scheme::ColorScheme;
+use crate::hwdevices::remap::{self, KeyRemap};
+use crate::layout::{self, Layout};
 use crate::plugins::audio;
+use crate::scheduler;
 use crate::{constants, util};
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -65,6 +69,18 @@ struct State {
 
     /// Device specific brightness
     device_brightness: HashMap<DeviceMakeModelSerial, i32>,
+
+    /// Placement of the devices on the virtual canvas
+    layout: Layout,
+
+    /// Device specific color calibration
+    device_calibration: HashMap<DeviceMakeModelSerial, ColorCalibration>,
+
+    /// Device specific frame rate limits
+    device_frame_rate: HashMap<DeviceMakeModelSerial, u64>,
+
+    /// Firmware key remappings of keyboards
+    device_key_remap: HashMap<DeviceMakeModelSerial, Vec<KeyRemap>>,
 }
 
 pub fn init_global_runtime_state() -> Result<()> {
@@ -156,6 +172,42 @@ pub fn init_global_runtime_state() -> Result<()> {
         *slot_names = default_slot_names;
     }
 
+    layout::init_layout(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<Layout>("layout")
+            .unwrap_or_default(),
+    );
+
+    calibration::init_calibrations(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<HashMap<DeviceMakeModelSerial, ColorCalibration>>("device_calibration")
+            .unwrap_or_default(),
+    );
+
+    scheduler::init_device_frame_rates(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<HashMap<DeviceMakeModelSerial, u64>>("device_frame_rate")
+            .unwrap_or_default(),
+    );
+
+    remap::init_key_remaps(
+        STATE
+            .read()
+            .as_ref()
+            .unwrap()
+            .get::<HashMap<DeviceMakeModelSerial, Vec<KeyRemap>>>("device_key_remap")
+            .unwrap_or_default(),
+    );
+
     perform_sanity_checks();
 
     Ok(())
@@ -279,6 +331,10 @@ pub fn save_runtime_state() -> Result<()> {
         enable_sfx: audio::ENABLE_SFX.load(Ordering::SeqCst),
         brightness: crate::BRIGHTNESS.load(Ordering::SeqCst) as i64,
         device_brightness,
+        layout: layout::LAYOUT.read().clone(),
+        device_calibration: calibration::get_calibrations(),
+        device_frame_rate: scheduler::get_device_frame_rates(),
+        device_key_remap: remap::get_key_remaps(),
     };
 
     let toml = toml::ser::to_string_pretty(&config).map_err(|e| StateError::StateWriteError {
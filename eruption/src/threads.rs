@@ -23,7 +23,7 @@ use evdev_rs::enums::EV_SYN;
 use evdev_rs::{Device, DeviceWrapper, GrabMode};
 use flume::{unbounded, Receiver, Sender};
 use log::{debug, error, info, trace, warn};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::fs::File;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -32,10 +32,12 @@ use std::time::{Duration, Instant};
 
 use crate::util::ratelimited;
 use crate::{
-    constants, dbus_interface, hwdevices, macros, plugins, script,
-    scripting::parameters::PlainParameter, sdk_support, uleds, DeviceAction, EvdevError,
-    KeyboardDevice, MainError, MouseDevice, COLOR_MAPS_READY_CONDITION, FAILED_TXS, KEY_STATES,
-    LUA_TXS, QUIT, REQUEST_FAILSAFE_MODE, RGBA, SDK_SUPPORT_ACTIVE, ULEDS_SUPPORT_ACTIVE,
+    battery, calibration, canvas_stream, constants, dbus_interface, hwdevices, layout, macros,
+    metrics, overlays, plugins, recording, scheduler, script, scripting::layers::Layer,
+    scripting::manifest::Capability, scripting::parameters::PlainParameter, sdk_support, uleds,
+    DeviceAction, EvdevError, KeyboardDevice, MainError, MouseDevice, COLOR_MAPS_READY_CONDITION,
+    FAILED_TXS, KEY_STATES, LUA_TXS, QUIT, REQUEST_FAILSAFE_MODE, RGBA, SDK_SUPPORT_ACTIVE,
+    ULEDS_SUPPORT_ACTIVE,
 };
 
 pub type Result<T> = std::result::Result<T, eyre::Error>;
@@ -48,6 +50,7 @@ pub enum DbusApiEvent {
     BrightnessChanged,
     DeviceStatusChanged,
     DeviceHotplug((u16, u16), bool),
+    BatteryLevelLow(u64, u8, bool),
 }
 
 /// Spawns the D-Bus API thread and executes it's main loop
@@ -88,6 +91,10 @@ pub fn spawn_dbus_api_thread(
                         DbusApiEvent::DeviceHotplug(device_info, remove) => {
                             dbus.notify_device_hotplug(device_info, remove)?
                         }
+
+                        DbusApiEvent::BatteryLevelLow(device, level, critical) => {
+                            dbus.notify_battery_level_low(device, level, critical)?
+                        }
                     },
 
                     Err(_e) => {
@@ -199,6 +206,8 @@ pub fn spawn_keyboard_input_thread(
                                 e
                             );
 
+                            metrics::input_event_dropped("keyboard");
+
                             // try to recover from an invalid state
                             // keyboard_device.write().close_all().unwrap_or_else(|e| {
                             //     warn!("Could not close the device: {}", e);
@@ -261,6 +270,38 @@ pub fn spawn_keyboard_input_thread(
     Ok(())
 }
 
+/// Spawns the input thread of a virtual device. Virtual devices replay their input
+/// events through `get_next_event_timeout()`, so this thread only keeps the channel open
+pub fn spawn_virtual_input_thread(
+    tx: Sender<Option<evdev_rs::InputEvent>>,
+    device_index: usize,
+) -> plugins::Result<()> {
+    thread::Builder::new()
+        .name(format!("events/virtual:{}", device_index))
+        .spawn(move || -> Result<()> {
+            #[cfg(feature = "profiling")]
+            coz::thread_init();
+
+            // the sender will be dropped when this thread terminates
+            let _tx = tx;
+
+            loop {
+                // check if we shall terminate the input thread
+                if QUIT.load(Ordering::SeqCst) {
+                    break Ok(());
+                }
+
+                thread::sleep(Duration::from_millis(constants::VIRTUAL_DEVICE_POLL_MILLIS));
+            }
+        })
+        .unwrap_or_else(|e| {
+            error!("Could not spawn a thread: {}", e);
+            panic!()
+        });
+
+    Ok(())
+}
+
 /// Spawns the mouse events thread and executes it's main loop
 pub fn spawn_mouse_input_thread(
     mouse_tx: Sender<Option<evdev_rs::InputEvent>>,
@@ -333,6 +374,8 @@ pub fn spawn_mouse_input_thread(
                         if let evdev_rs::enums::EventCode::EV_SYN(code) = k.1.clone().event_code {
                             if code == EV_SYN::SYN_DROPPED {
                                 warn!("Mouse:{} dropped some events, resyncing...", device_index);
+                                metrics::input_event_dropped("mouse");
+
                                 device.next_event(evdev_rs::ReadFlag::SYNC)?;
                             } else {
                                 // directly mirror SYN events to reduce input lag
@@ -400,6 +443,8 @@ pub fn spawn_mouse_input_thread(
                                 e
                             );
 
+                            metrics::input_event_dropped("mouse");
+
                             // try to recover from an invalid state
                             // mouse_device.write().close_all().unwrap_or_else(|e| {
                             //     warn!("Could not close the device: {}", e);
@@ -703,6 +748,8 @@ pub fn spawn_misc_input_thread(
                                 e
                             );
 
+                            metrics::input_event_dropped("misc");
+
                             // mark the device as failed
                             misc_device
                                 .write()
@@ -765,9 +812,22 @@ pub fn spawn_lua_thread(
     lua_rx: Receiver<script::Message>,
     script_file: &Path,
     parameters: &[PlainParameter],
+    capabilities: &[Capability],
+    layer: Layer,
 ) -> Result<()> {
     info!("Loading Lua script: {}", script_file.display());
 
+    if !capabilities.is_empty() {
+        info!(
+            "Granting capabilities: {}",
+            capabilities
+                .iter()
+                .map(|c| c.get_name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
     let builder = thread::Builder::new().name(format!(
         "{}:{}",
         thread_idx,
@@ -775,6 +835,7 @@ pub fn spawn_lua_thread(
     ));
 
     let script_file = script_file.to_path_buf();
+    let capabilities = capabilities.to_vec();
     let mut parameter_values: BTreeMap<String, PlainParameter> = parameters
         .iter()
         .map(|pv| (pv.name.clone(), pv.clone()))
@@ -784,8 +845,12 @@ pub fn spawn_lua_thread(
         #[cfg(feature = "profiling")]
         coz::thread_init();
 
+        script::LAYER.with(|l| *l.borrow_mut() = layer);
+        script::THREAD_INDEX.with(|i| *i.borrow_mut() = thread_idx);
+
         loop {
-            let result = script::run_script(&script_file, &mut parameter_values, &lua_rx);
+            let result =
+                script::run_script(&script_file, &mut parameter_values, &capabilities, &lua_rx);
 
             match result {
                 Ok(script::RunScriptResult::RestartScript) => {
@@ -843,64 +908,68 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                 Ok(message) => match message {
                     DeviceAction::RenderNow  => {
                         let current_frame_generation = script::FRAME_GENERATION_COUNTER.load(Ordering::SeqCst);
-                        if saved_frame_generation.load(Ordering::SeqCst) < current_frame_generation {
-                            // instruct the Lua VMs to realize their color maps, but only if at least one VM
-                            // submitted a new color map (performed a frame generation increment)
+
+                        // instruct the Lua VMs to realize their color maps, but only if at least one VM
+                        // submitted a new color map (performed a frame generation increment)
+                        let realize = saved_frame_generation.load(Ordering::SeqCst) < current_frame_generation;
+
+                        // otherwise, only send the current canvas to devices that lag behind, e.g. due to
+                        // their frame rate limit, or because a refresh of the devices has been requested
+                        if realize || scheduler::has_pending_devices(current_frame_generation) {
+                            let render_start = Instant::now();
 
                             // execute render "pipeline" now...
                             let mut drop_frame = false;
 
-                            // first, start with a clear canvas
-                            script::LED_MAP.write().copy_from_slice(
-                                &[RGBA {
+                            if realize {
+                                // first, start with a clear canvas
+                                script::LED_MAP.write().fill(RGBA {
                                     r: 0,
                                     g: 0,
                                     b: 0,
                                     a: 0,
-                                }; constants::CANVAS_SIZE],
-                            );
-
-                            // instruct Lua VMs to realize their color maps,
-                            // e.g. to blend their local color maps with the canvas
-                            *COLOR_MAPS_READY_CONDITION.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
-
-                            for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
-                                // if this tx failed previously, then skip it completely
-                                if !FAILED_TXS.read().contains(&index) {
-                                    // guarantee the right order of execution for the alpha blend
-                                    // operations, so we have to wait for the current Lua VM to
-                                    // complete its blending code, before continuing
-                                    let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
-
-                                    lua_tx
-                                        .send(script::Message::RealizeColorMap)
-                                        .unwrap_or_else(|e| {
-                                            error!("Send error during realization of color maps: {}", e);
-                                            FAILED_TXS.write().insert(index);
-                                        });
-
-                                    let result = COLOR_MAPS_READY_CONDITION.1.wait_for(
-                                        &mut pending,
-                                        Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
-                                    );
+                                });
+
+                                // instruct Lua VMs to realize their color maps,
+                                // e.g. to blend their local color maps with the canvas
+                                *COLOR_MAPS_READY_CONDITION.0.lock() = LUA_TXS.read().len() - FAILED_TXS.read().len();
+
+                                for (index, lua_tx) in LUA_TXS.read().iter().enumerate() {
+                                    // if this tx failed previously, then skip it completely
+                                    if !FAILED_TXS.read().contains(&index) {
+                                        // guarantee the right order of execution for the alpha blend
+                                        // operations, so we have to wait for the current Lua VM to
+                                        // complete its blending code, before continuing
+                                        let mut pending = COLOR_MAPS_READY_CONDITION.0.lock();
+
+                                        lua_tx
+                                            .send(script::Message::RealizeColorMap)
+                                            .unwrap_or_else(|e| {
+                                                error!("Send error during realization of color maps: {}", e);
+                                                FAILED_TXS.write().insert(index);
+                                            });
+
+                                        let result = COLOR_MAPS_READY_CONDITION.1.wait_for(
+                                            &mut pending,
+                                            Duration::from_millis(constants::TIMEOUT_CONDITION_MILLIS),
+                                        );
 
-                                    if result.timed_out() {
+                                        if result.timed_out() {
+                                            drop_frame = true;
+                                            warn!("Frame dropped: Timeout while waiting for a lock!");
+                                            break;
+                                        }
+                                    } else {
                                         drop_frame = true;
-                                        warn!("Frame dropped: Timeout while waiting for a lock!");
-                                        break;
                                     }
-                                } else {
-                                    drop_frame = true;
                                 }
-                            }
 
-                            if ULEDS_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
-                                // blend the LED map of the Userspace LEDs support plugin
-                                let uleds_led_map = uleds::LED_MAP.read();
-                                let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+                                if ULEDS_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
+                                    // blend the LED map of the Userspace LEDs support plugin
+                                    let uleds_led_map = uleds::LED_MAP.read();
+                                    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
-                                for chunks in script::LED_MAP.write().chunks_exact_mut(constants::CANVAS_SIZE) {
-                                    for (idx, background) in chunks.iter_mut().enumerate() {
+                                    for (idx, background) in script::LED_MAP.write().iter_mut().enumerate() {
                                         let bg = &background;
                                         let fg = uleds_led_map[idx];
 
@@ -915,15 +984,13 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                         *background = color;
                                     }
                                 }
-                            }
 
-                            if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
-                                // finally, blend the LED map of the SDK support plugin
-                                let sdk_led_map = sdk_support::LED_MAP.read();
-                                let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+                                if SDK_SUPPORT_ACTIVE.load(Ordering::SeqCst) {
+                                    // finally, blend the LED map of the SDK support plugin
+                                    let sdk_led_map = sdk_support::LED_MAP.read();
+                                    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
 
-                                for chunks in script::LED_MAP.write().chunks_exact_mut(constants::CANVAS_SIZE) {
-                                    for (idx, background) in chunks.iter_mut().enumerate() {
+                                    for (idx, background) in script::LED_MAP.write().iter_mut().enumerate() {
                                         let bg = &background;
                                         let fg = sdk_led_map[idx];
 
@@ -938,25 +1005,51 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                         *background = color;
                                     }
                                 }
-                            }
 
-                            // number of pending blend ops should have reached zero by now
-                            // may currently occur during switching of profiles
-                            let ops_pending = *COLOR_MAPS_READY_CONDITION.0.lock();
-                            if ops_pending > 0 {
-                                debug!(
-                                    "Pending blend ops before writing LED map to device: {}",
-                                    ops_pending
-                                        );
+                                if overlays::is_active() {
+                                    // blend the overlays that have been submitted via D-Bus on top of everything else
+                                    let brightness = crate::BRIGHTNESS.load(Ordering::SeqCst);
+
+                                    overlays::blend_overlays(&mut script::LED_MAP.write(), brightness);
+                                }
+
+                                // number of pending blend ops should have reached zero by now
+                                // may currently occur during switching of profiles
+                                let ops_pending = *COLOR_MAPS_READY_CONDITION.0.lock();
+                                if ops_pending > 0 {
+                                    debug!(
+                                        "Pending blend ops before writing LED map to device: {}",
+                                        ops_pending
+                                            );
+                                }
                             }
 
                             // send the final (combined) color map to all of the devices
                             if !drop_frame {
+                                let mut devices = HashSet::new();
+
                                 for keyboard_device in crate::KEYBOARD_DEVICES.read().iter() {
                                     if let Some(mut device) = keyboard_device.try_write() {
+                                        let device_make_model_serial = layout::device_make_model_serial(device.as_device());
+                                        devices.insert(device_make_model_serial.clone());
+
+                                        if !scheduler::is_device_due(&device_make_model_serial, current_frame_generation) {
+                                            continue;
+                                        }
+
                                         if let Ok(is_initialized) = device.is_initialized() {
                                             if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
+                                                let canvas = script::LED_MAP.read();
+                                                let led_map = layout::compose_led_map(device.as_device(), Some(&**device), &canvas);
+                                                let led_map = battery::apply_low_battery_overlay(&device_make_model_serial, led_map);
+                                                let led_map = calibration::apply_calibration(device.as_device(), led_map);
+
+                                                let write_start = Instant::now();
+                                                let result = device.send_led_map(&led_map);
+
+                                                metrics::device_written(&device_make_model_serial, write_start.elapsed(), result.is_err());
+
+                                                if let Err(e) = result {
                                                     ratelimited::error!("Error sending LED map to a device: {}", e);
 
                                                     if device.has_failed().unwrap_or(true) {
@@ -985,6 +1078,8 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                         } else {
                                             warn!("Could not query device status");
                                         }
+
+                                        scheduler::device_rendered(&device_make_model_serial, current_frame_generation);
                                     } else {
                                         debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
@@ -992,9 +1087,26 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
 
                                 for mouse_device in crate::MOUSE_DEVICES.read().iter() {
                                     if let Some(mut device) = mouse_device.try_write() {
+                                        let device_make_model_serial = layout::device_make_model_serial(device.as_device());
+                                        devices.insert(device_make_model_serial.clone());
+
+                                        if !scheduler::is_device_due(&device_make_model_serial, current_frame_generation) {
+                                            continue;
+                                        }
+
                                         if let Ok(is_initialized) = device.is_initialized() {
                                             if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
+                                                let canvas = script::LED_MAP.read();
+                                                let led_map = layout::compose_led_map(device.as_device(), None, &canvas);
+                                                let led_map = battery::apply_low_battery_overlay(&device_make_model_serial, led_map);
+                                                let led_map = calibration::apply_calibration(device.as_device(), led_map);
+
+                                                let write_start = Instant::now();
+                                                let result = device.send_led_map(&led_map);
+
+                                                metrics::device_written(&device_make_model_serial, write_start.elapsed(), result.is_err());
+
+                                                if let Err(e) = result {
                                                     ratelimited::error!("Error sending LED map to a device: {}", e);
 
                                                     if device.has_failed().unwrap_or(true) {
@@ -1023,6 +1135,8 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                         } else {
                                             warn!("Could not query device status");
                                         }
+
+                                        scheduler::device_rendered(&device_make_model_serial, current_frame_generation);
                                     } else {
                                         debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
@@ -1030,9 +1144,26 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
 
                                 for misc_device in crate::MISC_DEVICES.read().iter() {
                                     if let Some(mut device) = misc_device.try_write() {
+                                        let device_make_model_serial = layout::device_make_model_serial(device.as_device());
+                                        devices.insert(device_make_model_serial.clone());
+
+                                        if !scheduler::is_device_due(&device_make_model_serial, current_frame_generation) {
+                                            continue;
+                                        }
+
                                         if let Ok(is_initialized) = device.is_initialized() {
                                             if is_initialized {
-                                                if let Err(e) = device.send_led_map(&script::LED_MAP.read()) {
+                                                let canvas = script::LED_MAP.read();
+                                                let led_map = layout::compose_led_map(device.as_device(), None, &canvas);
+                                                let led_map = battery::apply_low_battery_overlay(&device_make_model_serial, led_map);
+                                                let led_map = calibration::apply_calibration(device.as_device(), led_map);
+
+                                                let write_start = Instant::now();
+                                                let result = device.send_led_map(&led_map);
+
+                                                metrics::device_written(&device_make_model_serial, write_start.elapsed(), result.is_err());
+
+                                                if let Err(e) = result {
                                                     ratelimited::error!("Error sending LED map to a device: {}", e);
 
                                                     if device.has_failed().unwrap_or(true) {
@@ -1061,20 +1192,37 @@ pub fn spawn_device_io_thread(dev_io_rx: Receiver<DeviceAction>) -> Result<()> {
                                         } else {
                                             warn!("Could not query device status");
                                         }
+
+                                        scheduler::device_rendered(&device_make_model_serial, current_frame_generation);
                                     } else {
                                         debug!("Skipped rendering a frame to a device, because we could not acquire a lock");
                                     }
                                 }
 
-                                // update the current frame generation
-                                saved_frame_generation.store(current_frame_generation, Ordering::SeqCst);
+                                scheduler::retain_devices(&devices);
+
+                                if realize {
+                                    // update the current frame generation
+                                    saved_frame_generation.store(current_frame_generation, Ordering::SeqCst);
+
+                                    script::LAST_RENDERED_LED_MAP
+                                        .write()
+                                        .copy_from_slice(&script::LED_MAP.read());
 
-                                script::LAST_RENDERED_LED_MAP
-                                    .write()
-                                    .copy_from_slice(&script::LED_MAP.read());
+                                    canvas_stream::publish_frame(&script::LAST_RENDERED_LED_MAP.read());
+                                    recording::record_frame(&script::LAST_RENDERED_LED_MAP.read());
+
+                                    scheduler::frame_rendered(render_start.elapsed());
+                                    metrics::frame_rendered();
+                                }
+                            } else {
+                                metrics::frame_dropped();
                             }
 
                             fps_counter += 1;
+                        } else {
+                            scheduler::frame_skipped();
+                            metrics::frame_skipped();
                         }
 
                         // calculate and log fps each second
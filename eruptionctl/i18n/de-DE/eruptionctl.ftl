@@ -45,6 +45,8 @@ dumping-devices = Liste der durch Eruption kontrollierten Geräte
 keyboard-devices = Tastaturen:
 mouse-devices = Mäuse:
 misc-devices = Sonstige Geräte:
+dumping-drivers = Liste der registrierten Gerätetreiber
+dumping-layout = Anordnung der Geräte auf der Zeichenfläche
 
 could-not-parse-config = Fehler beim Verarbeiten der Konfigurationsdatei: {$message}
 could-not-send-on-channel = Fehler beim Senden auf einem Channel: {$message}
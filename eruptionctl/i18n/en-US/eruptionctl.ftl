@@ -45,6 +45,8 @@ dumping-devices = Dumping Eruption managed devices list
 keyboard-devices = Keyboard devices:
 mouse-devices = Mouse devices:
 misc-devices = Miscellaneous devices:
+dumping-drivers = Dumping the driver registry
+dumping-layout = Dumping the canvas layout
 
 could-not-parse-config = Could not parse configuration file: {$message}
 could-not-send-on-channel = Could not send on a channel: {$message}
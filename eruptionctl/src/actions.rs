@@ -0,0 +1 @@
+../../eruption-process-monitor/src/actions.rs
\ No newline at end of file
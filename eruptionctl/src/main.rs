@@ -28,12 +28,15 @@ use std::env;
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::{process, sync::Arc};
 
+mod actions;
 mod color_scheme;
 mod constants;
 mod dbus_client;
 mod device;
 mod profiles;
+mod rule_metadata;
 mod scripting;
+mod selectors;
 mod subcommands;
 mod translations;
 mod util;
@@ -0,0 +1 @@
+../../eruption-process-monitor/src/rule_metadata.rs
\ No newline at end of file
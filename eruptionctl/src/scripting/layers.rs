@@ -0,0 +1 @@
+../../../eruption/src/scripting/layers.rs
\ No newline at end of file
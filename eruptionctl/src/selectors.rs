@@ -0,0 +1 @@
+../../eruption-process-monitor/src/selectors.rs
\ No newline at end of file
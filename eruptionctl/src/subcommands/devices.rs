@@ -20,6 +20,8 @@
 */
 
 use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
@@ -29,6 +31,7 @@ use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{Cell, CellAlignment, ContentArrangement, Table};
 use eyre::Context;
+use serde::Deserialize;
 use std::sync::atomic::Ordering;
 
 use crate::dbus_client::dbus_system_bus;
@@ -37,6 +40,70 @@ use crate::tr;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
+#[derive(Debug, thiserror::Error)]
+pub enum DevicesError {
+    #[error("The device does not support the parameter: {param}")]
+    UnsupportedParameter { param: String },
+
+    #[error("The parameter is read-only: {param}")]
+    ReadOnlyParameter { param: String },
+}
+
+/// The type and the valid range of a configuration or status value, as published by the daemon
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ValueType {
+    Bool,
+    Int { min: i64, max: i64 },
+    Choice { values: Vec<i64> },
+    Text,
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueType::Bool => write!(f, "bool"),
+            ValueType::Int { min, max } => write!(f, "int ({}..{})", min, max),
+            ValueType::Choice { values } => write!(
+                f,
+                "one of {}",
+                values
+                    .iter()
+                    .map(|v| format!("{}", v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ValueType::Text => write!(f, "text"),
+        }
+    }
+}
+
+/// A device specific configuration parameter
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigKey {
+    pub name: String,
+    pub description: String,
+    pub value_type: ValueType,
+    pub unit: Option<String>,
+    pub writable: bool,
+}
+
+/// A key of the status map that is reported by a device
+#[derive(Debug, Clone, Deserialize)]
+pub struct StatusKey {
+    pub name: String,
+    pub description: String,
+    pub value_type: ValueType,
+    pub unit: Option<String>,
+}
+
+/// The configuration keys that a device supports and the status keys that it reports
+#[derive(Debug, Clone, Deserialize)]
+pub struct DeviceSchema {
+    pub config: Vec<ConfigKey>,
+    pub status: Vec<StatusKey>,
+}
+
 /// Sub-commands of the "devices" command
 #[derive(Debug, clap::Parser)]
 pub enum DevicesSubcommands {
@@ -91,6 +158,82 @@ pub enum DevicesSubcommands {
         device: String,
         enable: Option<bool>,
     },
+
+    /// List the driver registry, including the source of each VID/PID binding
+    #[clap(display_order = 10)]
+    Drivers,
+
+    /// Show the canvas layout, e.g. the placement of each device on the virtual canvas
+    #[clap(display_order = 11)]
+    Layout,
+
+    /// Place a device on the virtual canvas
+    #[clap(display_order = 12)]
+    Place {
+        device: String,
+        x: u64,
+        y: u64,
+        width: u64,
+        height: u64,
+    },
+
+    /// Remove a device from the virtual canvas, it will use the legacy canvas afterwards
+    #[clap(display_order = 13)]
+    Unplace { device: String },
+
+    /// Get or set the color calibration of the LEDs of a device
+    #[clap(display_order = 14)]
+    Calibration {
+        device: String,
+
+        /// Exponent of the gamma curve, e.g.: 2.2
+        #[clap(long)]
+        gamma: Option<f64>,
+
+        /// Gains of the red, green and blue channels, e.g.: 1.0,0.9,0.8
+        #[clap(long, value_parser = parse_gains)]
+        gains: Option<(f64, f64, f64)>,
+
+        /// The color that is displayed instead of full white, e.g.: ffe0c0
+        #[clap(long, value_parser = parse_white_point)]
+        white_point: Option<(u8, u8, u8)>,
+
+        /// Reset the calibration of the device
+        #[clap(long, conflicts_with_all = ["gamma", "gains", "white_point"])]
+        reset: bool,
+    },
+
+    /// Get or set the frame rate limit of the LEDs of a device
+    #[clap(display_order = 15)]
+    FrameRate {
+        device: String,
+        fps: Option<u64>,
+
+        /// Remove the frame rate limit, the device will follow the target frame rate of the profile
+        #[clap(long, conflicts_with = "fps")]
+        reset: bool,
+    },
+
+    /// Show the configuration parameters and status values that are supported by a device
+    #[clap(display_order = 16)]
+    Schema { device: String },
+
+    /// Export or import the onboard (hardware) profiles of a mouse
+    #[clap(display_order = 17)]
+    Onboard {
+        #[clap(subcommand)]
+        command: OnboardSubcommands,
+    },
+}
+
+/// Sub-commands of the "devices onboard" command
+#[derive(Debug, clap::Parser)]
+pub enum OnboardSubcommands {
+    /// Read all onboard profiles of a mouse and save them to a file
+    Export { device: String, filename: PathBuf },
+
+    /// Write the onboard profiles from a file to a mouse of the same make and model
+    Import { device: String, filename: PathBuf },
 }
 
 pub async fn handle_command(command: DevicesSubcommands) -> Result<()> {
@@ -109,6 +252,35 @@ pub async fn handle_command(command: DevicesSubcommands) -> Result<()> {
         DevicesSubcommands::Brightness { device, brightness } => {
             brightness_command(device, brightness).await
         }
+        DevicesSubcommands::Drivers => drivers_command().await,
+        DevicesSubcommands::Layout => layout_command().await,
+        DevicesSubcommands::Place {
+            device,
+            x,
+            y,
+            width,
+            height,
+        } => place_command(device, x, y, width, height).await,
+        DevicesSubcommands::Unplace { device } => unplace_command(device).await,
+        DevicesSubcommands::Calibration {
+            device,
+            gamma,
+            gains,
+            white_point,
+            reset,
+        } => calibration_command(device, gamma, gains, white_point, reset).await,
+        DevicesSubcommands::FrameRate { device, fps, reset } => {
+            frame_rate_command(device, fps, reset).await
+        }
+        DevicesSubcommands::Schema { device } => schema_command(device).await,
+        DevicesSubcommands::Onboard { command } => match command {
+            OnboardSubcommands::Export { device, filename } => {
+                onboard_export_command(device, filename).await
+            }
+            OnboardSubcommands::Import { device, filename } => {
+                onboard_import_command(device, filename).await
+            }
+        },
     }
 }
 
@@ -285,6 +457,7 @@ async fn status_command(device: String) -> Result<()> {
 
     loop {
         let result = get_device_status(device).await?;
+        let schema = get_device_schema(device).await?;
 
         let mut table = Table::new();
         table
@@ -301,9 +474,20 @@ async fn status_command(device: String) -> Result<()> {
         v.sort_by_key(|&v| v.0);
 
         v.iter().for_each(|(k, v)| {
+            let unit = schema
+                .status
+                .iter()
+                .find(|key| &&key.name == k)
+                .and_then(|key| key.unit.as_ref());
+
+            let value = match unit {
+                Some(unit) => format!("{} {}", v, unit),
+                None => v.to_string(),
+            };
+
             table.add_row(vec![
                 Cell::new(k.to_owned()).set_alignment(CellAlignment::Left),
-                Cell::new(v.to_owned()).set_alignment(CellAlignment::Right),
+                Cell::new(value).set_alignment(CellAlignment::Right),
             ]);
 
             cntr += 2;
@@ -476,6 +660,355 @@ async fn brightness_command(device: String, brightness: Option<i64>) -> Result<(
     Ok(())
 }
 
+async fn drivers_command() -> Result<()> {
+    let drivers = get_driver_registry()
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    println!("{}\n", tr!("dumping-drivers").bold());
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["ID", "Device", "Driver", "Maturity", "Source"]);
+
+    for (usb_vid, usb_pid, make, model, family, maturity, source) in drivers.iter() {
+        table.add_row(vec![
+            Cell::new(format!("{:04x}:{:04x}", usb_vid, usb_pid)),
+            Cell::new(format!("{} {}", make, model)),
+            Cell::new(family),
+            Cell::new(maturity),
+            Cell::new(source),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
+
+async fn layout_command() -> Result<()> {
+    let (width, height, placements) = get_canvas_layout()
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let (keyboards, mice, misc) = get_devices().await?;
+    let devices: Vec<(u16, u16)> = keyboards
+        .iter()
+        .chain(mice.iter())
+        .chain(misc.iter())
+        .copied()
+        .collect();
+
+    println!("{}\n", tr!("dumping-layout").bold());
+    println!("Canvas: {}x{}\n", width, height);
+
+    if placements.is_empty() {
+        println!(
+            "{}",
+            "<No devices have been placed, all devices use the legacy canvas>".italic()
+        );
+
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Index", "Device", "X", "Y", "Width", "Height"]);
+
+    for (device, x, y, width, height) in placements.iter() {
+        let name = devices
+            .get(*device as usize)
+            .map(|(usb_vid, usb_pid)| {
+                format!(
+                    "{} {}",
+                    device::get_device_make(*usb_vid, *usb_pid).unwrap_or("<unknown make>"),
+                    device::get_device_model(*usb_vid, *usb_pid).unwrap_or("<unknown model>")
+                )
+            })
+            .unwrap_or_else(|| "<unknown device>".to_string());
+
+        table.add_row(vec![
+            Cell::new(format!("{:02}", device)),
+            Cell::new(name),
+            Cell::new(x).set_alignment(CellAlignment::Right),
+            Cell::new(y).set_alignment(CellAlignment::Right),
+            Cell::new(width).set_alignment(CellAlignment::Right),
+            Cell::new(height).set_alignment(CellAlignment::Right),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
+
+async fn place_command(device: String, x: u64, y: u64, width: u64, height: u64) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    set_device_placement(device, x, y, width, height)
+        .await
+        .wrap_err("Could not place the device on the canvas")
+        .suggestion(
+            "Please verify that the placement fits on the canvas: `eruptionctl devices layout`",
+        )?;
+
+    Ok(())
+}
+
+async fn unplace_command(device: String) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if !remove_device_placement(device).await? {
+        println!(
+            "{}",
+            "The device has not been placed on the canvas".italic()
+        );
+    }
+
+    Ok(())
+}
+
+async fn calibration_command(
+    device: String,
+    gamma: Option<f64>,
+    gains: Option<(f64, f64, f64)>,
+    white_point: Option<(u8, u8, u8)>,
+    reset: bool,
+) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if reset {
+        if !reset_device_calibration(device).await? {
+            println!("{}", "The device has not been calibrated".italic());
+        }
+
+        return Ok(());
+    }
+
+    let (current_gamma, current_gains, current_white_point) =
+        get_device_calibration(device).await?;
+
+    if gamma.is_some() || gains.is_some() || white_point.is_some() {
+        set_device_calibration(
+            device,
+            gamma.unwrap_or(current_gamma),
+            gains.unwrap_or(current_gains),
+            white_point.unwrap_or(current_white_point),
+        )
+        .await
+        .wrap_err("Could not set the color calibration of the device")
+        .suggestion("Please verify that gamma is within 0.1..4.0, and the gains within 0.0..2.0")?;
+    } else {
+        println!("Gamma: {}", format!("{:.2}", current_gamma).bold());
+        println!(
+            "Gains: {}",
+            format!(
+                "{:.2}, {:.2}, {:.2}",
+                current_gains.0, current_gains.1, current_gains.2
+            )
+            .bold()
+        );
+        println!(
+            "White point: {}",
+            format!(
+                "#{:02x}{:02x}{:02x}",
+                current_white_point.0, current_white_point.1, current_white_point.2
+            )
+            .bold()
+        );
+    }
+
+    Ok(())
+}
+
+async fn frame_rate_command(device: String, fps: Option<u64>, reset: bool) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    if reset {
+        set_device_frame_rate(device, 0).await?;
+    } else if let Some(fps) = fps {
+        set_device_frame_rate(device, fps)
+            .await
+            .wrap_err("Could not set the frame rate limit of the device")
+            .suggestion("Please verify that the frame rate is within 1..60")?;
+    } else {
+        match get_device_frame_rate(device).await? {
+            0 => println!("{}", "The frame rate of the device is not limited".italic()),
+            fps => println!("Frame rate limit: {}", format!("{} fps", fps).bold()),
+        }
+    }
+
+    Ok(())
+}
+
+async fn schema_command(device: String) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let schema = get_device_schema(device).await?;
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Parameter", "Type", "Unit", "Access", "Description"]);
+
+    for key in schema.config.iter() {
+        table.add_row(vec![
+            Cell::new(&key.name),
+            Cell::new(format!("{}", key.value_type)),
+            Cell::new(key.unit.as_deref().unwrap_or("")),
+            Cell::new(if key.writable { "read/write" } else { "read" }),
+            Cell::new(&key.description),
+        ]);
+    }
+
+    println!("{}", "Configuration".bold());
+    println!("{}", table);
+
+    let mut table = Table::new();
+    table
+        .load_preset(UTF8_FULL)
+        .apply_modifier(UTF8_ROUND_CORNERS)
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(vec!["Status", "Type", "Unit", "Description"]);
+
+    for key in schema.status.iter() {
+        table.add_row(vec![
+            Cell::new(&key.name),
+            Cell::new(format!("{}", key.value_type)),
+            Cell::new(key.unit.as_deref().unwrap_or("")),
+            Cell::new(&key.description),
+        ]);
+    }
+
+    println!("{}", "Status".bold());
+    println!("{}", table);
+
+    Ok(())
+}
+
+async fn onboard_export_command(device: String, filename: PathBuf) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let profiles = get_onboard_profiles(device)
+        .await
+        .wrap_err("Could not read the onboard profiles of the device")?;
+
+    let count = serde_json::from_str::<serde_json::Value>(&profiles)?["profiles"]
+        .as_array()
+        .map(|p| p.len())
+        .unwrap_or(0);
+
+    fs::write(&filename, profiles)
+        .wrap_err_with(|| format!("Could not write file: {}", filename.display()))?;
+
+    println!(
+        "Exported {} onboard profiles to: {}",
+        format!("{}", count).bold(),
+        filename.display()
+    );
+
+    Ok(())
+}
+
+async fn onboard_import_command(device: String, filename: PathBuf) -> Result<()> {
+    let device = device.parse::<u64>()?;
+
+    print_device_header(device)
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    let profiles = fs::read_to_string(&filename)
+        .wrap_err_with(|| format!("Could not read file: {}", filename.display()))?;
+
+    // catch syntax errors early, the daemon validates the contents
+    serde_json::from_str::<serde_json::Value>(&profiles)
+        .wrap_err("The file does not contain valid onboard profiles")?;
+
+    set_onboard_profiles(device, &profiles)
+        .await
+        .wrap_err("Could not write the onboard profiles to the device")
+        .suggestion(
+            "Onboard profiles may only be imported into a device of the same make and model",
+        )?;
+
+    println!("Imported onboard profiles from: {}", filename.display());
+
+    Ok(())
+}
+
+/// Parse the gains of a color calibration, e.g.: `1.0,0.9,0.8`
+fn parse_gains(s: &str) -> std::result::Result<(f64, f64, f64), String> {
+    let gains = s
+        .split(',')
+        .map(|gain| gain.trim().parse::<f64>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}", e))?;
+
+    match gains[..] {
+        [r, g, b] => Ok((r, g, b)),
+
+        _ => Err("Expected the gains of the red, green and blue channels".to_string()),
+    }
+}
+
+/// Parse the white point of a color calibration, e.g.: `ffe0c0` or `#ffe0c0`
+fn parse_white_point(s: &str) -> std::result::Result<(u8, u8, u8), String> {
+    let s = s.trim_start_matches('#');
+
+    if s.len() != 6 {
+        return Err("Expected a color in hexadecimal notation, e.g.: ffe0c0".to_string());
+    }
+
+    let component = |i: usize| {
+        s.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+            .ok_or_else(|| format!("Invalid color: {}", s))
+    };
+
+    Ok((component(0)?, component(2)?, component(4)?))
+}
+
 /// Enumerate all available devices
 async fn get_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>)> {
     let ((keyboards, mice, misc),): ((Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u16)>),) =
@@ -487,6 +1020,111 @@ async fn get_devices() -> Result<(Vec<(u16, u16)>, Vec<(u16, u16)>, Vec<(u16, u1
     Ok((keyboards, mice, misc))
 }
 
+/// Query the driver registry of the daemon
+async fn get_driver_registry() -> Result<Vec<(u16, u16, String, String, String, String, String)>> {
+    let (drivers,): (Vec<(u16, u16, String, String, String, String, String)>,) =
+        dbus_system_bus("/org/eruption/devices")
+            .await?
+            .method_call("org.eruption.Device", "GetDriverRegistry", ())
+            .await?;
+
+    Ok(drivers)
+}
+
+/// Query the canvas layout, as (width, height, placements)
+async fn get_canvas_layout() -> Result<(u64, u64, Vec<(u64, u64, u64, u64, u64)>)> {
+    let result: (u64, u64, Vec<(u64, u64, u64, u64, u64)>) =
+        dbus_system_bus("/org/eruption/devices")
+            .await?
+            .method_call("org.eruption.Device", "GetCanvasLayout", ())
+            .await?;
+
+    Ok(result)
+}
+
+/// Place a device on the virtual canvas
+async fn set_device_placement(device: u64, x: u64, y: u64, width: u64, height: u64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call(
+            "org.eruption.Device",
+            "SetDevicePlacement",
+            (device, x, y, width, height),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Remove a device from the virtual canvas
+async fn remove_device_placement(device: u64) -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "RemoveDevicePlacement", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Query the color calibration of a device, as (gamma, gains, white point)
+async fn get_device_calibration(device: u64) -> Result<(f64, (f64, f64, f64), (u8, u8, u8))> {
+    let result: (f64, (f64, f64, f64), (u8, u8, u8)) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceCalibration", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Set the color calibration of a device
+async fn set_device_calibration(
+    device: u64,
+    gamma: f64,
+    gains: (f64, f64, f64),
+    white_point: (u8, u8, u8),
+) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call(
+            "org.eruption.Device",
+            "SetDeviceCalibration",
+            (device, gamma, gains, white_point),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Reset the color calibration of a device
+async fn reset_device_calibration(device: u64) -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "ResetDeviceCalibration", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Get the frame rate limit of a device, zero means that the frame rate is not limited
+async fn get_device_frame_rate(device: u64) -> Result<u64> {
+    let (result,): (u64,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceFrameRate", (device,))
+        .await?;
+
+    Ok(result)
+}
+
+/// Set the frame rate limit of a device, zero removes the limit
+async fn set_device_frame_rate(device: u64, fps: u64) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "SetDeviceFrameRate", (device, fps))
+        .await?;
+
+    Ok(())
+}
+
 /// Get a device specific config param
 async fn get_device_config(device: u64, param: &str) -> Result<String> {
     let (result,): (String,) = dbus_system_bus("/org/eruption/devices")
@@ -513,8 +1151,66 @@ async fn get_device_status(device: u64) -> Result<HashMap<String, String>> {
     Ok(result)
 }
 
+/// Read the onboard profiles of a mouse, in the portable file format
+async fn get_onboard_profiles(device: u64) -> Result<String> {
+    let (profiles,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetOnboardProfiles", (device,))
+        .await?;
+
+    Ok(profiles)
+}
+
+/// Write onboard profiles in the portable file format to a mouse
+async fn set_onboard_profiles(device: u64, profiles: &str) -> Result<()> {
+    let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call(
+            "org.eruption.Device",
+            "SetOnboardProfiles",
+            (device, profiles.to_owned()),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Get the configuration and status schema of a device
+async fn get_device_schema(device: u64) -> Result<DeviceSchema> {
+    let (schema,): (String,) = dbus_system_bus("/org/eruption/devices")
+        .await?
+        .method_call("org.eruption.Device", "GetDeviceSchema", (device,))
+        .await?;
+
+    let result: DeviceSchema = serde_json::from_str(&schema)?;
+
+    Ok(result)
+}
+
 /// Set a device specific config param
 async fn set_device_config(device: u64, param: &str, value: &str) -> Result<()> {
+    let schema = get_device_schema(device).await?;
+
+    match schema.config.iter().find(|key| key.name == param) {
+        None => {
+            return Err(DevicesError::UnsupportedParameter {
+                param: param.to_owned(),
+            })
+            .suggestion(
+                "Please run `eruptionctl devices schema <DEVICE>` to list the supported parameters",
+            )
+        }
+
+        Some(key) if !key.writable => {
+            return Err(DevicesError::ReadOnlyParameter {
+                param: param.to_owned(),
+            }
+            .into())
+        }
+
+        Some(_) => {}
+    }
+
     let (_result,): (bool,) = dbus_system_bus("/org/eruption/devices")
         .await?
         .method_call(
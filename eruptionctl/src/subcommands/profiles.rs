@@ -22,6 +22,8 @@
 use color_eyre::Help;
 use colored::*;
 use eyre::Context;
+use std::fs;
+use std::path::PathBuf;
 
 use crate::dbus_client::dbus_system_bus;
 use crate::util;
@@ -42,6 +44,42 @@ pub enum ProfilesSubcommands {
     /// Edit a profile
     #[clap(display_order = 2)]
     Edit { profile_name: String },
+
+    /// Create a new profile from a list of scripts
+    #[clap(display_order = 3)]
+    Create {
+        /// The description of the new profile
+        #[clap(short, long, default_value = "")]
+        description: String,
+
+        name: String,
+
+        #[clap(required = true)]
+        scripts: Vec<String>,
+    },
+
+    /// Clone a profile under a new UUID
+    #[clap(display_order = 4)]
+    Clone { profile_name: String, name: String },
+
+    /// Change the name of a profile
+    #[clap(display_order = 5)]
+    Rename { profile_name: String, name: String },
+
+    /// Delete a profile, unless it is assigned to a slot
+    #[clap(display_order = 6)]
+    Delete { profile_name: String },
+
+    /// Export a profile, along with its non-stock scripts, to a single file
+    #[clap(display_order = 7)]
+    Export {
+        profile_name: String,
+        file_name: PathBuf,
+    },
+
+    /// Import a profile that has been exported before
+    #[clap(display_order = 8)]
+    Import { file_name: PathBuf },
 }
 
 pub async fn handle_command(command: ProfilesSubcommands) -> Result<()> {
@@ -49,6 +87,29 @@ pub async fn handle_command(command: ProfilesSubcommands) -> Result<()> {
         ProfilesSubcommands::Edit { profile_name } => edit_command(profile_name).await,
         ProfilesSubcommands::List => list_command().await,
         ProfilesSubcommands::Info { profile_name } => info_command(profile_name).await,
+
+        ProfilesSubcommands::Create {
+            description,
+            name,
+            scripts,
+        } => create_command(name, description, scripts).await,
+
+        ProfilesSubcommands::Clone { profile_name, name } => {
+            clone_command(profile_name, name).await
+        }
+
+        ProfilesSubcommands::Rename { profile_name, name } => {
+            rename_command(profile_name, name).await
+        }
+
+        ProfilesSubcommands::Delete { profile_name } => delete_command(profile_name).await,
+
+        ProfilesSubcommands::Export {
+            profile_name,
+            file_name,
+        } => export_command(profile_name, file_name).await,
+
+        ProfilesSubcommands::Import { file_name } => import_command(file_name).await,
     }
 }
 
@@ -91,6 +152,112 @@ async fn info_command(profile_name: String) -> Result<()> {
     Ok(())
 }
 
+async fn create_command(name: String, description: String, scripts: Vec<String>) -> Result<()> {
+    let (profile_file,): (String,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call(
+            "org.eruption.Profile",
+            "CreateProfile",
+            (name, description, scripts),
+        )
+        .await?;
+
+    println!("Created profile: {}", profile_file.bold());
+
+    Ok(())
+}
+
+async fn clone_command(profile_name: String, name: String) -> Result<()> {
+    match util::match_profile_by_name(&profile_name) {
+        Ok(profile) => {
+            let (profile_file,): (String,) = dbus_system_bus("/org/eruption/profile")
+                .await?
+                .method_call(
+                    "org.eruption.Profile",
+                    "CloneProfile",
+                    (profile.id.to_string(), name),
+                )
+                .await?;
+
+            println!("Created profile: {}", profile_file.bold());
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    Ok(())
+}
+
+async fn rename_command(profile_name: String, name: String) -> Result<()> {
+    match util::match_profile_by_name(&profile_name) {
+        Ok(profile) => {
+            let (_status,): (bool,) = dbus_system_bus("/org/eruption/profile")
+                .await?
+                .method_call(
+                    "org.eruption.Profile",
+                    "RenameProfile",
+                    (profile.id.to_string(), name),
+                )
+                .await?;
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    Ok(())
+}
+
+async fn delete_command(profile_name: String) -> Result<()> {
+    match util::match_profile_by_name(&profile_name) {
+        Ok(profile) => {
+            let (_status,): (bool,) = dbus_system_bus("/org/eruption/profile")
+                .await?
+                .method_call(
+                    "org.eruption.Profile",
+                    "DeleteProfile",
+                    (profile.id.to_string(),),
+                )
+                .await?;
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    Ok(())
+}
+
+async fn export_command(profile_name: String, file_name: PathBuf) -> Result<()> {
+    match util::match_profile_by_name(&profile_name) {
+        Ok(profile) => {
+            let (data,): (String,) = dbus_system_bus("/org/eruption/profile")
+                .await?
+                .method_call(
+                    "org.eruption.Profile",
+                    "ExportProfile",
+                    (profile.id.to_string(),),
+                )
+                .await?;
+
+            fs::write(&file_name, data)
+                .wrap_err_with(|| format!("Could not write file: {}", file_name.display()))?;
+        }
+        Err(err) => eprintln!("{}", err),
+    }
+
+    Ok(())
+}
+
+async fn import_command(file_name: PathBuf) -> Result<()> {
+    let data = fs::read_to_string(&file_name)
+        .wrap_err_with(|| format!("Could not read file: {}", file_name.display()))?;
+
+    let (profile_file,): (String,) = dbus_system_bus("/org/eruption/profile")
+        .await?
+        .method_call("org.eruption.Profile", "ImportProfile", (data,))
+        .await?;
+
+    println!("Imported profile: {}", profile_file.bold());
+
+    Ok(())
+}
+
 /// Enumerate all available profiles
 async fn get_profiles() -> Result<Vec<(String, String)>> {
     let (result,): (Vec<(String, String)>,) = dbus_system_bus("/org/eruption/profile")
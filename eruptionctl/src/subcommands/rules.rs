@@ -20,20 +20,16 @@
 */
 
 use colored::*;
-use std::fmt;
 
 use indexmap::IndexMap;
 
-use crate::{dbus_client::dbus_session_bus, translations::tr};
+use crate::{
+    actions::Action, dbus_client::dbus_session_bus, rule_metadata::RuleMetadata,
+    selectors::Selector, translations::tr,
+};
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
-#[derive(Debug, thiserror::Error)]
-pub enum RuleError {
-    #[error("Parse error: {description}")]
-    Parse { description: String },
-}
-
 /// Sub-commands of the "rules" command
 #[derive(Debug, clap::Parser)]
 pub enum RulesSubcommands {
@@ -43,7 +39,21 @@ pub enum RulesSubcommands {
 
     /// Add a new rule
     #[clap(display_order = 1, about(tr!("rules-add")))]
-    Add { rule: Vec<String> },
+    Add {
+        /// If multiple rules match, the rule with the highest priority wins
+        #[clap(long, default_value_t = 0, allow_hyphen_values = true)]
+        priority: i32,
+
+        /// Do not revert the effects of the rule when it stops matching
+        #[clap(long)]
+        sticky: bool,
+
+        /// Do not apply rules with a lower priority while this rule is in effect
+        #[clap(long)]
+        exclusive: bool,
+
+        rule: Vec<String>,
+    },
 
     /// Remove a rule by its index
     #[clap(display_order = 2, about(tr!("rules-remove")))]
@@ -58,149 +68,65 @@ pub enum RulesSubcommands {
     Disable { rule_index: usize },
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum WindowFocusedSelectorMode {
-    WindowName,
-    WindowInstance,
-    WindowClass,
-}
-
-impl fmt::Display for WindowFocusedSelectorMode {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            WindowFocusedSelectorMode::WindowName => {
-                write!(f, "Name")?;
-            }
-
-            WindowFocusedSelectorMode::WindowInstance => {
-                write!(f, "Instance")?;
-            }
-
-            WindowFocusedSelectorMode::WindowClass => {
-                write!(f, "Class")?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub enum Selector {
-    ProcessExec {
-        comm: String,
-    },
-    WindowFocused {
-        mode: WindowFocusedSelectorMode,
-        regex: String,
-    },
-}
-
-impl fmt::Display for Selector {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Selector::ProcessExec { comm } => {
-                write!(
-                    f,
-                    "On process execution: comm: '{}'",
-                    comm.to_string().bold()
-                )?;
-            }
-
-            Selector::WindowFocused { mode, regex } => {
-                write!(
-                    f,
-                    "On window focused: {}: '{}'",
-                    mode,
-                    regex.to_string().bold()
-                )?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub enum Action {
-    SwitchToProfile { profile_name: String },
-    SwitchToSlot { slot_index: u64 },
-}
-
-impl fmt::Display for Action {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Action::SwitchToProfile { profile_name } => {
-                write!(f, "Switch to profile: {}", profile_name.to_string().bold())?;
-            }
-
-            Action::SwitchToSlot { slot_index } => {
-                write!(
-                    f,
-                    "Switch to slot: {}",
-                    format!("{}", slot_index + 1).bold()
-                )?;
-            }
-        };
-
-        Ok(())
-    }
-}
-
-#[derive(Debug, Clone)]
-pub struct RuleMetadata {
-    /// Specifies whether the rule is enabled
-    pub enabled: bool,
-
-    /// Set to true if the rule is auto-generated
-    pub internal: bool,
-}
-
-impl std::default::Default for RuleMetadata {
-    fn default() -> Self {
-        RuleMetadata {
-            enabled: true,
-            internal: false,
-        }
-    }
-}
-
-impl fmt::Display for RuleMetadata {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "enabled: {}", self.enabled)?;
-        write!(f, ", internal: {}", self.internal)?;
-
-        Ok(())
-    }
-}
-
 fn print_usage_examples() {
     println!(
         r#"
 Please see below for some examples:
 
 Process:
-rules add exec <comm> [<profile-name.profile>|<slot number>]
+rules add exec <comm> <action>
 
 rules add exec gnome-calc.* /var/lib/eruption/profiles/profile1.profile
 rules add exec gnome-calc.* 2
+rules add exec 'sc2.*' 'brightness:30; sfx:off; ambient:on'
 
 
 Window:
-rules add window-[class|instance|name] <regex> [<profile-name.profile>|<slot number>]
+rules add window-[class|instance|name] <regex> <action>
 
 rules add window-name '.*YouTube.*Mozilla Firefox' /var/lib/eruption/profiles/profile1.profile
 rules add window-instance gnome-calculator 2
+rules add window-class 'steam_app_.*' 'brightness:30; param:wave.lua:speed_divisor=5'
 
 You may want to use the command line tool `xprop` to find the relevant information
-"#
+
+
+System:
+rules add time '[<days>] <HH:MM>-<HH:MM>' <action>
+rules add idle <seconds> <action>
+rules add locked <action>
+rules add power <ac|battery> <action>
+rules add device <vid>:<pid> <action>
+
+rules add time 'weekdays 22:00-07:00' night.profile
+rules add power battery 'brightness:30'
+rules add idle 300 'brightness:5'
+rules add device 1e7d:3098 2
+rules add window-class 'steam_app_.*' and power ac 'sfx:on'
+{}{}"#,
+        crate::selectors::USAGE,
+        crate::actions::USAGE
     );
 }
 
 pub async fn handle_command(command: RulesSubcommands) -> Result<()> {
     match command {
         RulesSubcommands::List => list_command().await,
-        RulesSubcommands::Add { rule } => add_command(&rule).await,
+        RulesSubcommands::Add {
+            priority,
+            sticky,
+            exclusive,
+            rule,
+        } => {
+            let metadata = RuleMetadata {
+                priority,
+                sticky,
+                exclusive,
+                ..Default::default()
+            };
+
+            add_command(&rule, metadata).await
+        }
         RulesSubcommands::Remove { rule_index } => remove_command(rule_index).await,
         RulesSubcommands::Enable { rule_index } => enable_command(rule_index).await,
         RulesSubcommands::Disable { rule_index } => disable_command(rule_index).await,
@@ -227,56 +153,50 @@ async fn list_command() -> Result<()> {
     Ok(())
 }
 
-async fn add_command(rule: &[String]) -> Result<()> {
-    if rule.len() != 3 {
-        eprintln!("Malformed rule definition");
-        print_usage_examples();
-    } else {
-        let sensor = rule[0].to_owned();
-        let selector = rule[1].to_owned();
-        let action = rule[2].to_owned();
-        let metadata = RuleMetadata {
-            enabled: true,
-            internal: false,
-        }
-        .to_string();
+async fn add_command(rule: &[String], new_metadata: RuleMetadata) -> Result<()> {
+    match rule.split_last() {
+        Some((action, selector)) if !selector.is_empty() => {
+            let new_selector = match Selector::from_args(selector) {
+                Ok(selector) => selector,
 
-        let (new_selector, _new_metadata, mut new_action) =
-            parse_rule(&(sensor, selector, action, metadata))?;
+                Err(e) => {
+                    eprintln!("{}", e);
+                    print_usage_examples();
 
-        // slot indices are 0-based
-        if let Action::SwitchToSlot { slot_index } = new_action {
-            new_action = Action::SwitchToSlot {
-                slot_index: slot_index.saturating_sub(1),
+                    return Ok(());
+                }
             };
-        }
 
-        let rules = enum_rules().await?;
-        let new_rule_index = rules.len().saturating_sub(1);
+            // slot indices are 0-based
+            let new_action = action.parse::<Action>()?.slot_numbers_to_indices();
+
+            let rules = enum_rules().await?;
+            let new_rule_index = rules.len().saturating_sub(1);
+
+            let mut result: IndexMap<Selector, (RuleMetadata, Action)> = IndexMap::new();
 
-        let mut result: IndexMap<Selector, (RuleMetadata, Action)> = IndexMap::new();
+            for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
+                if index == new_rule_index {
+                    if !metadata.internal {
+                        result.insert(selector.clone(), (metadata.clone(), action.clone()));
+                    }
 
-        for (index, (selector, (metadata, action))) in rules.iter().enumerate() {
-            if index == new_rule_index {
-                if !metadata.internal {
+                    result.insert(
+                        new_selector.clone(),
+                        (new_metadata.clone(), new_action.clone()),
+                    );
+                } else if !metadata.internal {
                     result.insert(selector.clone(), (metadata.clone(), action.clone()));
                 }
-
-                let new_metadata = RuleMetadata {
-                    enabled: true,
-                    internal: false,
-                };
-
-                result.insert(
-                    new_selector.clone(),
-                    (new_metadata.clone(), new_action.clone()),
-                );
-            } else if !metadata.internal {
-                result.insert(selector.clone(), (metadata.clone(), action.clone()));
             }
+
+            set_rules(&result).await?;
         }
 
-        set_rules(&result).await?;
+        _ => {
+            eprintln!("Malformed rule definition");
+            print_usage_examples();
+        }
     }
 
     Ok(())
@@ -361,37 +281,11 @@ async fn set_rules(rules: &IndexMap<Selector, (RuleMetadata, Action)>) -> Result
     let mut generated_rules: Vec<(String, String, String, String)> = Vec::new();
 
     for (_index, (selector, (metadata, action))) in rules.iter().enumerate() {
-        let (sensor, selector) = match selector {
-            Selector::ProcessExec { comm } => ("exec".to_string(), comm.to_owned()),
-
-            Selector::WindowFocused { mode, regex } => match mode {
-                WindowFocusedSelectorMode::WindowClass => {
-                    ("window-class".to_string(), regex.to_owned())
-                }
+        let (sensor, selector) = selector.to_rule_strings();
 
-                WindowFocusedSelectorMode::WindowInstance => {
-                    ("window-instance".to_string(), regex.to_owned())
-                }
+        let action = action.to_rule_string();
 
-                WindowFocusedSelectorMode::WindowName => {
-                    ("window-name".to_string(), regex.to_owned())
-                }
-            },
-        };
-
-        let action = match action {
-            Action::SwitchToProfile { profile_name } => profile_name.to_owned(),
-            Action::SwitchToSlot { slot_index } => format!("{slot_index}"),
-        };
-
-        let metadata = format!(
-            "({}, user-defined)",
-            if metadata.enabled {
-                "enabled"
-            } else {
-                "disabled"
-            }
-        );
+        let metadata = metadata.to_rule_string();
 
         generated_rules.push((sensor, selector, action, metadata));
     }
@@ -417,57 +311,16 @@ fn parse_rule(rule: &(String, String, String, String)) -> Result<(Selector, Rule
     let action = &rule.2;
     let metadata = &rule.3;
 
-    let mut parsed_selector = None;
-    let parsed_action;
-
     // parse metadata
-    let enabled = metadata.contains("enabled");
-
-    let internal = metadata.contains("internal");
-
-    let parsed_metadata = RuleMetadata { enabled, internal };
+    let parsed_metadata = RuleMetadata::from_rule_string(metadata);
 
     // parse sensor and selector
-    if sensor.contains("exec") {
-        parsed_selector = Some(Selector::ProcessExec {
-            comm: selector.to_owned(),
-        });
-    } else if sensor.contains("window-class") {
-        parsed_selector = Some(Selector::WindowFocused {
-            mode: WindowFocusedSelectorMode::WindowClass,
-            regex: selector.to_owned(),
-        });
-    } else if sensor.contains("window-instance") {
-        parsed_selector = Some(Selector::WindowFocused {
-            mode: WindowFocusedSelectorMode::WindowInstance,
-            regex: selector.to_owned(),
-        });
-    } else if sensor.contains("window-name") {
-        parsed_selector = Some(Selector::WindowFocused {
-            mode: WindowFocusedSelectorMode::WindowName,
-            regex: selector.to_owned(),
-        });
-    }
+    let parsed_selector = Selector::from_rule_strings(sensor, selector)?;
 
     // parse action
-    if parsed_selector.is_none() {
-        Err(RuleError::Parse {
-            description: "Syntax error in selector".to_owned(),
-        }
-        .into())
-    } else if action.contains(".profile") {
-        parsed_action = Action::SwitchToProfile {
-            profile_name: action.to_owned(),
-        };
-
-        Ok((parsed_selector.unwrap(), parsed_metadata, parsed_action))
-    } else {
-        parsed_action = Action::SwitchToSlot {
-            slot_index: action.parse::<u64>()?,
-        };
-
-        Ok((parsed_selector.unwrap(), parsed_metadata, parsed_action))
-    }
+    let parsed_action = action.parse::<Action>()?;
+
+    Ok((parsed_selector, parsed_metadata, parsed_action))
 }
 
 fn parse_rules(
@@ -83,6 +83,20 @@ async fn info_command(script_name: String) -> Result<()> {
                 script.description,
                 script.tags.as_ref().unwrap_or(&empty),
             );
+
+            if script.capabilities.is_empty() {
+                println!("Capabilities:\t{}", "none".italic());
+            } else {
+                println!("Capabilities:");
+
+                for capability in script.capabilities.iter() {
+                    println!(
+                        "\t\t{}: {}",
+                        capability.get_name().bold(),
+                        capability.get_description()
+                    );
+                }
+            }
         }
         None => eprintln!("Script not found."),
     }
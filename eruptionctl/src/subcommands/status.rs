@@ -28,6 +28,24 @@ use crate::dbus_client::dbus_system_bus;
 
 type Result<T> = std::result::Result<T, eyre::Error>;
 
+/// Target frame rate, frame rate, render time, rendered and skipped frames, and the
+/// CPU time and tick divisor of each script
+type RenderStats = (u64, f64, f64, u64, u64, Vec<(String, f64, u32)>);
+
+/// Seconds since the last reset, rendered, skipped, dropped and late frames, the duration of
+/// `on_tick()` of each script, the HID writes to each device, D-Bus calls and dropped input events
+type Metrics = (
+    f64,
+    u64,
+    u64,
+    u64,
+    u64,
+    Vec<(String, u64, f64, f64)>,
+    Vec<(String, u64, u64, f64, f64)>,
+    Vec<(String, u64)>,
+    Vec<(String, u64)>,
+);
+
 /// Sub-commands of the "status" command
 #[derive(Debug, clap::Parser)]
 pub enum StatusSubcommands {
@@ -38,12 +56,32 @@ pub enum StatusSubcommands {
     /// Shows the currently active slot
     #[clap(display_order = 1)]
     Slot,
+
+    /// Shows statistics of the render loop, e.g. the frame rate and the CPU time used by each script
+    #[clap(display_order = 2)]
+    Render,
+
+    /// Shows the metrics of the daemon, e.g. the duration of script ticks and the latency of HID writes
+    #[clap(display_order = 3)]
+    Metrics {
+        /// Print the metrics in OpenMetrics text format
+        #[clap(long)]
+        openmetrics: bool,
+
+        /// Reset all metrics
+        #[clap(long, conflicts_with = "openmetrics")]
+        reset: bool,
+    },
 }
 
 pub async fn handle_command(command: StatusSubcommands) -> Result<()> {
     match command {
         StatusSubcommands::Profile => profile_command().await,
         StatusSubcommands::Slot => slot_command().await,
+        StatusSubcommands::Render => render_command().await,
+        StatusSubcommands::Metrics { openmetrics, reset } => {
+            metrics_command(openmetrics, reset).await
+        }
     }
 }
 
@@ -68,6 +106,147 @@ async fn slot_command() -> Result<()> {
     Ok(())
 }
 
+async fn render_command() -> Result<()> {
+    let (target_fps, fps, render_time_percent, frames_rendered, frames_skipped, scripts) =
+        get_render_stats()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+    println!(
+        "Target frame rate: {}",
+        format!("{} fps", target_fps).bold()
+    );
+    println!("Actual frame rate: {}", format!("{:.1} fps", fps).bold());
+    println!(
+        "Render time: {}",
+        format!("{:.1}%", render_time_percent).bold()
+    );
+    println!(
+        "Frames rendered: {}, skipped: {}",
+        format!("{}", frames_rendered).bold(),
+        format!("{}", frames_skipped).bold()
+    );
+
+    println!("\nScripts:");
+
+    for (script_file, cpu_percent, tick_divisor) in scripts {
+        let script_name = std::path::Path::new(&script_file)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or(script_file);
+
+        let ticks = if tick_divisor > 1 {
+            format!("throttled, ticked on every {} frames", tick_divisor).yellow()
+        } else {
+            "ticked on every frame".normal()
+        };
+
+        println!(
+            "{}: {} CPU, {}",
+            script_name.bold(),
+            format!("{:.1}%", cpu_percent).bold(),
+            ticks
+        );
+    }
+
+    Ok(())
+}
+
+async fn metrics_command(openmetrics: bool, reset: bool) -> Result<()> {
+    if reset {
+        reset_metrics()
+            .await
+            .wrap_err("Could not reset the metrics")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        return Ok(());
+    }
+
+    if openmetrics {
+        let text = get_openmetrics()
+            .await
+            .wrap_err("Could not connect to the Eruption daemon")
+            .suggestion("Please verify that the Eruption daemon is running")?;
+
+        print!("{}", text);
+
+        return Ok(());
+    }
+
+    let (
+        seconds,
+        frames_rendered,
+        frames_skipped,
+        frames_dropped,
+        frames_late,
+        script_ticks,
+        device_writes,
+        dbus_calls,
+        dropped_input_events,
+    ) = get_metrics()
+        .await
+        .wrap_err("Could not connect to the Eruption daemon")
+        .suggestion("Please verify that the Eruption daemon is running")?;
+
+    println!(
+        "Collected over: {}",
+        format!("{:.0} seconds", seconds).bold()
+    );
+    println!(
+        "Frames rendered: {}, skipped: {}, dropped: {}, late: {}",
+        format!("{}", frames_rendered).bold(),
+        format!("{}", frames_skipped).bold(),
+        format!("{}", frames_dropped).bold(),
+        format!("{}", frames_late).bold()
+    );
+
+    println!("\nScript ticks:");
+
+    for (script, count, mean_ms, max_ms) in script_ticks {
+        println!(
+            "{}: {} ticks, mean: {}, max: {}",
+            script.bold(),
+            count,
+            format!("{:.3} ms", mean_ms).bold(),
+            format!("{:.3} ms", max_ms).bold()
+        );
+    }
+
+    println!("\nHID writes:");
+
+    for (device, count, failures, mean_ms, max_ms) in device_writes {
+        let failures = if failures > 0 {
+            format!("{} failed", failures).red()
+        } else {
+            "0 failed".normal()
+        };
+
+        println!(
+            "{}: {} writes, {}, mean: {}, max: {}",
+            device.bold(),
+            count,
+            failures,
+            format!("{:.3} ms", mean_ms).bold(),
+            format!("{:.3} ms", max_ms).bold()
+        );
+    }
+
+    println!("\nD-Bus calls:");
+
+    for (method, count) in dbus_calls {
+        println!("{}: {}", method.bold(), count);
+    }
+
+    println!("\nDropped input events:");
+
+    for (source, count) in dropped_input_events {
+        println!("{}: {}", source.bold(), format!("{}", count).yellow());
+    }
+
+    Ok(())
+}
+
 /// Get the name of the currently active profile
 async fn get_active_profile() -> Result<String> {
     let result: String = dbus_system_bus("/org/eruption/profile")
@@ -87,3 +266,43 @@ async fn get_active_slot() -> Result<usize> {
 
     Ok(result as usize)
 }
+
+/// Get the statistics of the render loop
+async fn get_render_stats() -> Result<RenderStats> {
+    let result: RenderStats = dbus_system_bus("/org/eruption/status")
+        .await?
+        .method_call("org.eruption.Status", "GetRenderStats", ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Get the metrics of the daemon
+async fn get_metrics() -> Result<Metrics> {
+    let result: Metrics = dbus_system_bus("/org/eruption/metrics")
+        .await?
+        .method_call("org.eruption.Metrics", "GetMetrics", ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Get the metrics of the daemon in OpenMetrics text format
+async fn get_openmetrics() -> Result<String> {
+    let (result,): (String,) = dbus_system_bus("/org/eruption/metrics")
+        .await?
+        .method_call("org.eruption.Metrics", "GetOpenMetrics", ())
+        .await?;
+
+    Ok(result)
+}
+
+/// Reset all metrics of the daemon
+async fn reset_metrics() -> Result<bool> {
+    let (result,): (bool,) = dbus_system_bus("/org/eruption/metrics")
+        .await?
+        .method_call("org.eruption.Metrics", "ResetMetrics", ())
+        .await?;
+
+    Ok(result)
+}
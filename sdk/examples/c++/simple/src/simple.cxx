@@ -19,13 +19,70 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+#include <chrono>
 #include <iostream>
+#include <thread>
 #include "eruption/eruption.h"
 #include "includes/simple.h"
 
+static const char *EXAMPLE_NAME = "Simple C++ Example #1";
+
+static int fail(const char *what) {
+    const char *error = eruption_last_error();
+    std::cerr << what << ": " << (error ? error : "unknown error") << std::endl;
+
+    return 1;
+}
+
 int main() {
-    // calls into Rust code
-    hello();
+    std::cout << "Welcome to the Eruption SDK!" << std::endl
+              << "You are running the \"" << EXAMPLE_NAME << "\" "
+              << "using liberuption version " << eruption_version() << std::endl
+              << std::endl;
+
+    std::cout << "Connecting to the Eruption daemon..." << std::endl;
+
+    EruptionConnection *connection = eruption_connection_new();
+    if (!connection)
+        return fail("Could not create a connection");
+
+    if (eruption_connect(connection) != ERUPTION_OK) {
+        eruption_connection_free(connection);
+        return fail("Could not connect to the Eruption daemon");
+    }
+
+    std::cout << "Successfully connected to the Eruption daemon" << std::endl;
+
+    char status[256];
+    if (eruption_get_server_status(connection, status, sizeof(status)) == ERUPTION_OK)
+        std::cout << "Status: " << status << std::endl;
+
+    // create a new canvas
+    EruptionCanvas *canvas = eruption_canvas_new();
+
+    const EruptionColor colors[] = {
+        {255, 0, 0, 128},
+        {0, 255, 0, 128},
+        {0, 0, 255, 128},
+        {0, 0, 0, 0},
+    };
+
+    for (const EruptionColor &color : colors) {
+        eruption_canvas_fill(canvas, color);
+
+        std::cout << "Submitting canvas..." << std::endl;
+        if (eruption_submit_canvas(connection, canvas) != ERUPTION_OK) {
+            fail("Could not submit the canvas");
+            break;
+        }
+
+        std::this_thread::sleep_for(std::chrono::milliseconds(1000));
+    }
+
+    std::cout << "Exiting now" << std::endl;
+
+    eruption_canvas_free(canvas);
+    eruption_connection_free(connection);
 
     return 0;
 }
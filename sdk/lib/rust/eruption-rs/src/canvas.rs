@@ -40,6 +40,15 @@ impl Canvas {
     pub fn fill(&mut self, color: Color) {
         self.data.fill(color);
     }
+
+    /// Returns the number of LEDs on the canvas
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
 }
 
 impl ops::Index<usize> for Canvas {
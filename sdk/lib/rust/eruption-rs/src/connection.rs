@@ -21,10 +21,15 @@
 
 use crate::canvas::Canvas;
 use crate::hardware::HotplugInfo;
-use crate::transport::{LocalTransport, ServerStatus, Transport};
+use crate::transport::{
+    Event, EventKind, LocalTransport, NetworkTransport, ServerStatus, Transport,
+};
 use crate::Result;
 use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Connection {
@@ -34,7 +39,7 @@ pub struct Connection {
 impl Connection {
     pub fn new(connection_type: ConnectionType) -> Result<Self> {
         Ok(Self {
-            con: Arc::new(Mutex::new(make_transport(&connection_type)?)),
+            con: make_transport(&connection_type)?,
         })
     }
 
@@ -57,11 +62,73 @@ impl Connection {
     pub fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
         self.con.lock().notify_device_hotplug(hotplug_info)
     }
+
+    /// Returns the path of the currently active profile
+    pub fn get_active_profile(&self) -> Result<PathBuf> {
+        self.con.lock().get_active_profile()
+    }
+
+    /// Switch to the profile `profile_file`, returns `true` if the profile has been switched
+    pub fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
+        self.con.lock().switch_profile(profile_file)
+    }
+
+    /// Set the parameters of the script `script_file` in the profile `profile_file`.
+    /// Values are supplied as strings and will be converted to the type declared in the
+    /// script's manifest
+    pub fn set_parameters(
+        &self,
+        profile_file: &Path,
+        script_file: &Path,
+        parameter_values: HashMap<String, String>,
+    ) -> Result<()> {
+        self.con
+            .lock()
+            .set_parameters(profile_file, script_file, parameter_values)
+    }
+
+    /// Subscribe to the specified kinds of events, replacing any previous subscription.
+    /// Use `wait_for_event` to receive them
+    pub fn subscribe(&self, events: &[EventKind]) -> Result<()> {
+        self.con.lock().subscribe(events)
+    }
+
+    /// Wait for the next event, at most for `timeout` (or indefinitely if `None`).
+    /// Returns `Ok(None)` if the timeout elapsed without an event being received.
+    /// Other requests on this connection are blocked while waiting
+    pub fn wait_for_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        self.con.lock().wait_for_event(timeout)
+    }
+
+    /// Start recording the canvas to the file `file_name` in the recordings directory of
+    /// the daemon. Only a single recording may be in progress at a time
+    pub fn start_recording(&self, file_name: &str) -> Result<()> {
+        self.con.lock().start_recording(file_name)
+    }
+
+    /// Stop the recording that is in progress, returns the number of recorded frames
+    pub fn stop_recording(&self) -> Result<u64> {
+        self.con.lock().stop_recording()
+    }
+
+    /// Replay the recording `file_name` on the SDK canvas, optionally in a loop.
+    /// Returns the number of frames and the duration of the recording
+    pub fn start_playback(&self, file_name: &str, looped: bool) -> Result<(u64, Duration)> {
+        self.con.lock().start_playback(file_name, looped)
+    }
+
+    /// Stop the playback, returns `true` if a playback had been in progress
+    pub fn stop_playback(&self) -> Result<bool> {
+        self.con.lock().stop_playback()
+    }
 }
 
 impl Drop for Connection {
     fn drop(&mut self) {
-        let _ = self.disconnect();
+        // only disconnect when the last clone of the connection goes away
+        if Arc::strong_count(&self.con) == 1 {
+            let _ = self.disconnect();
+        }
     }
 }
 
@@ -74,10 +141,17 @@ pub enum ConnectionType {
     /// Local transport
     Local,
 
-    /// Type REMOTE is currently not implemented
-    Remote,
+    /// Network transport, connects to the TCP listener of the Eruption daemon at
+    /// `address` (`host:port`) and authenticates using `token`
+    Remote { address: String, token: String },
 }
 
-fn make_transport(_connection_type: &ConnectionType) -> Result<impl Transport> {
-    LocalTransport::new()
+fn make_transport(connection_type: &ConnectionType) -> Result<Arc<Mutex<dyn Transport>>> {
+    match connection_type {
+        ConnectionType::Remote { address, token } => {
+            Ok(Arc::new(Mutex::new(NetworkTransport::new(address, token)?)))
+        }
+
+        _ => Ok(Arc::new(Mutex::new(LocalTransport::new()?))),
+    }
 }
@@ -21,21 +21,20 @@
 
 use crate::canvas::Canvas;
 use crate::hardware::HotplugInfo;
-use crate::transport::{ServerStatus, Transport};
+use crate::transport::{
+    notification_to_event, protocol, Event, EventKind, ServerStatus, Transport,
+};
 use crate::{util, Result};
 use eyre::eyre;
 use parking_lot::Mutex;
 use prost::Message;
 use socket2::{Domain, SockAddr, Socket, Type};
-use std::collections::HashMap;
-use std::io::{Cursor, Write};
+use std::collections::{HashMap, VecDeque};
+use std::io::{Cursor, ErrorKind, Write};
 use std::mem::MaybeUninit;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-
-pub mod protocol {
-    include!(concat!(env!("OUT_DIR"), "/sdk_support.rs"));
-}
+use std::time::Duration;
 
 const SOCKET_ADDRESS: &str = "/run/eruption/control.sock";
 const MAX_BUF: usize = 4096;
@@ -43,6 +42,9 @@ const MAX_BUF: usize = 4096;
 #[derive(Debug, Clone)]
 pub struct LocalTransport {
     pub(crate) socket: Arc<Mutex<Socket>>,
+
+    /// Notifications that have been received while waiting for a response
+    pub(crate) pending_events: Arc<Mutex<VecDeque<Event>>>,
 }
 
 impl LocalTransport {
@@ -53,30 +55,18 @@ impl LocalTransport {
                 Type::SEQPACKET,
                 None,
             )?)),
+            pending_events: Arc::new(Mutex::new(VecDeque::new())),
         })
     }
-}
-
-impl Transport for LocalTransport {
-    fn connect(&mut self) -> Result<()> {
-        let addr = SockAddr::unix(SOCKET_ADDRESS)?;
-        self.socket.lock().connect(&addr)?;
-
-        Ok(())
-    }
-
-    fn disconnect(&mut self) -> Result<()> {
-        self.socket.lock().flush()?;
-        // self.socket.lock().shutdown(Shutdown::Both)?;
-
-        Ok(())
-    }
 
-    fn get_server_status(&self) -> Result<ServerStatus> {
+    /// Send a request and wait for the matching response.
+    /// Notifications that arrive in the meantime are queued
+    fn transact(
+        &self,
+        request: protocol::request::RequestMessage,
+    ) -> Result<protocol::response::ResponseMessage> {
         let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::Status(
-                protocol::StatusRequest {},
-            )),
+            request_message: Some(request),
         };
 
         let mut buf = Vec::new();
@@ -85,119 +75,99 @@ impl Transport for LocalTransport {
         // send data
         let socket = self.socket.lock();
         match socket.send(&buf) {
-            Ok(_n) => {
+            Ok(_n) => loop {
                 // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
-
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
-
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::Status(status_response)) =
-                            result.response_message
-                        {
-                            Ok(ServerStatus {
-                                server: status_response.description,
-                            })
-                        } else {
-                            Err(eyre!("Unexpected response"))
+                match Self::receive(&socket)? {
+                    protocol::response::ResponseMessage::Notification(notification) => {
+                        if let Some(event) = notification_to_event(notification) {
+                            self.pending_events.lock().push_back(event);
                         }
                     }
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+                    response => return Ok(response),
                 }
-            }
+            },
 
             Err(_e) => Err(eyre!("Lost connection to Eruption")),
         }
     }
 
-    fn get_active_profile(&self) -> Result<PathBuf> {
-        let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::ActiveProfile(
-                protocol::ActiveProfileRequest {},
-            )),
-        };
+    fn receive(socket: &Socket) -> Result<protocol::response::ResponseMessage> {
+        let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        match socket.recv(&mut tmp) {
+            Ok(0) => Err(eyre!("Lost connection to Eruption")),
 
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
             Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
-
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
-
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::ActiveProfile(
-                            active_profile_response,
-                        )) = result.response_message
-                        {
-                            Ok(PathBuf::from(&active_profile_response.profile_file))
-                        } else {
-                            Err(eyre!("Unexpected response"))
-                        }
-                    }
+                let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
+                let result = protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
+                result
+                    .response_message
+                    .ok_or_else(|| eyre!("Unexpected response"))
             }
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+            Err(e) => Err(e.into()),
         }
     }
+}
 
-    fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
-        let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::SwitchProfile(
-                protocol::SwitchProfileRequest {
-                    profile_file: profile_file.to_string_lossy().to_string(),
-                },
-            )),
-        };
+impl Transport for LocalTransport {
+    fn connect(&mut self) -> Result<()> {
+        let addr = SockAddr::unix(SOCKET_ADDRESS)?;
+        self.socket.lock().connect(&addr)?;
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        Ok(())
+    }
 
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
-
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
-
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::SwitchProfile(
-                            switch_profile_response,
-                        )) = result.response_message
-                        {
-                            Ok(switch_profile_response.switched)
-                        } else {
-                            Err(eyre!("Unexpected response"))
-                        }
-                    }
+    fn disconnect(&mut self) -> Result<()> {
+        self.socket.lock().flush()?;
+        // self.socket.lock().shutdown(Shutdown::Both)?;
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
+        Ok(())
+    }
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+    fn get_server_status(&self) -> Result<ServerStatus> {
+        let response = self.transact(protocol::request::RequestMessage::Status(
+            protocol::StatusRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::Status(status_response) = response {
+            Ok(ServerStatus {
+                server: status_response.description,
+            })
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn get_active_profile(&self) -> Result<PathBuf> {
+        let response = self.transact(protocol::request::RequestMessage::ActiveProfile(
+            protocol::ActiveProfileRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::ActiveProfile(active_profile_response) =
+            response
+        {
+            Ok(PathBuf::from(&active_profile_response.profile_file))
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
+        let response = self.transact(protocol::request::RequestMessage::SwitchProfile(
+            protocol::SwitchProfileRequest {
+                profile_file: profile_file.to_string_lossy().to_string(),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::SwitchProfile(switch_profile_response) =
+            response
+        {
+            Ok(switch_profile_response.switched)
+        } else {
+            Err(eyre!("Unexpected response"))
         }
     }
 
@@ -207,48 +177,20 @@ impl Transport for LocalTransport {
         script_file: &Path,
         parameter_values: HashMap<String, String>,
     ) -> Result<()> {
-        let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::SetParameters(
-                protocol::SetParametersRequest {
-                    profile_file: profile_file.to_string_lossy().to_string(),
-                    script_file: script_file.to_string_lossy().to_string(),
-                    parameter_values,
-                },
-            )),
-        };
-
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
-
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
-
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
-
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::SetParameters(
-                            _set_parameters_response,
-                        )) = result.response_message
-                        {
-                            Ok(())
-                        } else {
-                            Err(eyre!("Unexpected response"))
-                        }
-                    }
-
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
-
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        let response = self.transact(protocol::request::RequestMessage::SetParameters(
+            protocol::SetParametersRequest {
+                profile_file: profile_file.to_string_lossy().to_string(),
+                script_file: script_file.to_string_lossy().to_string(),
+                parameter_values,
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::SetParameters(_set_parameters_response) =
+            response
+        {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
         }
     }
 
@@ -259,44 +201,14 @@ impl Transport for LocalTransport {
             .flat_map(|c| vec![c.r(), c.g(), c.b(), c.a()])
             .collect();
 
-        let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::SetCanvas(
-                protocol::SetCanvasRequest { canvas: bytes },
-            )),
-        };
-
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
-
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
-
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
-
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
-                        if let Some(protocol::response::ResponseMessage::SetCanvas(
-                            _set_canvas_response,
-                        )) = result.response_message
-                        {
-                            Ok(())
-                        } else {
-                            Err(eyre!("Unexpected response"))
-                        }
-                    }
-
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
-                }
-            }
+        let response = self.transact(protocol::request::RequestMessage::SetCanvas(
+            protocol::SetCanvasRequest { canvas: bytes },
+        ))?;
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        match response {
+            protocol::response::ResponseMessage::SetCanvas(_set_canvas_response) => Ok(()),
+            protocol::response::ResponseMessage::Error(error) => Err(eyre!(error.description)),
+            _ => Err(eyre!("Unexpected response")),
         }
     }
 
@@ -304,38 +216,123 @@ impl Transport for LocalTransport {
         let config = bincode::config::standard();
         let bytes: Vec<u8> = bincode::encode_to_vec(hotplug_info, config).unwrap();
 
-        let request = protocol::Request {
-            request_message: Some(protocol::request::RequestMessage::NotifyHotplug(
-                protocol::NotifyHotplugRequest { payload: bytes },
-            )),
-        };
+        let _response = self.transact(protocol::request::RequestMessage::NotifyHotplug(
+            protocol::NotifyHotplugRequest { payload: bytes },
+        ))?;
 
-        let mut buf = Vec::new();
-        request.encode_length_delimited(&mut buf)?;
+        Ok(())
+    }
 
-        // send data
-        let socket = self.socket.lock();
-        match socket.send(&buf) {
-            Ok(_n) => {
-                // read response
-                let mut tmp = [MaybeUninit::zeroed(); MAX_BUF];
+    fn subscribe(&self, events: &[EventKind]) -> Result<()> {
+        let response = self.transact(protocol::request::RequestMessage::Subscribe(
+            protocol::SubscribeRequest {
+                profile_changed: events.contains(&EventKind::ProfileChanged),
+                slot_changed: events.contains(&EventKind::SlotChanged),
+                brightness_changed: events.contains(&EventKind::BrightnessChanged),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::Subscribe(_subscribe_response) = response {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
 
-                match socket.recv(&mut tmp) {
-                    Ok(0) => Err(eyre!("Lost connection to Eruption")),
+    fn wait_for_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if let Some(event) = self.pending_events.lock().pop_front() {
+            return Ok(Some(event));
+        }
 
-                    Ok(_n) => {
-                        let tmp = unsafe { util::assume_init(&tmp[..tmp.len()]) };
-                        let _result =
-                            protocol::Response::decode_length_delimited(&mut Cursor::new(&tmp))?;
+        let socket = self.socket.lock();
 
-                        Ok(())
-                    }
+        // a zero timeout is rejected by the socket layer, so use the smallest possible one
+        let timeout = timeout.map(|t| t.max(Duration::from_millis(1)));
+        socket.set_read_timeout(timeout)?;
 
-                    Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        let result = loop {
+            match Self::receive(&socket) {
+                Ok(protocol::response::ResponseMessage::Notification(notification)) => {
+                    if let Some(event) = notification_to_event(notification) {
+                        break Ok(Some(event));
+                    }
                 }
+
+                Ok(_response) => break Err(eyre!("Unexpected response")),
+
+                Err(e) => match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                    Some(ErrorKind::WouldBlock) | Some(ErrorKind::TimedOut) => break Ok(None),
+
+                    _ => break Err(eyre!("Lost connection to Eruption")),
+                },
             }
+        };
 
-            Err(_e) => Err(eyre!("Lost connection to Eruption")),
+        socket.set_read_timeout(None)?;
+
+        result
+    }
+
+    fn start_recording(&self, file_name: &str) -> Result<()> {
+        let response = self.transact(protocol::request::RequestMessage::StartRecording(
+            protocol::StartRecordingRequest {
+                file_name: file_name.to_string(),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::StartRecording(_start_recording_response) =
+            response
+        {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn stop_recording(&self) -> Result<u64> {
+        let response = self.transact(protocol::request::RequestMessage::StopRecording(
+            protocol::StopRecordingRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::StopRecording(stop_recording_response) =
+            response
+        {
+            Ok(stop_recording_response.num_frames)
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn start_playback(&self, file_name: &str, looped: bool) -> Result<(u64, Duration)> {
+        let response = self.transact(protocol::request::RequestMessage::StartPlayback(
+            protocol::StartPlaybackRequest {
+                file_name: file_name.to_string(),
+                looped,
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::StartPlayback(start_playback_response) =
+            response
+        {
+            Ok((
+                start_playback_response.num_frames,
+                Duration::from_millis(start_playback_response.duration_millis),
+            ))
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn stop_playback(&self) -> Result<bool> {
+        let response = self.transact(protocol::request::RequestMessage::StopPlayback(
+            protocol::StopPlaybackRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::StopPlayback(stop_playback_response) = response
+        {
+            Ok(stop_playback_response.stopped)
+        } else {
+            Err(eyre!("Unexpected response"))
         }
     }
 }
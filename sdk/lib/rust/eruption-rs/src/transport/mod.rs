@@ -21,13 +21,20 @@
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::canvas::Canvas;
 use crate::hardware::HotplugInfo;
 use crate::Result;
 
 mod local;
+mod network;
 pub use local::*;
+pub use network::*;
+
+pub mod protocol {
+    include!(concat!(env!("OUT_DIR"), "/sdk_support.rs"));
+}
 
 pub trait Transport {
     fn connect(&mut self) -> Result<()>;
@@ -45,9 +52,64 @@ pub trait Transport {
     fn submit_canvas(&self, canvas: &Canvas) -> Result<()>;
 
     fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()>;
+
+    fn subscribe(&self, events: &[EventKind]) -> Result<()>;
+    fn wait_for_event(&self, timeout: Option<Duration>) -> Result<Option<Event>>;
+
+    fn start_recording(&self, file_name: &str) -> Result<()>;
+    fn stop_recording(&self) -> Result<u64>;
+    fn start_playback(&self, file_name: &str, looped: bool) -> Result<(u64, Duration)>;
+    fn stop_playback(&self) -> Result<bool>;
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct ServerStatus {
     pub server: String,
 }
+
+/// The kinds of events a client may subscribe to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    ProfileChanged,
+    SlotChanged,
+    BrightnessChanged,
+}
+
+/// An event that has been sent by the Eruption daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The active profile has been switched
+    ProfileChanged { profile_file: PathBuf },
+
+    /// The active slot has been switched (zero based)
+    SlotChanged { slot: usize },
+
+    /// The global brightness has been changed (in percent)
+    BrightnessChanged { brightness: i64 },
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::ProfileChanged { .. } => EventKind::ProfileChanged,
+            Event::SlotChanged { .. } => EventKind::SlotChanged,
+            Event::BrightnessChanged { .. } => EventKind::BrightnessChanged,
+        }
+    }
+}
+
+pub(crate) fn notification_to_event(notification: protocol::Notification) -> Option<Event> {
+    match notification.event? {
+        protocol::notification::Event::ProfileChanged(event) => Some(Event::ProfileChanged {
+            profile_file: PathBuf::from(event.profile_file),
+        }),
+
+        protocol::notification::Event::SlotChanged(event) => Some(Event::SlotChanged {
+            slot: event.slot as usize,
+        }),
+
+        protocol::notification::Event::BrightnessChanged(event) => Some(Event::BrightnessChanged {
+            brightness: event.brightness,
+        }),
+    }
+}
@@ -0,0 +1,492 @@
+/*  SPDX-License-Identifier: LGPL-3.0-or-later  */
+
+/*
+    This file is part of the Eruption SDK.
+
+    The Eruption SDK is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Lesser General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    The Eruption SDK is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Lesser General Public License for more details.
+
+    You should have received a copy of the GNU Lesser General Public License
+    along with the Eruption SDK.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+//! Network transport, talks to the TCP listener of the Eruption daemon.
+//! Messages are framed as length delimited protobuf messages
+
+use crate::canvas::Canvas;
+use crate::hardware::HotplugInfo;
+use crate::transport::{
+    notification_to_event, protocol, Event, EventKind, ServerStatus, Transport,
+};
+use crate::Result;
+use eyre::eyre;
+use parking_lot::Mutex;
+use prost::Message;
+use std::collections::{HashMap, VecDeque};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Default port of the network listener of the Eruption daemon
+pub const DEFAULT_NETWORK_PORT: u16 = 2359;
+
+/// Upper bound for the size of a single message
+const MAX_FRAME_SIZE: usize = 65536;
+
+/// Max. length of an encoded length delimiter (varint)
+const MAX_DELIMITER_LEN: usize = 10;
+
+#[derive(Debug)]
+struct NetworkConnection {
+    stream: TcpStream,
+    frames: FrameReader,
+}
+
+pub struct NetworkTransport {
+    address: String,
+    token: String,
+
+    connection: Arc<Mutex<Option<NetworkConnection>>>,
+
+    /// Notifications that have been received while waiting for a response
+    pending_events: Arc<Mutex<VecDeque<Event>>>,
+}
+
+impl std::fmt::Debug for NetworkTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // do not leak the token
+        f.debug_struct("NetworkTransport")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+impl NetworkTransport {
+    /// Create a new transport, that connects to `address` (`host:port`) and authenticates using `token`
+    pub fn new(address: &str, token: &str) -> Result<Self> {
+        Ok(Self {
+            address: address.to_string(),
+            token: token.to_string(),
+            connection: Arc::new(Mutex::new(None)),
+            pending_events: Arc::new(Mutex::new(VecDeque::new())),
+        })
+    }
+
+    /// Send a request and wait for the matching response.
+    /// Notifications that arrive in the meantime are queued
+    fn transact(
+        &self,
+        request: protocol::request::RequestMessage,
+    ) -> Result<protocol::response::ResponseMessage> {
+        let mut connection = self.connection.lock();
+        let connection = connection
+            .as_mut()
+            .ok_or_else(|| eyre!("Not connected to Eruption"))?;
+
+        connection.send(request)?;
+
+        loop {
+            match connection.receive()? {
+                protocol::response::ResponseMessage::Notification(notification) => {
+                    if let Some(event) = notification_to_event(notification) {
+                        self.pending_events.lock().push_back(event);
+                    }
+                }
+
+                response => return Ok(response),
+            }
+        }
+    }
+}
+
+impl NetworkConnection {
+    fn send(&mut self, request: protocol::request::RequestMessage) -> Result<()> {
+        let request = protocol::Request {
+            request_message: Some(request),
+        };
+
+        let mut buf = Vec::new();
+        request.encode_length_delimited(&mut buf)?;
+
+        self.stream
+            .write_all(&buf)
+            .map_err(|_e| eyre!("Lost connection to Eruption"))?;
+
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<protocol::response::ResponseMessage> {
+        let frame = self.frames.read_frame(&mut self.stream)?;
+        let result = protocol::Response::decode(&frame[..])?;
+
+        result
+            .response_message
+            .ok_or_else(|| eyre!("Unexpected response"))
+    }
+}
+
+impl Transport for NetworkTransport {
+    fn connect(&mut self) -> Result<()> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_nodelay(true)?;
+
+        let mut connection = NetworkConnection {
+            stream,
+            frames: FrameReader::default(),
+        };
+
+        // the daemon closes the connection, unless the first request is a valid authentication
+        connection.send(protocol::request::RequestMessage::Authenticate(
+            protocol::AuthenticateRequest {
+                token: self.token.clone(),
+            },
+        ))?;
+
+        match connection.receive()? {
+            protocol::response::ResponseMessage::Authenticate(response)
+                if response.authenticated =>
+            {
+                self.connection.lock().replace(connection);
+
+                Ok(())
+            }
+
+            _ => Err(eyre!("Authentication failed")),
+        }
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        if let Some(connection) = self.connection.lock().take() {
+            let _ = connection.stream.shutdown(std::net::Shutdown::Both);
+        }
+
+        Ok(())
+    }
+
+    fn get_server_status(&self) -> Result<ServerStatus> {
+        let response = self.transact(protocol::request::RequestMessage::Status(
+            protocol::StatusRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::Status(status_response) = response {
+            Ok(ServerStatus {
+                server: status_response.description,
+            })
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn get_active_profile(&self) -> Result<PathBuf> {
+        let response = self.transact(protocol::request::RequestMessage::ActiveProfile(
+            protocol::ActiveProfileRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::ActiveProfile(active_profile_response) =
+            response
+        {
+            Ok(PathBuf::from(&active_profile_response.profile_file))
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn switch_profile(&self, profile_file: &Path) -> Result<bool> {
+        let response = self.transact(protocol::request::RequestMessage::SwitchProfile(
+            protocol::SwitchProfileRequest {
+                profile_file: profile_file.to_string_lossy().to_string(),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::SwitchProfile(switch_profile_response) =
+            response
+        {
+            Ok(switch_profile_response.switched)
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn set_parameters(
+        &self,
+        profile_file: &Path,
+        script_file: &Path,
+        parameter_values: HashMap<String, String>,
+    ) -> Result<()> {
+        let response = self.transact(protocol::request::RequestMessage::SetParameters(
+            protocol::SetParametersRequest {
+                profile_file: profile_file.to_string_lossy().to_string(),
+                script_file: script_file.to_string_lossy().to_string(),
+                parameter_values,
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::SetParameters(_set_parameters_response) =
+            response
+        {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn submit_canvas(&self, canvas: &Canvas) -> Result<()> {
+        let bytes: Vec<u8> = canvas
+            .data
+            .iter()
+            .flat_map(|c| vec![c.r(), c.g(), c.b(), c.a()])
+            .collect();
+
+        let response = self.transact(protocol::request::RequestMessage::SetCanvas(
+            protocol::SetCanvasRequest { canvas: bytes },
+        ))?;
+
+        match response {
+            protocol::response::ResponseMessage::SetCanvas(_set_canvas_response) => Ok(()),
+            protocol::response::ResponseMessage::Error(error) => Err(eyre!(error.description)),
+            _ => Err(eyre!("Unexpected response")),
+        }
+    }
+
+    fn notify_device_hotplug(&self, hotplug_info: &HotplugInfo) -> Result<()> {
+        let config = bincode::config::standard();
+        let bytes: Vec<u8> = bincode::encode_to_vec(hotplug_info, config).unwrap();
+
+        let _response = self.transact(protocol::request::RequestMessage::NotifyHotplug(
+            protocol::NotifyHotplugRequest { payload: bytes },
+        ))?;
+
+        Ok(())
+    }
+
+    fn subscribe(&self, events: &[EventKind]) -> Result<()> {
+        let response = self.transact(protocol::request::RequestMessage::Subscribe(
+            protocol::SubscribeRequest {
+                profile_changed: events.contains(&EventKind::ProfileChanged),
+                slot_changed: events.contains(&EventKind::SlotChanged),
+                brightness_changed: events.contains(&EventKind::BrightnessChanged),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::Subscribe(_subscribe_response) = response {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn wait_for_event(&self, timeout: Option<Duration>) -> Result<Option<Event>> {
+        if let Some(event) = self.pending_events.lock().pop_front() {
+            return Ok(Some(event));
+        }
+
+        let mut connection = self.connection.lock();
+        let connection = connection
+            .as_mut()
+            .ok_or_else(|| eyre!("Not connected to Eruption"))?;
+
+        // a zero timeout is rejected by the socket layer, so use the smallest possible one
+        let timeout = timeout.map(|t| t.max(Duration::from_millis(1)));
+        connection.stream.set_read_timeout(timeout)?;
+
+        let result = loop {
+            match connection.receive() {
+                Ok(protocol::response::ResponseMessage::Notification(notification)) => {
+                    if let Some(event) = notification_to_event(notification) {
+                        break Ok(Some(event));
+                    }
+                }
+
+                Ok(_response) => break Err(eyre!("Unexpected response")),
+
+                Err(e) => match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                    Some(ErrorKind::WouldBlock) | Some(ErrorKind::TimedOut) => break Ok(None),
+
+                    _ => break Err(eyre!("Lost connection to Eruption")),
+                },
+            }
+        };
+
+        connection.stream.set_read_timeout(None)?;
+
+        result
+    }
+
+    fn start_recording(&self, file_name: &str) -> Result<()> {
+        let response = self.transact(protocol::request::RequestMessage::StartRecording(
+            protocol::StartRecordingRequest {
+                file_name: file_name.to_string(),
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::StartRecording(_start_recording_response) =
+            response
+        {
+            Ok(())
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn stop_recording(&self) -> Result<u64> {
+        let response = self.transact(protocol::request::RequestMessage::StopRecording(
+            protocol::StopRecordingRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::StopRecording(stop_recording_response) =
+            response
+        {
+            Ok(stop_recording_response.num_frames)
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn start_playback(&self, file_name: &str, looped: bool) -> Result<(u64, Duration)> {
+        let response = self.transact(protocol::request::RequestMessage::StartPlayback(
+            protocol::StartPlaybackRequest {
+                file_name: file_name.to_string(),
+                looped,
+            },
+        ))?;
+
+        if let protocol::response::ResponseMessage::StartPlayback(start_playback_response) =
+            response
+        {
+            Ok((
+                start_playback_response.num_frames,
+                Duration::from_millis(start_playback_response.duration_millis),
+            ))
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+
+    fn stop_playback(&self) -> Result<bool> {
+        let response = self.transact(protocol::request::RequestMessage::StopPlayback(
+            protocol::StopPlaybackRequest {},
+        ))?;
+
+        if let protocol::response::ResponseMessage::StopPlayback(stop_playback_response) = response
+        {
+            Ok(stop_playback_response.stopped)
+        } else {
+            Err(eyre!("Unexpected response"))
+        }
+    }
+}
+
+impl Drop for NetworkTransport {
+    fn drop(&mut self) {
+        let _ = self.disconnect();
+    }
+}
+
+/// Splits a byte stream into length delimited frames, partially received frames are
+/// kept until the remaining data arrives
+#[derive(Debug, Default)]
+pub(crate) struct FrameReader {
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    /// Read the next frame, honoring the read timeout of the stream
+    pub(crate) fn read_frame<R: Read>(&mut self, stream: &mut R) -> Result<Vec<u8>> {
+        loop {
+            if let Some(frame) = self.take_frame()? {
+                return Ok(frame);
+            }
+
+            let mut tmp = [0_u8; 4096];
+
+            match stream.read(&mut tmp) {
+                Ok(0) => return Err(eyre!("Lost connection to Eruption")),
+
+                Ok(n) => self.buf.extend_from_slice(&tmp[..n]),
+
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn take_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.buf.is_empty() {
+            return Ok(None);
+        }
+
+        match prost::decode_length_delimiter(&self.buf[..]) {
+            Ok(len) if len > MAX_FRAME_SIZE => Err(eyre!("Message too large: {} bytes", len)),
+
+            Ok(len) => {
+                let start = prost::length_delimiter_len(len);
+
+                if self.buf.len() >= start + len {
+                    let frame = self.buf[start..start + len].to_vec();
+                    self.buf.drain(..start + len);
+
+                    Ok(Some(frame))
+                } else {
+                    Ok(None)
+                }
+            }
+
+            // the length delimiter has not been received completely yet
+            Err(_e) if self.buf.len() < MAX_DELIMITER_LEN => Ok(None),
+
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn read_split_frames() -> Result<()> {
+        let mut data = Vec::new();
+
+        for description in ["first", "second"] {
+            protocol::StatusResponse {
+                description: description.to_string(),
+            }
+            .encode_length_delimited(&mut data)?;
+        }
+
+        // deliver the data one byte at a time
+        struct Trickle(Cursor<Vec<u8>>);
+
+        impl Read for Trickle {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.read(&mut buf[..1])
+            }
+        }
+
+        let mut stream = Trickle(Cursor::new(data));
+        let mut frames = FrameReader::default();
+
+        let first = protocol::StatusResponse::decode(&frames.read_frame(&mut stream)?[..])?;
+        let second = protocol::StatusResponse::decode(&frames.read_frame(&mut stream)?[..])?;
+
+        assert_eq!(first.description, "first");
+        assert_eq!(second.description, "second");
+
+        assert!(frames.read_frame(&mut stream).is_err());
+
+        Ok(())
+    }
+}
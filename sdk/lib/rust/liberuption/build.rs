@@ -21,13 +21,30 @@
 
 use std::env;
 use std::error::Error;
+use std::fs;
 
 fn main() -> Result<(), Box<dyn Error + 'static>> {
     let crate_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
 
-    cbindgen::generate(crate_dir)
+    cbindgen::generate(&crate_dir)
         .expect("Unable to generate bindings")
         .write_to_file("generated/eruption.h");
 
+    // generate the pkg-config file, the installation prefix may be overridden at build time
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=eruption.pc.in");
+    println!("cargo:rerun-if-env-changed=ERUPTION_PREFIX");
+
+    let prefix = env::var("ERUPTION_PREFIX").unwrap_or_else(|_| "/usr".to_string());
+    let version = env::var("CARGO_PKG_VERSION").unwrap();
+
+    let template = fs::read_to_string(format!("{}/eruption.pc.in", crate_dir))?;
+    let pc = template
+        .replace("@PREFIX@", &prefix)
+        .replace("@VERSION@", &version);
+
+    fs::write("generated/eruption.pc", pc)?;
+
     Ok(())
 }
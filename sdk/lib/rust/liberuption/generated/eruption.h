@@ -32,11 +32,209 @@
 #define ERUPTION_SDK_VERSION 0.0.5
 
 
+/**
+ * The operation completed successfully
+ */
+#define ERUPTION_OK 0
+
+/**
+ * A NULL pointer, a string that is not valid UTF-8 or an out of range index has been supplied
+ */
+#define ERUPTION_ERROR_INVALID_ARGUMENT -1
+
+/**
+ * The request could not be completed by the Eruption daemon
+ */
+#define ERUPTION_ERROR_CONNECTION -2
+
+/**
+ * The supplied buffer is too small to hold the result, including the terminating NUL byte
+ */
+#define ERUPTION_ERROR_BUFFER_TOO_SMALL -3
+
+/**
+ * The Eruption daemon refused to switch to the requested profile
+ */
+#define ERUPTION_ERROR_NOT_SWITCHED -4
+
+/**
+ * An internal error occurred
+ */
+#define ERUPTION_ERROR_INTERNAL -5
+
+/**
+ * Opaque handle to a canvas
+ */
+typedef struct EruptionCanvas EruptionCanvas;
+
+/**
+ * Opaque handle representing a connection to the Eruption daemon
+ */
+typedef struct EruptionConnection EruptionConnection;
+
+/**
+ * A RGBA color value
+ */
+typedef struct EruptionColor {
+  uint8_t r;
+  uint8_t g;
+  uint8_t b;
+  uint8_t a;
+} EruptionColor;
+
 #ifdef __cplusplus
 extern "C" {
 #endif // __cplusplus
 
-void hello(void);
+/**
+ * Returns the version of liberuption as a NUL terminated string
+ */
+const char *eruption_version(void);
+
+/**
+ * Returns a description of the last error that occurred on the calling thread, or NULL.
+ * The string is owned by the library and remains valid until the next call into the library
+ */
+const char *eruption_last_error(void);
+
+/**
+ * Allocate a new canvas, with all LEDs set to transparent black.
+ * The canvas must be released with `eruption_canvas_free()`
+ */
+EruptionCanvas *eruption_canvas_new(void);
+
+/**
+ * Release a canvas that has been allocated with `eruption_canvas_new()`
+ *
+ * # Safety
+ *
+ * `canvas` must be NULL or a pointer returned by `eruption_canvas_new()`, that has not been freed yet
+ */
+void eruption_canvas_free(EruptionCanvas *canvas);
+
+/**
+ * Returns the number of LEDs on the canvas, or 0 if `canvas` is NULL
+ *
+ * # Safety
+ *
+ * `canvas` must be NULL or a valid canvas
+ */
+uintptr_t eruption_canvas_size(const EruptionCanvas *canvas);
+
+/**
+ * Paint the whole canvas with `color`
+ *
+ * # Safety
+ *
+ * `canvas` must be NULL or a valid canvas
+ */
+int32_t eruption_canvas_fill(EruptionCanvas *canvas, struct EruptionColor color);
+
+/**
+ * Set the LED at `index` to `color`
+ *
+ * # Safety
+ *
+ * `canvas` must be NULL or a valid canvas
+ */
+int32_t eruption_canvas_set_pixel(EruptionCanvas *canvas,
+                                  uintptr_t index,
+                                  struct EruptionColor color);
+
+/**
+ * Store the color of the LED at `index` in `color`
+ *
+ * # Safety
+ *
+ * `canvas` must be NULL or a valid canvas, `color` must be NULL or point to writable memory
+ */
+int32_t eruption_canvas_get_pixel(const EruptionCanvas *canvas,
+                                  uintptr_t index,
+                                  struct EruptionColor *color);
+
+/**
+ * Create a new connection object, using the local transport.
+ * Returns NULL on error. The connection must be released with `eruption_connection_free()`
+ */
+EruptionConnection *eruption_connection_new(void);
+
+/**
+ * Release a connection, disconnecting from the Eruption daemon if required
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a pointer returned by `eruption_connection_new()`, that has not been freed yet
+ */
+void eruption_connection_free(EruptionConnection *connection);
+
+/**
+ * Connect to the Eruption daemon
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection
+ */
+int32_t eruption_connect(EruptionConnection *connection);
+
+/**
+ * Disconnect from the Eruption daemon
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection
+ */
+int32_t eruption_disconnect(EruptionConnection *connection);
+
+/**
+ * Store the status description of the Eruption daemon in `buf`, as a NUL terminated string
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection, `buf` must point to at least `buf_len` writable bytes
+ */
+int32_t eruption_get_server_status(EruptionConnection *connection, char *buf, uintptr_t buf_len);
+
+/**
+ * Store the path of the active profile in `buf`, as a NUL terminated string
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection, `buf` must point to at least `buf_len` writable bytes
+ */
+int32_t eruption_get_active_profile(EruptionConnection *connection, char *buf, uintptr_t buf_len);
+
+/**
+ * Switch to the profile `profile_file`.
+ * Returns `ERUPTION_ERROR_NOT_SWITCHED` if the daemon kept the current profile
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection, `profile_file` must be NULL or a NUL terminated string
+ */
+int32_t eruption_switch_profile(EruptionConnection *connection, const char *profile_file);
+
+/**
+ * Set the parameter `name` of the script `script_file` in the profile `profile_file` to `value`.
+ * The value is converted to the type declared in the script's manifest
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection, all strings must be NULL or NUL terminated
+ */
+int32_t eruption_set_parameter(EruptionConnection *connection,
+                               const char *profile_file,
+                               const char *script_file,
+                               const char *name,
+                               const char *value);
+
+/**
+ * Submit the canvas to the Eruption daemon
+ *
+ * # Safety
+ *
+ * `connection` must be NULL or a valid connection, `canvas` must be NULL or a valid canvas
+ */
+int32_t eruption_submit_canvas(EruptionConnection *connection, const EruptionCanvas *canvas);
 
 #ifdef __cplusplus
 } // extern "C"
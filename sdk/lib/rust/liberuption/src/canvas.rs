@@ -0,0 +1,239 @@
+/*  SPDX-License-Identifier: LGPL-3.0-or-later  */
+
+/*
+    This file is part of the Eruption SDK.
+
+    The Eruption SDK is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Lesser General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    The Eruption SDK is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Lesser General Public License for more details.
+
+    You should have received a copy of the GNU Lesser General Public License
+    along with the Eruption SDK.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use eruption_sdk::canvas::Canvas;
+use eruption_sdk::color::Color;
+
+use crate::{guard, set_last_error, ERUPTION_ERROR_INVALID_ARGUMENT, ERUPTION_OK};
+
+/// Opaque handle to a canvas
+pub struct EruptionCanvas {
+    pub(crate) inner: Canvas,
+}
+
+/// A RGBA color value
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct EruptionColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<EruptionColor> for Color {
+    fn from(c: EruptionColor) -> Self {
+        Color::new(c.r, c.g, c.b, c.a)
+    }
+}
+
+impl From<&Color> for EruptionColor {
+    fn from(c: &Color) -> Self {
+        EruptionColor {
+            r: c.r(),
+            g: c.g(),
+            b: c.b(),
+            a: c.a(),
+        }
+    }
+}
+
+/// Allocate a new canvas, with all LEDs set to transparent black.
+/// The canvas must be released with `eruption_canvas_free()`
+#[no_mangle]
+pub extern "C" fn eruption_canvas_new() -> *mut EruptionCanvas {
+    Box::into_raw(Box::new(EruptionCanvas {
+        inner: Canvas::new(),
+    }))
+}
+
+/// Release a canvas that has been allocated with `eruption_canvas_new()`
+///
+/// # Safety
+///
+/// `canvas` must be NULL or a pointer returned by `eruption_canvas_new()`, that has not been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn eruption_canvas_free(canvas: *mut EruptionCanvas) {
+    if !canvas.is_null() {
+        drop(Box::from_raw(canvas));
+    }
+}
+
+/// Returns the number of LEDs on the canvas, or 0 if `canvas` is NULL
+///
+/// # Safety
+///
+/// `canvas` must be NULL or a valid canvas
+#[no_mangle]
+pub unsafe extern "C" fn eruption_canvas_size(canvas: *const EruptionCanvas) -> usize {
+    canvas.as_ref().map(|c| c.inner.len()).unwrap_or(0)
+}
+
+/// Paint the whole canvas with `color`
+///
+/// # Safety
+///
+/// `canvas` must be NULL or a valid canvas
+#[no_mangle]
+pub unsafe extern "C" fn eruption_canvas_fill(
+    canvas: *mut EruptionCanvas,
+    color: EruptionColor,
+) -> i32 {
+    guard(|| match canvas.as_mut() {
+        Some(canvas) => {
+            canvas.inner.fill(color.into());
+
+            ERUPTION_OK
+        }
+
+        None => {
+            set_last_error("Invalid argument: NULL pointer");
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+    })
+}
+
+/// Set the LED at `index` to `color`
+///
+/// # Safety
+///
+/// `canvas` must be NULL or a valid canvas
+#[no_mangle]
+pub unsafe extern "C" fn eruption_canvas_set_pixel(
+    canvas: *mut EruptionCanvas,
+    index: usize,
+    color: EruptionColor,
+) -> i32 {
+    guard(|| match canvas.as_mut() {
+        Some(canvas) if index < canvas.inner.len() => {
+            canvas.inner[index] = color.into();
+
+            ERUPTION_OK
+        }
+
+        Some(_) => {
+            set_last_error(format!("Invalid argument: index {} is out of range", index));
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+
+        None => {
+            set_last_error("Invalid argument: NULL pointer");
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+    })
+}
+
+/// Store the color of the LED at `index` in `color`
+///
+/// # Safety
+///
+/// `canvas` must be NULL or a valid canvas, `color` must be NULL or point to writable memory
+#[no_mangle]
+pub unsafe extern "C" fn eruption_canvas_get_pixel(
+    canvas: *const EruptionCanvas,
+    index: usize,
+    color: *mut EruptionColor,
+) -> i32 {
+    guard(|| match (canvas.as_ref(), color.as_mut()) {
+        (Some(canvas), Some(color)) if index < canvas.inner.len() => {
+            *color = (&canvas.inner[index]).into();
+
+            ERUPTION_OK
+        }
+
+        (Some(_), Some(_)) => {
+            set_last_error(format!("Invalid argument: index {} is out of range", index));
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+
+        _ => {
+            set_last_error("Invalid argument: NULL pointer");
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_get_pixel() {
+        unsafe {
+            let canvas = eruption_canvas_new();
+            assert!(eruption_canvas_size(canvas) > 0);
+
+            let red = EruptionColor {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255,
+            };
+
+            assert_eq!(eruption_canvas_fill(canvas, red), ERUPTION_OK);
+            assert_eq!(
+                eruption_canvas_set_pixel(canvas, 1, EruptionColor::default()),
+                ERUPTION_OK
+            );
+
+            let mut color = EruptionColor::default();
+
+            assert_eq!(
+                eruption_canvas_get_pixel(canvas, 0, &mut color),
+                ERUPTION_OK
+            );
+            assert_eq!(color, red);
+
+            assert_eq!(
+                eruption_canvas_get_pixel(canvas, 1, &mut color),
+                ERUPTION_OK
+            );
+            assert_eq!(color, EruptionColor::default());
+
+            let size = eruption_canvas_size(canvas);
+            assert_eq!(
+                eruption_canvas_set_pixel(canvas, size, red),
+                ERUPTION_ERROR_INVALID_ARGUMENT
+            );
+            assert!(!crate::eruption_last_error().is_null());
+
+            eruption_canvas_free(canvas);
+        }
+    }
+
+    #[test]
+    fn null_canvas() {
+        unsafe {
+            assert_eq!(eruption_canvas_size(std::ptr::null()), 0);
+            assert_eq!(
+                eruption_canvas_fill(std::ptr::null_mut(), EruptionColor::default()),
+                ERUPTION_ERROR_INVALID_ARGUMENT
+            );
+
+            eruption_canvas_free(std::ptr::null_mut());
+        }
+    }
+}
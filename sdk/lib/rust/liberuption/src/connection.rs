@@ -0,0 +1,239 @@
+/*  SPDX-License-Identifier: LGPL-3.0-or-later  */
+
+/*
+    This file is part of the Eruption SDK.
+
+    The Eruption SDK is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Lesser General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    The Eruption SDK is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Lesser General Public License for more details.
+
+    You should have received a copy of the GNU Lesser General Public License
+    along with the Eruption SDK.  If not, see <http://www.gnu.org/licenses/>.
+
+    Copyright (c) 2019-2022, The Eruption Development Team
+*/
+
+use eruption_sdk::connection::{Connection, ConnectionType};
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::path::Path;
+
+use crate::canvas::EruptionCanvas;
+use crate::{
+    copy_to_buffer, guard, set_last_error, to_str, ERUPTION_ERROR_CONNECTION,
+    ERUPTION_ERROR_INVALID_ARGUMENT, ERUPTION_ERROR_NOT_SWITCHED, ERUPTION_OK,
+};
+
+/// Opaque handle representing a connection to the Eruption daemon
+pub struct EruptionConnection {
+    pub(crate) inner: Connection,
+}
+
+/// Create a new connection object, using the local transport.
+/// Returns NULL on error. The connection must be released with `eruption_connection_free()`
+#[no_mangle]
+pub extern "C" fn eruption_connection_new() -> *mut EruptionConnection {
+    match Connection::new(ConnectionType::Local) {
+        Ok(inner) => Box::into_raw(Box::new(EruptionConnection { inner })),
+
+        Err(e) => {
+            set_last_error(e);
+
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// Release a connection, disconnecting from the Eruption daemon if required
+///
+/// # Safety
+///
+/// `connection` must be NULL or a pointer returned by `eruption_connection_new()`, that has not been freed yet
+#[no_mangle]
+pub unsafe extern "C" fn eruption_connection_free(connection: *mut EruptionConnection) {
+    if !connection.is_null() {
+        drop(Box::from_raw(connection));
+    }
+}
+
+/// Run `f` on the connection, mapping errors to `ERUPTION_ERROR_CONNECTION`
+unsafe fn with_connection<F>(connection: *mut EruptionConnection, f: F) -> i32
+where
+    F: FnOnce(&Connection) -> eyre::Result<i32>,
+{
+    guard(|| match connection.as_ref() {
+        Some(connection) => match f(&connection.inner) {
+            Ok(result) => result,
+
+            Err(e) => {
+                set_last_error(e);
+
+                ERUPTION_ERROR_CONNECTION
+            }
+        },
+
+        None => {
+            set_last_error("Invalid argument: NULL pointer");
+
+            ERUPTION_ERROR_INVALID_ARGUMENT
+        }
+    })
+}
+
+/// Connect to the Eruption daemon
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection
+#[no_mangle]
+pub unsafe extern "C" fn eruption_connect(connection: *mut EruptionConnection) -> i32 {
+    with_connection(connection, |con| con.connect().map(|_| ERUPTION_OK))
+}
+
+/// Disconnect from the Eruption daemon
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection
+#[no_mangle]
+pub unsafe extern "C" fn eruption_disconnect(connection: *mut EruptionConnection) -> i32 {
+    with_connection(connection, |con| con.disconnect().map(|_| ERUPTION_OK))
+}
+
+/// Store the status description of the Eruption daemon in `buf`, as a NUL terminated string
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection, `buf` must point to at least `buf_len` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn eruption_get_server_status(
+    connection: *mut EruptionConnection,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> i32 {
+    with_connection(connection, |con| {
+        let status = con.get_server_status()?;
+
+        Ok(copy_to_buffer(&status.server, buf, buf_len))
+    })
+}
+
+/// Store the path of the active profile in `buf`, as a NUL terminated string
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection, `buf` must point to at least `buf_len` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn eruption_get_active_profile(
+    connection: *mut EruptionConnection,
+    buf: *mut c_char,
+    buf_len: usize,
+) -> i32 {
+    with_connection(connection, |con| {
+        let profile_file = con.get_active_profile()?;
+
+        Ok(copy_to_buffer(
+            &profile_file.to_string_lossy(),
+            buf,
+            buf_len,
+        ))
+    })
+}
+
+/// Switch to the profile `profile_file`.
+/// Returns `ERUPTION_ERROR_NOT_SWITCHED` if the daemon kept the current profile
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection, `profile_file` must be NULL or a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn eruption_switch_profile(
+    connection: *mut EruptionConnection,
+    profile_file: *const c_char,
+) -> i32 {
+    let profile_file = match to_str(profile_file) {
+        Some(profile_file) => profile_file,
+        None => return ERUPTION_ERROR_INVALID_ARGUMENT,
+    };
+
+    with_connection(connection, |con| {
+        if con.switch_profile(Path::new(profile_file))? {
+            Ok(ERUPTION_OK)
+        } else {
+            set_last_error(format!("Profile not switched: {}", profile_file));
+
+            Ok(ERUPTION_ERROR_NOT_SWITCHED)
+        }
+    })
+}
+
+/// Set the parameter `name` of the script `script_file` in the profile `profile_file` to `value`.
+/// The value is converted to the type declared in the script's manifest
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection, all strings must be NULL or NUL terminated
+#[no_mangle]
+pub unsafe extern "C" fn eruption_set_parameter(
+    connection: *mut EruptionConnection,
+    profile_file: *const c_char,
+    script_file: *const c_char,
+    name: *const c_char,
+    value: *const c_char,
+) -> i32 {
+    let (profile_file, script_file, name, value) = match (
+        to_str(profile_file),
+        to_str(script_file),
+        to_str(name),
+        to_str(value),
+    ) {
+        (Some(p), Some(s), Some(n), Some(v)) => (p, s, n, v),
+        _ => return ERUPTION_ERROR_INVALID_ARGUMENT,
+    };
+
+    with_connection(connection, |con| {
+        let mut parameter_values = HashMap::new();
+        parameter_values.insert(name.to_string(), value.to_string());
+
+        con.set_parameters(
+            Path::new(profile_file),
+            Path::new(script_file),
+            parameter_values,
+        )?;
+
+        Ok(ERUPTION_OK)
+    })
+}
+
+/// Submit the canvas to the Eruption daemon
+///
+/// # Safety
+///
+/// `connection` must be NULL or a valid connection, `canvas` must be NULL or a valid canvas
+#[no_mangle]
+pub unsafe extern "C" fn eruption_submit_canvas(
+    connection: *mut EruptionConnection,
+    canvas: *const EruptionCanvas,
+) -> i32 {
+    let canvas = match canvas.as_ref() {
+        Some(canvas) => canvas,
+
+        None => {
+            set_last_error("Invalid argument: NULL pointer");
+
+            return ERUPTION_ERROR_INVALID_ARGUMENT;
+        }
+    };
+
+    with_connection(connection, |con| {
+        con.submit_canvas(&canvas.inner)?;
+
+        Ok(ERUPTION_OK)
+    })
+}
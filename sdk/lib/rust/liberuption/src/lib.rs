@@ -19,7 +19,121 @@
     Copyright (c) 2019-2022, The Eruption Development Team
 */
 
+//! C interface to the Eruption SDK
+//!
+//! All functions returning an `int32_t` return `ERUPTION_OK` on success, or one of the
+//! negative `ERUPTION_ERROR_*` codes. A description of the last error that occurred on the
+//! calling thread may be obtained via `eruption_last_error()`.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+pub mod canvas;
+pub mod connection;
+
+/// The operation completed successfully
+pub const ERUPTION_OK: i32 = 0;
+
+/// A NULL pointer, a string that is not valid UTF-8 or an out of range index has been supplied
+pub const ERUPTION_ERROR_INVALID_ARGUMENT: i32 = -1;
+
+/// The request could not be completed by the Eruption daemon
+pub const ERUPTION_ERROR_CONNECTION: i32 = -2;
+
+/// The supplied buffer is too small to hold the result, including the terminating NUL byte
+pub const ERUPTION_ERROR_BUFFER_TOO_SMALL: i32 = -3;
+
+/// The Eruption daemon refused to switch to the requested profile
+pub const ERUPTION_ERROR_NOT_SWITCHED: i32 = -4;
+
+/// An internal error occurred
+pub const ERUPTION_ERROR_INTERNAL: i32 = -5;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+static LIB_VERSION: &[u8] = concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes();
+
+/// Returns the version of liberuption as a NUL terminated string
 #[no_mangle]
-pub extern "C" fn hello() {
-    println!("Hello from liberuption!");
+pub extern "C" fn eruption_version() -> *const c_char {
+    LIB_VERSION.as_ptr() as *const c_char
+}
+
+/// Returns a description of the last error that occurred on the calling thread, or NULL.
+/// The string is owned by the library and remains valid until the next call into the library
+#[no_mangle]
+pub extern "C" fn eruption_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| match &*e.borrow() {
+        Some(msg) => msg.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+pub(crate) fn set_last_error(description: impl ToString) {
+    let msg = CString::new(description.to_string().replace('\0', ""))
+        .unwrap_or_else(|_e| CString::new("Unknown error").unwrap());
+
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Run `f`, converting panics into `ERUPTION_ERROR_INTERNAL`, since unwinding
+/// across the FFI boundary is undefined behavior
+pub(crate) fn guard<F: FnOnce() -> i32>(f: F) -> i32 {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+
+        Err(_e) => {
+            set_last_error("Internal error");
+
+            ERUPTION_ERROR_INTERNAL
+        }
+    }
+}
+
+/// Converts a C string to a `&str`, records an error if the pointer is NULL or invalid
+pub(crate) unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        set_last_error("Invalid argument: NULL pointer");
+
+        return None;
+    }
+
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+
+        Err(_e) => {
+            set_last_error("Invalid argument: not a valid UTF-8 string");
+
+            None
+        }
+    }
+}
+
+/// Copy `s` to the buffer `buf` of size `buf_len`, including the terminating NUL byte
+pub(crate) unsafe fn copy_to_buffer(s: &str, buf: *mut c_char, buf_len: usize) -> i32 {
+    if buf.is_null() {
+        set_last_error("Invalid argument: NULL pointer");
+
+        return ERUPTION_ERROR_INVALID_ARGUMENT;
+    }
+
+    let bytes = s.as_bytes();
+
+    if bytes.len() + 1 > buf_len {
+        set_last_error(format!(
+            "Buffer too small: {} bytes required",
+            bytes.len() + 1
+        ));
+
+        return ERUPTION_ERROR_BUFFER_TOO_SMALL;
+    }
+
+    std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf as *mut u8, bytes.len());
+    *buf.add(bytes.len()) = 0;
+
+    ERUPTION_OK
 }
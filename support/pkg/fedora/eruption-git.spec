@@ -136,6 +136,7 @@ cp -a %{_builddir}/%{OrigName}-%{commit}/support/shell/completions/en_US/eruptio
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/shell/completions/en_US/eruption-process-monitor.zsh-completion %{buildroot}/%{_datarootdir}/zsh/site-functions/_eruption-process-monitor
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/shell/completions/en_US/eruptionctl.zsh-completion %{buildroot}/%{_datarootdir}/zsh/site-functions/_eruptionctl
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/config/eruption.conf %{buildroot}/%{_sysconfdir}/%{ShortName}/
+cp -a %{_builddir}/%{OrigName}-%{commit}/support/config/drivers.toml %{buildroot}/%{_sysconfdir}/%{ShortName}/
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/config/fx-proxy.conf %{buildroot}/%{_sysconfdir}/%{ShortName}/
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/config/audio-proxy.conf %{buildroot}/%{_sysconfdir}/%{ShortName}/
 cp -a %{_builddir}/%{OrigName}-%{commit}/support/config/process-monitor.conf %{buildroot}/%{_sysconfdir}/%{ShortName}/
@@ -284,6 +285,7 @@ install -Dp -m 0755 %{_builddir}/%{OrigName}-%{commit}/target/release/eruption-g
 %doc %{_mandir}/man5/process-monitor.conf.5.gz
 %dir %{_datarootdir}/icons/hicolor/scalable/apps/
 %config(noreplace) %{_sysconfdir}/%{ShortName}/%{ShortName}.conf
+%config(noreplace) %{_sysconfdir}/%{ShortName}/drivers.toml
 %config(noreplace) %{_sysconfdir}/%{ShortName}/fx-proxy.conf
 %config(noreplace) %{_sysconfdir}/%{ShortName}/audio-proxy.conf
 %config(noreplace) %{_sysconfdir}/%{ShortName}/process-monitor.conf
@@ -494,6 +496,8 @@ install -Dp -m 0755 %{_builddir}/%{OrigName}-%{commit}/target/release/eruption-g
 %{_datarootdir}/%{ShortName}/scripts/perlin.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/phonon.lua
 %{_datarootdir}/%{ShortName}/scripts/phonon.lua.manifest
+%{_datarootdir}/%{ShortName}/scripts/playback.lua
+%{_datarootdir}/%{ShortName}/scripts/playback.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/psychedelic.lua
 %{_datarootdir}/%{ShortName}/scripts/psychedelic.lua.manifest
 %{_datarootdir}/%{ShortName}/scripts/pulse.lua